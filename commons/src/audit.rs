@@ -0,0 +1,320 @@
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::{Error, HttpMessage, HttpRequest};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, TimeDelta, Utc};
+use data::access::audit_access::{get_audit_events_before, insert_audit_event};
+use data::dto::entity::{AuditEventDto, AuditEventList};
+use data::error::MeowithDataError;
+use data::model::audit_model::{AuditActorKind, AuditEvent, AUDIT_RETENTION_SECS};
+use data::model::user_model::User;
+use futures::StreamExt;
+use log::error;
+use scylla::client::caching_session::CachingSession;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::std_response::{NodeClientError, NodeClientResponse};
+
+const MAX_AUDIT_LIMIT: usize = 1000;
+/// The events read by a single query, a selective filter otherwise reads the whole log.
+const MAX_EXAMINED_EVENTS: usize = 10 * MAX_AUDIT_LIMIT;
+
+/// The user or token a request is made by.
+pub struct AuditActor {
+    kind: AuditActorKind,
+    id: Uuid,
+    name: String,
+}
+
+impl AuditActor {
+    pub fn user(user: &User) -> Self {
+        AuditActor {
+            kind: AuditActorKind::User,
+            id: user.id,
+            name: user.name.clone(),
+        }
+    }
+
+    pub fn token(issuer_id: Uuid, name: String) -> Self {
+        AuditActor {
+            kind: AuditActorKind::Token,
+            id: issuer_id,
+            name,
+        }
+    }
+}
+
+/// The app, bucket and path a request concerns, attached by the handlers of requests
+/// which do not name them in their path. Takes precedence over the path parameters.
+#[derive(Clone, Debug, Default)]
+pub struct AuditTarget {
+    pub app_id: Option<Uuid>,
+    pub bucket_id: Option<Uuid>,
+    pub path: Option<String>,
+}
+
+impl AuditTarget {
+    pub fn attach(self, req: &HttpRequest) {
+        req.extensions_mut().insert(self);
+    }
+}
+
+/// The record of a mutating request, awaiting its outcome.
+pub struct PendingAudit {
+    event: AuditEvent,
+}
+
+impl PendingAudit {
+    /// Starts recording the request, none for requests which change nothing.
+    /// The app defaults to the one of the token, if any.
+    pub fn start(req: &ServiceRequest, actor: AuditActor, app_id: Option<Uuid>) -> Option<Self> {
+        if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+            return None;
+        }
+        Some(PendingAudit {
+            event: AuditEvent {
+                app_id: app_id.unwrap_or_default(),
+                actor_kind: actor.kind.into(),
+                actor_id: actor.id,
+                actor_name: actor.name,
+                action: format!("{} {}", req.method(), req.path()),
+                source_ip: req.peer_addr().map(|addr| addr.ip().to_string()),
+                ..Default::default()
+            },
+        })
+    }
+
+    /// Records the outcome of the request, which has already been handled, so failures are only logged.
+    pub async fn finish<B>(
+        mut self,
+        res: &Result<ServiceResponse<B>, Error>,
+        session: &CachingSession,
+    ) {
+        match res {
+            Ok(res) => {
+                self.event.status = res.status().as_u16() as i32;
+                let request = res.request();
+                let target = request
+                    .extensions()
+                    .get::<AuditTarget>()
+                    .cloned()
+                    .unwrap_or_default();
+                let params = request.match_info();
+                let id_param = |name| params.get(name).and_then(|id| Uuid::parse_str(id).ok());
+                if let Some(app_id) = target.app_id.or_else(|| id_param("app_id")) {
+                    self.event.app_id = app_id;
+                }
+                self.event.bucket_id = target.bucket_id.or_else(|| id_param("bucket_id"));
+                self.event.path = target
+                    .path
+                    .or_else(|| params.get("path").map(str::to_string));
+            }
+            Err(err) => {
+                self.event.status = err.as_response_error().status_code().as_u16() as i32;
+            }
+        }
+        let now = Utc::now();
+        self.event.occurred = now;
+        self.event.period = AuditEvent::period_of(now);
+        self.event.id = Uuid::new_v4();
+
+        if let Err(err) = insert_audit_event(&self.event, session).await {
+            error!("Failed to record an audit event: {err:?}");
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+#[derive(Deserialize, Serialize, Default, Debug)]
+pub struct AuditQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// The name of the user or the token.
+    pub actor: Option<String>,
+    /// The user, or the issuer of the token.
+    pub actor_id: Option<Uuid>,
+    pub bucket_id: Option<Uuid>,
+    /// Matches the paths starting with it.
+    pub path: Option<String>,
+    pub outcome: Option<AuditOutcome>,
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, event: &AuditEvent) -> bool {
+        let success = (200..400).contains(&event.status);
+        self.actor
+            .as_ref()
+            .is_none_or(|actor| *actor == event.actor_name)
+            && self.actor_id.is_none_or(|id| id == event.actor_id)
+            && self.bucket_id.is_none_or(|id| Some(id) == event.bucket_id)
+            && self.path.as_ref().is_none_or(|prefix| {
+                event
+                    .path
+                    .as_ref()
+                    .is_some_and(|path| path.starts_with(prefix))
+            })
+            && self.outcome.is_none_or(|outcome| match outcome {
+                AuditOutcome::Success => success,
+                AuditOutcome::Failure => !success,
+            })
+    }
+}
+
+/// The position in the audit log, handed out to the client as an opaque cursor.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+struct AuditCursor {
+    occurred: DateTime<Utc>,
+    id: Uuid,
+}
+
+impl AuditCursor {
+    fn encode(&self) -> NodeClientResponse<String> {
+        let bytes = serde_cbor::to_vec(self).map_err(|_| NodeClientError::InternalError)?;
+        Ok(URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    fn decode(cursor: &str) -> NodeClientResponse<Self> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(cursor)
+            .map_err(|_| NodeClientError::BadRequest)?;
+        serde_cbor::from_slice(&bytes).map_err(|_| NodeClientError::BadRequest)
+    }
+}
+
+/// Returns the retained events of the app matching the query, the most recent first.
+/// The nil app holds the events outside of any app.
+/// A page stops short of the limit once enough events were read, with a cursor to go on from.
+pub async fn query_audit_events(
+    app_id: Uuid,
+    query: AuditQuery,
+    session: &CachingSession,
+) -> NodeClientResponse<AuditEventList> {
+    let limit = query.limit.unwrap_or(MAX_AUDIT_LIMIT);
+    if limit == 0 || limit > MAX_AUDIT_LIMIT {
+        return Err(NodeClientError::BadRequest);
+    }
+
+    let now = Utc::now();
+    let retained_since = now - TimeDelta::seconds(AUDIT_RETENTION_SECS);
+    let from = query.from.unwrap_or(retained_since).max(retained_since);
+    let mut position = match &query.cursor {
+        Some(cursor) => AuditCursor::decode(cursor)?,
+        None => AuditCursor {
+            occurred: query.to.unwrap_or(now),
+            id: Uuid::max(),
+        },
+    };
+    if position.occurred < from {
+        return Ok(AuditEventList {
+            events: vec![],
+            cursor: None,
+        });
+    }
+
+    let mut events = vec![];
+    let mut examined = 0;
+    let mut has_more = false;
+    'periods: for period in
+        (AuditEvent::period_of(from)..=AuditEvent::period_of(position.occurred)).rev()
+    {
+        let mut stream =
+            get_audit_events_before(app_id, period, (position.occurred, position.id), session)
+                .await?;
+        while let Some(event) = stream.next().await {
+            let event = event.map_err(MeowithDataError::from)?;
+            if event.occurred < from {
+                break 'periods;
+            }
+            if examined == MAX_EXAMINED_EVENTS {
+                has_more = true;
+                break 'periods;
+            }
+            examined += 1;
+            let matches = query.matches(&event);
+            if matches && events.len() == limit {
+                has_more = true;
+                break 'periods;
+            }
+            // Moves past the events filtered out as well, the next page does not read them again.
+            position = AuditCursor {
+                occurred: event.occurred,
+                id: event.id,
+            };
+            if matches {
+                events.push(event);
+            }
+        }
+    }
+
+    Ok(AuditEventList {
+        events: events
+            .into_iter()
+            .filter_map(|event| AuditEventDto::try_from(event).ok())
+            .collect(),
+        cursor: if has_more {
+            Some(position.encode()?)
+        } else {
+            None
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuditCursor, AuditOutcome, AuditQuery};
+    use chrono::Utc;
+    use data::model::audit_model::AuditEvent;
+    use uuid::Uuid;
+
+    fn event(path: Option<&str>, status: i32) -> AuditEvent {
+        AuditEvent {
+            actor_name: "token".to_string(),
+            path: path.map(str::to_string),
+            status,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_query_matches() {
+        let query = AuditQuery {
+            path: Some("dir/".to_string()),
+            outcome: Some(AuditOutcome::Failure),
+            ..Default::default()
+        };
+        assert!(query.matches(&event(Some("dir/file"), 404)));
+        assert!(!query.matches(&event(Some("dir/file"), 200)));
+        assert!(!query.matches(&event(Some("other/file"), 404)));
+        assert!(!query.matches(&event(None, 404)));
+
+        let query = AuditQuery {
+            actor: Some("token".to_string()),
+            outcome: Some(AuditOutcome::Success),
+            ..Default::default()
+        };
+        assert!(query.matches(&event(None, 204)));
+        assert!(!query.matches(&event(None, 400)));
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = AuditCursor {
+            occurred: Utc::now(),
+            id: Uuid::new_v4(),
+        };
+        let decoded = AuditCursor::decode(&cursor.encode().unwrap()).unwrap();
+        assert_eq!(decoded.occurred, cursor.occurred);
+        assert_eq!(decoded.id, cursor.id);
+        assert!(AuditCursor::decode("invalid").is_err());
+    }
+}
@@ -1,8 +1,25 @@
 use num_enum::{IntoPrimitive, TryFromPrimitive};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 #[derive(IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
 pub enum CacheId {
     ValidateNonce = 0u8,
     NodeStorageMap = 1u8,
+    BucketPolicy = 2u8,
+    AppLimits = 3u8,
+}
+
+/// Identifies the cached entries of a single bucket.
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct BucketCacheKey {
+    pub app_id: Uuid,
+    pub bucket_id: Uuid,
+}
+
+/// Identifies the cached entries of a single app.
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct AppCacheKey {
+    pub app_id: Uuid,
 }
@@ -11,6 +11,8 @@ pub enum MeowithIoError {
     InvalidDataDir,
     InsufficientDiskSpace,
     Paused,
+    /// The chunk is being modified, or does not hold the expected contents.
+    Conflict,
 }
 
 impl Error for MeowithIoError {}
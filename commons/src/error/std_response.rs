@@ -1,7 +1,7 @@
 use crate::error::io_error::MeowithIoError;
 use crate::error::mdsftp_error::MDSFTPError;
 use actix_web::error::PayloadError;
-use actix_web::http::header::ContentType;
+use actix_web::http::header::{ContentType, CONTENT_RANGE, RETRY_AFTER};
 use actix_web::http::StatusCode;
 use actix_web::{error, HttpResponse};
 use bcrypt::BcryptError;
@@ -24,10 +24,33 @@ pub enum NodeClientError {
     EntityExists,
     NoSuchSession,
     BadAuth,
-    InsufficientStorage { message: String },
-    ProtocolError { message: String },
+    InsufficientStorage {
+        message: String,
+    },
+    ProtocolError {
+        message: String,
+    },
     NotEmpty,
-    RangeUnsatisfiable,
+    /// None of the requested ranges fit within the entity of the given size.
+    RangeUnsatisfiable {
+        size: u64,
+    },
+    PreconditionFailed,
+    /// Another token holds an exclusive lease on the path.
+    Locked {
+        message: String,
+    },
+    /// The changes following the cursor are no longer retained, the client has to resync.
+    CursorExpired,
+    /// The file is under a legal hold or within the retention of the bucket object lock.
+    ObjectLocked {
+        message: String,
+    },
+    /// Too many requests within the current window, or too many transfers at once.
+    /// The client should retry after the given number of seconds.
+    RateLimited {
+        retry_after: u64,
+    },
 }
 
 impl std::error::Error for NodeClientError {}
@@ -44,15 +67,29 @@ impl error::ResponseError for NodeClientError {
             NodeClientError::NoSuchSession => StatusCode::NOT_FOUND,
             NodeClientError::EntityExists => StatusCode::BAD_REQUEST,
             NodeClientError::NotEmpty => StatusCode::BAD_REQUEST,
-            NodeClientError::RangeUnsatisfiable => StatusCode::RANGE_NOT_SATISFIABLE,
+            NodeClientError::RangeUnsatisfiable { .. } => StatusCode::RANGE_NOT_SATISFIABLE,
             NodeClientError::ProtocolError { .. } => StatusCode::BAD_REQUEST,
+            NodeClientError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            NodeClientError::Locked { .. } => StatusCode::LOCKED,
+            NodeClientError::CursorExpired => StatusCode::GONE,
+            NodeClientError::ObjectLocked { .. } => StatusCode::FORBIDDEN,
+            NodeClientError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
         }
     }
 
     fn error_response(&self) -> HttpResponse {
-        HttpResponse::build(self.status_code())
-            .insert_header(ContentType::json())
-            .json(self)
+        let mut response = HttpResponse::build(self.status_code());
+        response.insert_header(ContentType::json());
+        match self {
+            NodeClientError::RateLimited { retry_after } => {
+                response.insert_header((RETRY_AFTER, retry_after.to_string()));
+            }
+            NodeClientError::RangeUnsatisfiable { size } => {
+                response.insert_header((CONTENT_RANGE, format!("bytes */{size}")));
+            }
+            _ => {}
+        }
+        response.json(self)
     }
 }
 
@@ -73,6 +110,7 @@ impl From<MeowithIoError> for NodeClientError {
     fn from(value: MeowithIoError) -> Self {
         match value {
             MeowithIoError::NotFound => NodeClientError::NotFound,
+            MeowithIoError::Conflict => NodeClientError::PreconditionFailed,
             _ => {
                 error!("MEOWITH IO ERROR: {:?}", value);
                 NodeClientError::InternalError
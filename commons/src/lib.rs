@@ -1,4 +1,5 @@
 pub mod access_token_service;
+pub mod audit;
 pub mod autoconfigure;
 pub mod cache;
 pub mod context;
@@ -7,3 +8,4 @@ pub mod middleware_actions;
 pub mod pause_handle;
 pub mod permission;
 pub mod ssl_acceptor;
+pub mod webhook;
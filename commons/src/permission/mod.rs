@@ -1,11 +1,11 @@
 pub mod check;
 
 use crate::permission::check::PermissionListEntryBounds;
-use data::dto::entity::ScopedPermission;
+use data::dto::entity::{RateLimitsDto, ScopedPermission};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppTokenPermit {
     pub bucket_id: Uuid,
     pub allowance: u64,
@@ -18,6 +18,9 @@ pub struct AppTokenData {
     pub name: String,
     pub nonce: Uuid,
     pub perms: Vec<AppTokenPermit>,
+    /// Missing from tokens issued before limits existed.
+    #[serde(default)]
+    pub limits: Option<RateLimitsDto>,
 }
 
 impl From<ScopedPermission> for AppTokenPermit {
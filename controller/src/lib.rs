@@ -14,6 +14,7 @@ use crate::ioutils::read_file;
 use crate::mgpp::mgpp::{start_server, ControllerAuthenticator};
 use crate::middleware::node_internal_middleware::NodeVerify;
 use crate::middleware::user_middleware::UserMiddlewareRequestTransform;
+use crate::public::routes::audit::get_audit_log;
 use crate::public::routes::auth::{login, own_user_info};
 use crate::public::routes::node_management::{
     create_register_code, delete_node, delete_register_code, list_register_codes, status,
@@ -247,10 +248,15 @@ pub async fn start_controller(config: ControllerConfig) -> std::io::Result<Contr
             .service(update_quota)
             .wrap(UserMiddlewareRequestTransform);
 
+        let audit_scope = web::scope("/audit")
+            .service(get_audit_log)
+            .wrap(UserMiddlewareRequestTransform);
+
         let public_scope = web::scope("/api/public")
             .service(register_codes)
             .service(node_scope)
-            .service(user_scope);
+            .service(user_scope)
+            .service(audit_scope);
 
         let auth_scope = web::scope("/api/auth").service(login);
 
@@ -5,6 +5,7 @@ use actix_web::{
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
     Error, HttpMessage,
 };
+use commons::audit::{AuditActor, PendingAudit};
 use commons::error::std_response::NodeClientError;
 use commons::middleware_actions::remove_bearer_prefix;
 use data::access::user_access::get_user_from_id;
@@ -83,12 +84,15 @@ where
                 return Err(Error::from(NodeClientError::BadAuth));
             }
 
+            let audit = PendingAudit::start(&req, AuditActor::user(&user), None);
             req.extensions_mut().insert(user);
 
-            let fut = svc.call(req);
-
-            let res = fut.await?;
-            Ok(res)
+            let app_data = app_data.clone();
+            let res = svc.call(req).await;
+            if let Some(audit) = audit {
+                audit.finish(&res, &app_data.session).await;
+            }
+            res
         })
     }
 }
@@ -0,0 +1,19 @@
+use crate::AppState;
+use actix_web::web::Json;
+use actix_web::{get, web};
+use commons::audit::{query_audit_events, AuditQuery};
+use commons::error::std_response::NodeClientResponse;
+use data::dto::entity::AuditEventList;
+use uuid::Uuid;
+
+/// Returns the audit events of any app, the nil app holding those of node and user management.
+#[get("/{app_id}")]
+pub async fn get_audit_log(
+    req: web::Path<Uuid>,
+    query: web::Query<AuditQuery>,
+    data: web::Data<AppState>,
+) -> NodeClientResponse<Json<AuditEventList>> {
+    query_audit_events(req.into_inner(), query.into_inner(), &data.session)
+        .await
+        .map(Json)
+}
@@ -1,3 +1,4 @@
+pub mod audit;
 pub mod auth;
 pub mod node_management;
 pub mod user_management;
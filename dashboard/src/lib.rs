@@ -5,10 +5,14 @@ use crate::init_procedure::{initializer_heart, register_node};
 use crate::public::auth::auth_routes::{get_methods, login, own_user_info, register};
 use crate::public::routes::application::{
     add_member, buckets, create_application, delete_application, delete_member, edit_application,
+    edit_application_limits, get_application_deletion, get_application_limits, get_audit_log,
     list_members, list_owned,
 };
 use crate::public::routes::bucket::{
-    create_bucket, delete_bucket_handler, edit_bucket, get_sessions,
+    create_bucket, delete_bucket_handler, edit_bucket, edit_bucket_cors, edit_bucket_lifecycle,
+    edit_bucket_notifications, edit_bucket_object_lock, edit_bucket_public_access,
+    edit_bucket_trash, edit_bucket_versioning, get_bucket_cors, get_bucket_deletion,
+    get_bucket_lifecycle, get_bucket_notifications, get_bucket_public_access, get_sessions,
 };
 use crate::public::routes::role::{
     create_role, delete_role, get_roles, modify_role, update_roles_for_member,
@@ -181,7 +185,11 @@ pub async fn start_dashboard(config: DashboardConfig) -> std::io::Result<Dashboa
             .wrap(UserMiddlewareRequestTransform)
             .service(create_application)
             .service(delete_application)
+            .service(get_application_deletion)
             .service(edit_application)
+            .service(get_application_limits)
+            .service(edit_application_limits)
+            .service(get_audit_log)
             .service(list_owned)
             .service(buckets)
             .service(add_member)
@@ -197,7 +205,19 @@ pub async fn start_dashboard(config: DashboardConfig) -> std::io::Result<Dashboa
         let bucket_scope = web::scope("/bucket")
             .wrap(UserMiddlewareRequestTransform)
             .service(delete_bucket_handler)
+            .service(get_bucket_deletion)
             .service(edit_bucket)
+            .service(edit_bucket_versioning)
+            .service(edit_bucket_trash)
+            .service(edit_bucket_object_lock)
+            .service(edit_bucket_lifecycle)
+            .service(get_bucket_lifecycle)
+            .service(edit_bucket_notifications)
+            .service(get_bucket_notifications)
+            .service(edit_bucket_public_access)
+            .service(get_bucket_public_access)
+            .service(edit_bucket_cors)
+            .service(get_bucket_cors)
             .service(get_sessions)
             .service(create_bucket);
 
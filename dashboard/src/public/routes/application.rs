@@ -1,11 +1,16 @@
 use crate::public::service::application_service::{
-    do_add_member, do_create_app, do_delete_app, do_delete_member, do_edit_app, do_list_apps,
-    do_list_buckets, do_list_members,
+    do_add_member, do_create_app, do_delete_app, do_delete_member, do_edit_app, do_edit_app_limits,
+    do_get_app_deletion, do_get_app_limits, do_get_audit_log, do_list_apps, do_list_buckets,
+    do_list_members,
 };
 use crate::AppState;
-use actix_web::{delete, get, patch, post, web, HttpResponse};
+use actix_web::{delete, get, patch, post, web, HttpRequest, HttpResponse};
+use commons::audit::{AuditQuery, AuditTarget};
 use commons::error::std_response::{NodeClientError, NodeClientResponse};
-use data::dto::entity::{AppDto, AppList, BucketList, MemberIdRequest, MemberListDTO};
+use data::dto::entity::{
+    AppDto, AppList, AuditEventList, BucketJobList, BucketList, MemberIdRequest, MemberListDTO,
+    RateLimitsDto,
+};
 use data::model::user_model::User;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -33,9 +38,33 @@ impl CreateApplicationRequest {
     }
 }
 
+/// Rejects limits that do not fit into their columns.
+pub(crate) fn validate_limits(limits: &RateLimitsDto) -> NodeClientResponse<()> {
+    if limits.requests_per_second > i32::MAX as u32
+        || limits.concurrent_transfers > i32::MAX as u32
+        || limits.upload_bytes_per_second > i64::MAX as u64
+        || limits.download_bytes_per_second > i64::MAX as u64
+    {
+        return Err(NodeClientError::BadRequest);
+    }
+    Ok(())
+}
+
 #[derive(Serialize, Deserialize)]
-struct DeleteApplicationRequest {
-    id: Uuid,
+pub struct DeleteApplicationRequest {
+    pub id: Uuid,
+    /// Deletes the buckets of the app along with all of their contents, in the background.
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Attributes the audit event of the request to the app not named in its path.
+fn audit_app(app_id: Uuid, req: &HttpRequest) {
+    AuditTarget {
+        app_id: Some(app_id),
+        ..Default::default()
+    }
+    .attach(req);
 }
 
 #[get("/list")]
@@ -57,31 +86,76 @@ pub async fn buckets(
 
 #[post("/create")]
 pub async fn create_application(
+    http: HttpRequest,
     req: web::Json<CreateApplicationRequest>,
     state: web::Data<AppState>,
     user: User,
 ) -> NodeClientResponse<web::Json<AppDto>> {
     req.validate()?;
-    do_create_app(req.0, &state.session, user).await
+    let app = do_create_app(req.0, &state.session, user).await?;
+    audit_app(app.id, &http);
+    Ok(app)
 }
 
 #[patch("/edit/{id}")]
 pub async fn edit_application(
+    http: HttpRequest,
     req: web::Json<EditApplicationQuotaRequest>,
     path: web::Path<Uuid>,
     state: web::Data<AppState>,
     user: User,
 ) -> NodeClientResponse<web::Json<EmptyResponse>> {
+    audit_app(*path, &http);
     do_edit_app(path.into_inner(), req.into_inner(), &state.session, user).await
 }
 
 #[delete("/delete")]
 pub async fn delete_application(
+    http: HttpRequest,
     req: web::Json<DeleteApplicationRequest>,
     state: web::Data<AppState>,
     user: User,
 ) -> NodeClientResponse<HttpResponse> {
-    do_delete_app(req.id, &state.session, user).await?;
+    audit_app(req.id, &http);
+    match do_delete_app(req.id, req.force, &state, user).await? {
+        Some(jobs) => Ok(HttpResponse::Accepted().json(jobs)),
+        None => Ok(HttpResponse::Ok().finish()),
+    }
+}
+
+#[get("/deletion/{id}")]
+pub async fn get_application_deletion(
+    user: User,
+    path: web::Path<Uuid>,
+    state: web::Data<AppState>,
+) -> NodeClientResponse<web::Json<BucketJobList>> {
+    do_get_app_deletion(path.into_inner(), user, &state.session)
+        .await
+        .map(web::Json)
+}
+
+#[get("/limits/{id}")]
+pub async fn get_application_limits(
+    user: User,
+    path: web::Path<Uuid>,
+    state: web::Data<AppState>,
+) -> NodeClientResponse<web::Json<RateLimitsDto>> {
+    do_get_app_limits(path.into_inner(), user, &state.session)
+        .await
+        .map(web::Json)
+}
+
+#[patch("/limits/{id}")]
+pub async fn edit_application_limits(
+    http: HttpRequest,
+    req: web::Json<RateLimitsDto>,
+    path: web::Path<Uuid>,
+    state: web::Data<AppState>,
+    user: User,
+) -> NodeClientResponse<HttpResponse> {
+    validate_limits(&req)?;
+    audit_app(*path, &http);
+    do_edit_app_limits(path.into_inner(), req.into_inner(), &state, user).await?;
     Ok(HttpResponse::Ok().finish())
 }
 
@@ -113,3 +187,15 @@ pub async fn list_members(
 ) -> NodeClientResponse<web::Json<MemberListDTO>> {
     do_list_members(req_path.into_inner(), user, &state.session).await
 }
+
+#[get("/audit/{app_id}")]
+pub async fn get_audit_log(
+    path: web::Path<Uuid>,
+    query: web::Query<AuditQuery>,
+    state: web::Data<AppState>,
+    user: User,
+) -> NodeClientResponse<web::Json<AuditEventList>> {
+    do_get_audit_log(path.into_inner(), query.into_inner(), user, &state.session)
+        .await
+        .map(web::Json)
+}
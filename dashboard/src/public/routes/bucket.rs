@@ -1,10 +1,20 @@
 use crate::public::service::bucket_service::{
-    do_create_bucket, do_delete_bucket, do_edit_bucket, do_get_upload_sessions,
+    do_create_bucket, do_delete_bucket, do_edit_bucket, do_edit_bucket_cors,
+    do_edit_bucket_lifecycle, do_edit_bucket_notifications, do_edit_bucket_object_lock,
+    do_edit_bucket_public_access, do_edit_bucket_trash, do_edit_bucket_versioning,
+    do_get_bucket_deletion, do_get_cors_rules, do_get_lifecycle_rules, do_get_notifications,
+    do_get_public_access, do_get_upload_sessions,
 };
 use crate::AppState;
-use actix_web::{delete, get, patch, post, web, HttpResponse};
+use actix_web::{delete, get, patch, post, web, HttpRequest, HttpResponse};
+use commons::audit::AuditTarget;
 use commons::error::std_response::{NodeClientError, NodeClientResponse};
-use data::dto::entity::{BucketDto, UploadSessionsResponse};
+use commons::webhook::validate_webhook_url;
+use data::dto::entity::{
+    BucketDto, BucketJobDto, CorsRuleDto, CorsRuleList, LifecycleRuleList, NotificationConfigList,
+    PublicAccessDto, UploadSessionsResponse,
+};
+use data::model::file_model::{BucketEventKind, LifecycleAction, LifecycleCondition};
 use data::model::user_model::User;
 use log::info;
 use serde::{Deserialize, Serialize};
@@ -16,16 +26,254 @@ pub struct CreateBucketRequest {
     pub app_id: Uuid,
     pub quota: u64,
     pub atomic_upload: bool,
+    #[serde(default)]
+    pub versioning: bool,
+    /// Seconds deleted files are kept in the trash, the trash is disabled if absent or 0.
+    #[serde(default)]
+    pub trash_retention: Option<u64>,
+    /// Seconds newly written files are protected for, no object lock if absent or 0.
+    #[serde(default)]
+    pub object_lock_retention: Option<u64>,
+}
+
+/// Changes the given settings of a bucket, the omitted ones are kept.
+#[derive(Serialize, Deserialize, Default)]
+pub struct EditBucketRequest {
+    /// Unique within the app.
+    pub name: Option<String>,
+    pub quota: Option<u64>,
+    pub atomic_upload: Option<bool>,
+    pub versioning: Option<bool>,
+    /// Seconds, 0 disables the trash.
+    pub trash_retention: Option<u64>,
+    /// Seconds, 0 disables the object lock for files written from now on.
+    pub object_lock_retention: Option<u64>,
+}
+
+impl EditBucketRequest {
+    fn validate(&self) -> NodeClientResponse<()> {
+        if self
+            .name
+            .as_ref()
+            .is_some_and(|name| !valid_bucket_name(name))
+            || self
+                .object_lock_retention
+                .is_some_and(|retention| retention > MAX_OBJECT_LOCK_RETENTION)
+            || self.quota.is_some_and(|quota| quota > i64::MAX as u64)
+            || self
+                .trash_retention
+                .is_some_and(|retention| retention > i64::MAX as u64)
+        {
+            return Err(NodeClientError::BadRequest);
+        }
+        Ok(())
+    }
+}
+
+fn valid_bucket_name(name: &str) -> bool {
+    name.len() >= 3
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct EditBucketQuotaRequest {
-    pub quota: u64,
+pub struct EditBucketVersioningRequest {
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EditBucketTrashRequest {
+    /// Seconds, 0 disables the trash.
+    pub retention: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EditBucketObjectLockRequest {
+    /// Seconds, 0 disables the object lock for files written from now on.
+    pub retention: u64,
+}
+
+/// 100 years, keeping the retention representable as a timestamp.
+const MAX_OBJECT_LOCK_RETENTION: u64 = 100 * 365 * 24 * 3600;
+const MAX_LIFECYCLE_RULES: usize = 64;
+const MAX_PATH_LENGTH: usize = 2048;
+
+#[derive(Serialize, Deserialize)]
+pub struct LifecycleRuleRequest {
+    #[serde(default)]
+    pub prefix: String,
+    pub action: LifecycleAction,
+    pub condition: LifecycleCondition,
+    /// Seconds
+    pub age: u64,
+}
+
+/// Replaces all the lifecycle rules of a bucket, an empty list removes them.
+#[derive(Serialize, Deserialize)]
+pub struct EditBucketLifecycleRequest {
+    pub rules: Vec<LifecycleRuleRequest>,
+}
+
+impl EditBucketLifecycleRequest {
+    fn validate(&self) -> NodeClientResponse<()> {
+        if self.rules.len() > MAX_LIFECYCLE_RULES {
+            return Err(NodeClientError::BadRequest);
+        }
+        for rule in &self.rules {
+            if rule.age == 0 || rule.age > i64::MAX as u64 || rule.prefix.len() > MAX_PATH_LENGTH {
+                return Err(NodeClientError::BadRequest);
+            }
+        }
+        Ok(())
+    }
+}
+
+const MAX_NOTIFICATION_CONFIGS: usize = 16;
+const MAX_SECRET_LENGTH: usize = 256;
+
+#[derive(Serialize, Deserialize)]
+pub struct NotificationConfigRequest {
+    /// Keeps the id of an existing webhook, so that its pending deliveries are kept as well.
+    /// Without one, a webhook with the same url keeps its id, otherwise a new one is assigned.
+    #[serde(default)]
+    pub id: Option<Uuid>,
+    pub events: Vec<BucketEventKind>,
+    #[serde(default)]
+    pub prefix: String,
+    pub url: String,
+    /// Signs the deliveries, see the `X-Meowith-Signature` header.
+    pub secret: String,
+}
+
+/// Replaces all the webhooks of a bucket, an empty list removes them.
+#[derive(Serialize, Deserialize)]
+pub struct EditBucketNotificationsRequest {
+    pub configs: Vec<NotificationConfigRequest>,
+}
+
+impl EditBucketNotificationsRequest {
+    fn validate(&self, allow_private_webhooks: bool) -> NodeClientResponse<()> {
+        if self.configs.len() > MAX_NOTIFICATION_CONFIGS {
+            return Err(NodeClientError::BadRequest);
+        }
+        for config in &self.configs {
+            if config.events.is_empty()
+                || validate_webhook_url(&config.url, allow_private_webhooks).is_err()
+                || config.url.len() > MAX_PATH_LENGTH
+                || config.secret.is_empty()
+                || config.secret.len() > MAX_SECRET_LENGTH
+                || config.prefix.len() > MAX_PATH_LENGTH
+            {
+                return Err(NodeClientError::BadRequest);
+            }
+        }
+        Ok(())
+    }
+}
+
+const MAX_PUBLIC_PREFIXES: usize = 32;
+
+/// Replaces the anonymous read access of a bucket, no prefixes make it private again.
+#[derive(Serialize, Deserialize)]
+pub struct EditBucketPublicAccessRequest {
+    /// An empty prefix exposes the whole bucket, end it with a `/` to expose a single directory.
+    pub prefixes: Vec<String>,
+    /// Also allow listing the directories under the prefixes.
+    #[serde(default)]
+    pub listing: bool,
+    /// Anonymous requests accepted per minute by each node.
+    pub requests_per_minute: u32,
+}
+
+impl EditBucketPublicAccessRequest {
+    fn validate(&self) -> NodeClientResponse<()> {
+        if self.prefixes.len() > MAX_PUBLIC_PREFIXES
+            || self
+                .prefixes
+                .iter()
+                .any(|prefix| prefix.len() > MAX_PATH_LENGTH)
+        {
+            return Err(NodeClientError::BadRequest);
+        }
+        if !self.prefixes.is_empty()
+            && (self.requests_per_minute == 0 || self.requests_per_minute > i32::MAX as u32)
+        {
+            return Err(NodeClientError::BadRequest);
+        }
+        Ok(())
+    }
+}
+
+const MAX_CORS_RULES: usize = 16;
+const MAX_CORS_ENTRIES: usize = 64;
+const MAX_CORS_MAX_AGE: u32 = 24 * 3600;
+const CORS_METHODS: [&str; 6] = ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE"];
+
+/// Replaces all the CORS rules of a bucket, an empty list denies every cross-origin request.
+#[derive(Serialize, Deserialize)]
+pub struct EditBucketCorsRequest {
+    pub rules: Vec<CorsRuleDto>,
+}
+
+impl EditBucketCorsRequest {
+    fn validate(&self) -> NodeClientResponse<()> {
+        if self.rules.len() > MAX_CORS_RULES {
+            return Err(NodeClientError::BadRequest);
+        }
+        for rule in &self.rules {
+            let origins_valid = !rule.allowed_origins.is_empty()
+                && rule.allowed_origins.iter().all(|origin| {
+                    origin == "*" || origin.starts_with("http://") || origin.starts_with("https://")
+                });
+            let methods_valid = !rule.allowed_methods.is_empty()
+                && rule
+                    .allowed_methods
+                    .iter()
+                    .all(|method| CORS_METHODS.contains(&method.as_str()));
+            let headers_valid = rule
+                .allowed_headers
+                .iter()
+                .chain(rule.expose_headers.iter())
+                .all(|header| {
+                    header == "*"
+                        || (!header.is_empty()
+                            && header.len() <= MAX_PATH_LENGTH
+                            && header
+                                .bytes()
+                                .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-'))
+                });
+            let sizes_valid = [
+                &rule.allowed_origins,
+                &rule.allowed_methods,
+                &rule.allowed_headers,
+                &rule.expose_headers,
+            ]
+            .iter()
+            .all(|entries| entries.len() <= MAX_CORS_ENTRIES);
+            if !(origins_valid
+                && methods_valid
+                && headers_valid
+                && sizes_valid
+                && rule.max_age <= MAX_CORS_MAX_AGE)
+            {
+                return Err(NodeClientError::BadRequest);
+            }
+        }
+        Ok(())
+    }
 }
 
 impl CreateBucketRequest {
     fn validate(&self) -> NodeClientResponse<()> {
-        if self.name.len() < 3 || self.name.len() > 64 {
+        if !valid_bucket_name(&self.name) {
+            return Err(NodeClientError::BadRequest);
+        }
+        if self
+            .object_lock_retention
+            .is_some_and(|retention| retention > MAX_OBJECT_LOCK_RETENTION)
+        {
             return Err(NodeClientError::BadRequest);
         }
         Ok(())
@@ -34,40 +282,212 @@ impl CreateBucketRequest {
 
 #[post("/create")]
 pub async fn create_bucket(
+    http: HttpRequest,
     app_state: web::Data<AppState>,
     req: web::Json<CreateBucketRequest>,
     user: User,
 ) -> NodeClientResponse<web::Json<BucketDto>> {
     req.validate()?;
-    do_create_bucket(app_state, req.0, user).await
+    let app_id = req.app_id;
+    let bucket = do_create_bucket(app_state, req.0, user).await?;
+    AuditTarget {
+        app_id: Some(app_id),
+        bucket_id: Some(bucket.id),
+        ..Default::default()
+    }
+    .attach(&http);
+    Ok(bucket)
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct DelReq {
     pub app_id: Uuid,
     pub bucket_id: Uuid,
+    /// Deletes a non-empty bucket along with all of its contents, in the background.
+    #[serde(default)]
+    pub force: bool,
 }
 
 #[delete("/delete")]
 pub async fn delete_bucket_handler(
+    http: HttpRequest,
     path: web::Json<DelReq>,
     app_state: web::Data<AppState>,
     user: User,
 ) -> NodeClientResponse<HttpResponse> {
     info!("Deleting bucket");
     let params = path.into_inner();
-    do_delete_bucket(&app_state.session, params.app_id, params.bucket_id, user).await?;
-    Ok(HttpResponse::Ok().finish())
+    AuditTarget {
+        app_id: Some(params.app_id),
+        bucket_id: Some(params.bucket_id),
+        ..Default::default()
+    }
+    .attach(&http);
+    let job = do_delete_bucket(
+        &app_state,
+        params.app_id,
+        params.bucket_id,
+        params.force,
+        user,
+    )
+    .await?;
+    match job {
+        Some(job) => Ok(HttpResponse::Accepted().json(job)),
+        None => Ok(HttpResponse::Ok().finish()),
+    }
+}
+
+#[get("/deletion/{app_id}/{bucket_id}")]
+pub async fn get_bucket_deletion(
+    app_state: web::Data<AppState>,
+    which: web::Path<(Uuid, Uuid)>,
+    user: User,
+) -> NodeClientResponse<web::Json<BucketJobDto>> {
+    do_get_bucket_deletion(&app_state.session, which.0, which.1, user)
+        .await
+        .map(web::Json)
 }
 
 #[patch("/update/{app_id}/{bucket_id}")]
 pub async fn edit_bucket(
     app_state: web::Data<AppState>,
     which: web::Path<(Uuid, Uuid)>,
-    req: web::Json<EditBucketQuotaRequest>,
+    req: web::Json<EditBucketRequest>,
+    user: User,
+) -> NodeClientResponse<web::Json<BucketDto>> {
+    req.validate()?;
+    do_edit_bucket(&app_state, req.into_inner(), which.0, which.1, user)
+        .await
+        .map(web::Json)
+}
+
+#[patch("/versioning/{app_id}/{bucket_id}")]
+pub async fn edit_bucket_versioning(
+    app_state: web::Data<AppState>,
+    which: web::Path<(Uuid, Uuid)>,
+    req: web::Json<EditBucketVersioningRequest>,
+    user: User,
+) -> NodeClientResponse<HttpResponse> {
+    do_edit_bucket_versioning(&app_state.session, req.into_inner(), which.0, which.1, user).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[patch("/trash/{app_id}/{bucket_id}")]
+pub async fn edit_bucket_trash(
+    app_state: web::Data<AppState>,
+    which: web::Path<(Uuid, Uuid)>,
+    req: web::Json<EditBucketTrashRequest>,
+    user: User,
+) -> NodeClientResponse<HttpResponse> {
+    do_edit_bucket_trash(&app_state.session, req.into_inner(), which.0, which.1, user).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[patch("/object-lock/{app_id}/{bucket_id}")]
+pub async fn edit_bucket_object_lock(
+    app_state: web::Data<AppState>,
+    which: web::Path<(Uuid, Uuid)>,
+    req: web::Json<EditBucketObjectLockRequest>,
+    user: User,
+) -> NodeClientResponse<HttpResponse> {
+    if req.retention > MAX_OBJECT_LOCK_RETENTION {
+        return Err(NodeClientError::BadRequest);
+    }
+    do_edit_bucket_object_lock(&app_state.session, req.into_inner(), which.0, which.1, user)
+        .await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[get("/lifecycle/{app_id}/{bucket_id}")]
+pub async fn get_bucket_lifecycle(
+    app_state: web::Data<AppState>,
+    which: web::Path<(Uuid, Uuid)>,
+    user: User,
+) -> NodeClientResponse<web::Json<LifecycleRuleList>> {
+    do_get_lifecycle_rules(&app_state.session, which.0, which.1, user)
+        .await
+        .map(web::Json)
+}
+
+#[patch("/lifecycle/{app_id}/{bucket_id}")]
+pub async fn edit_bucket_lifecycle(
+    app_state: web::Data<AppState>,
+    which: web::Path<(Uuid, Uuid)>,
+    req: web::Json<EditBucketLifecycleRequest>,
     user: User,
 ) -> NodeClientResponse<HttpResponse> {
-    do_edit_bucket(&app_state.session, req.into_inner(), which.0, which.1, user).await?;
+    req.validate()?;
+    do_edit_bucket_lifecycle(&app_state.session, req.into_inner(), which.0, which.1, user).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[get("/notifications/{app_id}/{bucket_id}")]
+pub async fn get_bucket_notifications(
+    app_state: web::Data<AppState>,
+    which: web::Path<(Uuid, Uuid)>,
+    user: User,
+) -> NodeClientResponse<web::Json<NotificationConfigList>> {
+    do_get_notifications(&app_state.session, which.0, which.1, user)
+        .await
+        .map(web::Json)
+}
+
+#[patch("/notifications/{app_id}/{bucket_id}")]
+pub async fn edit_bucket_notifications(
+    app_state: web::Data<AppState>,
+    which: web::Path<(Uuid, Uuid)>,
+    req: web::Json<EditBucketNotificationsRequest>,
+    user: User,
+) -> NodeClientResponse<HttpResponse> {
+    req.validate(app_state.global_config.allow_private_webhooks)?;
+    do_edit_bucket_notifications(&app_state.session, req.into_inner(), which.0, which.1, user)
+        .await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[get("/public-access/{app_id}/{bucket_id}")]
+pub async fn get_bucket_public_access(
+    app_state: web::Data<AppState>,
+    which: web::Path<(Uuid, Uuid)>,
+    user: User,
+) -> NodeClientResponse<web::Json<PublicAccessDto>> {
+    do_get_public_access(&app_state.session, which.0, which.1, user)
+        .await
+        .map(web::Json)
+}
+
+#[patch("/public-access/{app_id}/{bucket_id}")]
+pub async fn edit_bucket_public_access(
+    app_state: web::Data<AppState>,
+    which: web::Path<(Uuid, Uuid)>,
+    req: web::Json<EditBucketPublicAccessRequest>,
+    user: User,
+) -> NodeClientResponse<HttpResponse> {
+    req.validate()?;
+    do_edit_bucket_public_access(&app_state, req.into_inner(), which.0, which.1, user).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[get("/cors/{app_id}/{bucket_id}")]
+pub async fn get_bucket_cors(
+    app_state: web::Data<AppState>,
+    which: web::Path<(Uuid, Uuid)>,
+    user: User,
+) -> NodeClientResponse<web::Json<CorsRuleList>> {
+    do_get_cors_rules(&app_state.session, which.0, which.1, user)
+        .await
+        .map(web::Json)
+}
+
+#[patch("/cors/{app_id}/{bucket_id}")]
+pub async fn edit_bucket_cors(
+    app_state: web::Data<AppState>,
+    which: web::Path<(Uuid, Uuid)>,
+    req: web::Json<EditBucketCorsRequest>,
+    user: User,
+) -> NodeClientResponse<HttpResponse> {
+    req.validate()?;
+    do_edit_bucket_cors(&app_state, req.into_inner(), which.0, which.1, user).await?;
     Ok(HttpResponse::Ok().finish())
 }
 
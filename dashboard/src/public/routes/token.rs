@@ -1,7 +1,9 @@
+use crate::public::routes::application::validate_limits;
 use crate::public::service::token_service::{do_delete_token, do_issue_app_token, do_list_tokens};
 use crate::AppState;
 use actix_web::web::Data;
-use actix_web::{delete, get, post, web, HttpResponse};
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use commons::audit::AuditTarget;
 use commons::error::std_response::NodeClientResponse;
 use data::dto::entity::{
     TokenDeleteRequest, TokenIssueRequest, TokenListRequest, TokenListResponse,
@@ -16,10 +18,19 @@ pub struct AppTokenResponse {
 
 #[post("/issue")]
 pub async fn issue_app_token(
+    http: HttpRequest,
     req: web::Json<TokenIssueRequest>,
     app_state: Data<AppState>,
     user: User,
 ) -> NodeClientResponse<web::Json<AppTokenResponse>> {
+    if let Some(limits) = &req.limits {
+        validate_limits(limits)?;
+    }
+    AuditTarget {
+        app_id: Some(req.app_id),
+        ..Default::default()
+    }
+    .attach(&http);
     let token = do_issue_app_token(req.0, app_state, user).await?;
     Ok(web::Json(AppTokenResponse { token }))
 }
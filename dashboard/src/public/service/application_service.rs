@@ -1,20 +1,29 @@
 use crate::public::routes::application::{
     CreateApplicationRequest, EditApplicationQuotaRequest, EmptyResponse,
 };
+use crate::public::service::bucket_service::{force_delete_bucket, latest_deletion_job};
 use crate::public::service::{has_app_permission, PermCheckScope, NO_ALLOWANCE};
+use crate::AppState;
 use actix_web::web;
 use chrono::Utc;
+use commons::audit::{query_audit_events, AuditQuery};
+use commons::cache::{AppCacheKey, CacheId};
 use commons::error::std_response::{NodeClientError, NodeClientResponse};
 use data::access::app_access::{
     delete_app, delete_app_member, get_app_by_id, get_app_members, get_apps_by_owner,
-    get_members_by_id, insert_app, insert_app_member, maybe_get_app_member, update_app_quota,
+    get_members_by_id, insert_app, insert_app_member, maybe_get_app_member, update_app_deleting,
+    update_app_limits, update_app_quota,
 };
 use data::access::file_access::{get_buckets, maybe_get_first_bucket};
 use data::access::user_access::maybe_get_user_from_id;
-use data::dto::entity::{AppDto, AppList, BucketList, MemberListDTO, MemberedApp};
+use data::dto::entity::{
+    AppDto, AppList, AuditEventList, BucketJobDto, BucketJobList, BucketList, MemberListDTO,
+    MemberedApp, RateLimitsDto,
+};
 use data::error::MeowithDataError;
 use data::model::app_model::App;
 use data::model::user_model::User;
+use protocol::mgpp::packet::MGPPPacket;
 use scylla::client::caching_session::CachingSession;
 use uuid::Uuid;
 
@@ -39,6 +48,8 @@ pub async fn do_create_app(
         quota: req.quota as i64,
         created: now,
         last_modified: now,
+        deleting: None,
+        limits: None,
     };
 
     insert_app(&app, session).await?;
@@ -72,22 +83,141 @@ pub async fn do_edit_app(
     Ok(web::Json(EmptyResponse))
 }
 
+/// Deletes an app without buckets right away.
+/// A forced delete marks the app as deleting and force deletes each of its buckets,
+/// the app along with its tokens, roles and members is removed once the last bucket is gone.
+/// Returns the deletion jobs of the buckets, if there are any.
 pub async fn do_delete_app(
     id: Uuid,
-    session: &CachingSession,
+    force: bool,
+    state: &AppState,
     user: User,
-) -> NodeClientResponse<()> {
-    let app = get_app_by_id(id, session).await?;
+) -> NodeClientResponse<Option<BucketJobList>> {
+    let session = &state.session;
+    let mut app = get_app_by_id(id, session).await?;
     if user.id != app.owner_id {
         return Err(NodeClientError::BadAuth);
     }
 
     let bucket = maybe_get_first_bucket(id, session).await?;
-    if bucket.is_some() {
+    if bucket.is_none() {
+        delete_app(&app, session).await?;
+        return Ok(None);
+    }
+    if !force {
         return Err(NodeClientError::EntityExists);
     }
 
-    delete_app(&app, session).await?;
+    app.deleting = Some(true);
+    update_app_deleting(&app, session).await?;
+    let buckets = get_buckets(id, session)
+        .await?
+        .try_collect()
+        .await
+        .map_err(MeowithDataError::from)?;
+    let mut jobs = vec![];
+    for bucket in buckets {
+        jobs.push(force_delete_bucket(state, bucket).await?);
+    }
+    Ok(Some(BucketJobList { jobs }))
+}
+
+/// The deletion jobs of the buckets the forced deletion of the app still waits for.
+pub async fn do_get_app_deletion(
+    id: Uuid,
+    user: User,
+    session: &CachingSession,
+) -> NodeClientResponse<BucketJobList> {
+    let app = get_app_by_id(id, session).await?;
+    has_app_permission(
+        &user,
+        &app,
+        *NO_ALLOWANCE,
+        session,
+        PermCheckScope::Application,
+    )
+    .await?;
+    if !app.is_deleting() {
+        return Err(NodeClientError::NotFound);
+    }
+
+    let buckets = get_buckets(id, session)
+        .await?
+        .try_collect()
+        .await
+        .map_err(MeowithDataError::from)?;
+    let mut jobs = vec![];
+    for bucket in buckets {
+        if let Some(job) = latest_deletion_job(bucket.id, session).await? {
+            if let Ok(job) = BucketJobDto::try_from(job) {
+                jobs.push(job);
+            }
+        }
+    }
+    Ok(BucketJobList { jobs })
+}
+
+pub async fn do_get_app_limits(
+    id: Uuid,
+    user: User,
+    session: &CachingSession,
+) -> NodeClientResponse<RateLimitsDto> {
+    let app = get_app_by_id(id, session).await?;
+    has_app_permission(
+        &user,
+        &app,
+        *NO_ALLOWANCE,
+        session,
+        PermCheckScope::Application,
+    )
+    .await?;
+
+    Ok(app.limits.map(RateLimitsDto::from).unwrap_or_default())
+}
+
+/// Returns the audit events of the app, readable by its owner only.
+pub async fn do_get_audit_log(
+    id: Uuid,
+    query: AuditQuery,
+    user: User,
+    session: &CachingSession,
+) -> NodeClientResponse<AuditEventList> {
+    let app = get_app_by_id(id, session).await?;
+    if user.id != app.owner_id {
+        return Err(NodeClientError::BadAuth);
+    }
+
+    query_audit_events(app.id, query, session).await
+}
+
+/// Replaces the limits of the app, all zero limits remove them.
+pub async fn do_edit_app_limits(
+    id: Uuid,
+    limits: RateLimitsDto,
+    state: &AppState,
+    user: User,
+) -> NodeClientResponse<()> {
+    let session = &state.session;
+    let mut app = get_app_by_id(id, session).await?;
+    if user.id != app.owner_id {
+        return Err(NodeClientError::BadAuth);
+    }
+
+    app.limits = if limits.is_unlimited() {
+        None
+    } else {
+        Some(limits.into())
+    };
+    update_app_limits(&app, session).await?;
+    let cache_id: u8 = CacheId::AppLimits.into();
+    state
+        .mgpp_client
+        .write_packet(MGPPPacket::InvalidateCache {
+            cache_id: cache_id as u32,
+            cache_key: serde_cbor::to_vec(&AppCacheKey { app_id: id }).unwrap(),
+        })
+        .await?;
+
     Ok(())
 }
 
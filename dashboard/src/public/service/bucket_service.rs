@@ -1,4 +1,8 @@
-use crate::public::routes::bucket::{CreateBucketRequest, EditBucketQuotaRequest};
+use crate::public::routes::bucket::{
+    CreateBucketRequest, EditBucketCorsRequest, EditBucketLifecycleRequest,
+    EditBucketNotificationsRequest, EditBucketObjectLockRequest, EditBucketPublicAccessRequest,
+    EditBucketRequest, EditBucketTrashRequest, EditBucketVersioningRequest,
+};
 use crate::public::service::application_service::get_user_used_app_quota;
 use crate::public::service::{
     has_app_permission, PermCheckScope, ALTER_BUCKET_ALLOWANCE, CREATE_BUCKET_ALLOWANCE,
@@ -7,18 +11,32 @@ use crate::public::service::{
 use crate::AppState;
 use actix_web::web;
 use chrono::Utc;
+use commons::cache::{BucketCacheKey, CacheId};
 use commons::error::std_response::{NodeClientError, NodeClientResponse};
 use data::access::app_access::get_app_by_id;
 use data::access::file_access::{
-    delete_bucket, get_bucket, get_buckets, get_upload_sessions, insert_bucket,
-    maybe_get_first_child_from_directory, maybe_get_first_file_from_directory, update_bucket_quota,
-    BucketItem,
+    delete_bucket, get_bucket, get_bucket_by_name, get_bucket_jobs, get_buckets,
+    get_upload_sessions, insert_bucket, maybe_get_first_bucket_snapshot,
+    maybe_get_first_child_from_directory, maybe_get_first_file_from_directory,
+    maybe_get_first_file_version, maybe_get_first_trashed_file, release_bucket_name,
+    save_bucket_job, try_claim_bucket_name, update_bucket_atomic_upload, update_bucket_cors_rules,
+    update_bucket_deleting, update_bucket_lifecycle_rules, update_bucket_name,
+    update_bucket_notifications, update_bucket_object_lock_retention, update_bucket_public_access,
+    update_bucket_quota, update_bucket_trash_retention, update_bucket_versioning, BucketItem,
+};
+use data::dto::entity::{
+    BucketDto, BucketJobDto, CorsRuleDto, CorsRuleList, LifecycleRuleDto, LifecycleRuleList,
+    NotificationConfigDto, NotificationConfigList, PublicAccessDto, UploadSession,
+    UploadSessionsResponse,
 };
-use data::dto::entity::{BucketDto, UploadSession, UploadSessionsResponse};
 use data::error::MeowithDataError;
-use data::model::file_model::Bucket;
+use data::model::app_model::App;
+use data::model::file_model::{
+    Bucket, BucketJob, CorsRule, JobKind, LifecycleRule, NotificationConfig, PublicAccess,
+};
 use data::model::user_model::User;
 use futures::StreamExt;
+use protocol::mgpp::packet::MGPPPacket;
 use scylla::client::caching_session::CachingSession;
 use uuid::Uuid;
 
@@ -28,6 +46,9 @@ pub async fn do_create_bucket(
     user: User,
 ) -> NodeClientResponse<web::Json<BucketDto>> {
     let app = get_app_by_id(req.app_id, &app_state.session).await?;
+    if app.is_deleting() {
+        return Err(NodeClientError::NotFound);
+    }
     has_app_permission(
         &user,
         &app,
@@ -36,7 +57,6 @@ pub async fn do_create_bucket(
         PermCheckScope::Application,
     )
     .await?;
-
     let buckets = get_buckets(req.app_id, &app_state.session)
         .await?
         .collect::<Vec<BucketItem>>()
@@ -56,10 +76,12 @@ pub async fn do_create_bucket(
         });
     }
 
+    let bucket_id = Uuid::new_v4();
+    claim_bucket_name(req.app_id, &req.name, bucket_id, &app_state.session).await?;
     let now = Utc::now();
     let bucket = Bucket {
         app_id: req.app_id,
-        id: Uuid::new_v4(),
+        id: bucket_id,
         name: req.name,
         encrypted: false, // TODO
         atomic_upload: req.atomic_upload,
@@ -68,19 +90,34 @@ pub async fn do_create_bucket(
         space_taken: 0,
         created: now,
         last_modified: now,
+        versioning: Some(req.versioning),
+        trash_retention: req.trash_retention.map(|retention| retention as i64),
+        lifecycle_rules: None,
+        notifications: None,
+        object_lock_retention: req.object_lock_retention.map(|retention| retention as i64),
+        public_access: None,
+        cors_rules: None,
+        deleting: None,
     };
 
-    insert_bucket(&bucket, &app_state.session).await?;
+    if let Err(err) = insert_bucket(&bucket, &app_state.session).await {
+        release_bucket_name(bucket.app_id, &bucket.name, bucket.id, &app_state.session).await?;
+        return Err(err.into());
+    }
 
     Ok(web::Json(bucket.into()))
 }
 
+/// Deletes an empty bucket right away.
+/// A forced delete removes a non-empty bucket as well, by a background job whose state is returned.
 pub async fn do_delete_bucket(
-    session: &CachingSession,
+    state: &AppState,
     app_id: Uuid,
     bucket_id: Uuid,
+    force: bool,
     user: User,
-) -> NodeClientResponse<()> {
+) -> NodeClientResponse<Option<BucketJobDto>> {
+    let session = &state.session;
     let bucket = get_bucket(app_id, bucket_id, session).await?;
     let app = get_app_by_id(app_id, session).await?;
     has_app_permission(
@@ -92,6 +129,13 @@ pub async fn do_delete_bucket(
     )
     .await?;
 
+    if force {
+        return force_delete_bucket(state, bucket).await.map(Some);
+    }
+    if bucket.is_deleting() {
+        return Err(NodeClientError::NotFound);
+    }
+
     if maybe_get_first_file_from_directory(bucket_id, None, session)
         .await?
         .is_some()
@@ -104,19 +148,108 @@ pub async fn do_delete_bucket(
     {
         return Err(NodeClientError::EntityExists);
     }
+    if maybe_get_first_file_version(bucket_id, session)
+        .await?
+        .is_some()
+    {
+        return Err(NodeClientError::EntityExists);
+    }
+    if maybe_get_first_trashed_file(bucket_id, session)
+        .await?
+        .is_some()
+    {
+        return Err(NodeClientError::EntityExists);
+    }
+    if maybe_get_first_bucket_snapshot(bucket_id, session)
+        .await?
+        .is_some()
+    {
+        return Err(NodeClientError::EntityExists);
+    }
 
     delete_bucket(&bucket, session).await?;
-    Ok(())
+    invalidate_bucket_policy(state, app_id, bucket_id).await?;
+    Ok(None)
 }
 
-pub async fn do_edit_bucket(
+/// Marks the bucket as deleting, which makes the nodes refuse any request to it,
+/// and enqueues the job removing it. The job is picked up by the job recovery worker of a node.
+/// A deletion already underway is returned instead of being enqueued twice.
+pub(crate) async fn force_delete_bucket(
+    state: &AppState,
+    mut bucket: Bucket,
+) -> NodeClientResponse<BucketJobDto> {
+    let session = &state.session;
+    if !bucket.is_deleting() {
+        bucket.deleting = Some(true);
+        update_bucket_deleting(&bucket, session).await?;
+        invalidate_bucket_policy(state, bucket.app_id, bucket.id).await?;
+    }
+
+    let job = match latest_deletion_job(bucket.id, session).await? {
+        Some(job) if job.is_running() => job,
+        _ => {
+            let job = BucketJob::new(
+                JobKind::DeleteBucket,
+                bucket.app_id,
+                bucket.id,
+                bucket.name.clone(),
+                Uuid::nil(),
+            );
+            save_bucket_job(&job, session).await?;
+            job
+        }
+    };
+    BucketJobDto::try_from(job).map_err(|_| NodeClientError::InternalError)
+}
+
+/// The most recent deletion job of the bucket, kept for a while after the bucket is gone.
+pub(crate) async fn latest_deletion_job(
+    bucket_id: Uuid,
+    session: &CachingSession,
+) -> NodeClientResponse<Option<BucketJob>> {
+    let jobs: Vec<BucketJob> = get_bucket_jobs(bucket_id, session)
+        .await?
+        .try_collect()
+        .await
+        .map_err(MeowithDataError::from)?;
+    Ok(jobs
+        .into_iter()
+        .filter(|job| job.kind == i8::from(JobKind::DeleteBucket))
+        .max_by_key(|job| job.created))
+}
+
+pub async fn do_get_bucket_deletion(
     session: &CachingSession,
-    req: EditBucketQuotaRequest,
     app_id: Uuid,
     bucket_id: Uuid,
     user: User,
-) -> NodeClientResponse<()> {
-    let mut bucket = get_bucket(app_id, bucket_id, session).await?;
+) -> NodeClientResponse<BucketJobDto> {
+    let app = get_app_by_id(app_id, session).await?;
+    has_app_permission(
+        &user,
+        &app,
+        *NO_ALLOWANCE,
+        session,
+        PermCheckScope::Application,
+    )
+    .await?;
+
+    let job = latest_deletion_job(bucket_id, session)
+        .await?
+        .ok_or(NodeClientError::NotFound)?;
+    BucketJobDto::try_from(job).map_err(|_| NodeClientError::InternalError)
+}
+
+pub async fn do_edit_bucket(
+    state: &AppState,
+    req: EditBucketRequest,
+    app_id: Uuid,
+    bucket_id: Uuid,
+    user: User,
+) -> NodeClientResponse<BucketDto> {
+    let session = &state.session;
+    let mut bucket = get_live_bucket(app_id, bucket_id, session).await?;
     let app = get_app_by_id(app_id, session).await?;
     has_app_permission(
         &user,
@@ -126,7 +259,67 @@ pub async fn do_edit_bucket(
         PermCheckScope::Application,
     )
     .await?;
-    let reservations = get_upload_sessions(app_id, bucket_id, session).await?;
+
+    // Each setting present is written on its own, keeping concurrent edits of the others.
+    if let Some(quota) = req.quota {
+        check_bucket_quota(&app, &bucket, quota, session).await?;
+        bucket.quota = quota as i64;
+        update_bucket_quota(&bucket, session).await?;
+    }
+    if let Some(atomic_upload) = req.atomic_upload {
+        bucket.atomic_upload = atomic_upload;
+        update_bucket_atomic_upload(&bucket, session).await?;
+    }
+    if let Some(versioning) = req.versioning {
+        bucket.versioning = Some(versioning);
+        update_bucket_versioning(&bucket, session).await?;
+    }
+    if let Some(retention) = req.trash_retention {
+        bucket.trash_retention = Some(retention as i64);
+        update_bucket_trash_retention(&bucket, session).await?;
+    }
+    if let Some(retention) = req.object_lock_retention {
+        bucket.object_lock_retention = Some(retention as i64);
+        update_bucket_object_lock_retention(&bucket, session).await?;
+    }
+    match req.name {
+        Some(name) if name != bucket.name => {
+            claim_bucket_name(app_id, &name, bucket_id, session).await?;
+            let old_name = std::mem::replace(&mut bucket.name, name);
+            if let Err(err) = update_bucket_name(&bucket, session).await {
+                release_bucket_name(app_id, &bucket.name, bucket_id, session).await?;
+                return Err(err.into());
+            }
+            release_bucket_name(app_id, &old_name, bucket_id, session).await?;
+        }
+        _ => {}
+    }
+    invalidate_bucket_policy(state, app_id, bucket_id).await?;
+
+    Ok(bucket.into())
+}
+
+/// The bucket, unless it is being deleted.
+async fn get_live_bucket(
+    app_id: Uuid,
+    bucket_id: Uuid,
+    session: &CachingSession,
+) -> NodeClientResponse<Bucket> {
+    let bucket = get_bucket(app_id, bucket_id, session).await?;
+    if bucket.is_deleting() {
+        return Err(NodeClientError::NotFound);
+    }
+    Ok(bucket)
+}
+
+/// Rejects a quota not covered by the app quota, or too small for the current contents of the bucket.
+async fn check_bucket_quota(
+    app: &App,
+    bucket: &Bucket,
+    quota: u64,
+    session: &CachingSession,
+) -> NodeClientResponse<()> {
+    let reservations = get_upload_sessions(bucket.app_id, bucket.id, session).await?;
     let reserved: i64 = reservations
         .try_collect()
         .await
@@ -134,21 +327,385 @@ pub async fn do_edit_bucket(
         .into_iter()
         .map(|x| x.size)
         .sum();
-    let app_used = get_user_used_app_quota(&app, session).await? - bucket.quota as i64;
+    let app_used = get_user_used_app_quota(app, session).await? - bucket.quota;
 
-    if app_used + req.quota as i64 > app.quota {
+    if app_used + quota as i64 > app.quota {
         return Err(NodeClientError::InsufficientStorage {
             message: "Too little quota to edit bucket".to_string(),
         });
     }
-    if (req.quota as i64) < (bucket.space_taken + reserved) {
+    if (quota as i64) < (bucket.space_taken + reserved) {
         return Err(NodeClientError::InsufficientStorage {
             message: "Requested new quota wouldn't fit the current buckets contents".to_string(),
         });
     }
+    Ok(())
+}
+
+/// Claims the name for the bucket, failing if another bucket of the app holds it.
+async fn claim_bucket_name(
+    app_id: Uuid,
+    name: &str,
+    bucket_id: Uuid,
+    session: &CachingSession,
+) -> NodeClientResponse<()> {
+    // Buckets created before the names were claimed are only found by the name index.
+    match get_bucket_by_name(app_id, name.to_string(), session).await {
+        Ok(bucket) if bucket.id != bucket_id => return Err(NodeClientError::EntityExists),
+        Ok(_) | Err(MeowithDataError::NotFound) => {}
+        Err(err) => return Err(err.into()),
+    }
+    if !try_claim_bucket_name(app_id, name, bucket_id, session).await? {
+        return Err(NodeClientError::EntityExists);
+    }
+    Ok(())
+}
+
+/// Drops the policy of the bucket cached by the nodes, applying the change cluster-wide.
+async fn invalidate_bucket_policy(
+    state: &AppState,
+    app_id: Uuid,
+    bucket_id: Uuid,
+) -> NodeClientResponse<()> {
+    let cache_id: u8 = CacheId::BucketPolicy.into();
+    state
+        .mgpp_client
+        .write_packet(MGPPPacket::InvalidateCache {
+            cache_id: cache_id as u32,
+            cache_key: serde_cbor::to_vec(&BucketCacheKey { app_id, bucket_id }).unwrap(),
+        })
+        .await?;
+    Ok(())
+}
+
+pub async fn do_edit_bucket_versioning(
+    session: &CachingSession,
+    req: EditBucketVersioningRequest,
+    app_id: Uuid,
+    bucket_id: Uuid,
+    user: User,
+) -> NodeClientResponse<()> {
+    let mut bucket = get_live_bucket(app_id, bucket_id, session).await?;
+    let app = get_app_by_id(app_id, session).await?;
+    has_app_permission(
+        &user,
+        &app,
+        *ALTER_BUCKET_ALLOWANCE,
+        session,
+        PermCheckScope::Application,
+    )
+    .await?;
+
+    // Disabling versioning keeps the already retained versions, they have to be removed explicitly.
+    bucket.versioning = Some(req.enabled);
+    update_bucket_versioning(&bucket, session).await?;
+
+    Ok(())
+}
+
+pub async fn do_edit_bucket_trash(
+    session: &CachingSession,
+    req: EditBucketTrashRequest,
+    app_id: Uuid,
+    bucket_id: Uuid,
+    user: User,
+) -> NodeClientResponse<()> {
+    let mut bucket = get_live_bucket(app_id, bucket_id, session).await?;
+    let app = get_app_by_id(app_id, session).await?;
+    has_app_permission(
+        &user,
+        &app,
+        *ALTER_BUCKET_ALLOWANCE,
+        session,
+        PermCheckScope::Application,
+    )
+    .await?;
+
+    // Already trashed files keep the expiry they were deleted with.
+    bucket.trash_retention = Some(req.retention as i64);
+    update_bucket_trash_retention(&bucket, session).await?;
+
+    Ok(())
+}
+
+pub async fn do_edit_bucket_object_lock(
+    session: &CachingSession,
+    req: EditBucketObjectLockRequest,
+    app_id: Uuid,
+    bucket_id: Uuid,
+    user: User,
+) -> NodeClientResponse<()> {
+    let mut bucket = get_live_bucket(app_id, bucket_id, session).await?;
+    let app = get_app_by_id(app_id, session).await?;
+    has_app_permission(
+        &user,
+        &app,
+        *ALTER_BUCKET_ALLOWANCE,
+        session,
+        PermCheckScope::Application,
+    )
+    .await?;
+
+    // Files already written keep their retention, neither shortening nor disabling it releases them.
+    bucket.object_lock_retention = Some(req.retention as i64);
+    update_bucket_object_lock_retention(&bucket, session).await?;
+
+    Ok(())
+}
+
+pub async fn do_get_lifecycle_rules(
+    session: &CachingSession,
+    app_id: Uuid,
+    bucket_id: Uuid,
+    user: User,
+) -> NodeClientResponse<LifecycleRuleList> {
+    let bucket = get_live_bucket(app_id, bucket_id, session).await?;
+    let app = get_app_by_id(app_id, session).await?;
+    has_app_permission(
+        &user,
+        &app,
+        *NO_ALLOWANCE,
+        session,
+        PermCheckScope::Application,
+    )
+    .await?;
+
+    Ok(LifecycleRuleList {
+        rules: bucket
+            .lifecycle_rules
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|rule| LifecycleRuleDto::try_from(rule).ok())
+            .collect(),
+    })
+}
+
+pub async fn do_edit_bucket_lifecycle(
+    session: &CachingSession,
+    req: EditBucketLifecycleRequest,
+    app_id: Uuid,
+    bucket_id: Uuid,
+    user: User,
+) -> NodeClientResponse<()> {
+    let mut bucket = get_live_bucket(app_id, bucket_id, session).await?;
+    let app = get_app_by_id(app_id, session).await?;
+    has_app_permission(
+        &user,
+        &app,
+        *ALTER_BUCKET_ALLOWANCE,
+        session,
+        PermCheckScope::Application,
+    )
+    .await?;
+
+    bucket.lifecycle_rules = Some(
+        req.rules
+            .into_iter()
+            .map(|rule| LifecycleRule {
+                id: Uuid::new_v4(),
+                prefix: rule.prefix,
+                action: rule.action.into(),
+                condition: rule.condition.into(),
+                age: rule.age as i64,
+            })
+            .collect(),
+    );
+    update_bucket_lifecycle_rules(&bucket, session).await?;
+
+    Ok(())
+}
+
+pub async fn do_get_notifications(
+    session: &CachingSession,
+    app_id: Uuid,
+    bucket_id: Uuid,
+    user: User,
+) -> NodeClientResponse<NotificationConfigList> {
+    let bucket = get_live_bucket(app_id, bucket_id, session).await?;
+    let app = get_app_by_id(app_id, session).await?;
+    has_app_permission(
+        &user,
+        &app,
+        *NO_ALLOWANCE,
+        session,
+        PermCheckScope::Application,
+    )
+    .await?;
+
+    Ok(NotificationConfigList {
+        configs: bucket
+            .notifications
+            .unwrap_or_default()
+            .into_iter()
+            .map(NotificationConfigDto::from)
+            .collect(),
+    })
+}
+
+pub async fn do_edit_bucket_notifications(
+    session: &CachingSession,
+    req: EditBucketNotificationsRequest,
+    app_id: Uuid,
+    bucket_id: Uuid,
+    user: User,
+) -> NodeClientResponse<()> {
+    let mut bucket = get_live_bucket(app_id, bucket_id, session).await?;
+    let app = get_app_by_id(app_id, session).await?;
+    has_app_permission(
+        &user,
+        &app,
+        *ALTER_BUCKET_ALLOWANCE,
+        session,
+        PermCheckScope::Application,
+    )
+    .await?;
+
+    // Pending deliveries of removed configs are dropped by the nodes, kept ones retain their id.
+    let mut existing = bucket.notifications.take().unwrap_or_default();
+    let mut configs = Vec::with_capacity(req.configs.len());
+    for config in req.configs {
+        let kept = match config.id {
+            Some(id) => Some(
+                existing
+                    .iter()
+                    .position(|existing| existing.id == id)
+                    .ok_or(NodeClientError::BadRequest)?,
+            ),
+            None => existing
+                .iter()
+                .position(|existing| existing.url == config.url),
+        };
+        configs.push(NotificationConfig {
+            id: kept.map_or_else(Uuid::new_v4, |index| existing.remove(index).id),
+            events: config.events.into_iter().map(i8::from).collect(),
+            prefix: config.prefix,
+            url: config.url,
+            secret: config.secret,
+        });
+    }
+    bucket.notifications = Some(configs);
+    update_bucket_notifications(&bucket, session).await?;
+
+    Ok(())
+}
+
+pub async fn do_get_public_access(
+    session: &CachingSession,
+    app_id: Uuid,
+    bucket_id: Uuid,
+    user: User,
+) -> NodeClientResponse<PublicAccessDto> {
+    let bucket = get_live_bucket(app_id, bucket_id, session).await?;
+    let app = get_app_by_id(app_id, session).await?;
+    has_app_permission(
+        &user,
+        &app,
+        *NO_ALLOWANCE,
+        session,
+        PermCheckScope::Application,
+    )
+    .await?;
+
+    Ok(bucket
+        .public_access
+        .map(PublicAccessDto::from)
+        .unwrap_or_default())
+}
+
+pub async fn do_edit_bucket_public_access(
+    state: &AppState,
+    req: EditBucketPublicAccessRequest,
+    app_id: Uuid,
+    bucket_id: Uuid,
+    user: User,
+) -> NodeClientResponse<()> {
+    let session = &state.session;
+    let mut bucket = get_live_bucket(app_id, bucket_id, session).await?;
+    let app = get_app_by_id(app_id, session).await?;
+    has_app_permission(
+        &user,
+        &app,
+        *ALTER_BUCKET_ALLOWANCE,
+        session,
+        PermCheckScope::Application,
+    )
+    .await?;
+
+    bucket.public_access = if req.prefixes.is_empty() {
+        None
+    } else {
+        Some(PublicAccess {
+            prefixes: req.prefixes,
+            listing: req.listing,
+            requests_per_minute: req.requests_per_minute as i32,
+        })
+    };
+    update_bucket_public_access(&bucket, session).await?;
+    invalidate_bucket_policy(state, app_id, bucket_id).await?;
+
+    Ok(())
+}
+
+pub async fn do_get_cors_rules(
+    session: &CachingSession,
+    app_id: Uuid,
+    bucket_id: Uuid,
+    user: User,
+) -> NodeClientResponse<CorsRuleList> {
+    let bucket = get_live_bucket(app_id, bucket_id, session).await?;
+    let app = get_app_by_id(app_id, session).await?;
+    has_app_permission(
+        &user,
+        &app,
+        *NO_ALLOWANCE,
+        session,
+        PermCheckScope::Application,
+    )
+    .await?;
+
+    Ok(CorsRuleList {
+        rules: bucket
+            .cors_rules
+            .unwrap_or_default()
+            .into_iter()
+            .map(CorsRuleDto::from)
+            .collect(),
+    })
+}
+
+pub async fn do_edit_bucket_cors(
+    state: &AppState,
+    req: EditBucketCorsRequest,
+    app_id: Uuid,
+    bucket_id: Uuid,
+    user: User,
+) -> NodeClientResponse<()> {
+    let session = &state.session;
+    let mut bucket = get_live_bucket(app_id, bucket_id, session).await?;
+    let app = get_app_by_id(app_id, session).await?;
+    has_app_permission(
+        &user,
+        &app,
+        *ALTER_BUCKET_ALLOWANCE,
+        session,
+        PermCheckScope::Application,
+    )
+    .await?;
 
-    bucket.quota = req.quota as i64;
-    update_bucket_quota(&bucket, session).await?;
+    bucket.cors_rules = Some(
+        req.rules
+            .into_iter()
+            .map(|rule| CorsRule {
+                allowed_origins: rule.allowed_origins,
+                allowed_methods: rule.allowed_methods,
+                allowed_headers: rule.allowed_headers,
+                expose_headers: rule.expose_headers,
+                max_age: rule.max_age as i32,
+            })
+            .collect(),
+    );
+    update_bucket_cors_rules(&bucket, session).await?;
+    invalidate_bucket_policy(state, app_id, bucket_id).await?;
 
     Ok(())
 }
@@ -159,7 +716,7 @@ pub async fn do_get_upload_sessions(
     app_id: Uuid,
     user: User,
 ) -> NodeClientResponse<web::Json<UploadSessionsResponse>> {
-    let _ = get_bucket(app_id, bucket_id, session).await?; // Assert the bucket even exists.
+    let _ = get_live_bucket(app_id, bucket_id, session).await?; // Assert the bucket even exists.
     let app = get_app_by_id(app_id, session).await?;
     has_app_permission(
         &user,
@@ -8,7 +8,7 @@ use actix_web::web::Data;
 use chrono::Utc;
 use commons::access_token_service::ClaimKey;
 use commons::cache::CacheId;
-use commons::error::std_response::NodeClientResponse;
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
 use commons::permission::{AppTokenData, AppTokenPermit};
 use data::access::app_access::{
     delete_app_token, get_app_by_id, get_app_token, get_app_tokens, get_app_tokens_by_issuer,
@@ -31,6 +31,9 @@ pub async fn do_issue_app_token(
     user: User,
 ) -> NodeClientResponse<String> {
     let app = get_app_by_id(req.app_id, &app_state.session).await?;
+    if app.is_deleting() {
+        return Err(NodeClientError::NotFound);
+    }
 
     for perm in &req.perms {
         has_app_permission(
@@ -45,6 +48,7 @@ pub async fn do_issue_app_token(
 
     let nonce = Uuid::new_v4();
     let now = Utc::now();
+    let limits = req.limits.filter(|limits| !limits.is_unlimited());
 
     let token = AppToken {
         app_id: app.id,
@@ -53,6 +57,7 @@ pub async fn do_issue_app_token(
         nonce,
         created: now,
         last_modified: now,
+        limits: limits.map(Into::into),
     };
     let token_data = AppTokenData {
         app_id: app.id,
@@ -60,6 +65,7 @@ pub async fn do_issue_app_token(
         name: req.name,
         nonce,
         perms: req.perms.into_iter().map(AppTokenPermit::from).collect(),
+        limits,
     };
 
     insert_app_token(&token, &app_state.session).await?;
@@ -1,6 +1,7 @@
 use crate::error::MeowithDataError;
 use crate::model::app_model::{
-    App, AppByOwner, AppMember, AppToken, MemberByUser, UpdateAppQuota, UserRole,
+    App, AppByOwner, AppMember, AppToken, MemberByUser, UpdateAppDeleting, UpdateAppLimits,
+    UpdateAppQuota, UserRole,
 };
 use charybdis::errors::CharybdisError;
 use charybdis::operations::{Delete, Insert, Update};
@@ -215,6 +216,34 @@ pub async fn get_members_by_id(
         .map_err(MeowithDataError::from)
 }
 
+pub async fn update_app_deleting(
+    app: &App,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    UpdateAppDeleting {
+        id: app.id,
+        deleting: app.deleting,
+    }
+    .update()
+    .execute(session)
+    .await
+    .map_err(|e| e.into())
+}
+
+pub async fn update_app_limits(
+    app: &App,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    UpdateAppLimits {
+        id: app.id,
+        limits: app.limits.clone(),
+    }
+    .update()
+    .execute(session)
+    .await
+    .map_err(|e| e.into())
+}
+
 pub async fn update_app_quota(
     id: Uuid,
     quota: i64,
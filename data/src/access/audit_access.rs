@@ -0,0 +1,37 @@
+use charybdis::operations::Insert;
+use charybdis::stream::CharybdisModelStream;
+use chrono::{DateTime, Utc};
+use scylla::client::caching_session::CachingSession;
+use uuid::Uuid;
+
+use crate::error::MeowithDataError;
+use crate::model::audit_model::{find_audit_event, AuditEvent};
+
+pub async fn insert_audit_event(
+    event: &AuditEvent,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    event
+        .insert()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    Ok(())
+}
+
+/// Lists the events of a single period preceding the `(occurred, id)` clustering key `before`,
+/// the most recent first.
+pub async fn get_audit_events_before(
+    app_id: Uuid,
+    period: i64,
+    before: (DateTime<Utc>, Uuid),
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<AuditEvent>, MeowithDataError> {
+    find_audit_event!(
+        "app_id = ? AND period = ? AND (occurred, id) < (?, ?) ORDER BY occurred DESC, id DESC",
+        (app_id, period, before.0, before.1)
+    )
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
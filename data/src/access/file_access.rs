@@ -1,28 +1,53 @@
 use async_stream::stream;
 use charybdis::batch::ModelBatch;
 use charybdis::errors::CharybdisError;
+use charybdis::model::{BaseModel, Model};
 use charybdis::operations::{Delete, Find, Insert, Update};
 use charybdis::stream::CharybdisModelStream;
-use charybdis::types::{BigInt, Timestamp};
-use chrono::Utc;
-use futures::stream::Skip;
-use futures::stream::Take;
+use charybdis::types::{BigInt, Text, Timestamp};
+use chrono::{DateTime, Utc};
 use futures::{try_join, Stream, StreamExt, TryFutureExt};
 use log::{error, trace};
 use scylla::client::caching_session::CachingSession;
 use scylla::response::query_result::QueryResult;
-use std::collections::VecDeque;
+use scylla::statement::batch::Batch;
+use scylla::value::Row;
+use std::collections::{HashMap, VecDeque};
 use uuid::Uuid;
 
 pub const ROOT_DIR: Uuid = Uuid::from_u128(0);
 
+use crate::access::microservice_node_access::lwt_applied;
 use crate::error::MeowithDataError;
 use crate::model::file_model::{
-    update_bucket_query, update_bucket_upload_session_query, Bucket, BucketUploadSession,
-    Directory, File, UpdateBucketQuota, UpdateFileChunks,
+    find_bucket_change, find_bucket_notification, find_directory, find_file, find_file_leases,
+    find_file_version, find_snapshot_chunk, find_trashed_file, update_bucket_query,
+    update_bucket_upload_session_query, update_file_query, Bucket, BucketChange, BucketJob,
+    BucketName, BucketNotification, BucketSnapshot, BucketUploadSession, Directory, File,
+    FileLeases, FileVersion, JobKind, RunningBucketJob, SnapshotChunk, SnapshotPendingChunk,
+    TrashedFile, UpdateBucketAtomicUpload, UpdateBucketCorsRules, UpdateBucketDeleting,
+    UpdateBucketLifecycleRules, UpdateBucketName, UpdateBucketNotifications,
+    UpdateBucketObjectLockRetention, UpdateBucketPublicAccess, UpdateBucketQuota,
+    UpdateBucketTrashRetention, UpdateBucketVersioning, UpdateFileChunks, UpdateFileLegalHold,
+    UpdateFileVersionChunks, UpdateTrashedFileChunks,
 };
 use crate::pathlib::split_path;
 
+static INSERT_FILE_LEASES_QUERY: &str =
+    "INSERT INTO file_leases (bucket_id, path, leases, version) VALUES (?, ?, ?, ?) IF NOT EXISTS USING TTL ?";
+static UPDATE_FILE_LEASES_QUERY: &str =
+    "UPDATE file_leases USING TTL ? SET leases = ?, version = ? WHERE bucket_id = ? AND path = ? IF version = ?";
+static DELETE_FILE_LEASES_QUERY: &str =
+    "DELETE FROM file_leases WHERE bucket_id = ? AND path = ? IF version = ?";
+static DELETE_BUCKET_NAME_QUERY: &str =
+    "DELETE FROM bucket_names WHERE app_id = ? AND name = ? IF bucket_id = ?";
+static DELETE_FILE_IF_ETAG_QUERY: &str =
+    "DELETE FROM files WHERE bucket_id = ? AND directory = ? AND name = ? IF etag = ?";
+static UPDATE_HELD_BUCKET_JOB_QUERY: &str =
+    "UPDATE bucket_jobs SET app_id = ?, kind = ?, state = ?, path = ?, holder = ?, total_files = ?, processed_files = ?, failed_files = ?, deleted_size = ?, errors = ?, created = ?, last_update = ? WHERE bucket_id = ? AND id = ? IF holder = ?";
+static REPLACE_BUCKET_JOB_QUERY: &str =
+    "UPDATE bucket_jobs SET app_id = ?, kind = ?, state = ?, path = ?, holder = ?, total_files = ?, processed_files = ?, failed_files = ?, deleted_size = ?, errors = ?, created = ?, last_update = ? WHERE bucket_id = ? AND id = ? IF holder = ? AND last_update = ?";
+
 pub type FileItem = Result<File, CharybdisError>;
 pub type BucketItem = Result<Bucket, CharybdisError>;
 pub type DirectoryItem = Result<Directory, MeowithDataError>;
@@ -30,6 +55,22 @@ pub type DirectoryListItem = Result<Directory, CharybdisError>;
 pub type FileDir = (File, Option<Directory>);
 pub type MaybeFileDir = (Option<File>, Option<Directory>);
 
+/// Reads at most `limit` rows off the stream, for the callers going through a partition page by page
+/// rather than holding all of it.
+pub async fn read_page<T: BaseModel>(
+    mut stream: CharybdisModelStream<T>,
+    limit: usize,
+) -> Result<Vec<T>, MeowithDataError> {
+    let mut page = vec![];
+    while page.len() < limit {
+        match stream.next().await {
+            Some(row) => page.push(row.map_err(MeowithDataError::from)?),
+            None => break,
+        }
+    }
+    Ok(page)
+}
+
 /// Mapper for directory ids
 /// DID::of(directory).0
 /// expr.into::<DID>()
@@ -155,6 +196,15 @@ pub async fn get_buckets(
         .map_err(MeowithDataError::from)
 }
 
+pub async fn get_all_buckets(
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<Bucket>, MeowithDataError> {
+    Bucket::find_all()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
 pub async fn insert_directory(
     directory: &Directory,
     session: &CachingSession,
@@ -178,18 +228,46 @@ pub async fn get_directories_from_bucket(
         .map_err(MeowithDataError::from)
 }
 
-pub async fn get_directories_from_bucket_paginated(
+/// Lists the directories of the bucket following the `(parent, name)` clustering key `after`,
+/// in the clustering order or in reverse of it.
+pub async fn get_directories_from_bucket_after(
     bucket_id: Uuid,
+    after: Option<(String, String)>,
+    descending: bool,
     session: &CachingSession,
-    start: u64,
-    end: u64,
-) -> Result<Take<Skip<CharybdisModelStream<Directory>>>, MeowithDataError> {
-    Ok(Directory::find_by_bucket_id(bucket_id)
-        .execute(session)
-        .await
-        .map_err(MeowithDataError::from)?
-        .skip(start as usize)
-        .take((end - start) as usize))
+) -> Result<CharybdisModelStream<Directory>, MeowithDataError> {
+    match (after, descending) {
+        (None, false) => {
+            Directory::find_by_bucket_id(bucket_id)
+                .execute(session)
+                .await
+        }
+        (None, true) => {
+            find_directory!(
+                "bucket_id = ? ORDER BY parent DESC, name DESC",
+                (bucket_id,)
+            )
+            .execute(session)
+            .await
+        }
+        (Some((parent, name)), false) => {
+            find_directory!(
+                "bucket_id = ? AND (parent, name) > (?, ?)",
+                (bucket_id, parent, name)
+            )
+            .execute(session)
+            .await
+        }
+        (Some((parent, name)), true) => {
+            find_directory!(
+                "bucket_id = ? AND (parent, name) < (?, ?) ORDER BY parent DESC, name DESC",
+                (bucket_id, parent, name)
+            )
+            .execute(session)
+            .await
+        }
+    }
+    .map_err(MeowithDataError::from)
 }
 
 pub async fn get_files_from_bucket(
@@ -202,19 +280,42 @@ pub async fn get_files_from_bucket(
         .map_err(MeowithDataError::from)
 }
 
-// Note: rewrite this when the driver will support proper paging.
-pub async fn get_files_from_bucket_paginated(
+/// Lists the files of the bucket following the `(directory, name)` clustering key `after`,
+/// in the clustering order or in reverse of it.
+pub async fn get_files_from_bucket_after(
     bucket_id: Uuid,
+    after: Option<(Uuid, String)>,
+    descending: bool,
     session: &CachingSession,
-    start: u64,
-    end: u64,
-) -> Result<Take<Skip<CharybdisModelStream<File>>>, MeowithDataError> {
-    Ok(File::find_by_bucket_id(bucket_id)
-        .execute(session)
-        .await
-        .map_err(MeowithDataError::from)?
-        .skip(start as usize)
-        .take((end - start) as usize))
+) -> Result<CharybdisModelStream<File>, MeowithDataError> {
+    match (after, descending) {
+        (None, false) => File::find_by_bucket_id(bucket_id).execute(session).await,
+        (None, true) => {
+            find_file!(
+                "bucket_id = ? ORDER BY directory DESC, name DESC",
+                (bucket_id,)
+            )
+            .execute(session)
+            .await
+        }
+        (Some((directory, name)), false) => {
+            find_file!(
+                "bucket_id = ? AND (directory, name) > (?, ?)",
+                (bucket_id, directory, name)
+            )
+            .execute(session)
+            .await
+        }
+        (Some((directory, name)), true) => {
+            find_file!(
+                "bucket_id = ? AND (directory, name) < (?, ?) ORDER BY directory DESC, name DESC",
+                (bucket_id, directory, name)
+            )
+            .execute(session)
+            .await
+        }
+    }
+    .map_err(MeowithDataError::from)
 }
 
 pub async fn get_files_from_bucket_and_directory(
@@ -228,21 +329,48 @@ pub async fn get_files_from_bucket_and_directory(
         .map_err(MeowithDataError::from)
 }
 
-pub async fn get_files_from_bucket_and_directory_paginated(
+/// Lists the files of a directory by name, starting at `bound` inclusively when ascending,
+/// or before `bound` exclusively when descending.
+pub async fn get_files_from_directory_bounded(
     bucket_id: Uuid,
     directory: Option<Uuid>,
+    bound: Option<String>,
+    descending: bool,
     session: &CachingSession,
-    start: u64,
-    end: u64,
-) -> Result<Take<Skip<CharybdisModelStream<File>>>, MeowithDataError> {
-    Ok(
-        File::find_by_bucket_id_and_directory(bucket_id, directory.unwrap_or(ROOT_DIR))
+) -> Result<CharybdisModelStream<File>, MeowithDataError> {
+    let directory = directory.unwrap_or(ROOT_DIR);
+    match (bound, descending) {
+        (None, false) => {
+            File::find_by_bucket_id_and_directory(bucket_id, directory)
+                .execute(session)
+                .await
+        }
+        (None, true) => {
+            find_file!(
+                "bucket_id = ? AND directory = ? ORDER BY directory DESC, name DESC",
+                (bucket_id, directory)
+            )
             .execute(session)
             .await
-            .map_err(MeowithDataError::from)?
-            .skip(start as usize)
-            .take((end - start) as usize),
-    )
+        }
+        (Some(bound), false) => {
+            find_file!(
+                "bucket_id = ? AND directory = ? AND name >= ?",
+                (bucket_id, directory, bound)
+            )
+            .execute(session)
+            .await
+        }
+        (Some(bound), true) => {
+            find_file!(
+                "bucket_id = ? AND directory = ? AND name < ? ORDER BY directory DESC, name DESC",
+                (bucket_id, directory, bound)
+            )
+            .execute(session)
+            .await
+        }
+    }
+    .map_err(MeowithDataError::from)
 }
 
 pub async fn maybe_get_first_file_from_directory(
@@ -278,6 +406,48 @@ pub async fn get_sub_dirs(
         .map_err(MeowithDataError::from)
 }
 
+/// Lists the subdirectories by name, with the same bounds as [get_files_from_directory_bounded].
+pub async fn get_sub_dirs_bounded(
+    bucket_id: Uuid,
+    path: String,
+    bound: Option<String>,
+    descending: bool,
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<Directory>, MeowithDataError> {
+    match (bound, descending) {
+        (None, false) => {
+            Directory::find_by_bucket_id_and_parent(bucket_id, path)
+                .execute(session)
+                .await
+        }
+        (None, true) => {
+            find_directory!(
+                "bucket_id = ? AND parent = ? ORDER BY parent DESC, name DESC",
+                (bucket_id, path)
+            )
+            .execute(session)
+            .await
+        }
+        (Some(bound), false) => {
+            find_directory!(
+                "bucket_id = ? AND parent = ? AND name >= ?",
+                (bucket_id, path, bound)
+            )
+            .execute(session)
+            .await
+        }
+        (Some(bound), true) => {
+            find_directory!(
+                "bucket_id = ? AND parent = ? AND name < ? ORDER BY parent DESC, name DESC",
+                (bucket_id, path, bound)
+            )
+            .execute(session)
+            .await
+        }
+    }
+    .map_err(MeowithDataError::from)
+}
+
 pub async fn get_all_files(
     session: &CachingSession,
 ) -> Result<CharybdisModelStream<File>, MeowithDataError> {
@@ -298,6 +468,39 @@ pub async fn maybe_get_file_by_id(
         .map_err(MeowithDataError::from)
 }
 
+pub async fn maybe_get_directory_by_id(
+    bucket_id: Uuid,
+    id: Uuid,
+    session: &CachingSession,
+) -> Result<Option<Directory>, MeowithDataError> {
+    Directory::maybe_find_first_by_bucket_id_and_id(bucket_id, id)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+/// Resolves the full path of the directory through the cache, filling it in on a miss.
+/// A missing directory resolves to the root.
+pub async fn get_directory_path_cached(
+    bucket_id: Uuid,
+    directory: Uuid,
+    cache: &mut HashMap<Uuid, String>,
+    session: &CachingSession,
+) -> Result<String, MeowithDataError> {
+    if directory == ROOT_DIR {
+        return Ok(String::new());
+    }
+    if let Some(path) = cache.get(&directory) {
+        return Ok(path.clone());
+    }
+    let path = maybe_get_directory_by_id(bucket_id, directory, session)
+        .await?
+        .map(|directory| directory.full_path())
+        .unwrap_or_default();
+    cache.insert(directory, path.clone());
+    Ok(path)
+}
+
 pub async fn get_file(
     bucket_id: Uuid,
     directory: Option<Uuid>,
@@ -374,6 +577,37 @@ pub async fn update_file_path(
     Ok(new_file)
 }
 
+/// Like [update_file_path], but only while the file keeps the etag it was read with
+/// and nothing else got created at the new path.
+/// Returns the moved file if the rename was applied.
+pub async fn try_update_file_path(
+    file: &File,
+    directory: Option<Uuid>,
+    name: String,
+    session: &CachingSession,
+) -> Result<Option<File>, MeowithDataError> {
+    let mut new_file = file.clone();
+    new_file.directory = directory.unwrap_or(Uuid::from_u128(0));
+    new_file.name = name;
+    new_file.last_modified = Utc::now();
+    // Conditional batches have to stay within a single partition, which the bucket is.
+    let mut batch = Batch::default();
+    batch.append_statement(DELETE_FILE_IF_ETAG_QUERY);
+    batch.append_statement(File::INSERT_IF_NOT_EXIST_QUERY);
+    let result = session
+        .batch(
+            &batch,
+            (
+                (file.bucket_id, file.directory, &file.name, &file.etag),
+                &new_file,
+            ),
+        )
+        .await?
+        .into_rows_result()?;
+
+    Ok(lwt_applied(result.rows::<Row>()?.next().transpose()?).then_some(new_file))
+}
+
 pub async fn update_file_chunks(
     file: &File,
     session: &CachingSession,
@@ -390,6 +624,77 @@ pub async fn update_file_chunks(
     .map_err(MeowithDataError::from)
 }
 
+/// Replaces the contents of the file, unless they changed since `expected_etag` was read.
+/// Returns whether the update was applied.
+pub async fn update_file_contents(
+    file: &File,
+    expected_etag: Option<String>,
+    session: &CachingSession,
+) -> Result<bool, MeowithDataError> {
+    let update_query = concat!(
+        update_file_query!("size = ?, chunk_ids = ?, last_modified = ?, etag = ?"),
+        " IF etag = ?"
+    );
+
+    let result = session
+        .execute_unpaged(
+            update_query,
+            (
+                file.size,
+                &file.chunk_ids,
+                file.last_modified,
+                &file.etag,
+                file.bucket_id,
+                file.directory,
+                &file.name,
+                expected_etag,
+            ),
+        )
+        .await?
+        .into_rows_result()?;
+    let mut rows = result.rows::<(bool, Option<Text>)>()?;
+
+    match rows.next() {
+        Some(row) => Ok(row?.0),
+        None => Err(MeowithDataError::UnknownFailure),
+    }
+}
+
+/// Replaces the metadata, unless the file got deleted since it was read.
+/// Returns whether the update was applied.
+pub async fn update_file_metadata(
+    file: &File,
+    session: &CachingSession,
+) -> Result<bool, MeowithDataError> {
+    // A plain update is an upsert, which would bring a concurrently deleted file back.
+    let update_query = concat!(update_file_query!("metadata = ?"), " IF EXISTS");
+
+    let result = session
+        .execute_unpaged(
+            update_query,
+            (&file.metadata, file.bucket_id, file.directory, &file.name),
+        )
+        .await?
+        .into_rows_result()?;
+    Ok(lwt_applied(result.rows::<Row>()?.next().transpose()?))
+}
+
+pub async fn update_file_legal_hold(
+    file: &File,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    UpdateFileLegalHold {
+        bucket_id: file.bucket_id,
+        directory: file.directory,
+        name: file.name.clone(),
+        legal_hold: file.legal_hold,
+    }
+    .update()
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
 // Same deal here, as with the file path
 pub async fn update_directory_path(
     directory: &Directory,
@@ -436,6 +741,36 @@ pub async fn delete_file(
     Ok(())
 }
 
+/// Like [delete_file], but only while the file keeps the etag it was read with.
+/// Returns whether the file was deleted.
+pub async fn try_delete_file(
+    file: &File,
+    bucket: &Bucket,
+    session: &CachingSession,
+) -> Result<bool, MeowithDataError> {
+    if !try_delete_file_row(file, session).await? {
+        return Ok(false);
+    }
+    update_bucket_space(bucket.clone(), -1, -file.size, session).await?;
+
+    Ok(true)
+}
+
+async fn try_delete_file_row(
+    file: &File,
+    session: &CachingSession,
+) -> Result<bool, MeowithDataError> {
+    // The stored etag is compared, files without one match a null.
+    let result = session
+        .execute_unpaged(
+            DELETE_FILE_IF_ETAG_QUERY,
+            (file.bucket_id, file.directory, &file.name, &file.etag),
+        )
+        .await?
+        .into_rows_result()?;
+    Ok(lwt_applied(result.rows::<Row>()?.next().transpose()?))
+}
+
 pub async fn get_bucket_by_name(
     app_id: Uuid,
     name: String,
@@ -462,6 +797,7 @@ pub async fn delete_bucket(
     bucket: &Bucket,
     session: &CachingSession,
 ) -> Result<QueryResult, MeowithDataError> {
+    release_bucket_name(bucket.app_id, &bucket.name, bucket.id, session).await?;
     bucket
         .delete()
         .execute(session)
@@ -469,6 +805,38 @@ pub async fn delete_bucket(
         .map_err(MeowithDataError::from)
 }
 
+/// Claims the name for the bucket, returns whether it was still free.
+pub async fn try_claim_bucket_name(
+    app_id: Uuid,
+    name: &str,
+    bucket_id: Uuid,
+    session: &CachingSession,
+) -> Result<bool, MeowithDataError> {
+    let claim = BucketName {
+        app_id,
+        name: name.to_string(),
+        bucket_id,
+    };
+    let result = session
+        .execute_unpaged(BucketName::INSERT_IF_NOT_EXIST_QUERY, &claim)
+        .await?
+        .into_rows_result()?;
+    Ok(lwt_applied(result.rows::<Row>()?.next().transpose()?))
+}
+
+/// Frees the name, if it is still claimed by the bucket.
+pub async fn release_bucket_name(
+    app_id: Uuid,
+    name: &str,
+    bucket_id: Uuid,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    session
+        .execute_unpaged(DELETE_BUCKET_NAME_QUERY, (app_id, name, bucket_id))
+        .await?;
+    Ok(())
+}
+
 const QUERY_ATTEMPTS: u16 = 2048;
 
 pub async fn update_bucket_space(
@@ -547,108 +915,1246 @@ pub async fn insert_file(
     Ok(())
 }
 
-pub async fn insert_upload_session(
-    bucket_upload_session: &BucketUploadSession,
+/// Inserts the file, unless there already is one at its path.
+pub async fn try_insert_file(
+    file: &File,
+    bucket: &Bucket,
+    session: &CachingSession,
+) -> Result<bool, MeowithDataError> {
+    let result = session
+        .execute_unpaged(File::INSERT_IF_NOT_EXIST_QUERY, file)
+        .await?
+        .into_rows_result()?;
+    if !lwt_applied(result.rows::<Row>()?.next().transpose()?) {
+        return Ok(false);
+    }
+    update_bucket_space(bucket.clone(), 1, file.size, session).await?;
+
+    Ok(true)
+}
+
+pub async fn update_bucket_versioning(
+    bucket: &Bucket,
     session: &CachingSession,
 ) -> Result<QueryResult, MeowithDataError> {
-    bucket_upload_session
-        .insert()
-        .execute(session)
-        .await
-        .map_err(MeowithDataError::from)
+    UpdateBucketVersioning {
+        app_id: bucket.app_id,
+        id: bucket.id,
+        versioning: bucket.versioning,
+    }
+    .update()
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
 }
 
-pub async fn get_upload_session(
-    app_id: Uuid,
-    bucket: Uuid,
-    id: Uuid,
+/// Replaces the current file with the provided version of it.
+/// The chunks stay in place, thus only the file count is affected.
+pub async fn archive_file(
+    file: &File,
+    version: &FileVersion,
+    bucket: &Bucket,
     session: &CachingSession,
-) -> Result<BucketUploadSession, MeowithDataError> {
-    BucketUploadSession::find_first_by_app_id_and_bucket_and_id(app_id, bucket, id)
+) -> Result<(), MeowithDataError> {
+    // Insert the version first, so that the chunks stay referenced in case of a failure.
+    version
+        .insert()
         .execute(session)
         .await
-        .map_err(MeowithDataError::from)
+        .map_err(MeowithDataError::from)?;
+    let _ = try_join!(
+        file.delete()
+            .execute(session)
+            .map_err(MeowithDataError::from),
+        update_bucket_space(bucket.clone(), -1, 0, session)
+    )?;
+
+    Ok(())
 }
 
-pub async fn get_upload_sessions(
-    app_id: Uuid,
-    bucket: Uuid,
+/// Like [archive_file], but only while the file keeps the etag it was read with.
+/// Returns whether the file was archived.
+pub async fn try_archive_file(
+    file: &File,
+    version: &FileVersion,
+    bucket: &Bucket,
     session: &CachingSession,
-) -> Result<CharybdisModelStream<BucketUploadSession>, MeowithDataError> {
-    BucketUploadSession::find_by_app_id_and_bucket(app_id, bucket)
+) -> Result<bool, MeowithDataError> {
+    version
+        .insert()
         .execute(session)
         .await
-        .map_err(MeowithDataError::from)
+        .map_err(MeowithDataError::from)?;
+    if !try_delete_file_row(file, session).await? {
+        // The file changed in the meantime, the version would be a stale copy of it.
+        version
+            .delete()
+            .execute(session)
+            .await
+            .map_err(MeowithDataError::from)?;
+        return Ok(false);
+    }
+    update_bucket_space(bucket.clone(), -1, 0, session).await?;
+
+    Ok(true)
 }
 
-pub async fn delete_upload_session(
-    bucket_upload_session: &BucketUploadSession,
+/// Replaces the contents of the file like [update_file_contents], keeping the previous ones
+/// as the provided version. Returns whether the contents were replaced.
+pub async fn archive_file_contents(
+    file: &File,
+    expected_etag: Option<String>,
+    version: &FileVersion,
     session: &CachingSession,
-) -> Result<QueryResult, MeowithDataError> {
-    bucket_upload_session
-        .delete()
+) -> Result<bool, MeowithDataError> {
+    // Insert the version first, so that the previous chunks stay referenced in case of a failure.
+    version
+        .insert()
         .execute(session)
         .await
-        .map_err(MeowithDataError::from)
-}
+        .map_err(MeowithDataError::from)?;
+    if !update_file_contents(file, expected_etag, session).await? {
+        version
+            .delete()
+            .execute(session)
+            .await
+            .map_err(MeowithDataError::from)?;
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+/// Makes the version current again, the caller is responsible for archiving the existing file first.
+pub async fn restore_file_version(
+    version: &FileVersion,
+    file: &File,
+    bucket: &Bucket,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    file.insert()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    let _ = try_join!(
+        version
+            .delete()
+            .execute(session)
+            .map_err(MeowithDataError::from),
+        update_bucket_space(bucket.clone(), 1, 0, session)
+    )?;
+
+    Ok(())
+}
+
+pub async fn delete_file_version(
+    version: &FileVersion,
+    bucket: &Bucket,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    let _ = try_join!(
+        version
+            .delete()
+            .execute(session)
+            .map_err(MeowithDataError::from),
+        update_bucket_space(bucket.clone(), 0, -version.size, session)
+    )?;
+
+    Ok(())
+}
+
+/// Lists the versions of the path, the newest first, as their ids are time ordered.
+pub async fn get_file_versions(
+    bucket_id: Uuid,
+    path: String,
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<FileVersion>, MeowithDataError> {
+    find_file_version!(
+        "bucket_id = ? AND path = ? ORDER BY path DESC, version_id DESC",
+        (bucket_id, path)
+    )
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
+/// Lists the versions of the path older than the given one, the newest first.
+pub async fn get_file_versions_before(
+    bucket_id: Uuid,
+    path: String,
+    before: Uuid,
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<FileVersion>, MeowithDataError> {
+    find_file_version!(
+        "bucket_id = ? AND path = ? AND version_id < ? ORDER BY path DESC, version_id DESC",
+        (bucket_id, path, before)
+    )
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
+pub async fn get_bucket_file_versions(
+    bucket_id: Uuid,
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<FileVersion>, MeowithDataError> {
+    FileVersion::find_by_bucket_id(bucket_id)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+/// Lists the versions of the bucket following the `(path, version_id)` clustering key `after`.
+pub async fn get_bucket_file_versions_after(
+    bucket_id: Uuid,
+    after: Option<(String, Uuid)>,
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<FileVersion>, MeowithDataError> {
+    match after {
+        None => {
+            FileVersion::find_by_bucket_id(bucket_id)
+                .execute(session)
+                .await
+        }
+        Some((path, version_id)) => {
+            find_file_version!(
+                "bucket_id = ? AND (path, version_id) > (?, ?)",
+                (bucket_id, path, version_id)
+            )
+            .execute(session)
+            .await
+        }
+    }
+    .map_err(MeowithDataError::from)
+}
+
+pub async fn get_file_version(
+    bucket_id: Uuid,
+    path: String,
+    version_id: Uuid,
+    session: &CachingSession,
+) -> Result<FileVersion, MeowithDataError> {
+    FileVersion::find_by_bucket_id_and_path_and_version_id(bucket_id, path, version_id)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn maybe_get_file_version_by_file_id(
+    bucket_id: Uuid,
+    file_id: Uuid,
+    session: &CachingSession,
+) -> Result<Option<FileVersion>, MeowithDataError> {
+    FileVersion::maybe_find_first_by_bucket_id_and_file_id(bucket_id, file_id)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn maybe_get_first_file_version(
+    bucket_id: Uuid,
+    session: &CachingSession,
+) -> Result<Option<FileVersion>, MeowithDataError> {
+    FileVersion::maybe_find_first_by_bucket_id(bucket_id)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn get_all_file_versions(
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<FileVersion>, MeowithDataError> {
+    FileVersion::find_all()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn update_file_version_chunks(
+    version: &FileVersion,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    UpdateFileVersionChunks {
+        bucket_id: version.bucket_id,
+        path: version.path.clone(),
+        version_id: version.version_id,
+        chunk_ids: version.chunk_ids.clone(),
+    }
+    .update()
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
+pub async fn update_bucket_trash_retention(
+    bucket: &Bucket,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    UpdateBucketTrashRetention {
+        app_id: bucket.app_id,
+        id: bucket.id,
+        trash_retention: bucket.trash_retention,
+    }
+    .update()
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
+pub async fn update_bucket_object_lock_retention(
+    bucket: &Bucket,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    UpdateBucketObjectLockRetention {
+        app_id: bucket.app_id,
+        id: bucket.id,
+        object_lock_retention: bucket.object_lock_retention,
+    }
+    .update()
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
+pub async fn update_bucket_name(
+    bucket: &Bucket,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    UpdateBucketName {
+        app_id: bucket.app_id,
+        id: bucket.id,
+        name: bucket.name.clone(),
+    }
+    .update()
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
+pub async fn update_bucket_atomic_upload(
+    bucket: &Bucket,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    UpdateBucketAtomicUpload {
+        app_id: bucket.app_id,
+        id: bucket.id,
+        atomic_upload: bucket.atomic_upload,
+    }
+    .update()
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
+pub async fn update_bucket_public_access(
+    bucket: &Bucket,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    UpdateBucketPublicAccess {
+        app_id: bucket.app_id,
+        id: bucket.id,
+        public_access: bucket.public_access.clone(),
+    }
+    .update()
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
+pub async fn update_bucket_cors_rules(
+    bucket: &Bucket,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    UpdateBucketCorsRules {
+        app_id: bucket.app_id,
+        id: bucket.id,
+        cors_rules: bucket.cors_rules.clone(),
+    }
+    .update()
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
+pub async fn update_bucket_deleting(
+    bucket: &Bucket,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    UpdateBucketDeleting {
+        app_id: bucket.app_id,
+        id: bucket.id,
+        deleting: bucket.deleting,
+    }
+    .update()
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
+pub async fn update_bucket_lifecycle_rules(
+    bucket: &Bucket,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    UpdateBucketLifecycleRules {
+        app_id: bucket.app_id,
+        id: bucket.id,
+        lifecycle_rules: bucket.lifecycle_rules.clone(),
+    }
+    .update()
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
+pub async fn update_bucket_notifications(
+    bucket: &Bucket,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    UpdateBucketNotifications {
+        app_id: bucket.app_id,
+        id: bucket.id,
+        notifications: bucket.notifications.clone(),
+    }
+    .update()
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
+/// Moves the file to the trash.
+/// The chunks stay in place, thus only the file count is affected.
+pub async fn trash_file(
+    file: &File,
+    entry: &TrashedFile,
+    bucket: &Bucket,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    // Insert the entry first, so that the chunks stay referenced in case of a failure.
+    entry
+        .insert()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    let _ = try_join!(
+        file.delete()
+            .execute(session)
+            .map_err(MeowithDataError::from),
+        update_bucket_space(bucket.clone(), -1, 0, session)
+    )?;
+
+    Ok(())
+}
+
+/// Like [trash_file], but only while the file keeps the etag it was read with.
+/// Returns whether the file was trashed.
+pub async fn try_trash_file(
+    file: &File,
+    entry: &TrashedFile,
+    bucket: &Bucket,
+    session: &CachingSession,
+) -> Result<bool, MeowithDataError> {
+    entry
+        .insert()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    if !try_delete_file_row(file, session).await? {
+        // The file changed in the meantime, the entry would be a stale copy of it.
+        entry
+            .delete()
+            .execute(session)
+            .await
+            .map_err(MeowithDataError::from)?;
+        return Ok(false);
+    }
+    update_bucket_space(bucket.clone(), -1, 0, session).await?;
+
+    Ok(true)
+}
+
+/// Replaces the contents of the file like [update_file_contents], moving the previous ones
+/// to the trash as the provided entry. Returns whether the contents were replaced.
+pub async fn trash_file_contents(
+    file: &File,
+    expected_etag: Option<String>,
+    entry: &TrashedFile,
+    session: &CachingSession,
+) -> Result<bool, MeowithDataError> {
+    entry
+        .insert()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    if !update_file_contents(file, expected_etag, session).await? {
+        entry
+            .delete()
+            .execute(session)
+            .await
+            .map_err(MeowithDataError::from)?;
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+pub async fn restore_trashed_file(
+    entry: &TrashedFile,
+    file: &File,
+    bucket: &Bucket,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    file.insert()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    let _ = try_join!(
+        entry
+            .delete()
+            .execute(session)
+            .map_err(MeowithDataError::from),
+        update_bucket_space(bucket.clone(), 1, 0, session)
+    )?;
+
+    Ok(())
+}
+
+pub async fn purge_trashed_file(
+    entry: &TrashedFile,
+    bucket: Option<&Bucket>,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    entry
+        .delete()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    if let Some(bucket) = bucket {
+        update_bucket_space(bucket.clone(), 0, -entry.size, session).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn get_trashed_files(
+    bucket_id: Uuid,
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<TrashedFile>, MeowithDataError> {
+    TrashedFile::find_by_bucket_id(bucket_id)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+/// Lists the trash of the bucket following the `(path, id)` clustering key `after`.
+pub async fn get_trashed_files_after(
+    bucket_id: Uuid,
+    after: (String, Uuid),
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<TrashedFile>, MeowithDataError> {
+    find_trashed_file!(
+        "bucket_id = ? AND (path, id) > (?, ?)",
+        (bucket_id, after.0, after.1)
+    )
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
+/// Lists the trash entries of the path, which may have been trashed more than once.
+pub async fn get_trashed_files_at(
+    bucket_id: Uuid,
+    path: String,
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<TrashedFile>, MeowithDataError> {
+    find_trashed_file!("bucket_id = ? AND path = ?", (bucket_id, path))
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+/// Lists the trash entries beneath the directory, the whole trash for the root.
+pub async fn get_trashed_files_within(
+    bucket_id: Uuid,
+    directory: &str,
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<TrashedFile>, MeowithDataError> {
+    if directory.is_empty() {
+        return get_trashed_files(bucket_id, session).await;
+    }
+    // '0' directly follows '/', so the range covers exactly the paths prefixed with the directory
+    find_trashed_file!(
+        "bucket_id = ? AND path >= ? AND path < ?",
+        (bucket_id, format!("{directory}/"), format!("{directory}0"))
+    )
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
+pub async fn get_all_trashed_files(
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<TrashedFile>, MeowithDataError> {
+    TrashedFile::find_all()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn update_trashed_file_chunks(
+    entry: &TrashedFile,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    UpdateTrashedFileChunks {
+        bucket_id: entry.bucket_id,
+        path: entry.path.clone(),
+        id: entry.id,
+        chunk_ids: entry.chunk_ids.clone(),
+    }
+    .update()
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
+pub async fn maybe_get_trashed_file_by_file_id(
+    bucket_id: Uuid,
+    file_id: Uuid,
+    session: &CachingSession,
+) -> Result<Option<TrashedFile>, MeowithDataError> {
+    TrashedFile::maybe_find_first_by_bucket_id_and_file_id(bucket_id, file_id)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn maybe_get_first_trashed_file(
+    bucket_id: Uuid,
+    session: &CachingSession,
+) -> Result<Option<TrashedFile>, MeowithDataError> {
+    TrashedFile::maybe_find_first_by_bucket_id(bucket_id)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn insert_upload_session(
+    bucket_upload_session: &BucketUploadSession,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    bucket_upload_session
+        .insert()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn get_upload_session(
+    app_id: Uuid,
+    bucket: Uuid,
+    id: Uuid,
+    session: &CachingSession,
+) -> Result<BucketUploadSession, MeowithDataError> {
+    BucketUploadSession::find_first_by_app_id_and_bucket_and_id(app_id, bucket, id)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn get_upload_sessions(
+    app_id: Uuid,
+    bucket: Uuid,
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<BucketUploadSession>, MeowithDataError> {
+    BucketUploadSession::find_by_app_id_and_bucket(app_id, bucket)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn delete_upload_session(
+    bucket_upload_session: &BucketUploadSession,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    bucket_upload_session
+        .delete()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
 
 pub async fn delete_upload_session_by(
     app_id: Uuid,
     bucket_id: Uuid,
-    id: Uuid,
+    id: Uuid,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    BucketUploadSession::delete_by_app_id_and_bucket_and_id(app_id, bucket_id, id)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn try_update_upload_session(
+    upload_session: &mut BucketUploadSession,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    // We are updating all fields to ensure their ttl is consistent
+    let update_query = concat!(
+    update_bucket_upload_session_query!("file_id = ?, path = ?, size = ?, durable = ?, fragments = ?, last_access = ?, state = ?, metadata = ?"),
+    " IF last_access = ?",
+    );
+
+    let last_access = upload_session.last_access;
+    upload_session.last_access = Utc::now();
+    let result = session
+        .execute_unpaged(
+            update_query,
+            (
+                &upload_session.file_id,
+                &upload_session.path,
+                &upload_session.size,
+                &upload_session.durable,
+                &upload_session.fragments,
+                &upload_session.last_access,
+                &upload_session.state,
+                &upload_session.metadata,
+                &upload_session.app_id,
+                &upload_session.bucket,
+                &upload_session.id,
+                last_access,
+            ),
+        )
+        .await?
+        .into_rows_result()?;
+    trace!("try_update_upload_session {:?}", result);
+    let mut rows = result.rows()?;
+
+    if let Some(row) = rows.next() {
+        let (applied, db_last_access): (bool, Timestamp) = row?;
+        trace!("res {applied} {db_last_access} {upload_session:?}");
+        return if applied {
+            Ok(())
+        } else {
+            upload_session.last_access = db_last_access;
+            Err(MeowithDataError::LockingError)
+        };
+    }
+
+    trace!("No result rows for try_update_upload_session");
+    Err(MeowithDataError::LockingError)
+}
+
+/// Writes the whole job, creating it if needed.
+pub async fn save_bucket_job(
+    job: &BucketJob,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    // Indexed first, so that a running job is always found by the recovery.
+    if job.is_running() {
+        RunningBucketJob::of(job)
+            .insert()
+            .execute(session)
+            .await
+            .map_err(MeowithDataError::from)?;
+    }
+    job.insert()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    Ok(())
+}
+
+/// Writes the whole job, as long as it is still held by its holder.
+/// Returns false once another node took the job over.
+pub async fn try_save_held_bucket_job(
+    job: &BucketJob,
+    session: &CachingSession,
+) -> Result<bool, MeowithDataError> {
+    let result = session
+        .execute_unpaged(
+            UPDATE_HELD_BUCKET_JOB_QUERY,
+            (
+                job.app_id,
+                job.kind,
+                job.state,
+                &job.path,
+                job.holder,
+                job.total_files,
+                job.processed_files,
+                job.failed_files,
+                job.deleted_size,
+                &job.errors,
+                job.created,
+                job.last_update,
+                job.bucket_id,
+                job.id,
+                job.holder,
+            ),
+        )
+        .await?
+        .into_rows_result()?;
+    let applied = lwt_applied(result.rows::<Row>()?.next().transpose()?);
+    if applied && !job.is_running() {
+        RunningBucketJob::of(job)
+            .delete()
+            .execute(session)
+            .await
+            .map_err(MeowithDataError::from)?;
+    }
+    Ok(applied)
+}
+
+/// Writes the whole job over the `previous` read of it,
+/// unless it changed hands or was updated since.
+pub async fn try_replace_bucket_job(
+    job: &BucketJob,
+    previous: &BucketJob,
+    session: &CachingSession,
+) -> Result<bool, MeowithDataError> {
+    let result = session
+        .execute_unpaged(
+            REPLACE_BUCKET_JOB_QUERY,
+            (
+                job.app_id,
+                job.kind,
+                job.state,
+                &job.path,
+                job.holder,
+                job.total_files,
+                job.processed_files,
+                job.failed_files,
+                job.deleted_size,
+                &job.errors,
+                job.created,
+                job.last_update,
+                job.bucket_id,
+                job.id,
+                previous.holder,
+                previous.last_update,
+            ),
+        )
+        .await?
+        .into_rows_result()?;
+    let applied = lwt_applied(result.rows::<Row>()?.next().transpose()?);
+    if applied && job.is_running() {
+        // Renews the index entry, which would otherwise expire ahead of a long running job.
+        RunningBucketJob::of(job)
+            .insert()
+            .execute(session)
+            .await
+            .map_err(MeowithDataError::from)?;
+    }
+    Ok(applied)
+}
+
+pub async fn get_bucket_job(
+    bucket_id: Uuid,
+    id: Uuid,
+    session: &CachingSession,
+) -> Result<BucketJob, MeowithDataError> {
+    BucketJob::find_by_bucket_id_and_id(bucket_id, id)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn get_bucket_jobs(
+    bucket_id: Uuid,
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<BucketJob>, MeowithDataError> {
+    BucketJob::find_by_bucket_id(bucket_id)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn get_running_bucket_jobs(
+    kind: JobKind,
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<RunningBucketJob>, MeowithDataError> {
+    RunningBucketJob::find_by_kind(kind.into())
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn delete_running_bucket_job(
+    running: &RunningBucketJob,
     session: &CachingSession,
 ) -> Result<QueryResult, MeowithDataError> {
-    BucketUploadSession::delete_by_app_id_and_bucket_and_id(app_id, bucket_id, id)
+    running
+        .delete()
         .execute(session)
         .await
         .map_err(MeowithDataError::from)
 }
 
-pub async fn try_update_upload_session(
-    upload_session: &mut BucketUploadSession,
+pub async fn maybe_get_file_leases(
+    bucket_id: Uuid,
+    path: &str,
     session: &CachingSession,
-) -> Result<(), MeowithDataError> {
-    // We are updating all fields to ensure their ttl is consistent
-    let update_query = concat!(
-    update_bucket_upload_session_query!("file_id = ?, path = ?, size = ?, durable = ?, fragments = ?, last_access = ?, state = ?"),
-    " IF last_access = ?",
-    );
+) -> Result<Option<FileLeases>, MeowithDataError> {
+    FileLeases::maybe_find_first_by_bucket_id_and_path(bucket_id, path.to_string())
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
 
-    let last_access = upload_session.last_access;
-    upload_session.last_access = Utc::now();
+/// The leases on every path beneath the directory, an empty path being the root.
+pub async fn get_file_leases_within(
+    bucket_id: Uuid,
+    directory: &str,
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<FileLeases>, MeowithDataError> {
+    if directory.is_empty() {
+        return FileLeases::find_by_bucket_id(bucket_id)
+            .execute(session)
+            .await
+            .map_err(MeowithDataError::from);
+    }
+    // '0' directly follows '/', so the range covers exactly the paths prefixed with the directory
+    find_file_leases!(
+        "bucket_id = ? AND path >= ? AND path < ?",
+        (bucket_id, format!("{directory}/"), format!("{directory}0"))
+    )
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
+/// Writes the leases for `ttl` seconds, unless they changed since `expected_version` was read.
+/// `None` expects no leases to be present.
+/// Returns whether the write was applied.
+pub async fn try_save_file_leases(
+    leases: &FileLeases,
+    expected_version: Option<Uuid>,
+    ttl: i32,
+    session: &CachingSession,
+) -> Result<bool, MeowithDataError> {
+    let result = match expected_version {
+        None => {
+            session
+                .execute_unpaged(
+                    INSERT_FILE_LEASES_QUERY,
+                    (
+                        leases.bucket_id,
+                        &leases.path,
+                        &leases.leases,
+                        leases.version,
+                        ttl,
+                    ),
+                )
+                .await?
+        }
+        Some(expected_version) => {
+            session
+                .execute_unpaged(
+                    UPDATE_FILE_LEASES_QUERY,
+                    (
+                        ttl,
+                        &leases.leases,
+                        leases.version,
+                        leases.bucket_id,
+                        &leases.path,
+                        expected_version,
+                    ),
+                )
+                .await?
+        }
+    }
+    .into_rows_result()?;
+    Ok(lwt_applied(result.rows::<Row>()?.next().transpose()?))
+}
+
+/// Removes the leases, unless they changed since `expected_version` was read.
+/// Returns whether the removal was applied.
+pub async fn try_delete_file_leases(
+    bucket_id: Uuid,
+    path: &str,
+    expected_version: Uuid,
+    session: &CachingSession,
+) -> Result<bool, MeowithDataError> {
     let result = session
         .execute_unpaged(
-            update_query,
-            (
-                &upload_session.file_id,
-                &upload_session.path,
-                &upload_session.size,
-                &upload_session.durable,
-                &upload_session.fragments,
-                &upload_session.last_access,
-                &upload_session.state,
-                &upload_session.app_id,
-                &upload_session.bucket,
-                &upload_session.id,
-                last_access,
-            ),
+            DELETE_FILE_LEASES_QUERY,
+            (bucket_id, path, expected_version),
         )
         .await?
         .into_rows_result()?;
-    trace!("try_update_upload_session {:?}", result);
-    let mut rows = result.rows()?;
+    Ok(lwt_applied(result.rows::<Row>()?.next().transpose()?))
+}
 
-    if let Some(row) = rows.next() {
-        let (applied, db_last_access): (bool, Timestamp) = row?;
-        trace!("res {applied} {db_last_access} {upload_session:?}");
-        return if applied {
-            Ok(())
-        } else {
-            upload_session.last_access = db_last_access;
-            Err(MeowithDataError::LockingError)
-        };
-    }
+pub async fn delete_bucket_leases(
+    bucket_id: Uuid,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    FileLeases::delete_by_bucket_id(bucket_id)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
 
-    trace!("No result rows for try_update_upload_session");
-    Err(MeowithDataError::LockingError)
+/// Writes the whole delivery, creating it if needed.
+pub async fn save_bucket_notification(
+    notification: &BucketNotification,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    notification
+        .insert()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    Ok(())
+}
+
+pub async fn delete_bucket_notification(
+    notification: &BucketNotification,
+    session: &CachingSession,
+) -> Result<QueryResult, MeowithDataError> {
+    notification
+        .delete()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+/// Moves the delivery to its new `next_attempt`, which is a part of its key.
+pub async fn reschedule_bucket_notification(
+    previous_attempt: DateTime<Utc>,
+    notification: &BucketNotification,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    // the row only moves within the shard partition, so the batch stays atomic
+    BucketNotification::batch()
+        .append_delete(&BucketNotification {
+            next_attempt: previous_attempt,
+            ..notification.clone()
+        })
+        .append_insert(notification)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    Ok(())
+}
+
+/// The deliveries of the shard due by `now`, the longest overdue first.
+pub async fn get_due_bucket_notifications(
+    shard: i8,
+    now: DateTime<Utc>,
+    limit: i32,
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<BucketNotification>, MeowithDataError> {
+    find_bucket_notification!(
+        "shard = ? AND next_attempt <= ? LIMIT ?",
+        (shard, now, limit)
+    )
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
+pub async fn insert_bucket_change(
+    change: &BucketChange,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    change
+        .insert()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    Ok(())
+}
+
+/// Lists the changes of a single period following the `(occurred, id)` clustering key `after`.
+pub async fn get_bucket_changes_after(
+    bucket_id: Uuid,
+    period: i64,
+    after: (DateTime<Utc>, Uuid),
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<BucketChange>, MeowithDataError> {
+    find_bucket_change!(
+        "bucket_id = ? AND period = ? AND (occurred, id) > (?, ?)",
+        (bucket_id, period, after.0, after.1)
+    )
+    .execute(session)
+    .await
+    .map_err(MeowithDataError::from)
+}
+
+/// Writes the whole snapshot, creating it if needed.
+pub async fn save_bucket_snapshot(
+    snapshot: &BucketSnapshot,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    snapshot
+        .insert()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    Ok(())
+}
+
+pub async fn get_bucket_snapshot(
+    bucket_id: Uuid,
+    id: Uuid,
+    session: &CachingSession,
+) -> Result<BucketSnapshot, MeowithDataError> {
+    BucketSnapshot::find_by_bucket_id_and_id(bucket_id, id)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn get_bucket_snapshots(
+    bucket_id: Uuid,
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<BucketSnapshot>, MeowithDataError> {
+    BucketSnapshot::find_by_bucket_id(bucket_id)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn get_all_bucket_snapshots(
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<BucketSnapshot>, MeowithDataError> {
+    BucketSnapshot::find_all()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn maybe_get_first_bucket_snapshot(
+    bucket_id: Uuid,
+    session: &CachingSession,
+) -> Result<Option<BucketSnapshot>, MeowithDataError> {
+    BucketSnapshot::maybe_find_first_by_bucket_id(bucket_id)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn delete_bucket_snapshot(
+    snapshot: &BucketSnapshot,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    snapshot
+        .delete()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    Ok(())
+}
+
+/// Copies the file into the snapshot, leaving the space accounting of the bucket untouched.
+pub async fn insert_snapshot_file(
+    file: &File,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    file.insert()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    Ok(())
+}
+
+pub async fn delete_snapshot_file(
+    file: &File,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    file.delete()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    Ok(())
+}
+
+pub async fn insert_snapshot_chunk(
+    chunk: &SnapshotChunk,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    chunk
+        .insert()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    Ok(())
+}
+
+pub async fn delete_snapshot_chunk(
+    chunk: &SnapshotChunk,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    chunk
+        .delete()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    Ok(())
+}
+
+/// Any snapshot still referencing the chunk.
+pub async fn maybe_get_first_snapshot_chunk(
+    chunk_id: Uuid,
+    session: &CachingSession,
+) -> Result<Option<SnapshotChunk>, MeowithDataError> {
+    SnapshotChunk::maybe_find_first_by_chunk_id(chunk_id)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+/// The holds of the snapshots on any of the chunks.
+/// The amount of chunks per query is bounded by the partition key restrictions of the cluster.
+pub async fn get_snapshot_chunks(
+    chunk_ids: Vec<Uuid>,
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<SnapshotChunk>, MeowithDataError> {
+    find_snapshot_chunk!("chunk_id IN ?", (chunk_ids,))
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn insert_snapshot_pending_chunk(
+    chunk: &SnapshotPendingChunk,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    chunk
+        .insert()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    Ok(())
+}
+
+pub async fn delete_snapshot_pending_chunk(
+    chunk: &SnapshotPendingChunk,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    chunk
+        .delete()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)?;
+    Ok(())
+}
+
+pub async fn get_snapshot_pending_chunks(
+    snapshot_id: Uuid,
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<SnapshotPendingChunk>, MeowithDataError> {
+    SnapshotPendingChunk::find_by_snapshot_id(snapshot_id)
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
+}
+
+pub async fn get_all_snapshot_pending_chunks(
+    session: &CachingSession,
+) -> Result<CharybdisModelStream<SnapshotPendingChunk>, MeowithDataError> {
+    SnapshotPendingChunk::find_all()
+        .execute(session)
+        .await
+        .map_err(MeowithDataError::from)
 }
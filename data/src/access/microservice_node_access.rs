@@ -11,9 +11,14 @@ use scylla::client::caching_session::CachingSession;
 use scylla::client::pager::TypedRowStream;
 use scylla::errors::PagerExecutionError;
 use scylla::response::query_result::QueryResult;
+use scylla::value::{CqlValue, Row};
 
 static GET_ALL_NODES_QUERY: &str = "SELECT microservice_type, id, max_space, used_space, access_token, access_token_issued_at, renewal_token, address, created, register_code FROM microservice_nodes";
 static GET_ALL_CODES_QUERY: &str = "SELECT code, created, valid FROM service_register_codes";
+static ACQUIRE_WORKER_LEASE_QUERY: &str =
+    "INSERT INTO worker_leases (name, holder) VALUES (?, ?) IF NOT EXISTS USING TTL ?";
+static RENEW_WORKER_LEASE_QUERY: &str =
+    "UPDATE worker_leases USING TTL ? SET holder = ? WHERE name = ? IF holder = ?";
 
 partial_microservice_node!(
     UpdateMicroservice,
@@ -149,3 +154,30 @@ pub async fn update_service_access_token(
     };
     update.update().execute(session).await.map_err(|e| e.into())
 }
+
+/// Attempts to acquire, or renew if already held, the named worker lease for `ttl` seconds.
+/// Returns whether the lease is held by `holder` afterward.
+pub async fn try_acquire_worker_lease(
+    name: &str,
+    holder: Uuid,
+    ttl: i32,
+    session: &CachingSession,
+) -> Result<bool, MeowithDataError> {
+    let acquired = session
+        .execute_unpaged(ACQUIRE_WORKER_LEASE_QUERY, (name, holder, ttl))
+        .await?
+        .into_rows_result()?;
+    if lwt_applied(acquired.rows::<Row>()?.next().transpose()?) {
+        return Ok(true);
+    }
+
+    let renewed = session
+        .execute_unpaged(RENEW_WORKER_LEASE_QUERY, (ttl, holder, name, holder))
+        .await?
+        .into_rows_result()?;
+    Ok(lwt_applied(renewed.rows::<Row>()?.next().transpose()?))
+}
+
+pub(crate) fn lwt_applied(row: Option<Row>) -> bool {
+    row.is_some_and(|row| matches!(row.columns.first(), Some(Some(CqlValue::Boolean(true)))))
+}
@@ -1,4 +1,5 @@
 pub mod app_access;
+pub mod audit_access;
 pub mod file_access;
 pub mod microservice_node_access;
 pub mod user_access;
@@ -9,12 +9,22 @@ pub struct GeneralConfiguration {
     pub login_methods: Vec<String>,
     pub cat_id_config: Option<CatIdAppConfiguration>,
     pub fs_limits: FsLimitConfiguration,
+    /// Lets webhooks target loopback, private and link-local addresses, meant for local setups.
+    #[serde(default)]
+    pub allow_private_webhooks: bool,
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct FsLimitConfiguration {
     pub max_path_length: u32,
     pub max_directory_depth: u32,
+    /// The largest zip archive accepted for extraction, as zip archives are buffered whole on disk.
+    #[serde(default = "default_max_zip_archive_size")]
+    pub max_zip_archive_size: u64,
+}
+
+fn default_max_zip_archive_size() -> u64 {
+    4 * 1024 * 1024 * 1024
 }
 
 impl FsLimitConfiguration {
@@ -22,6 +32,7 @@ impl FsLimitConfiguration {
         Self {
             max_path_length: 4096,
             max_directory_depth: 256,
+            max_zip_archive_size: default_max_zip_archive_size(),
         }
     }
 
@@ -101,6 +112,7 @@ impl GeneralConfiguration {
             login_methods: vec!["BASIC".to_string()],
             cat_id_config: None,
             fs_limits: Default::default(),
+            allow_private_webhooks: false,
         }
     }
 }
@@ -1,17 +1,33 @@
 use crate::dto::controller::UpdateStorageNodeProperties;
-use crate::model::app_model::{App, AppByOwner, AppMember, AppToken, MemberByUser, UserRole};
-use crate::model::file_model::{Bucket, BucketUploadSession};
+use crate::model::app_model::{
+    App, AppByOwner, AppMember, AppToken, MemberByUser, RateLimits, UserRole,
+};
+use crate::model::audit_model::{AuditActorKind, AuditEvent};
+use crate::model::file_model::{
+    Bucket, BucketChange, BucketEventKind, BucketJob, BucketNotification, BucketSnapshot,
+    BucketUploadSession, CorsRule, FileMetadata, FileVersion, JobKind, JobState, LifecycleAction,
+    LifecycleCondition, LifecycleRule, NotificationConfig, PublicAccess, SnapshotState,
+    TrashedFile,
+};
 use crate::model::microservice_node_model::ServiceRegisterCode;
 use crate::model::user_model::{User, UsersByName};
 use charybdis::types::{BigInt, Boolean, Text, Timestamp};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EntityList {
     pub entities: Vec<Entity>,
+    /// The names grouped by the delimiter of the listing, see [Entity::name].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub common_prefixes: Vec<String>,
+    /// Continues the listing, present only when there may be more entities.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -27,6 +43,62 @@ pub struct Entity {
     pub is_dir: bool,
     pub created: DateTime<Utc>,
     pub last_modified: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub metadata: Option<FileMetadataDto>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub etag: Option<String>,
+    /// The file cannot be overwritten, renamed or deleted before then.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub retain_until: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub legal_hold: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct FileMetadataDto {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub content_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub content_disposition: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub cache_control: Option<String>,
+    /// User defined key value pairs, returned as `x-meowith-meta-{key}` headers on download.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    #[serde(default)]
+    pub user: HashMap<String, String>,
+}
+
+impl From<FileMetadata> for FileMetadataDto {
+    fn from(value: FileMetadata) -> Self {
+        FileMetadataDto {
+            content_type: value.content_type,
+            content_disposition: value.content_disposition,
+            cache_control: value.cache_control,
+            user: value.user.unwrap_or_default(),
+        }
+    }
+}
+
+impl From<FileMetadataDto> for FileMetadata {
+    fn from(value: FileMetadataDto) -> Self {
+        FileMetadata {
+            content_type: value.content_type,
+            content_disposition: value.content_disposition,
+            cache_control: value.cache_control,
+            user: if value.user.is_empty() {
+                None
+            } else {
+                Some(value.user)
+            },
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -121,6 +193,13 @@ pub struct BucketDto {
     pub space_taken: BigInt,
     pub created: Timestamp,
     pub last_modified: Timestamp,
+    pub versioning: Boolean,
+    /// Seconds deleted files are kept in the trash, 0 if the trash is disabled.
+    pub trash_retention: BigInt,
+    /// Seconds newly written files are protected for, 0 if the object lock is disabled.
+    pub object_lock_retention: BigInt,
+    /// The bucket is being removed by a deletion job and can no longer be accessed.
+    pub deleting: Boolean,
 }
 
 impl From<Bucket> for BucketDto {
@@ -136,10 +215,423 @@ impl From<Bucket> for BucketDto {
             space_taken: value.space_taken,
             created: value.created,
             last_modified: value.last_modified,
+            versioning: value.versioning.unwrap_or(false),
+            trash_retention: value.trash_retention.unwrap_or(0),
+            object_lock_retention: value.object_lock_retention.unwrap_or(0),
+            deleting: value.deleting.unwrap_or(false),
         }
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileVersionList {
+    pub versions: Vec<FileVersionDto>,
+    /// Continues the listing after the last returned version, present only when there may be more.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub next_cursor: Option<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileVersionDto {
+    pub version_id: Uuid,
+    pub size: u64,
+    pub created: DateTime<Utc>,
+    pub last_modified: DateTime<Utc>,
+    pub archived: DateTime<Utc>,
+}
+
+impl From<FileVersion> for FileVersionDto {
+    fn from(value: FileVersion) -> Self {
+        FileVersionDto {
+            version_id: value.version_id,
+            size: value.size as u64,
+            created: value.created,
+            last_modified: value.last_modified,
+            archived: value.archived,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileVersionRequest {
+    pub version_id: Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FileVersionSelector {
+    #[serde(default)]
+    pub version_id: Option<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrashList {
+    pub entries: Vec<TrashedFileDto>,
+    /// Continues the listing after the last returned entry, present only when there may be more.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrashedFileDto {
+    pub id: Uuid,
+    pub path: String,
+    pub size: u64,
+    pub created: DateTime<Utc>,
+    pub last_modified: DateTime<Utc>,
+    pub deleted: DateTime<Utc>,
+    pub expires: DateTime<Utc>,
+}
+
+impl From<TrashedFile> for TrashedFileDto {
+    fn from(value: TrashedFile) -> Self {
+        TrashedFileDto {
+            id: value.id,
+            path: value.path,
+            size: value.size as u64,
+            created: value.created,
+            last_modified: value.last_modified,
+            deleted: value.deleted,
+            expires: value.expires,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrashRestoreResponse {
+    pub restored: u64,
+    /// Entries not restored, as a file already exists under their path.
+    pub skipped: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LifecycleRuleList {
+    pub rules: Vec<LifecycleRuleDto>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LifecycleRuleDto {
+    pub id: Uuid,
+    pub prefix: Text,
+    pub action: LifecycleAction,
+    pub condition: LifecycleCondition,
+    /// Seconds
+    pub age: BigInt,
+}
+
+impl TryFrom<LifecycleRule> for LifecycleRuleDto {
+    type Error = ();
+
+    fn try_from(value: LifecycleRule) -> Result<Self, Self::Error> {
+        Ok(LifecycleRuleDto {
+            id: value.id,
+            prefix: value.prefix,
+            action: LifecycleAction::try_from(value.action).map_err(|_| ())?,
+            condition: LifecycleCondition::try_from(value.condition).map_err(|_| ())?,
+            age: value.age,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NotificationConfigList {
+    pub configs: Vec<NotificationConfigDto>,
+}
+
+/// A webhook of a bucket, the signing secret is never returned.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NotificationConfigDto {
+    pub id: Uuid,
+    pub events: Vec<BucketEventKind>,
+    pub prefix: Text,
+    pub url: Text,
+}
+
+impl From<NotificationConfig> for NotificationConfigDto {
+    fn from(value: NotificationConfig) -> Self {
+        NotificationConfigDto {
+            id: value.id,
+            events: value
+                .events
+                .into_iter()
+                .filter_map(|kind| BucketEventKind::try_from(kind).ok())
+                .collect(),
+            prefix: value.prefix,
+            url: value.url,
+        }
+    }
+}
+
+/// The anonymous read access of a bucket, disabled when there are no prefixes.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PublicAccessDto {
+    pub prefixes: Vec<String>,
+    pub listing: bool,
+    pub requests_per_minute: u32,
+}
+
+impl From<PublicAccess> for PublicAccessDto {
+    fn from(value: PublicAccess) -> Self {
+        PublicAccessDto {
+            prefixes: value.prefixes,
+            listing: value.listing,
+            requests_per_minute: value.requests_per_minute as u32,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CorsRuleList {
+    pub rules: Vec<CorsRuleDto>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CorsRuleDto {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    #[serde(default)]
+    pub allowed_headers: Vec<String>,
+    #[serde(default)]
+    pub expose_headers: Vec<String>,
+    /// Seconds
+    #[serde(default)]
+    pub max_age: u32,
+}
+
+impl From<CorsRule> for CorsRuleDto {
+    fn from(value: CorsRule) -> Self {
+        CorsRuleDto {
+            allowed_origins: value.allowed_origins,
+            allowed_methods: value.allowed_methods,
+            allowed_headers: value.allowed_headers,
+            expose_headers: value.expose_headers,
+            max_age: value.max_age as u32,
+        }
+    }
+}
+
+/// The body of a webhook delivery.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BucketEventDto {
+    /// Identifies the delivery, retries repeat it.
+    pub id: Uuid,
+    pub event: BucketEventKind,
+    pub app_id: Uuid,
+    pub bucket_id: Uuid,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub old_path: Option<String>,
+    pub directory: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub size: Option<BigInt>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub etag: Option<String>,
+    pub time: DateTime<Utc>,
+}
+
+impl TryFrom<BucketNotification> for BucketEventDto {
+    type Error = ();
+
+    fn try_from(value: BucketNotification) -> Result<Self, Self::Error> {
+        Ok(BucketEventDto {
+            id: value.id,
+            event: BucketEventKind::try_from(value.kind).map_err(|_| ())?,
+            app_id: value.app_id,
+            bucket_id: value.bucket_id,
+            path: value.path,
+            old_path: value.old_path,
+            directory: value.directory,
+            size: value.size,
+            etag: value.etag,
+            time: value.occurred,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BucketChangeList {
+    pub changes: Vec<BucketChangeDto>,
+    /// Continues the feed after the returned changes, also present when there are none.
+    pub cursor: String,
+    /// Whether more changes are available right away, the limit having been reached.
+    pub has_more: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BucketChangeDto {
+    pub id: Uuid,
+    pub event: BucketEventKind,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub old_path: Option<String>,
+    pub directory: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub size: Option<BigInt>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub etag: Option<String>,
+    pub time: DateTime<Utc>,
+}
+
+impl TryFrom<BucketChange> for BucketChangeDto {
+    type Error = ();
+
+    fn try_from(value: BucketChange) -> Result<Self, Self::Error> {
+        Ok(BucketChangeDto {
+            id: value.id,
+            event: BucketEventKind::try_from(value.kind).map_err(|_| ())?,
+            path: value.path,
+            old_path: value.old_path,
+            directory: value.directory,
+            size: value.size,
+            etag: value.etag,
+            time: value.occurred,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditEventList {
+    /// The most recent first.
+    pub events: Vec<AuditEventDto>,
+    /// Continues with the events preceding the returned ones, absent once there are none left.
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditEventDto {
+    pub id: Uuid,
+    pub time: DateTime<Utc>,
+    pub app_id: Uuid,
+    pub actor_kind: AuditActorKind,
+    /// The user, or the issuer of the token.
+    pub actor_id: Uuid,
+    pub actor_name: String,
+    pub action: String,
+    #[serde(default)]
+    pub bucket_id: Option<Uuid>,
+    #[serde(default)]
+    pub path: Option<String>,
+    pub status: u16,
+    #[serde(default)]
+    pub source_ip: Option<String>,
+}
+
+impl TryFrom<AuditEvent> for AuditEventDto {
+    type Error = ();
+
+    fn try_from(value: AuditEvent) -> Result<Self, Self::Error> {
+        Ok(AuditEventDto {
+            id: value.id,
+            time: value.occurred,
+            app_id: value.app_id,
+            actor_kind: AuditActorKind::try_from(value.actor_kind).map_err(|_| ())?,
+            actor_id: value.actor_id,
+            actor_name: value.actor_name,
+            action: value.action,
+            bucket_id: value.bucket_id,
+            path: value.path,
+            status: value.status as u16,
+            source_ip: value.source_ip,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BucketJobList {
+    pub jobs: Vec<BucketJobDto>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BucketJobDto {
+    pub id: Uuid,
+    pub kind: JobKind,
+    pub state: JobState,
+    pub path: String,
+    pub total_files: u64,
+    pub processed_files: u64,
+    pub failed_files: u64,
+    pub deleted_size: u64,
+    pub errors: Vec<String>,
+    pub created: DateTime<Utc>,
+    pub last_update: DateTime<Utc>,
+}
+
+impl TryFrom<BucketJob> for BucketJobDto {
+    type Error = ();
+
+    fn try_from(value: BucketJob) -> Result<Self, Self::Error> {
+        Ok(BucketJobDto {
+            id: value.id,
+            kind: JobKind::try_from(value.kind).map_err(|_| ())?,
+            state: JobState::try_from(value.state).map_err(|_| ())?,
+            path: value.path,
+            total_files: value.total_files as u64,
+            processed_files: value.processed_files as u64,
+            failed_files: value.failed_files as u64,
+            deleted_size: value.deleted_size as u64,
+            errors: value.errors.unwrap_or_default(),
+            created: value.created,
+            last_update: value.last_update,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateSnapshotRequest {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RestoreSnapshotRequest {
+    /// The empty bucket of the same app the snapshot is restored into.
+    pub bucket_id: Uuid,
+}
+
+/// Browses a snapshot of the bucket instead of its current contents.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SnapshotSelector {
+    #[serde(default)]
+    pub snapshot_id: Option<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BucketSnapshotList {
+    pub snapshots: Vec<BucketSnapshotDto>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BucketSnapshotDto {
+    pub id: Uuid,
+    pub name: String,
+    pub state: SnapshotState,
+    pub file_count: u64,
+    pub space_taken: u64,
+    pub created: DateTime<Utc>,
+}
+
+impl TryFrom<BucketSnapshot> for BucketSnapshotDto {
+    type Error = ();
+
+    fn try_from(value: BucketSnapshot) -> Result<Self, Self::Error> {
+        Ok(BucketSnapshotDto {
+            id: value.id,
+            name: value.name,
+            state: SnapshotState::try_from(value.state).map_err(|_| ())?,
+            file_count: value.file_count as u64,
+            space_taken: value.space_taken as u64,
+            created: value.created,
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UploadSessionStartResponse {
     /// To be used in the path
@@ -155,6 +647,8 @@ pub struct UploadSessionStartResponse {
 pub struct UploadSessionRequest {
     /// Entry size in bytes
     pub size: u64,
+    #[serde(default)]
+    pub metadata: Option<FileMetadataDto>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -279,6 +773,7 @@ pub struct AppTokenDTO {
     pub last_modified: DateTime<Utc>,
     pub issuer_id: Uuid,
     pub name: String,
+    pub limits: Option<RateLimitsDto>,
 }
 
 impl From<AppToken> for AppTokenDTO {
@@ -288,6 +783,7 @@ impl From<AppToken> for AppTokenDTO {
             last_modified: value.last_modified,
             issuer_id: value.issuer_id,
             name: value.name,
+            limits: value.limits.map(RateLimitsDto::from),
         }
     }
 }
@@ -308,6 +804,50 @@ pub struct TokenIssueRequest {
     pub app_id: Uuid,
     pub name: String,
     pub perms: Vec<ScopedPermission>,
+    /// Applied on top of the limits of the app.
+    #[serde(default)]
+    pub limits: Option<RateLimitsDto>,
+}
+
+/// The traffic allowed to an app or a token across the cluster, zero leaves a limit unset.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct RateLimitsDto {
+    #[serde(default)]
+    pub requests_per_second: u32,
+    #[serde(default)]
+    pub concurrent_transfers: u32,
+    #[serde(default)]
+    pub upload_bytes_per_second: u64,
+    #[serde(default)]
+    pub download_bytes_per_second: u64,
+}
+
+impl RateLimitsDto {
+    pub fn is_unlimited(&self) -> bool {
+        *self == RateLimitsDto::default()
+    }
+}
+
+impl From<RateLimits> for RateLimitsDto {
+    fn from(value: RateLimits) -> Self {
+        RateLimitsDto {
+            requests_per_second: value.requests_per_second as u32,
+            concurrent_transfers: value.concurrent_transfers as u32,
+            upload_bytes_per_second: value.upload_bytes_per_second as u64,
+            download_bytes_per_second: value.download_bytes_per_second as u64,
+        }
+    }
+}
+
+impl From<RateLimitsDto> for RateLimits {
+    fn from(value: RateLimitsDto) -> Self {
+        RateLimits {
+            requests_per_second: value.requests_per_second as i32,
+            concurrent_transfers: value.concurrent_transfers as i32,
+            upload_bytes_per_second: value.upload_bytes_per_second as i64,
+            download_bytes_per_second: value.download_bytes_per_second as i64,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -315,11 +855,108 @@ pub struct RenameEntityRequest {
     pub to: String,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CopyEntityRequest {
+    pub to: String,
+    pub bucket_id: Option<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOperation {
+    Delete {
+        path: String,
+    },
+    Rename {
+        path: String,
+        to: String,
+    },
+    Mkdir {
+        path: String,
+    },
+    Copy {
+        path: String,
+        to: String,
+        #[serde(default)]
+        bucket_id: Option<Uuid>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchRequest {
+    pub operations: Vec<BatchOperation>,
+    /// Validate every operation before any of them is executed.
+    #[serde(default)]
+    pub atomic: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchItemResult {
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchResponse {
+    /// False if the atomic validation failed and nothing has been executed.
+    pub executed: bool,
+    pub results: Vec<BatchItemResult>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DeleteDirectoryRequest {
     pub recursive: bool,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ExtractionResult {
+    pub files: u64,
+    pub directories: u64,
+    /// Links and other entries without contents, which are not extracted.
+    pub skipped: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaseMode {
+    Shared,
+    Exclusive,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LeaseAcquireRequest {
+    pub mode: LeaseMode,
+    /// In seconds.
+    pub ttl: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LeaseRenewRequest {
+    pub id: Uuid,
+    /// In seconds, counted from the renewal.
+    pub ttl: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LeaseReleaseRequest {
+    pub id: Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LeaseDto {
+    pub id: Uuid,
+    pub mode: LeaseMode,
+    pub expires: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LegalHoldRequest {
+    /// Places the hold if true, removes it otherwise.
+    pub hold: bool,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct NodeStatusResponse {
     pub nodes: Vec<NodeStatus>,
@@ -1,5 +1,15 @@
-use charybdis::macros::{charybdis_model, charybdis_view_model};
-use charybdis::types::{BigInt, Set, Text, Timestamp, Tuple, Uuid};
+use charybdis::macros::{charybdis_model, charybdis_udt_model, charybdis_view_model};
+use charybdis::types::{BigInt, Boolean, Frozen, Int, Set, Text, Timestamp, Tuple, Uuid};
+
+/// The traffic allowed to an app or a token across the cluster, zero leaves a limit unset.
+#[charybdis_udt_model(type_name = ratelimits)]
+#[derive(Eq, PartialEq, Clone, Debug, Default)]
+pub struct RateLimits {
+    pub requests_per_second: Int,
+    pub concurrent_transfers: Int,
+    pub upload_bytes_per_second: BigInt,
+    pub download_bytes_per_second: BigInt,
+}
 
 #[charybdis_model(
     table_name = apps,
@@ -16,9 +26,20 @@ pub struct App {
     pub quota: BigInt,
     pub created: Timestamp,
     pub last_modified: Timestamp,
+    /// Set once a forced deletion is requested, the app is removed along with its last bucket.
+    pub deleting: Option<Boolean>,
+    pub limits: Option<Frozen<RateLimits>>,
+}
+
+impl App {
+    pub fn is_deleting(&self) -> bool {
+        self.deleting.unwrap_or(false)
+    }
 }
 
 partial_app!(UpdateAppQuota, id, quota);
+partial_app!(UpdateAppDeleting, id, deleting);
+partial_app!(UpdateAppLimits, id, limits);
 
 #[charybdis_view_model(
     table_name = apps_by_owner,
@@ -93,6 +114,8 @@ pub struct AppToken {
     pub nonce: Uuid,
     pub created: Timestamp,
     pub last_modified: Timestamp,
+    /// A copy of the limits carried by the token, for listing.
+    pub limits: Option<Frozen<RateLimits>>,
 }
 
 impl Default for App {
@@ -104,6 +127,8 @@ impl Default for App {
             quota: 0,
             created: Default::default(),
             last_modified: Default::default(),
+            deleting: None,
+            limits: None,
         }
     }
 }
@@ -0,0 +1,64 @@
+use charybdis::macros::charybdis_model;
+use charybdis::types::{BigInt, Int, Text, Timestamp, TinyInt, Uuid};
+use chrono::{DateTime, Utc};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use serde::{Deserialize, Serialize};
+
+/// Seconds the audit events of an app are grouped into a single partition for.
+pub const AUDIT_PERIOD_SECS: i64 = 86400;
+/// Seconds audit events are retained for, matching the ttl of [AuditEvent].
+pub const AUDIT_RETENTION_SECS: i64 = 7776000;
+
+/// A mutating request, recorded along with its outcome once it has been handled.
+/// Events are never updated, and are stored under the nil app when outside of any app,
+/// such as the management of nodes and users.
+#[charybdis_model(
+    table_name = audit_events,
+    partition_keys = [app_id, period],
+    clustering_keys = [occurred, id],
+    global_secondary_indexes = [],
+    local_secondary_indexes = [],
+    static_columns = [],
+    table_options = r#"default_time_to_live = 7776000;"#
+)]
+#[derive(Clone, Debug, Default)]
+pub struct AuditEvent {
+    pub app_id: Uuid,
+    /// See [AuditEvent::period_of].
+    pub period: BigInt,
+    pub occurred: Timestamp,
+    /// Orders the events which occurred within the same millisecond.
+    pub id: Uuid,
+    /// maps to [AuditActorKind]
+    pub actor_kind: TinyInt,
+    /// The user, or the issuer of the token.
+    pub actor_id: Uuid,
+    /// The name of the user or of the token.
+    pub actor_name: Text,
+    /// The method and path of the request.
+    pub action: Text,
+    pub bucket_id: Option<Uuid>,
+    /// The file or directory within the bucket.
+    pub path: Option<Text>,
+    /// The status code of the response.
+    pub status: Int,
+    pub source_ip: Option<Text>,
+}
+
+impl AuditEvent {
+    /// The partition events occurring at the given time are stored in.
+    pub fn period_of(time: DateTime<Utc>) -> i64 {
+        time.timestamp().div_euclid(AUDIT_PERIOD_SECS)
+    }
+}
+
+#[derive(
+    Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive, Clone, Copy, Serialize, Deserialize,
+)]
+#[repr(i8)]
+pub enum AuditActorKind {
+    /// A user of the dashboard or the controller.
+    User = 1i8,
+    /// An app token, used with the nodes.
+    Token = 2i8,
+}
@@ -1,7 +1,11 @@
 use crate::pathlib::join_parent_name;
 use charybdis::macros::{charybdis_model, charybdis_udt_model};
-use charybdis::types::{BigInt, Boolean, Frozen, Set, Text, Timestamp, TinyInt, Uuid};
+use charybdis::types::{
+    BigInt, Boolean, Frozen, Int, List, Map, Set, Text, Timestamp, TinyInt, Uuid,
+};
+use chrono::{DateTime, TimeDelta, Utc};
 use num_enum::{IntoPrimitive, TryFromPrimitive};
+use serde::{Deserialize, Serialize};
 use strum::EnumIter;
 
 #[charybdis_udt_model(type_name = filechunk)]
@@ -13,6 +17,17 @@ pub struct FileChunk {
     pub chunk_order: TinyInt,
 }
 
+/// Client supplied metadata of a file, set at upload time and editable afterwards.
+#[charybdis_udt_model(type_name = filemetadata)]
+#[derive(Eq, PartialEq, Clone, Debug, Default)]
+pub struct FileMetadata {
+    pub content_type: Option<Text>,
+    pub content_disposition: Option<Text>,
+    pub cache_control: Option<Text>,
+    /// The `x-meowith-meta-*` headers, keyed without the prefix.
+    pub user: Option<Map<Text, Text>>,
+}
+
 #[charybdis_model(
     table_name = files,
     partition_keys = [bucket_id],
@@ -31,15 +46,45 @@ pub struct File {
     pub chunk_ids: Set<Frozen<FileChunk>>,
     pub created: Timestamp,
     pub last_modified: Timestamp,
+    pub metadata: Option<Frozen<FileMetadata>>,
+    /// Identifies the contents, see [File::content_etag].
+    pub etag: Option<Text>,
+    /// Set from the object lock of the bucket when the file is written, see [Bucket::retain_until].
+    pub retain_until: Option<Timestamp>,
+    /// Protects the file regardless of its retention, until removed.
+    pub legal_hold: Option<Boolean>,
 }
 
 impl File {
     pub fn full_path(&self, parent: &str) -> String {
         join_parent_name(parent, &self.name)
     }
+
+    /// Every upload produces a new file id, so the id identifies the contents of the file.
+    /// Renames and metadata edits leave it untouched.
+    pub fn content_etag(file_id: Uuid) -> String {
+        file_id.simple().to_string()
+    }
+
+    /// Files stored before etags were introduced derive theirs on the fly.
+    pub fn etag(&self) -> String {
+        self.etag
+            .clone()
+            .unwrap_or_else(|| File::content_etag(self.id))
+    }
+
+    pub fn has_legal_hold(&self) -> bool {
+        self.legal_hold.unwrap_or(false)
+    }
+
+    /// A protected file cannot be overwritten, renamed or deleted.
+    pub fn is_protected(&self, now: DateTime<Utc>) -> bool {
+        self.has_legal_hold() || self.retain_until.is_some_and(|until| until > now)
+    }
 }
 
 partial_file!(UpdateFileChunks, bucket_id, directory, name, chunk_ids);
+partial_file!(UpdateFileLegalHold, bucket_id, directory, name, legal_hold);
 
 #[charybdis_model(
     table_name = directories,
@@ -65,6 +110,22 @@ impl Directory {
     }
 }
 
+/// Claims a bucket name within an app, keeping the names unique across concurrent writers.
+#[charybdis_model(
+    table_name = bucket_names,
+    partition_keys = [app_id],
+    clustering_keys = [name],
+    global_secondary_indexes = [],
+    local_secondary_indexes = [],
+    static_columns = []
+)]
+#[derive(Debug, Clone)]
+pub struct BucketName {
+    pub app_id: Uuid,
+    pub name: Text,
+    pub bucket_id: Uuid,
+}
+
 #[charybdis_model(
     table_name = buckets,
     partition_keys = [app_id],
@@ -85,6 +146,43 @@ pub struct Bucket {
     pub space_taken: BigInt, // avoid querying sum(size)
     pub created: Timestamp,
     pub last_modified: Timestamp,
+    /// Keep the previous contents of overwritten or deleted files as [FileVersion]s.
+    pub versioning: Option<Boolean>,
+    /// Seconds a deleted file is kept in the trash before being purged, no trash if absent or 0.
+    pub trash_retention: Option<BigInt>,
+    /// Evaluated periodically by the lifecycle worker.
+    pub lifecycle_rules: Option<List<Frozen<LifecycleRule>>>,
+    /// Webhooks notified of changes to the files of the bucket.
+    pub notifications: Option<List<Frozen<NotificationConfig>>>,
+    /// Seconds a newly written file is protected for, no object lock if absent or 0.
+    pub object_lock_retention: Option<BigInt>,
+    /// Paths readable without a token, not public if absent.
+    pub public_access: Option<Frozen<PublicAccess>>,
+    /// Evaluated by the nodes on cross-origin requests, in order, the first match applies.
+    pub cors_rules: Option<List<Frozen<CorsRule>>>,
+    /// Set once a forced deletion is requested, the bucket is inaccessible until its job removes it.
+    pub deleting: Option<Boolean>,
+}
+
+impl Bucket {
+    pub fn is_deleting(&self) -> bool {
+        self.deleting.unwrap_or(false)
+    }
+
+    pub fn versioning_enabled(&self) -> bool {
+        self.versioning.unwrap_or(false)
+    }
+
+    pub fn trash_enabled(&self) -> bool {
+        self.trash_retention.is_some_and(|retention| retention > 0)
+    }
+
+    /// The retention of a file written at the given time.
+    pub fn retain_until(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.object_lock_retention
+            .filter(|retention| *retention > 0)
+            .map(|retention| now + TimeDelta::seconds(retention))
+    }
 }
 
 impl Default for Bucket {
@@ -100,11 +198,644 @@ impl Default for Bucket {
             space_taken: 0,
             created: Default::default(),
             last_modified: Default::default(),
+            versioning: None,
+            trash_retention: None,
+            lifecycle_rules: None,
+            notifications: None,
+            object_lock_retention: None,
+            public_access: None,
+            cors_rules: None,
+            deleting: None,
         }
     }
 }
 
 partial_bucket!(UpdateBucketQuota, app_id, id, quota);
+partial_bucket!(UpdateBucketName, app_id, id, name);
+partial_bucket!(UpdateBucketAtomicUpload, app_id, id, atomic_upload);
+partial_bucket!(UpdateBucketVersioning, app_id, id, versioning);
+partial_bucket!(UpdateBucketTrashRetention, app_id, id, trash_retention);
+partial_bucket!(UpdateBucketLifecycleRules, app_id, id, lifecycle_rules);
+partial_bucket!(UpdateBucketNotifications, app_id, id, notifications);
+partial_bucket!(
+    UpdateBucketObjectLockRetention,
+    app_id,
+    id,
+    object_lock_retention
+);
+partial_bucket!(UpdateBucketPublicAccess, app_id, id, public_access);
+partial_bucket!(UpdateBucketCorsRules, app_id, id, cors_rules);
+partial_bucket!(UpdateBucketDeleting, app_id, id, deleting);
+
+#[charybdis_udt_model(type_name = lifecyclerule)]
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct LifecycleRule {
+    pub id: Uuid,
+    /// Only objects whose full path starts with the prefix are affected, an empty prefix matches all.
+    pub prefix: Text,
+    /// maps to [LifecycleAction]
+    pub action: TinyInt,
+    /// maps to [LifecycleCondition]
+    pub condition: TinyInt,
+    /// The age in seconds past which the action is applied.
+    pub age: BigInt,
+}
+
+impl LifecycleRule {
+    pub fn matches(&self, path: &str) -> bool {
+        path.starts_with(&self.prefix)
+    }
+
+    /// Checks the condition against the creation and last modification dates of an object.
+    pub fn is_due(
+        &self,
+        now: DateTime<Utc>,
+        created: DateTime<Utc>,
+        last_modified: DateTime<Utc>,
+    ) -> bool {
+        let since = match LifecycleCondition::try_from(self.condition) {
+            Ok(LifecycleCondition::Age) => created,
+            Ok(LifecycleCondition::LastModified) => last_modified,
+            Err(_) => return false,
+        };
+        now.signed_duration_since(since) >= TimeDelta::seconds(self.age)
+    }
+}
+
+#[derive(
+    Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive, Clone, Copy, Serialize, Deserialize,
+)]
+#[repr(i8)]
+pub enum LifecycleAction {
+    /// Delete matching files, honoring the bucket versioning and trash settings.
+    Expire = 1i8,
+    /// Permanently remove matching non-current [FileVersion]s.
+    PurgeVersions = 2i8,
+    /// Abort matching durable [BucketUploadSession]s, freeing their fragments.
+    AbortUploads = 3i8,
+}
+
+#[derive(
+    Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive, Clone, Copy, Serialize, Deserialize,
+)]
+#[repr(i8)]
+pub enum LifecycleCondition {
+    /// Time since creation. Versions age from the moment they were superseded.
+    Age = 1i8,
+    /// Time since the last modification. Upload sessions use their last access.
+    LastModified = 2i8,
+}
+
+#[charybdis_udt_model(type_name = notificationconfig)]
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct NotificationConfig {
+    pub id: Uuid,
+    /// maps to [BucketEventKind]
+    pub events: List<TinyInt>,
+    /// Only events on paths starting with the prefix are delivered, an empty prefix matches all.
+    pub prefix: Text,
+    pub url: Text,
+    /// The key the deliveries are signed with, using HMAC-SHA256.
+    pub secret: Text,
+}
+
+impl NotificationConfig {
+    pub fn matches(&self, kind: BucketEventKind, path: &str) -> bool {
+        self.events.contains(&kind.into()) && path.starts_with(&self.prefix)
+    }
+}
+
+#[charybdis_udt_model(type_name = publicaccess)]
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct PublicAccess {
+    /// Files whose full path starts with one of the prefixes can be downloaded anonymously,
+    /// an empty prefix exposes the whole bucket.
+    pub prefixes: List<Text>,
+    /// Also allow listing the directories under the prefixes.
+    pub listing: Boolean,
+    /// Anonymous requests accepted per minute by each node, for the whole bucket.
+    pub requests_per_minute: Int,
+}
+
+impl PublicAccess {
+    pub fn matches(&self, path: &str) -> bool {
+        self.prefixes.iter().any(|prefix| path.starts_with(prefix))
+    }
+
+    /// Whether everything within the directory is exposed, the empty path being the root.
+    pub fn matches_directory(&self, path: &str) -> bool {
+        if path.is_empty() {
+            return self.matches(path);
+        }
+        self.matches(&format!("{path}/"))
+    }
+}
+
+#[charybdis_udt_model(type_name = corsrule)]
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct CorsRule {
+    /// Exact origins such as `https://example.com`, `*` allows any.
+    pub allowed_origins: List<Text>,
+    /// Upper case HTTP methods.
+    pub allowed_methods: List<Text>,
+    /// Request headers allowed on top of the CORS-safelisted ones, `*` allows any.
+    pub allowed_headers: List<Text>,
+    /// Response headers readable by the browser on top of the CORS-safelisted ones.
+    pub expose_headers: List<Text>,
+    /// Seconds the browser may cache a preflight response for.
+    pub max_age: Int,
+}
+
+impl CorsRule {
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        self.allowed_origins
+            .iter()
+            .any(|allowed| allowed == "*" || allowed == origin)
+    }
+
+    pub fn allows_method(&self, method: &str) -> bool {
+        self.allowed_methods.iter().any(|allowed| allowed == method)
+    }
+
+    pub fn allows_header(&self, header: &str) -> bool {
+        self.allowed_headers
+            .iter()
+            .any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(header))
+    }
+}
+
+#[derive(
+    Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive, Clone, Copy, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+#[repr(i8)]
+pub enum BucketEventKind {
+    /// A file was uploaded to a path where none existed.
+    Created = 1i8,
+    /// A file was uploaded in place of an existing one, or its contents were modified in place.
+    Overwritten = 2i8,
+    Deleted = 3i8,
+    /// A file or a directory was moved to another path.
+    Renamed = 4i8,
+}
+
+/// The amount of partitions the queued deliveries are spread over.
+pub const NOTIFICATION_SHARDS: i8 = 16;
+
+/// An event awaiting delivery to a single webhook of a bucket.
+/// Ordered by the time of the next attempt within its shard, so that the due ones are read first.
+/// Deliveries are always written whole, so that every column shares the same expiry.
+#[charybdis_model(
+    table_name = notification_queue,
+    partition_keys = [shard],
+    clustering_keys = [next_attempt, id],
+    global_secondary_indexes = [],
+    local_secondary_indexes = [],
+    static_columns = [],
+    table_options = r#"default_time_to_live = 604800;"#
+)]
+#[derive(Clone, Debug, Default)]
+pub struct BucketNotification {
+    /// Derived from the id, see [BucketNotification::shard_of].
+    pub shard: TinyInt,
+    pub next_attempt: Timestamp,
+    /// Identifies the delivery, repeated on every attempt.
+    pub id: Uuid,
+    pub bucket_id: Uuid,
+    pub app_id: Uuid,
+    /// The [NotificationConfig] to deliver to.
+    pub config_id: Uuid,
+    /// maps to [BucketEventKind]
+    pub kind: TinyInt,
+    pub path: Text,
+    /// The previous path of a renamed entity.
+    pub old_path: Option<Text>,
+    pub directory: Boolean,
+    pub size: Option<BigInt>,
+    pub etag: Option<Text>,
+    pub occurred: Timestamp,
+    pub attempts: Int,
+    pub last_error: Option<Text>,
+}
+
+impl BucketNotification {
+    pub fn shard_of(id: Uuid) -> i8 {
+        (id.as_u128() % NOTIFICATION_SHARDS as u128) as i8
+    }
+}
+
+/// Seconds the changes of a bucket are grouped into a single partition for.
+pub const CHANGE_PERIOD_SECS: i64 = 3600;
+/// Seconds changes are retained for, matching the ttl of [BucketChange].
+pub const CHANGE_RETENTION_SECS: i64 = 604800;
+
+/// An entry of the change log of a bucket, ordered by the time it occurred.
+#[charybdis_model(
+    table_name = bucket_changes,
+    partition_keys = [bucket_id, period],
+    clustering_keys = [occurred, id],
+    global_secondary_indexes = [],
+    local_secondary_indexes = [],
+    static_columns = [],
+    table_options = r#"default_time_to_live = 604800;"#
+)]
+#[derive(Clone, Debug, Default)]
+pub struct BucketChange {
+    pub bucket_id: Uuid,
+    /// See [BucketChange::period_of].
+    pub period: BigInt,
+    pub occurred: Timestamp,
+    /// Orders the changes which occurred within the same millisecond.
+    pub id: Uuid,
+    /// maps to [BucketEventKind]
+    pub kind: TinyInt,
+    pub path: Text,
+    /// The previous path of a renamed entity.
+    pub old_path: Option<Text>,
+    pub directory: Boolean,
+    pub size: Option<BigInt>,
+    pub etag: Option<Text>,
+}
+
+impl BucketChange {
+    /// The partition changes occurring at the given time are stored in.
+    pub fn period_of(time: DateTime<Utc>) -> i64 {
+        time.timestamp().div_euclid(CHANGE_PERIOD_SECS)
+    }
+}
+
+/// A retained, non-current version of a file in a bucket with versioning enabled.
+/// Versions are keyed by the full path of the file, so that they survive the removal of their directory.
+#[charybdis_model(
+    table_name = file_versions,
+    partition_keys = [bucket_id],
+    clustering_keys = [path, version_id],
+    global_secondary_indexes = [],
+    local_secondary_indexes = [file_id],
+    static_columns = []
+)]
+#[derive(Clone, Debug, Default)]
+pub struct FileVersion {
+    pub bucket_id: Uuid,
+    pub path: Text,
+    /// Time ordered (v7), so that the versions of a path are stored in the order they were archived.
+    pub version_id: Uuid,
+    pub file_id: Uuid, // The id of the file this version was taken from
+    pub size: BigInt,
+    pub chunk_ids: Set<Frozen<FileChunk>>,
+    pub created: Timestamp,
+    pub last_modified: Timestamp,
+    /// The moment the version stopped being the current one.
+    pub archived: Timestamp,
+    pub metadata: Option<Frozen<FileMetadata>>,
+    pub etag: Option<Text>,
+}
+
+partial_file_version!(
+    UpdateFileVersionChunks,
+    bucket_id,
+    path,
+    version_id,
+    chunk_ids
+);
+
+impl FileVersion {
+    pub fn of(file: &File, path: String, version_id: Uuid, archived: Timestamp) -> Self {
+        FileVersion {
+            bucket_id: file.bucket_id,
+            path,
+            version_id,
+            file_id: file.id,
+            size: file.size,
+            chunk_ids: file.chunk_ids.clone(),
+            created: file.created,
+            last_modified: file.last_modified,
+            archived,
+            metadata: file.metadata.clone(),
+            etag: file.etag.clone(),
+        }
+    }
+
+    pub fn etag(&self) -> String {
+        self.etag
+            .clone()
+            .unwrap_or_else(|| File::content_etag(self.file_id))
+    }
+
+    pub fn into_file(self, directory: Uuid, name: String) -> File {
+        File {
+            bucket_id: self.bucket_id,
+            directory,
+            name,
+            id: self.file_id,
+            size: self.size,
+            chunk_ids: self.chunk_ids,
+            created: self.created,
+            last_modified: self.last_modified,
+            metadata: self.metadata,
+            etag: self.etag,
+            retain_until: None,
+            legal_hold: None,
+        }
+    }
+}
+
+/// A named, immutable view of the files and directories of a bucket at the moment it was taken.
+/// Its entries are copies of the [File] and [Directory] rows of the bucket,
+/// stored in the same tables with the snapshot id in place of the bucket id,
+/// so that they can be browsed with the same queries as the bucket itself.
+/// Scans over every file row therefore see the entries of the snapshots as well.
+#[charybdis_model(
+    table_name = bucket_snapshots,
+    partition_keys = [bucket_id],
+    clustering_keys = [id],
+    global_secondary_indexes = [],
+    local_secondary_indexes = [],
+    static_columns = []
+)]
+#[derive(Clone, Debug, Default)]
+pub struct BucketSnapshot {
+    pub bucket_id: Uuid,
+    pub id: Uuid,
+    pub app_id: Uuid,
+    pub name: Text,
+    /// maps to [SnapshotState]
+    pub state: TinyInt,
+    pub file_count: BigInt,
+    pub space_taken: BigInt,
+    pub created: Timestamp,
+}
+
+impl BucketSnapshot {
+    pub fn is_ready(&self) -> bool {
+        self.state == i8::from(SnapshotState::Ready)
+    }
+
+    pub fn is_creating(&self) -> bool {
+        self.state == i8::from(SnapshotState::Creating)
+    }
+}
+
+#[derive(
+    Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive, Clone, Copy, Serialize, Deserialize,
+)]
+#[repr(i8)]
+pub enum SnapshotState {
+    /// The entries are being copied, the snapshot cannot be browsed yet.
+    Creating = 1i8,
+    Ready = 2i8,
+    /// The entries are being removed, a failed deletion can be retried.
+    Deleting = 3i8,
+}
+
+/// Keeps a chunk referenced by a snapshot from being deleted along with the file it belongs to.
+/// Once the last snapshot referencing it is deleted, the chunk is removed
+/// unless the file, or a version or trash entry of it, still uses the chunk.
+#[charybdis_model(
+    table_name = snapshot_chunks,
+    partition_keys = [chunk_id],
+    clustering_keys = [snapshot_id],
+    global_secondary_indexes = [],
+    local_secondary_indexes = [],
+    static_columns = []
+)]
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotChunk {
+    pub chunk_id: Uuid,
+    pub snapshot_id: Uuid,
+}
+
+/// A chunk released by the bucket while one of its snapshots was being taken.
+/// It is held for the snapshot until the copy finishes, as a copied entry may still reference it,
+/// after which it is released unless one does.
+#[charybdis_model(
+    table_name = snapshot_pending_chunks,
+    partition_keys = [snapshot_id],
+    clustering_keys = [chunk_id],
+    global_secondary_indexes = [],
+    local_secondary_indexes = [],
+    static_columns = []
+)]
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotPendingChunk {
+    pub snapshot_id: Uuid,
+    pub chunk_id: Uuid,
+    pub chunk: Frozen<FileChunk>,
+}
+
+/// A file deleted from a bucket with the trash enabled.
+/// It can be restored by its path until it expires, after which it is purged by a background worker.
+#[charybdis_model(
+    table_name = bucket_trash,
+    partition_keys = [bucket_id],
+    clustering_keys = [path, id],
+    global_secondary_indexes = [],
+    local_secondary_indexes = [file_id],
+    static_columns = []
+)]
+#[derive(Clone, Debug, Default)]
+pub struct TrashedFile {
+    pub bucket_id: Uuid,
+    pub path: Text,
+    pub id: Uuid,
+    pub app_id: Uuid,
+    pub file_id: Uuid,
+    pub size: BigInt,
+    pub chunk_ids: Set<Frozen<FileChunk>>,
+    pub created: Timestamp,
+    pub last_modified: Timestamp,
+    pub deleted: Timestamp,
+    pub expires: Timestamp,
+    pub metadata: Option<Frozen<FileMetadata>>,
+    pub etag: Option<Text>,
+}
+
+partial_trashed_file!(UpdateTrashedFileChunks, bucket_id, path, id, chunk_ids);
+
+impl TrashedFile {
+    pub fn of(file: &File, bucket: &Bucket, path: String, id: Uuid, deleted: Timestamp) -> Self {
+        TrashedFile {
+            bucket_id: file.bucket_id,
+            path,
+            id,
+            app_id: bucket.app_id,
+            file_id: file.id,
+            size: file.size,
+            chunk_ids: file.chunk_ids.clone(),
+            created: file.created,
+            last_modified: file.last_modified,
+            deleted,
+            expires: deleted + TimeDelta::seconds(bucket.trash_retention.unwrap_or_default()),
+            metadata: file.metadata.clone(),
+            etag: file.etag.clone(),
+        }
+    }
+
+    pub fn into_file(self, directory: Uuid, name: String) -> File {
+        File {
+            bucket_id: self.bucket_id,
+            directory,
+            name,
+            id: self.file_id,
+            size: self.size,
+            chunk_ids: self.chunk_ids,
+            created: self.created,
+            last_modified: self.last_modified,
+            metadata: self.metadata,
+            etag: self.etag,
+            retain_until: None,
+            legal_hold: None,
+        }
+    }
+}
+
+/// A long-running operation on a bucket, executed in the background.
+/// Jobs are always written whole, so that every column shares the same expiry.
+#[charybdis_model(
+    table_name = bucket_jobs,
+    partition_keys = [bucket_id],
+    clustering_keys = [id],
+    global_secondary_indexes = [],
+    local_secondary_indexes = [],
+    static_columns = [],
+    table_options = r#"default_time_to_live = 604800;"#
+)]
+#[derive(Clone, Debug, Default)]
+pub struct BucketJob {
+    pub bucket_id: Uuid,
+    pub id: Uuid,
+    pub app_id: Uuid,
+    /// maps to [JobKind]
+    pub kind: TinyInt,
+    /// maps to [JobState]
+    pub state: TinyInt,
+    /// The entity the job operates on.
+    pub path: Text,
+    /// The node executing the job, nil while the job waits for a node to pick it up.
+    pub holder: Uuid,
+    pub total_files: BigInt,
+    pub processed_files: BigInt,
+    pub failed_files: BigInt,
+    pub deleted_size: BigInt,
+    /// The first few failures, for the final report.
+    pub errors: Option<List<Text>>,
+    pub created: Timestamp,
+    pub last_update: Timestamp,
+}
+
+impl BucketJob {
+    pub fn new(kind: JobKind, app_id: Uuid, bucket_id: Uuid, path: String, holder: Uuid) -> Self {
+        let now = Utc::now();
+        BucketJob {
+            bucket_id,
+            id: Uuid::new_v4(),
+            app_id,
+            kind: kind.into(),
+            state: JobState::Running.into(),
+            path,
+            holder,
+            total_files: 0,
+            processed_files: 0,
+            failed_files: 0,
+            deleted_size: 0,
+            errors: None,
+            created: now,
+            last_update: now,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state == i8::from(JobState::Running)
+    }
+}
+
+/// Points at a running job, so that the jobs left behind by a node are found without a scan
+/// over every job. Expires along with the job it points at.
+#[charybdis_model(
+    table_name = running_bucket_jobs,
+    partition_keys = [kind],
+    clustering_keys = [bucket_id, id],
+    global_secondary_indexes = [],
+    local_secondary_indexes = [],
+    static_columns = [],
+    table_options = r#"default_time_to_live = 604800;"#
+)]
+#[derive(Clone, Debug, Default)]
+pub struct RunningBucketJob {
+    /// maps to [JobKind]
+    pub kind: TinyInt,
+    pub bucket_id: Uuid,
+    pub id: Uuid,
+}
+
+impl RunningBucketJob {
+    pub fn of(job: &BucketJob) -> Self {
+        RunningBucketJob {
+            kind: job.kind,
+            bucket_id: job.bucket_id,
+            id: job.id,
+        }
+    }
+}
+
+#[derive(
+    Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive, Clone, Copy, Serialize, Deserialize,
+)]
+#[repr(i8)]
+pub enum JobKind {
+    /// Removes a directory along with every file and directory within it.
+    DeleteDirectory = 1i8,
+    /// Removes a bucket along with all of its contents, chunks, snapshots and sessions.
+    DeleteBucket = 2i8,
+    /// Copies the entries of a bucket into the snapshot named by the path of the job.
+    CreateSnapshot = 3i8,
+}
+
+#[derive(
+    Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive, Clone, Copy, Serialize, Deserialize,
+)]
+#[repr(i8)]
+pub enum JobState {
+    Running = 1i8,
+    Completed = 2i8,
+    /// Finished, with some of the work left undone.
+    Failed = 3i8,
+}
+
+/// An advisory lease taken by a client on a file path.
+#[charybdis_udt_model(type_name = lease)]
+#[derive(Eq, PartialEq, Clone, Debug, Default)]
+pub struct Lease {
+    /// Identifies the token which took the lease.
+    pub holder: Text,
+    pub exclusive: Boolean,
+    pub expires: Timestamp,
+}
+
+impl Lease {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.expires > now
+    }
+}
+
+/// Every lease held on a path, which does not need to point at an existing file.
+/// The row is replaced as a whole, guarded by `version`,
+/// and expires along with the longest lease within it.
+#[charybdis_model(
+    table_name = file_leases,
+    partition_keys = [bucket_id],
+    clustering_keys = [path],
+    global_secondary_indexes = [],
+    local_secondary_indexes = [],
+    static_columns = []
+)]
+#[derive(Clone, Debug, Default)]
+pub struct FileLeases {
+    pub bucket_id: Uuid,
+    pub path: Text,
+    pub leases: Map<Uuid, Frozen<Lease>>,
+    pub version: Uuid,
+}
 
 #[charybdis_model(
     table_name = bucket_upload_session,
@@ -128,6 +859,11 @@ pub struct BucketUploadSession {
     pub last_access: Timestamp,
     /// maps to [SessionState]
     pub state: TinyInt,
+    /// Applied to the file once the upload completes.
+    pub metadata: Option<Frozen<FileMetadata>>,
+    /// Set for conditional uploads, to the id of the file the upload may replace when it commits,
+    /// or nil if there may be no file at the path by then.
+    pub expected_file: Option<Uuid>,
 }
 
 #[derive(Debug, Hash, Eq, PartialEq, EnumIter, IntoPrimitive, TryFromPrimitive, Clone, Copy)]
@@ -155,6 +891,8 @@ impl Default for BucketUploadSession {
             fragments: Default::default(),
             last_access: Default::default(),
             state: 0,
+            metadata: None,
+            expected_file: None,
         }
     }
 }
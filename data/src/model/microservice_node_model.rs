@@ -44,6 +44,21 @@ pub struct ServiceRegisterCode {
     pub valid: Boolean,
 }
 
+/// A cluster-wide lease on a background worker, ensuring only a single node runs it at a time.
+/// The lease expires via the row ttl unless renewed by its holder.
+#[charybdis_model(
+    table_name = worker_leases,
+    partition_keys = [name],
+    clustering_keys = [],
+    global_secondary_indexes = [],
+    local_secondary_indexes = [],
+    static_columns = []
+)]
+pub struct WorkerLease {
+    pub name: Text,
+    pub holder: Uuid,
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
 pub enum MicroserviceType {
     StorageNode,
@@ -1,4 +1,5 @@
 pub mod app_model;
+pub mod audit_model;
 pub mod file_model;
 pub mod microservice_node_model;
 pub mod permission_model;
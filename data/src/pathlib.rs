@@ -72,6 +72,7 @@ pub fn normalize(path: &str) -> String {
 /// let config = FsLimitConfiguration {
 ///     max_path_length: 50,
 ///     max_directory_depth: 3,
+///     max_zip_archive_size: 1024,
 /// };
 ///
 /// assert_eq!(prepare_path("/valid/path", &config), Some("valid/path".to_string()));
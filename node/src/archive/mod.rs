@@ -0,0 +1,152 @@
+use chrono::{DateTime, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Deserialize;
+use std::io::Write;
+
+use crate::archive::tar::TarEncoder;
+use crate::archive::zip::ZipEncoder;
+
+pub mod tar;
+mod tests;
+pub mod zip;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum ArchiveFormat {
+    #[default]
+    #[serde(rename = "tar")]
+    Tar,
+    #[serde(rename = "tar.gz")]
+    TarGz,
+    #[serde(rename = "zip")]
+    Zip,
+}
+
+impl ArchiveFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+
+    pub fn mime(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "application/x-tar",
+            ArchiveFormat::TarGz => "application/gzip",
+            ArchiveFormat::Zip => "application/zip",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum EntryKind {
+    Directory,
+    File,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    /// Relative to the root of the archive, without a trailing slash.
+    pub path: String,
+    pub kind: EntryKind,
+    pub size: u64,
+    pub modified: DateTime<Utc>,
+}
+
+enum Encoder {
+    Tar(TarEncoder),
+    Zip(ZipEncoder),
+}
+
+/// Encodes an archive incrementally, one entry at a time.
+/// The contents of a file entry are fed in pieces, so that no file has to be held in memory.
+///
+/// The encoded bytes accumulate until [ArchiveWriter::take_output] is called.
+pub struct ArchiveWriter {
+    encoder: Encoder,
+    gzip: Option<GzEncoder<Vec<u8>>>,
+    output: Vec<u8>,
+}
+
+impl ArchiveWriter {
+    pub fn new(format: ArchiveFormat) -> Self {
+        let encoder = match format {
+            ArchiveFormat::Tar | ArchiveFormat::TarGz => Encoder::Tar(TarEncoder::default()),
+            ArchiveFormat::Zip => Encoder::Zip(ZipEncoder::default()),
+        };
+        let gzip = (format == ArchiveFormat::TarGz)
+            .then(|| GzEncoder::new(Vec::new(), Compression::default()));
+        ArchiveWriter {
+            encoder,
+            gzip,
+            output: Vec::new(),
+        }
+    }
+
+    pub fn start_entry(&mut self, entry: &ArchiveEntry) {
+        match &mut self.encoder {
+            Encoder::Tar(tar) => tar.start_entry(entry, &mut self.output),
+            Encoder::Zip(zip) => zip.start_entry(entry, &mut self.output),
+        }
+        self.compress();
+    }
+
+    pub fn write_data(&mut self, data: &[u8]) {
+        match &mut self.encoder {
+            Encoder::Tar(tar) => tar.write_data(data, &mut self.output),
+            Encoder::Zip(zip) => zip.write_data(data, &mut self.output),
+        }
+        self.compress();
+    }
+
+    /// Fails if the amount of data written does not match the size of the entry.
+    pub fn finish_entry(&mut self) -> std::io::Result<()> {
+        match &mut self.encoder {
+            Encoder::Tar(tar) => tar.finish_entry(&mut self.output)?,
+            Encoder::Zip(zip) => zip.finish_entry(&mut self.output)?,
+        }
+        self.compress();
+        Ok(())
+    }
+
+    /// Writes the end of the archive, no entries may be started afterward.
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        match &mut self.encoder {
+            Encoder::Tar(tar) => tar.finish(&mut self.output),
+            Encoder::Zip(zip) => zip.finish(&mut self.output),
+        }
+        self.compress();
+        if let Some(gzip) = &mut self.gzip {
+            gzip.try_finish()?;
+        }
+        Ok(())
+    }
+
+    pub fn take_output(&mut self) -> Vec<u8> {
+        match &mut self.gzip {
+            Some(gzip) => std::mem::take(gzip.get_mut()),
+            None => std::mem::take(&mut self.output),
+        }
+    }
+
+    fn compress(&mut self) {
+        if let Some(gzip) = &mut self.gzip {
+            // writing into a vec cannot fail
+            let _ = gzip.write_all(&self.output);
+            self.output.clear();
+        }
+    }
+}
+
+fn size_mismatch(expected: u64, written: u64) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("Archive entry size mismatch, expected {expected} bytes, got {written}"),
+    )
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string())
+}
@@ -0,0 +1,248 @@
+use crate::archive::{invalid_data, size_mismatch, ArchiveEntry, EntryKind};
+
+pub const BLOCK_SIZE: usize = 512;
+const NAME_LENGTH: usize = 100;
+/// The largest size fitting into the 11 octal digits of the header's size field.
+const MAX_OCTAL_SIZE: u64 = 0o77777777777;
+
+const TYPE_FILE: u8 = b'0';
+const TYPE_FILE_LEGACY: u8 = 0;
+const TYPE_CONTIGUOUS_FILE: u8 = b'7';
+const TYPE_DIRECTORY: u8 = b'5';
+const TYPE_PAX_HEADER: u8 = b'x';
+const TYPE_GLOBAL_PAX_HEADER: u8 = b'g';
+const TYPE_GNU_LONG_NAME: u8 = b'L';
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TarEntryType {
+    File,
+    Directory,
+    /// Applies to the entry that follows.
+    PaxHeader,
+    GlobalPaxHeader,
+    /// Holds the name of the entry that follows.
+    LongName,
+    /// Links, devices and anything else without contents of its own.
+    Other,
+}
+
+#[derive(Debug)]
+pub struct TarHeader {
+    pub name: Vec<u8>,
+    pub size: u64,
+    pub entry_type: TarEntryType,
+}
+
+/// Writes POSIX (pax) tar archives.
+/// Names that do not fit into the ustar header and files over 8 GiB get an extended header.
+#[derive(Default)]
+pub struct TarEncoder {
+    expected: u64,
+    written: u64,
+}
+
+impl TarEncoder {
+    pub fn start_entry(&mut self, entry: &ArchiveEntry, out: &mut Vec<u8>) {
+        let (name, size, mode, type_flag) = match entry.kind {
+            EntryKind::Directory => (format!("{}/", entry.path), 0, 0o755, TYPE_DIRECTORY),
+            EntryKind::File => (entry.path.clone(), entry.size, 0o644, TYPE_FILE),
+        };
+        let mtime = entry.modified.timestamp().max(0) as u64;
+
+        let mut records = String::new();
+        if name.len() > NAME_LENGTH {
+            records.push_str(&pax_record("path", &name));
+        }
+        if size > MAX_OCTAL_SIZE {
+            records.push_str(&pax_record("size", &size.to_string()));
+        }
+        if !records.is_empty() {
+            out.extend_from_slice(&header(
+                name.as_bytes(),
+                records.len() as u64,
+                mtime,
+                0o644,
+                TYPE_PAX_HEADER,
+            ));
+            out.extend_from_slice(records.as_bytes());
+            pad(records.len() as u64, out);
+        }
+
+        // the extended header takes precedence over the size field, which is left empty then
+        let header_size = if size > MAX_OCTAL_SIZE { 0 } else { size };
+        out.extend_from_slice(&header(
+            name.as_bytes(),
+            header_size,
+            mtime,
+            mode,
+            type_flag,
+        ));
+        self.expected = size;
+        self.written = 0;
+    }
+
+    pub fn write_data(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        self.written += data.len() as u64;
+        out.extend_from_slice(data);
+    }
+
+    pub fn finish_entry(&mut self, out: &mut Vec<u8>) -> std::io::Result<()> {
+        if self.written != self.expected {
+            return Err(size_mismatch(self.expected, self.written));
+        }
+        pad(self.written, out);
+        Ok(())
+    }
+
+    pub fn finish(&mut self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&[0; 2 * BLOCK_SIZE]);
+    }
+}
+
+/// Parses a header block, `None` marks the end of the archive.
+/// Reads ustar, pax and GNU headers, as well as the pre-POSIX format.
+pub fn parse_header(block: &[u8; BLOCK_SIZE]) -> std::io::Result<Option<TarHeader>> {
+    if block.iter().all(|byte| *byte == 0) {
+        return Ok(None);
+    }
+    let checksum: u64 = block[..148]
+        .iter()
+        .chain(&[b' '; 8])
+        .chain(&block[156..])
+        .map(|byte| *byte as u64)
+        .sum();
+    if parse_numeric(&block[148..156])? != checksum {
+        return Err(invalid_data("Invalid tar header checksum"));
+    }
+
+    let mut name = until_nul(&block[..100]).to_vec();
+    if &block[257..262] == b"ustar" {
+        let prefix = until_nul(&block[345..500]);
+        if !prefix.is_empty() {
+            name = [prefix, b"/", &name].concat();
+        }
+    }
+    let entry_type = match block[156] {
+        // old archives mark directories by the trailing slash only
+        TYPE_FILE | TYPE_FILE_LEGACY | TYPE_CONTIGUOUS_FILE if name.ends_with(b"/") => {
+            TarEntryType::Directory
+        }
+        TYPE_FILE | TYPE_FILE_LEGACY | TYPE_CONTIGUOUS_FILE => TarEntryType::File,
+        TYPE_DIRECTORY => TarEntryType::Directory,
+        TYPE_PAX_HEADER => TarEntryType::PaxHeader,
+        TYPE_GLOBAL_PAX_HEADER => TarEntryType::GlobalPaxHeader,
+        TYPE_GNU_LONG_NAME => TarEntryType::LongName,
+        _ => TarEntryType::Other,
+    };
+
+    Ok(Some(TarHeader {
+        name,
+        size: parse_numeric(&block[124..136])?,
+        entry_type,
+    }))
+}
+
+/// The key-value records of a pax extended header.
+pub fn parse_pax_records(mut data: &[u8]) -> std::io::Result<Vec<(String, Vec<u8>)>> {
+    let mut records = vec![];
+    // the header may be padded with NULs
+    while data.first().is_some_and(|byte| *byte != 0) {
+        let space = data
+            .iter()
+            .position(|byte| *byte == b' ')
+            .ok_or_else(|| invalid_data("Malformed pax record"))?;
+        let length: usize = std::str::from_utf8(&data[..space])
+            .ok()
+            .and_then(|length| length.parse().ok())
+            .filter(|length| *length > space + 1 && *length <= data.len())
+            .ok_or_else(|| invalid_data("Malformed pax record length"))?;
+        let record = &data[space + 1..length];
+        let record = record
+            .strip_suffix(b"\n")
+            .ok_or_else(|| invalid_data("Unterminated pax record"))?;
+        let equals = record
+            .iter()
+            .position(|byte| *byte == b'=')
+            .ok_or_else(|| invalid_data("Malformed pax record"))?;
+        let key = String::from_utf8(record[..equals].to_vec())
+            .map_err(|_| invalid_data("Malformed pax record key"))?;
+        records.push((key, record[equals + 1..].to_vec()));
+        data = &data[length..];
+    }
+    Ok(records)
+}
+
+/// The amount of zeroes following contents of the given size.
+pub fn padding(size: u64) -> u64 {
+    (BLOCK_SIZE as u64 - size % BLOCK_SIZE as u64) % BLOCK_SIZE as u64
+}
+
+fn until_nul(field: &[u8]) -> &[u8] {
+    let end = field
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(field.len());
+    &field[..end]
+}
+
+/// Octal, or base-256 as GNU tar writes values too large for the octal digits.
+fn parse_numeric(field: &[u8]) -> std::io::Result<u64> {
+    if field[0] & 0x80 != 0 {
+        return Ok(field[1..]
+            .iter()
+            .fold(0u64, |value, byte| (value << 8) | *byte as u64));
+    }
+    let digits = std::str::from_utf8(until_nul(field))
+        .map_err(|_| invalid_data("Malformed tar header number"))?
+        .trim();
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(digits, 8).map_err(|_| invalid_data("Malformed tar header number"))
+}
+
+fn header(name: &[u8], size: u64, mtime: u64, mode: u64, type_flag: u8) -> [u8; BLOCK_SIZE] {
+    let mut block = [0u8; BLOCK_SIZE];
+    // overlong names are cut short, the extended header carries the full one
+    let name_length = name.len().min(NAME_LENGTH);
+    block[..name_length].copy_from_slice(&name[..name_length]);
+    octal(&mut block[100..108], mode);
+    octal(&mut block[108..116], 0); // uid
+    octal(&mut block[116..124], 0); // gid
+    octal(&mut block[124..136], size);
+    octal(&mut block[136..148], mtime);
+    block[156] = type_flag;
+    block[257..263].copy_from_slice(b"ustar\0");
+    block[263..265].copy_from_slice(b"00");
+
+    // the checksum is calculated with its own field filled with spaces
+    block[148..156].fill(b' ');
+    let checksum: u32 = block.iter().map(|byte| *byte as u32).sum();
+    block[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+    block
+}
+
+fn octal(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    let value = format!("{value:0digits$o}");
+    field[..digits].copy_from_slice(value.as_bytes());
+    field[digits] = 0;
+}
+
+/// A `"<length> <key>=<value>\n"` record, where the length includes itself.
+fn pax_record(key: &str, value: &str) -> String {
+    let base = key.len() + value.len() + 3;
+    let mut length = base;
+    loop {
+        let next = base + length.to_string().len();
+        if next == length {
+            break;
+        }
+        length = next;
+    }
+    format!("{length} {key}={value}\n")
+}
+
+fn pad(written: u64, out: &mut Vec<u8>) {
+    out.resize(out.len() + padding(written) as usize, 0);
+}
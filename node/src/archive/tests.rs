@@ -0,0 +1,223 @@
+#[cfg(test)]
+mod archive_tests {
+    use std::io::Read;
+
+    use chrono::{TimeZone, Utc};
+    use flate2::read::GzDecoder;
+
+    use crate::archive::tar::{padding, parse_header, parse_pax_records, TarEntryType};
+    use crate::archive::zip::{
+        parse_central_directory, parse_end_record, parse_local_header, EndRecord,
+        LOCAL_HEADER_LENGTH,
+    };
+    use crate::archive::{ArchiveEntry, ArchiveFormat, ArchiveWriter, EntryKind};
+
+    fn entries(name_length: usize) -> Vec<(ArchiveEntry, Vec<u8>)> {
+        let modified = Utc.with_ymd_and_hms(2024, 5, 17, 12, 30, 10).unwrap();
+        vec![
+            (
+                ArchiveEntry {
+                    path: "dir".to_string(),
+                    kind: EntryKind::Directory,
+                    size: 0,
+                    modified,
+                },
+                vec![],
+            ),
+            (
+                ArchiveEntry {
+                    path: format!("dir/{}", "a".repeat(name_length)),
+                    kind: EntryKind::File,
+                    size: 700,
+                    modified,
+                },
+                (0..700).map(|i| i as u8).collect(),
+            ),
+        ]
+    }
+
+    fn encode(format: ArchiveFormat, entries: &[(ArchiveEntry, Vec<u8>)]) -> Vec<u8> {
+        let mut writer = ArchiveWriter::new(format);
+        let mut output = vec![];
+        for (entry, data) in entries {
+            writer.start_entry(entry);
+            for piece in data.chunks(128) {
+                writer.write_data(piece);
+                output.extend(writer.take_output());
+            }
+            writer.finish_entry().unwrap();
+        }
+        writer.finish().unwrap();
+        output.extend(writer.take_output());
+        output
+    }
+
+    fn octal(field: &[u8]) -> u64 {
+        let digits = std::str::from_utf8(field).unwrap().trim_end_matches('\0');
+        u64::from_str_radix(digits.trim(), 8).unwrap()
+    }
+
+    fn le_u32(data: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_tar() {
+        let archive = encode(ArchiveFormat::Tar, &entries(8));
+        // directory header, file header, 2 blocks of data, end of archive
+        assert_eq!(archive.len(), 6 * 512);
+
+        let directory = &archive[..512];
+        assert!(directory.starts_with(b"dir/\0"));
+        assert_eq!(directory[156], b'5');
+        assert_eq!(&directory[257..263], b"ustar\0");
+        assert_eq!(octal(&directory[136..148]), 1715949010);
+
+        let file = &archive[512..1024];
+        assert!(file.starts_with(b"dir/aaaaaaaa\0"));
+        assert_eq!(file[156], b'0');
+        assert_eq!(octal(&file[124..136]), 700);
+        let mut blank = file.to_vec();
+        blank[148..156].fill(b' ');
+        let checksum: u64 = blank.iter().map(|byte| *byte as u64).sum();
+        assert_eq!(octal(&file[148..155]), checksum);
+
+        assert_eq!(archive[1024..1724], entries(8)[1].1);
+        assert!(archive[1724..].iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn test_tar_long_name() {
+        let archive = encode(ArchiveFormat::Tar, &entries(200));
+        let pax = &archive[512..1024];
+        assert_eq!(pax[156], b'x');
+        let record = format!("214 path=dir/{}\n", "a".repeat(200));
+        assert_eq!(octal(&pax[124..136]), record.len() as u64);
+        assert_eq!(&archive[1024..1024 + record.len()], record.as_bytes());
+        assert_eq!(archive[1536 + 156], b'0');
+    }
+
+    #[test]
+    fn test_tar_gz() {
+        let entries = entries(8);
+        let mut decoded = vec![];
+        GzDecoder::new(&encode(ArchiveFormat::TarGz, &entries)[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, encode(ArchiveFormat::Tar, &entries));
+    }
+
+    #[test]
+    fn test_zip() {
+        let entries = entries(8);
+        let archive = encode(ArchiveFormat::Zip, &entries);
+
+        let end = archive.len() - 22;
+        assert_eq!(le_u32(&archive, end), 0x06054b50);
+        assert_eq!(
+            u16::from_le_bytes([archive[end + 10], archive[end + 11]]),
+            2
+        );
+        let directory_offset = le_u32(&archive, end + 16) as usize;
+
+        // the second central header belongs to the file
+        let first_name_length = 4;
+        let second = directory_offset + 46 + first_name_length + 9;
+        assert_eq!(le_u32(&archive, second), 0x02014b50);
+        assert_eq!(
+            le_u32(&archive, second + 16),
+            crc32fast::hash(&entries[1].1)
+        );
+        assert_eq!(le_u32(&archive, second + 24), 700);
+        let local = le_u32(&archive, second + 42) as usize;
+        assert_eq!(le_u32(&archive, local), 0x04034b50);
+
+        let data = local + 30 + 12 + 9;
+        assert_eq!(archive[data..data + 700], entries[1].1);
+        assert_eq!(le_u32(&archive, data + 700), 0x08074b50);
+        assert_eq!(le_u32(&archive, data + 704), crc32fast::hash(&entries[1].1));
+    }
+
+    #[test]
+    fn test_size_mismatch() {
+        for format in [ArchiveFormat::Tar, ArchiveFormat::Zip] {
+            let mut writer = ArchiveWriter::new(format);
+            writer.start_entry(&entries(8)[1].0);
+            writer.write_data(&[0; 10]);
+            assert!(writer.finish_entry().is_err());
+        }
+    }
+
+    #[test]
+    fn test_parse_tar() {
+        let archive = encode(ArchiveFormat::Tar, &entries(8));
+        let directory = parse_header(archive[..512].try_into().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(directory.name, b"dir/");
+        assert_eq!(directory.entry_type, TarEntryType::Directory);
+
+        let file = parse_header(archive[512..1024].try_into().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(file.name, b"dir/aaaaaaaa");
+        assert_eq!(file.size, 700);
+        assert_eq!(file.entry_type, TarEntryType::File);
+        assert_eq!(padding(file.size), 324);
+
+        let end = parse_header(archive[2048..2560].try_into().unwrap()).unwrap();
+        assert!(end.is_none());
+
+        let mut corrupt = archive[512..1024].to_vec();
+        corrupt[0] = b'b';
+        assert!(parse_header(corrupt[..].try_into().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_parse_tar_long_name() {
+        let archive = encode(ArchiveFormat::Tar, &entries(200));
+        let pax = parse_header(archive[512..1024].try_into().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(pax.entry_type, TarEntryType::PaxHeader);
+        let records = parse_pax_records(&archive[1024..1024 + pax.size as usize]).unwrap();
+        assert_eq!(
+            records,
+            vec![(
+                "path".to_string(),
+                format!("dir/{}", "a".repeat(200)).into_bytes()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_parse_zip() {
+        let entries = entries(8);
+        let archive = encode(ArchiveFormat::Zip, &entries);
+
+        let EndRecord::Directory(directory) = parse_end_record(&archive).unwrap() else {
+            panic!("Expected a plain end record");
+        };
+        assert_eq!(directory.entries, 2);
+        let start = directory.offset as usize;
+        let parsed = parse_central_directory(
+            &archive[start..start + directory.size as usize],
+            directory.entries,
+        )
+        .unwrap();
+        assert_eq!(parsed[0].name, b"dir/");
+        assert!(parsed[0].is_directory());
+
+        let file = &parsed[1];
+        assert_eq!(file.name, b"dir/aaaaaaaa");
+        assert_eq!(file.size, 700);
+        assert_eq!(file.compressed_size, 700);
+        assert_eq!(file.crc, crc32fast::hash(&entries[1].1));
+        let offset = file.offset as usize;
+        let data = parse_local_header(&archive[offset..offset + LOCAL_HEADER_LENGTH], file.offset)
+            .unwrap() as usize;
+        assert_eq!(archive[data..data + 700], entries[1].1);
+
+        assert!(parse_end_record(&archive[..archive.len() - 1]).is_err());
+    }
+}
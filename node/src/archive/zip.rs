@@ -0,0 +1,453 @@
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use crc32fast::Hasher;
+
+use crate::archive::{invalid_data, size_mismatch, ArchiveEntry, EntryKind};
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x08074b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const ZIP64_END_SIGNATURE: u32 = 0x06064b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+const END_SIGNATURE: u32 = 0x06054b50;
+
+const FLAG_ENCRYPTED: u16 = 1;
+const FLAG_DATA_DESCRIPTOR: u16 = 1 << 3;
+const FLAG_UTF8: u16 = 1 << 11;
+pub const METHOD_STORED: u16 = 0;
+pub const METHOD_DEFLATED: u16 = 8;
+const VERSION_DEFAULT: u16 = 20;
+const VERSION_ZIP64: u16 = 45;
+/// Unix, so that the upper half of the external attributes holds the file mode.
+const VERSION_MADE_BY: u16 = (3 << 8) | VERSION_ZIP64;
+
+const EXTRA_ZIP64: u16 = 0x0001;
+const EXTRA_TIMESTAMP: u16 = 0x5455;
+const TIMESTAMP_MODIFIED: u8 = 1;
+
+/// Values from this one up are moved into the zip64 extra field.
+const U32_LIMIT: u64 = 0xFFFFFFFF;
+const U16_LIMIT: u64 = 0xFFFF;
+
+pub const LOCAL_HEADER_LENGTH: usize = 30;
+const CENTRAL_HEADER_LENGTH: usize = 46;
+pub const ZIP64_END_LENGTH: usize = 56;
+const ZIP64_LOCATOR_LENGTH: usize = 20;
+const END_LENGTH: usize = 22;
+/// The end record lies within this distance from the end, as the comment following it is at most 64 KiB long.
+pub const MAX_END_DISTANCE: u64 = (ZIP64_LOCATOR_LENGTH + END_LENGTH + 0xFFFF) as u64;
+
+const ATTRIBUTES_FILE: u32 = 0o100644 << 16;
+const ATTRIBUTES_DIRECTORY: u32 = (0o40755 << 16) | 0x10;
+
+struct CentralRecord {
+    name: Vec<u8>,
+    flags: u16,
+    crc: u32,
+    size: u64,
+    offset: u64,
+    dos_time: u16,
+    dos_date: u16,
+    mtime: u32,
+    attributes: u32,
+}
+
+impl CentralRecord {
+    fn zip64(&self) -> bool {
+        self.size >= U32_LIMIT || self.offset >= U32_LIMIT
+    }
+}
+
+/// Writes zip archives as a stream.
+/// The entries are stored uncompressed, with the checksum following the contents in a data descriptor.
+#[derive(Default)]
+pub struct ZipEncoder {
+    /// The amount of bytes written so far.
+    offset: u64,
+    records: Vec<CentralRecord>,
+    current: Option<CentralRecord>,
+    hasher: Hasher,
+    written: u64,
+}
+
+impl ZipEncoder {
+    pub fn start_entry(&mut self, entry: &ArchiveEntry, out: &mut Vec<u8>) {
+        let (name, size, flags, attributes) = match entry.kind {
+            EntryKind::Directory => (
+                format!("{}/", entry.path),
+                0,
+                FLAG_UTF8,
+                ATTRIBUTES_DIRECTORY,
+            ),
+            EntryKind::File => (
+                entry.path.clone(),
+                entry.size,
+                FLAG_UTF8 | FLAG_DATA_DESCRIPTOR,
+                ATTRIBUTES_FILE,
+            ),
+        };
+        let (dos_time, dos_date) = dos_date_time(&entry.modified);
+        let record = CentralRecord {
+            name: name.into_bytes(),
+            flags,
+            crc: 0,
+            size,
+            offset: self.offset,
+            dos_time,
+            dos_date,
+            mtime: entry.modified.timestamp().clamp(0, u32::MAX as i64) as u32,
+            attributes,
+        };
+
+        let start = out.len();
+        let zip64 = size >= U32_LIMIT;
+        let mut extra = timestamp_extra(record.mtime);
+        if zip64 {
+            // the sizes follow in the data descriptor, the field only announces their width
+            put_u16(&mut extra, EXTRA_ZIP64);
+            put_u16(&mut extra, 16);
+            put_u64(&mut extra, 0);
+            put_u64(&mut extra, 0);
+        }
+        let local_size = if zip64 { U32_LIMIT as u32 } else { 0 };
+
+        put_u32(out, LOCAL_HEADER_SIGNATURE);
+        put_u16(
+            out,
+            if zip64 {
+                VERSION_ZIP64
+            } else {
+                VERSION_DEFAULT
+            },
+        );
+        put_u16(out, flags);
+        put_u16(out, METHOD_STORED);
+        put_u16(out, dos_time);
+        put_u16(out, dos_date);
+        put_u32(out, 0); // crc, in the data descriptor
+        put_u32(out, local_size);
+        put_u32(out, local_size);
+        put_u16(out, record.name.len() as u16);
+        put_u16(out, extra.len() as u16);
+        out.extend_from_slice(&record.name);
+        out.extend_from_slice(&extra);
+
+        self.offset += (out.len() - start) as u64;
+        self.written = 0;
+        self.hasher = Hasher::new();
+        self.current = Some(record);
+    }
+
+    pub fn write_data(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        self.hasher.update(data);
+        self.written += data.len() as u64;
+        self.offset += data.len() as u64;
+        out.extend_from_slice(data);
+    }
+
+    pub fn finish_entry(&mut self, out: &mut Vec<u8>) -> std::io::Result<()> {
+        let Some(mut record) = self.current.take() else {
+            return Ok(());
+        };
+        if self.written != record.size {
+            return Err(size_mismatch(record.size, self.written));
+        }
+        record.crc = std::mem::take(&mut self.hasher).finalize();
+
+        if record.flags & FLAG_DATA_DESCRIPTOR != 0 {
+            let start = out.len();
+            put_u32(out, DATA_DESCRIPTOR_SIGNATURE);
+            put_u32(out, record.crc);
+            if record.size >= U32_LIMIT {
+                put_u64(out, record.size);
+                put_u64(out, record.size);
+            } else {
+                put_u32(out, record.size as u32);
+                put_u32(out, record.size as u32);
+            }
+            self.offset += (out.len() - start) as u64;
+        }
+        self.records.push(record);
+        Ok(())
+    }
+
+    pub fn finish(&mut self, out: &mut Vec<u8>) {
+        let directory_offset = self.offset;
+        let start = out.len();
+        for record in &self.records {
+            let mut extra = timestamp_extra(record.mtime);
+            if record.zip64() {
+                let mut fields = vec![];
+                if record.size >= U32_LIMIT {
+                    put_u64(&mut fields, record.size);
+                    put_u64(&mut fields, record.size);
+                }
+                if record.offset >= U32_LIMIT {
+                    put_u64(&mut fields, record.offset);
+                }
+                put_u16(&mut extra, EXTRA_ZIP64);
+                put_u16(&mut extra, fields.len() as u16);
+                extra.extend_from_slice(&fields);
+            }
+
+            put_u32(out, CENTRAL_HEADER_SIGNATURE);
+            put_u16(out, VERSION_MADE_BY);
+            put_u16(
+                out,
+                if record.zip64() {
+                    VERSION_ZIP64
+                } else {
+                    VERSION_DEFAULT
+                },
+            );
+            put_u16(out, record.flags);
+            put_u16(out, METHOD_STORED);
+            put_u16(out, record.dos_time);
+            put_u16(out, record.dos_date);
+            put_u32(out, record.crc);
+            put_u32(out, record.size.min(U32_LIMIT) as u32);
+            put_u32(out, record.size.min(U32_LIMIT) as u32);
+            put_u16(out, record.name.len() as u16);
+            put_u16(out, extra.len() as u16);
+            put_u16(out, 0); // comment length
+            put_u16(out, 0); // disk number
+            put_u16(out, 0); // internal attributes
+            put_u32(out, record.attributes);
+            put_u32(out, record.offset.min(U32_LIMIT) as u32);
+            out.extend_from_slice(&record.name);
+            out.extend_from_slice(&extra);
+        }
+        let directory_size = (out.len() - start) as u64;
+        let entries = self.records.len() as u64;
+
+        if entries >= U16_LIMIT || directory_size >= U32_LIMIT || directory_offset >= U32_LIMIT {
+            let zip64_end_offset = directory_offset + directory_size;
+            put_u32(out, ZIP64_END_SIGNATURE);
+            put_u64(out, 44); // size of the remaining record
+            put_u16(out, VERSION_MADE_BY);
+            put_u16(out, VERSION_ZIP64);
+            put_u32(out, 0); // disk number
+            put_u32(out, 0); // disk with the central directory
+            put_u64(out, entries);
+            put_u64(out, entries);
+            put_u64(out, directory_size);
+            put_u64(out, directory_offset);
+
+            put_u32(out, ZIP64_LOCATOR_SIGNATURE);
+            put_u32(out, 0); // disk with the zip64 end record
+            put_u64(out, zip64_end_offset);
+            put_u32(out, 1); // total disks
+        }
+
+        put_u32(out, END_SIGNATURE);
+        put_u16(out, 0); // disk number
+        put_u16(out, 0); // disk with the central directory
+        put_u16(out, entries.min(U16_LIMIT) as u16);
+        put_u16(out, entries.min(U16_LIMIT) as u16);
+        put_u32(out, directory_size.min(U32_LIMIT) as u32);
+        put_u32(out, directory_offset.min(U32_LIMIT) as u32);
+        put_u16(out, 0); // comment length
+
+        self.offset += (out.len() - start) as u64;
+    }
+}
+
+/// An entry as listed in the central directory.
+#[derive(Debug)]
+pub struct ZipEntry {
+    pub name: Vec<u8>,
+    pub flags: u16,
+    pub method: u16,
+    pub crc: u32,
+    pub compressed_size: u64,
+    pub size: u64,
+    /// Of the local header.
+    pub offset: u64,
+}
+
+impl ZipEntry {
+    pub fn is_directory(&self) -> bool {
+        self.name.ends_with(b"/")
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.flags & FLAG_ENCRYPTED != 0
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct CentralDirectory {
+    pub offset: u64,
+    pub size: u64,
+    pub entries: u64,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum EndRecord {
+    Directory(CentralDirectory),
+    /// The offset of the zip64 end record, which locates the central directory instead.
+    Zip64(u64),
+}
+
+/// Finds the end record within the last [MAX_END_DISTANCE] bytes of the archive.
+pub fn parse_end_record(tail: &[u8]) -> std::io::Result<EndRecord> {
+    let position = (0..=tail.len().saturating_sub(END_LENGTH))
+        .rev()
+        .find(|position| {
+            le_u32(tail, *position).ok() == Some(END_SIGNATURE)
+                // the comment has to reach the end exactly, so that signatures within it are skipped
+                && le_u16(tail, position + 20)
+                    .is_ok_and(|comment| position + END_LENGTH + comment as usize == tail.len())
+        })
+        .ok_or_else(|| invalid_data("Missing zip end record"))?;
+
+    let entries = le_u16(tail, position + 10)? as u64;
+    let size = le_u32(tail, position + 12)? as u64;
+    let offset = le_u32(tail, position + 16)? as u64;
+    if entries < U16_LIMIT && size < U32_LIMIT && offset < U32_LIMIT {
+        return Ok(EndRecord::Directory(CentralDirectory {
+            offset,
+            size,
+            entries,
+        }));
+    }
+
+    let locator = position
+        .checked_sub(ZIP64_LOCATOR_LENGTH)
+        .filter(|locator| le_u32(tail, *locator).ok() == Some(ZIP64_LOCATOR_SIGNATURE))
+        .ok_or_else(|| invalid_data("Missing zip64 end record locator"))?;
+    Ok(EndRecord::Zip64(le_u64(tail, locator + 8)?))
+}
+
+pub fn parse_zip64_end_record(record: &[u8]) -> std::io::Result<CentralDirectory> {
+    if le_u32(record, 0)? != ZIP64_END_SIGNATURE {
+        return Err(invalid_data("Invalid zip64 end record"));
+    }
+    Ok(CentralDirectory {
+        entries: le_u64(record, 32)?,
+        size: le_u64(record, 40)?,
+        offset: le_u64(record, 48)?,
+    })
+}
+
+pub fn parse_central_directory(data: &[u8], entries: u64) -> std::io::Result<Vec<ZipEntry>> {
+    let mut parsed = vec![];
+    let mut position = 0;
+    for _ in 0..entries {
+        if le_u32(data, position)? != CENTRAL_HEADER_SIGNATURE {
+            return Err(invalid_data("Invalid zip central directory header"));
+        }
+        let name_length = le_u16(data, position + 28)? as usize;
+        let extra_length = le_u16(data, position + 30)? as usize;
+        let comment_length = le_u16(data, position + 32)? as usize;
+        let name_start = position + CENTRAL_HEADER_LENGTH;
+        let extra_start = name_start + name_length;
+        let name = data
+            .get(name_start..extra_start)
+            .ok_or_else(|| invalid_data("Truncated zip central directory"))?;
+        let extra = data
+            .get(extra_start..extra_start + extra_length)
+            .ok_or_else(|| invalid_data("Truncated zip central directory"))?;
+
+        let mut entry = ZipEntry {
+            name: name.to_vec(),
+            flags: le_u16(data, position + 8)?,
+            method: le_u16(data, position + 10)?,
+            crc: le_u32(data, position + 16)?,
+            compressed_size: le_u32(data, position + 20)? as u64,
+            size: le_u32(data, position + 24)? as u64,
+            offset: le_u32(data, position + 42)? as u64,
+        };
+        apply_zip64_extra(&mut entry, extra)?;
+        parsed.push(entry);
+        position = extra_start + extra_length + comment_length;
+    }
+    Ok(parsed)
+}
+
+/// The saturated fields are listed in the zip64 extra field, in the order of the header.
+fn apply_zip64_extra(entry: &mut ZipEntry, mut extra: &[u8]) -> std::io::Result<()> {
+    while extra.len() >= 4 {
+        let tag = le_u16(extra, 0)?;
+        let length = le_u16(extra, 2)? as usize;
+        let field = extra
+            .get(4..4 + length)
+            .ok_or_else(|| invalid_data("Truncated zip extra field"))?;
+        if tag == EXTRA_ZIP64 {
+            let mut position = 0;
+            for value in [
+                &mut entry.size,
+                &mut entry.compressed_size,
+                &mut entry.offset,
+            ] {
+                if *value == U32_LIMIT {
+                    *value = le_u64(field, position)?;
+                    position += 8;
+                }
+            }
+        }
+        extra = &extra[4 + length..];
+    }
+    Ok(())
+}
+
+/// The offset of an entry's contents, which follow the variable length local header.
+pub fn parse_local_header(header: &[u8], offset: u64) -> std::io::Result<u64> {
+    if le_u32(header, 0)? != LOCAL_HEADER_SIGNATURE {
+        return Err(invalid_data("Invalid zip local header"));
+    }
+    let name_length = le_u16(header, 26)? as u64;
+    let extra_length = le_u16(header, 28)? as u64;
+    Ok(offset + LOCAL_HEADER_LENGTH as u64 + name_length + extra_length)
+}
+
+fn le_u16(data: &[u8], at: usize) -> std::io::Result<u16> {
+    data.get(at..at + 2)
+        .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| invalid_data("Truncated zip record"))
+}
+
+fn le_u32(data: &[u8], at: usize) -> std::io::Result<u32> {
+    data.get(at..at + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| invalid_data("Truncated zip record"))
+}
+
+fn le_u64(data: &[u8], at: usize) -> std::io::Result<u64> {
+    data.get(at..at + 8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| invalid_data("Truncated zip record"))
+}
+
+/// MS-DOS time and date, which can only represent the years from 1980 to 2107.
+fn dos_date_time(time: &DateTime<Utc>) -> (u16, u16) {
+    if time.year() < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let year = time.year().min(2107) as u16 - 1980;
+    let dos_time =
+        ((time.hour() as u16) << 11) | ((time.minute() as u16) << 5) | (time.second() as u16 / 2);
+    let dos_date = (year << 9) | ((time.month() as u16) << 5) | time.day() as u16;
+    (dos_time, dos_date)
+}
+
+/// The extended timestamp field, keeping the modification time exact to the second.
+fn timestamp_extra(mtime: u32) -> Vec<u8> {
+    let mut extra = vec![];
+    put_u16(&mut extra, EXTRA_TIMESTAMP);
+    put_u16(&mut extra, 5);
+    extra.push(TIMESTAMP_MODIFIED);
+    put_u32(&mut extra, mtime);
+    extra
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
@@ -1,11 +1,17 @@
 use crate::caching::invalidator::CacheInvalidator;
 use async_trait::async_trait;
 use cached::proc_macro::cached;
-use cached::{Cached, TimedCache};
+use cached::{Cached, TimedCache, TimedSizedCache};
 use commons::access_token_service::ClaimKey;
+use commons::cache::{AppCacheKey, BucketCacheKey};
 use commons::permission::AppTokenData;
-use data::access::app_access::get_app_token;
+use data::access::app_access::{get_app_by_id, get_app_token};
+use data::access::file_access::get_bucket;
+use data::dto::entity::RateLimitsDto;
+use data::error::MeowithDataError;
+use data::model::file_model::{CorsRule, PublicAccess};
 use scylla::client::caching_session::CachingSession;
+use uuid::Uuid;
 
 #[cached(
     ty = "TimedCache<ClaimKey, bool>",
@@ -26,6 +32,61 @@ pub async fn validate_nonce(claims: &AppTokenData, session: &CachingSession) ->
     }
 }
 
+/// The settings of a bucket evaluated before, or in place of, the authentication of a request.
+#[derive(Clone, Debug, Default)]
+pub struct BucketPolicy {
+    /// None for private buckets.
+    pub public_access: Option<PublicAccess>,
+    pub cors_rules: Vec<CorsRule>,
+    /// Set while the bucket awaits its deletion, no request is served then.
+    pub deleting: bool,
+}
+
+/// The policy of a bucket, the default one for missing buckets.
+#[cached(
+    ty = "TimedSizedCache<(Uuid, Uuid), BucketPolicy>",
+    create = "{ TimedSizedCache::with_size_and_lifespan(1024, 60) }",
+    convert = r#"{ (app_id, bucket_id) }"#,
+    result = true
+)]
+pub async fn get_bucket_policy(
+    app_id: Uuid,
+    bucket_id: Uuid,
+    session: &CachingSession,
+) -> Result<BucketPolicy, MeowithDataError> {
+    match get_bucket(app_id, bucket_id, session).await {
+        Ok(bucket) if bucket.is_deleting() => Ok(BucketPolicy {
+            deleting: true,
+            ..Default::default()
+        }),
+        Ok(bucket) => Ok(BucketPolicy {
+            public_access: bucket.public_access,
+            cors_rules: bucket.cors_rules.unwrap_or_default(),
+            deleting: false,
+        }),
+        Err(MeowithDataError::NotFound) => Ok(BucketPolicy::default()),
+        Err(err) => Err(err),
+    }
+}
+
+/// The limits of an app, none for missing apps and apps without limits.
+#[cached(
+    ty = "TimedSizedCache<Uuid, Option<RateLimitsDto>>",
+    create = "{ TimedSizedCache::with_size_and_lifespan(1024, 60) }",
+    convert = r#"{ app_id }"#,
+    result = true
+)]
+pub async fn get_app_limits(
+    app_id: Uuid,
+    session: &CachingSession,
+) -> Result<Option<RateLimitsDto>, MeowithDataError> {
+    match get_app_by_id(app_id, session).await {
+        Ok(app) => Ok(app.limits.map(RateLimitsDto::from)),
+        Err(MeowithDataError::NotFound) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
 #[derive(Debug)]
 pub struct ValidateNonceInvalidator;
 
@@ -38,3 +99,33 @@ impl CacheInvalidator for ValidateNonceInvalidator {
         }
     }
 }
+
+#[derive(Debug)]
+pub struct BucketPolicyInvalidator;
+
+#[async_trait]
+impl CacheInvalidator for BucketPolicyInvalidator {
+    async fn invalidate(&self, cache_key: &[u8]) {
+        let bucket_key: serde_cbor::error::Result<BucketCacheKey> =
+            serde_cbor::from_slice(cache_key);
+        if let Ok(bucket_key) = bucket_key {
+            GET_BUCKET_POLICY
+                .lock()
+                .await
+                .cache_remove(&(bucket_key.app_id, bucket_key.bucket_id));
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AppLimitsInvalidator;
+
+#[async_trait]
+impl CacheInvalidator for AppLimitsInvalidator {
+    async fn invalidate(&self, cache_key: &[u8]) {
+        let app_key: serde_cbor::error::Result<AppCacheKey> = serde_cbor::from_slice(cache_key);
+        if let Ok(app_key) = app_key {
+            GET_APP_LIMITS.lock().await.cache_remove(&app_key.app_id);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::caching::db::ValidateNonceInvalidator;
+use crate::caching::db::{AppLimitsInvalidator, BucketPolicyInvalidator, ValidateNonceInvalidator};
 use crate::caching::mgpp_handler::NsmData;
 use crate::caching::node_storage_map::NodeStorageMapInvalidator;
 use async_trait::async_trait;
@@ -19,6 +19,11 @@ pub fn insert_invalidator_map(
         CacheId::ValidateNonce.into(),
         Box::new(ValidateNonceInvalidator {}),
     );
+    invalidator_map.insert(
+        CacheId::BucketPolicy.into(),
+        Box::new(BucketPolicyInvalidator {}),
+    );
+    invalidator_map.insert(CacheId::AppLimits.into(), Box::new(AppLimitsInvalidator {}));
     invalidator_map.insert(
         CacheId::NodeStorageMap.into(),
         Box::new(NodeStorageMapInvalidator {
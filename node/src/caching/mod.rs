@@ -1,4 +1,4 @@
-use crate::caching::db::VALIDATE_NONCE;
+use crate::caching::db::{GET_APP_LIMITS, GET_BUCKET_POLICY, VALIDATE_NONCE};
 use cached::Cached;
 
 pub mod db;
@@ -9,5 +9,7 @@ pub mod node_storage_map;
 /// Clears all caches, to be used upon re-connecting with the control network, as by that time
 /// invalidation packets might have been missed.
 pub async fn clear_caches() {
-    VALIDATE_NONCE.lock().await.cache_clear()
+    VALIDATE_NONCE.lock().await.cache_clear();
+    GET_BUCKET_POLICY.lock().await.cache_clear();
+    GET_APP_LIMITS.lock().await.cache_clear();
 }
@@ -3,7 +3,7 @@ use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use log::{error, trace, warn};
+use log::{debug, error, trace, warn};
 use tokio::io::{AsyncWrite, AsyncWriteExt};
 use tokio::select;
 use tokio::sync::mpsc::Sender;
@@ -432,6 +432,64 @@ impl ChannelPacketHandler for MeowithMDSFTPChannelPacketHandler {
         Ok(())
     }
 
+    async fn handle_merge(
+        &mut self,
+        channel: Channel,
+        chunk_id: Uuid,
+        target_id: Uuid,
+        target_size: u64,
+    ) -> MDSFTPResult<()> {
+        match self
+            .fragment_ledger
+            .merge_chunk(&chunk_id, &target_id, target_size)
+            .await
+        {
+            Ok(_) => channel.respond_commit_ok().await?,
+            Err(MeowithIoError::NotFound) => {
+                channel.respond_commit_err(ChunkErrorKind::NotFound).await?
+            }
+            Err(err) => {
+                debug!("Merge of {chunk_id} into {target_id} failed {err}");
+                channel
+                    .respond_commit_err(ChunkErrorKind::NotAvailable)
+                    .await?
+            }
+        }
+        channel.close(Ok(())).await;
+        Ok(())
+    }
+
+    async fn handle_duplicate(
+        &mut self,
+        channel: Channel,
+        chunk_id: Uuid,
+        size: u64,
+        associated_bucket_id: Uuid,
+        associated_file_id: Uuid,
+    ) -> MDSFTPResult<()> {
+        match self
+            .fragment_ledger
+            .duplicate_chunk(&chunk_id, size, associated_bucket_id, associated_file_id)
+            .await
+        {
+            Ok(id) => {
+                channel.respond_reserve_ok(id, self.chunk_buffer).await?;
+                channel.close(Ok(())).await;
+            }
+            Err(MeowithIoError::InsufficientDiskSpace) => {
+                channel
+                    .respond_reserve_err(self.fragment_ledger.get_available_space())
+                    .await?;
+                channel.close(Ok(())).await;
+            }
+            Err(err) => {
+                debug!("Duplicate of {chunk_id} failed {err}");
+                channel.close(Err(MDSFTPError::NoSuchChunkId)).await;
+            }
+        }
+        Ok(())
+    }
+
     async fn handle_query(&mut self, channel: Channel, chunk_id: Uuid) -> MDSFTPResult<()> {
         if let Some(data) = self
             .fragment_ledger
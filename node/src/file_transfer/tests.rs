@@ -257,5 +257,94 @@ mod fp63_tests {
 
             assert_eq!(meta.len(), file_size);
         }
+
+        {
+            debug!("Testing merge");
+            let appended_size = 1024;
+            let channel = client_pool.channel(&id1).await.unwrap();
+            let reserve = channel
+                .try_reserve(
+                    appended_size,
+                    Uuid::new_v4(),
+                    Uuid::new_v4(),
+                    ReserveFlags {
+                        auto_start: true,
+                        durable: false,
+                        temp: false,
+                        overwrite: false,
+                    },
+                )
+                .await
+                .expect("Reserve failed");
+            let handler = Box::new(MeowithMDSFTPChannelPacketHandler::new(
+                client_ledger.clone(),
+                16,
+                u16::MAX as u32,
+            ));
+            let reader = client_ledger
+                .fragment_read_stream(&file_a_id)
+                .await
+                .expect("Read fail");
+            let handle = channel
+                .send_content(reader, appended_size, reserve.chunk_buffer, handler)
+                .await
+                .expect("Delegate failed");
+            handle.await;
+
+            // A stale size is refused, leaving both chunks as they were.
+            let channel = client_pool.channel(&id1).await.unwrap();
+            assert!(channel
+                .merge(reserve.chunk_id, uploaded_id, file_size - 1)
+                .await
+                .is_err());
+            let channel = client_pool.channel(&id1).await.unwrap();
+            channel
+                .merge(reserve.chunk_id, uploaded_id, file_size)
+                .await
+                .expect("Merge failed");
+
+            let merged_meta = server_ledger
+                .existing_fragment_meta(&uploaded_id)
+                .await
+                .unwrap();
+            assert_eq!(merged_meta.disk_content_size, file_size + appended_size);
+            assert!(!server_ledger.fragment_exists(&reserve.chunk_id).await);
+
+            let mut merged = fs::read(node_dir_one.join(uploaded_id.to_string())).unwrap();
+            assert_eq!(merged.split_off(file_size as usize), random_bytes[..1024]);
+            assert_eq!(merged, random_bytes);
+        }
+
+        {
+            debug!("Testing duplicate");
+            let channel = client_pool.channel(&id1).await.unwrap();
+            assert!(channel
+                .duplicate(Uuid::new_v4(), file_size, Uuid::new_v4(), Uuid::new_v4())
+                .await
+                .is_err());
+            let channel = client_pool.channel(&id1).await.unwrap();
+            let duplicate = channel
+                .duplicate(uploaded_id, file_size, Uuid::new_v4(), Uuid::new_v4())
+                .await
+                .expect("Duplicate failed");
+            assert_ne!(duplicate.chunk_id, uploaded_id);
+
+            let channel = client_pool.channel(&id1).await.unwrap();
+            channel
+                .commit(duplicate.chunk_id, CommitFlags::r#final())
+                .await
+                .expect("Commit failed");
+            let duplicated = fs::read(node_dir_one.join(duplicate.chunk_id.to_string())).unwrap();
+            assert_eq!(duplicated, random_bytes);
+            // The source, merged into above, keeps all of its content.
+            assert_eq!(
+                server_ledger
+                    .existing_fragment_meta(&uploaded_id)
+                    .await
+                    .unwrap()
+                    .disk_content_size,
+                file_size + 1024
+            );
+        }
     }
 }
@@ -11,10 +11,11 @@ use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::fs::{File, OpenOptions};
-use tokio::io::{BufReader, BufStream, BufWriter};
+use tokio::io::{AsyncReadExt, BufReader, BufStream, BufWriter};
 use tokio::sync::{Mutex, RwLock};
 use tokio::task::JoinHandle;
 use tokio::time;
+use tokio::try_join;
 use uuid::Uuid;
 
 use protocol::mdsftp::handler::{AbstractFileStream, AbstractReadStream, AbstractWriteStream};
@@ -24,8 +25,13 @@ use crate::io::get_space;
 use crate::locking::file_lock_table::FileLockTable;
 use crate::public::service::durable_transfer_session_manager::DURABLE_UPLOAD_SESSION_VALIDITY_TIME_SECS;
 use commons::error::io_error::{MeowithIoError, MeowithIoResult};
-use data::access::file_access::{get_all_files, maybe_get_file_by_id};
+use data::access::file_access::{
+    get_all_bucket_snapshots, get_all_file_versions, get_all_files, get_all_trashed_files,
+    maybe_get_file_by_id, maybe_get_file_version_by_file_id, maybe_get_first_snapshot_chunk,
+    maybe_get_trashed_file_by_file_id,
+};
 use data::dto::controller::UpdateStorageNodeProperties;
+use data::model::file_model::FileChunk;
 
 pub type LockTable = FileLockTable<Uuid>;
 
@@ -63,6 +69,7 @@ impl FragmentLedger {
             disk_content_size: Default::default(),
             reservation_map: Default::default(),
             uncommited_map: Default::default(),
+            merging: Default::default(),
             ext_metadata_store: RwLock::new(Some(ext_metadata_store)),
             housekeeper_handle: std::sync::Mutex::new(None),
             disk_reserved_size: Default::default(),
@@ -229,11 +236,23 @@ impl FragmentLedger {
             let assoc_meta = ext_metadata_store.get(id);
             match assoc_meta {
                 Ok(assoc) => {
-                    let file = maybe_get_file_by_id(assoc.bucket_id(), assoc.file_id(), session)
-                        .await
-                        .map_err(|e| MeowithIoError::Internal(Some(Box::new(e))))?;
-
-                    if file.is_none() {
+                    let (file, version, trashed, held) = try_join!(
+                        maybe_get_file_by_id(assoc.bucket_id(), assoc.file_id(), session),
+                        maybe_get_file_version_by_file_id(
+                            assoc.bucket_id(),
+                            assoc.file_id(),
+                            session
+                        ),
+                        maybe_get_trashed_file_by_file_id(
+                            assoc.bucket_id(),
+                            assoc.file_id(),
+                            session
+                        ),
+                        maybe_get_first_snapshot_chunk(*id, session)
+                    )
+                    .map_err(|e| MeowithIoError::Internal(Some(Box::new(e))))?;
+
+                    if file.is_none() && version.is_none() && trashed.is_none() && held.is_none() {
                         warn!("No associated file found for {}", id);
                         mark.push(*id);
                     }
@@ -265,6 +284,18 @@ impl FragmentLedger {
             chunk_ids_with_no_assoc.len()
         );
 
+        // The entries of the snapshots share the files table, their chunks belong to the bucket.
+        let mut snapshot_buckets = HashMap::new();
+        let mut snapshot_stream = get_all_bucket_snapshots(session)
+            .await
+            .map_err(|e| MeowithIoError::Internal(Some(Box::new(e))))?
+            .into_stream();
+
+        while let Some(snapshot) = snapshot_stream.next().await {
+            let snapshot = snapshot.map_err(|e| MeowithIoError::Internal(Some(Box::new(e))))?;
+            snapshot_buckets.insert(snapshot.id, snapshot.bucket_id);
+        }
+
         let mut file_stream = get_all_files(session)
             .await
             .map_err(|e| MeowithIoError::Internal(Some(Box::new(e))))?
@@ -272,33 +303,50 @@ impl FragmentLedger {
 
         while let Some(file) = file_stream.next().await {
             let file = file.map_err(|e| MeowithIoError::Internal(Some(Box::new(e))))?;
+            let bucket_id = snapshot_buckets
+                .get(&file.bucket_id)
+                .copied()
+                .unwrap_or(file.bucket_id);
+            self.restore_fragment_metadata(
+                bucket_id,
+                file.id,
+                &file.chunk_ids,
+                &mut chunk_ids_with_no_assoc,
+            )
+            .await;
+        }
 
-            let chunk_id = file
-                .chunk_ids
-                .iter()
-                .find(|it| chunk_ids_with_no_assoc.contains(&it.chunk_id));
-            if let Some(chunk_id) = chunk_id {
-                let chunk_id = chunk_id.chunk_id;
-                info!(
-                    "Found missing fragment metadata for file: {} chunk: {chunk_id}",
-                    file.id
-                );
-                self._internal
-                    .ext_metadata_store
-                    .read()
-                    .await
-                    .as_ref()
-                    .map(|x| {
-                        x.insert(
-                            chunk_id,
-                            ExtFragmentMeta {
-                                bucket_id: file.bucket_id.to_u128_le(),
-                                file_id: file.id.to_u128_le(),
-                            },
-                        )
-                    });
-                chunk_ids_with_no_assoc.remove(&chunk_id);
-            }
+        let mut trash_stream = get_all_trashed_files(session)
+            .await
+            .map_err(|e| MeowithIoError::Internal(Some(Box::new(e))))?
+            .into_stream();
+
+        while let Some(entry) = trash_stream.next().await {
+            let entry = entry.map_err(|e| MeowithIoError::Internal(Some(Box::new(e))))?;
+            self.restore_fragment_metadata(
+                entry.bucket_id,
+                entry.file_id,
+                &entry.chunk_ids,
+                &mut chunk_ids_with_no_assoc,
+            )
+            .await;
+        }
+
+        // Chunks of retained versions are not orphaned either.
+        let mut version_stream = get_all_file_versions(session)
+            .await
+            .map_err(|e| MeowithIoError::Internal(Some(Box::new(e))))?
+            .into_stream();
+
+        while let Some(version) = version_stream.next().await {
+            let version = version.map_err(|e| MeowithIoError::Internal(Some(Box::new(e))))?;
+            self.restore_fragment_metadata(
+                version.bucket_id,
+                version.file_id,
+                &version.chunk_ids,
+                &mut chunk_ids_with_no_assoc,
+            )
+            .await;
         }
 
         info!(
@@ -312,6 +360,37 @@ impl FragmentLedger {
         Ok(())
     }
 
+    async fn restore_fragment_metadata(
+        &self,
+        bucket_id: Uuid,
+        file_id: Uuid,
+        chunks: &HashSet<FileChunk>,
+        chunk_ids_with_no_assoc: &mut HashSet<Uuid>,
+    ) {
+        let chunk_id = chunks
+            .iter()
+            .find(|it| chunk_ids_with_no_assoc.contains(&it.chunk_id));
+        if let Some(chunk_id) = chunk_id {
+            let chunk_id = chunk_id.chunk_id;
+            info!("Found missing fragment metadata for file: {file_id} chunk: {chunk_id}");
+            self._internal
+                .ext_metadata_store
+                .read()
+                .await
+                .as_ref()
+                .map(|x| {
+                    x.insert(
+                        chunk_id,
+                        ExtFragmentMeta {
+                            bucket_id: bucket_id.to_u128_le(),
+                            file_id: file_id.to_u128_le(),
+                        },
+                    )
+                });
+            chunk_ids_with_no_assoc.remove(&chunk_id);
+        }
+    }
+
     pub async fn get_storage_info(&self) -> UpdateStorageNodeProperties {
         let max = self._internal.max_physical_size.load(ORDERING_MAX_LOAD);
         UpdateStorageNodeProperties {
@@ -630,6 +709,140 @@ impl FragmentLedger {
         Ok(())
     }
 
+    /// Appends the contents of a transferred, uncommitted chunk to the end of a committed one,
+    /// removing the former. Nothing is merged unless the target holds exactly `target_size` bytes,
+    /// so that a merge never builds upon the leftovers of one whose file update did not go through.
+    /// The bytes of the target below `target_size` are left untouched, readers are thus unaffected.
+    pub async fn merge_chunk(
+        &self,
+        id: &Uuid,
+        target_id: &Uuid,
+        target_size: u64,
+    ) -> MeowithIoResult<()> {
+        trace!("Fragment ledger Merging chunk {id} into {target_id}");
+        {
+            let uncommited = self._internal.uncommited_map.read().await;
+            if !uncommited.contains_key(id)
+                || uncommited.contains_key(target_id)
+                || self._internal.reservation_map.read().await.contains_key(id)
+                || !self.fragment_exists(target_id).await
+            {
+                return Err(MeowithIoError::NotFound);
+            }
+        }
+        if !self._internal.merging.lock().await.insert(*target_id) {
+            return Err(MeowithIoError::Conflict);
+        }
+        let result = self.append_to_chunk(id, target_id, target_size).await;
+        self._internal.merging.lock().await.remove(target_id);
+        result
+    }
+
+    async fn append_to_chunk(
+        &self,
+        id: &Uuid,
+        target_id: &Uuid,
+        target_size: u64,
+    ) -> MeowithIoResult<()> {
+        let target_path = self.get_path(target_id, false);
+        let mut target = OpenOptions::new()
+            .append(true)
+            .open(&target_path)
+            .await
+            .map_err(MeowithIoError::from)?;
+        if target.metadata().await?.len() != target_size {
+            return Err(MeowithIoError::Conflict);
+        }
+
+        let mut source = File::open(self.get_path(id, true))
+            .await
+            .map_err(MeowithIoError::from)?;
+        let copied = async {
+            tokio::io::copy(&mut source, &mut target).await?;
+            target.sync_data().await
+        }
+        .await;
+        if let Err(err) = copied {
+            // Drop the partially appended data, keeping the chunk mergeable.
+            let _ = target.set_len(target_size).await;
+            return Err(MeowithIoError::from(err));
+        }
+
+        let physical_size = target_path
+            .size_on_disk()
+            .map_err(|_| MeowithIoError::Internal(None))?;
+        self.delete_chunk(id).await?;
+        let mut chunks = self._internal.chunk_set.write().await;
+        // Unless deleted in the meantime, the target takes over the content of the merged chunk.
+        if let Some(chunk) = chunks.get_mut(target_id) {
+            let content_size = target.metadata().await?.len();
+            self._internal
+                .disk_content_size
+                .fetch_add(content_size - chunk.disk_content_size, ORDERING_DISK_STORE);
+            self._internal
+                .disk_physical_size
+                .fetch_add(physical_size, ORDERING_DISK_STORE);
+            self._internal
+                .disk_physical_size
+                .fetch_sub(chunk.disk_physical_size, ORDERING_DISK_STORE);
+            chunk.disk_content_size = content_size;
+            chunk.disk_physical_size = physical_size;
+        }
+        Ok(())
+    }
+
+    /// Copies the first `size` bytes of a committed chunk into a new chunk associated with the
+    /// given file, reserving the space for it. The copy is left uncommitted, like a finished upload,
+    /// so that it is dropped unless committed or kept alive.
+    pub async fn duplicate_chunk(
+        &self,
+        id: &Uuid,
+        size: u64,
+        associated_bucket_id: Uuid,
+        associated_file_id: Uuid,
+    ) -> MeowithIoResult<Uuid> {
+        trace!("Fragment ledger Duplicating {size} bytes of chunk {id}");
+        match self.existing_fragment_meta(id).await {
+            Some(meta) if meta.disk_content_size >= size => {}
+            _ => return Err(MeowithIoError::NotFound),
+        }
+        if self._internal.uncommited_map.read().await.contains_key(id) {
+            return Err(MeowithIoError::NotFound);
+        }
+
+        let duplicate_id = self
+            .try_reserve(size, associated_bucket_id, associated_file_id, false)
+            .await?;
+        let copied = async {
+            let _guard = self.lock_table().read(*id).await;
+            let source = File::open(self.get_path(id, false)).await?;
+            let mut target = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(self.get_path(&duplicate_id, true))
+                .await?;
+            let copied = tokio::io::copy(&mut source.take(size), &mut target).await?;
+            target.sync_data().await?;
+            Ok::<u64, std::io::Error>(copied)
+        }
+        .await;
+
+        match copied {
+            // A short copy drops the reservation along with the partial data.
+            Ok(copied) => self.release_reservation(&duplicate_id, copied).await?,
+            Err(err) => {
+                let _ = self.cancel_reservation(&duplicate_id).await;
+                return Err(MeowithIoError::from(err));
+            }
+        }
+        if self.fragment_exists(&duplicate_id).await {
+            Ok(duplicate_id)
+        } else {
+            Err(MeowithIoError::NotFound)
+        }
+    }
+
     /// Update the timeout on the chunk.
     pub(crate) async fn commit_alive(&self, chunk_id: &Uuid) -> MeowithIoResult<()> {
         trace!("Fragment ledger commit alive {chunk_id}");
@@ -697,6 +910,8 @@ struct InternalLedger {
     chunk_set: RwLock<HashMap<Uuid, FragmentMeta>>,
     reservation_map: RwLock<HashMap<Uuid, Reservation>>,
     uncommited_map: RwLock<HashMap<Uuid, CommitInfo>>,
+    /// Committed chunks currently being extended by a merge.
+    merging: Mutex<HashSet<Uuid>>,
     ext_metadata_store: RwLock<Option<Box<dyn ExtFragmentMetaStore>>>,
 
     housekeeper_handle: std::sync::Mutex<Option<JoinHandle<()>>>,
@@ -6,18 +6,35 @@ use std::collections::HashMap;
 
 use crate::caching::clear_caches;
 use crate::io::fragment_ledger::FragmentLedger;
+use crate::public::middleware::cors_middleware::BucketCors;
 use crate::public::middleware::user_middleware::UserAuthenticate;
+use crate::public::rate_limiter::RateLimiter;
+use crate::public::routes::batch::batch;
+use crate::public::routes::change_feed::list_changes;
 use crate::public::routes::entity_action::{
-    create_directory, delete_directory, delete_file, rename_directory, rename_file,
+    copy_directory, copy_file, create_directory, delete_directory, delete_file, rename_directory,
+    rename_file,
 };
 use crate::public::routes::entity_list::{
     get_bucket_info, list_bucket_directories, list_bucket_files, list_directory, stat_entity,
 };
+use crate::public::routes::file_metadata::update_metadata;
 use crate::public::routes::file_transfer::{
-    download, resume_durable_upload, start_upload_durable, upload_durable, upload_oneshot,
+    append, download, download_archive, extract_archive, resume_durable_upload,
+    start_upload_durable, upload_durable, upload_oneshot, write_range,
 };
+use crate::public::routes::file_version::{delete_version, list_versions, restore_version};
+use crate::public::routes::job::{get_job, list_jobs};
+use crate::public::routes::lease::{acquire_lease, release_lease, renew_lease};
+use crate::public::routes::object_lock::set_legal_hold;
+use crate::public::routes::public_access::{public_download, public_list_directory};
+use crate::public::routes::snapshot::{
+    create_snapshot, delete_snapshot, list_snapshots, restore_snapshot,
+};
+use crate::public::routes::trash::{list_trash, restore_trash};
 use crate::public::service::durable_transfer_session_manager::DurableTransferSessionManager;
-use actix_cors::Cors;
+use crate::public::throttling::TrafficLimiter;
+use crate::worker::initialize_workers;
 use actix_web::dev::ServerHandle;
 use actix_web::web::Data;
 use actix_web::{web, App, HttpServer};
@@ -29,6 +46,7 @@ use commons::context::microservice_request_context::{MicroserviceRequestContext,
 use commons::error::std_response::NodeClientError;
 use commons::pause_handle::ApplicationPauseHandle;
 use commons::ssl_acceptor::build_provided_ssl_acceptor_builder;
+use commons::webhook::PublicResolver;
 use data::database_session::{build_session, CACHE_SIZE};
 use log::trace;
 use mgpp::connect_mgpp;
@@ -39,10 +57,12 @@ use protocol::mgpp::client::MGPPClient;
 use scylla::client::caching_session::CachingSession;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{Mutex, RwLock};
 use tokio::task::{AbortHandle, JoinHandle};
 use uuid::Uuid;
 
+pub mod archive;
 pub mod caching;
 pub mod config;
 pub mod file_transfer;
@@ -52,12 +72,17 @@ pub mod locking;
 pub mod mgpp;
 pub mod peer;
 pub mod public;
+pub mod worker;
+
+/// Bounds a single webhook delivery attempt.
+const WEBHOOK_TIMEOUT_SECS: u64 = 10;
 
 pub struct NodeHandle {
     external_handle: ServerHandle,
     mdsftp_server: MDSFTPServer,
     pub mgpp_client: MGPPClient,
     heart_handle: AbortHandle,
+    worker_handle: AbortHandle,
     req_ctx: Arc<MicroserviceRequestContext>,
     pub join_handle: JoinHandle<()>,
     fragment_ledger: FragmentLedger,
@@ -67,6 +92,7 @@ impl NodeHandle {
     pub async fn shutdown(&self, forceful: bool) {
         self.external_handle.stop(!forceful).await;
         self.heart_handle.abort();
+        self.worker_handle.abort();
         let _ = self.mgpp_client.shutdown().await;
         self.mdsftp_server.shutdown().await;
         self.req_ctx.shutdown().await;
@@ -84,6 +110,12 @@ pub struct AppState {
     req_ctx: Arc<MicroserviceRequestContext>,
     pause_handle: Arc<Mutex<Option<ServerHandle>>>,
     last_peer_refresh: Arc<Mutex<DateTime<Utc>>>,
+    webhook_client: reqwest::Client,
+    allow_private_webhooks: bool,
+    /// Anonymous requests per public bucket.
+    public_rate_limiter: RateLimiter<Uuid>,
+    /// Requests, transfers and bandwidth per app and token.
+    traffic_limiter: Arc<TrafficLimiter>,
 }
 
 impl AppState {
@@ -157,6 +189,19 @@ impl ApplicationPauseHandle for NodePauseHandle {
     }
 }
 
+/// Redirects are not followed, as they could lead the delivery to an internal address.
+fn webhook_client(allow_private: bool) -> reqwest::Client {
+    let builder = reqwest::Client::builder()
+        .timeout(Duration::from_secs(WEBHOOK_TIMEOUT_SECS))
+        .redirect(reqwest::redirect::Policy::none());
+    let builder = if allow_private {
+        builder
+    } else {
+        builder.dns_resolver(Arc::new(PublicResolver))
+    };
+    builder.build().expect("Webhook client creation failed")
+}
+
 pub async fn start_node(config: NodeConfigInstance) -> std::io::Result<NodeHandle> {
     let init_res = register_node(&config).await;
     let mut req_ctx = init_res.0;
@@ -248,6 +293,10 @@ pub async fn start_node(config: NodeConfigInstance) -> std::io::Result<NodeHandl
         req_ctx,
         pause_handle: pause_handle.clone(),
         last_peer_refresh: Arc::new(Default::default()),
+        webhook_client: webhook_client(global_conf.allow_private_webhooks),
+        allow_private_webhooks: global_conf.allow_private_webhooks,
+        public_rate_limiter: RateLimiter::new(Duration::from_secs(60)),
+        traffic_limiter: Arc::new(TrafficLimiter::default()),
     });
     app_data.upload_manager.init_session(app_data.clone()).await;
 
@@ -259,18 +308,31 @@ pub async fn start_node(config: NodeConfigInstance) -> std::io::Result<NodeHandl
         .set_up_auto_reconnect(node_pause_handle.clone())
         .await;
 
+    let worker_handle = initialize_workers(app_data.clone());
+
     let external_server = HttpServer::new(move || {
-        let cors = Cors::permissive();
         let external_app_data = app_data.clone();
         let fs_limit_configuration = fs_limit_configuration.clone();
 
         let file_scope = web::scope("/api/file")
+            .service(list_versions)
+            .service(restore_version)
+            .service(delete_version)
+            .service(acquire_lease)
+            .service(renew_lease)
+            .service(release_lease)
+            .service(set_legal_hold)
+            .service(restore_trash)
+            .service(update_metadata)
             .service(upload_oneshot)
+            .service(append)
+            .service(write_range)
             .service(upload_durable)
             .service(start_upload_durable)
             .service(resume_durable_upload)
             .service(download)
             .service(rename_file)
+            .service(copy_file)
             .service(delete_file)
             .wrap(UserAuthenticate);
 
@@ -278,7 +340,10 @@ pub async fn start_node(config: NodeConfigInstance) -> std::io::Result<NodeHandl
             .service(create_directory)
             .service(delete_directory)
             .service(rename_directory)
+            .service(copy_directory)
             .service(list_directory)
+            .service(download_archive)
+            .service(extract_archive)
             .wrap(UserAuthenticate);
 
         let bucket_scope = web::scope("/api/bucket")
@@ -286,15 +351,29 @@ pub async fn start_node(config: NodeConfigInstance) -> std::io::Result<NodeHandl
             .service(list_bucket_directories)
             .service(stat_entity)
             .service(get_bucket_info)
+            .service(list_trash)
+            .service(batch)
+            .service(list_jobs)
+            .service(get_job)
+            .service(list_changes)
+            .service(restore_snapshot)
+            .service(create_snapshot)
+            .service(list_snapshots)
+            .service(delete_snapshot)
             .wrap(UserAuthenticate);
 
+        let public_scope = web::scope("/api/public")
+            .service(public_download)
+            .service(public_list_directory);
+
         App::new()
             .app_data(external_app_data)
             .app_data(fs_limit_configuration)
-            .wrap(cors)
+            .wrap(BucketCors)
             .service(file_scope)
             .service(directory_scope)
             .service(bucket_scope)
+            .service(public_scope)
     });
 
     let external_server = if external_ssl.is_some() {
@@ -335,5 +414,6 @@ pub async fn start_node(config: NodeConfigInstance) -> std::io::Result<NodeHandl
         req_ctx: req_ctx_handle,
         join_handle,
         heart_handle,
+        worker_handle,
     })
 }
@@ -0,0 +1,78 @@
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpRequest};
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use data::dto::config::FsLimitConfiguration;
+use data::pathlib::prepare_path;
+use serde::Deserialize;
+use std::future::Future;
+use std::pin::Pin;
+use uuid::Uuid;
+
+#[derive(Deserialize, Debug)]
+pub struct CopyEntityRequest {
+    pub to: String,
+    /// The destination bucket, must belong to the same app. Defaults to the source bucket.
+    pub bucket_id: Option<Uuid>,
+    #[serde(skip)]
+    cached_path: Option<String>,
+}
+
+impl CopyEntityRequest {
+    pub fn check_valid(
+        &mut self,
+        fs_limit_configuration: &FsLimitConfiguration,
+    ) -> NodeClientResponse<()> {
+        let path = prepare_path(&self.to, fs_limit_configuration);
+        if let Some(prepared_path) = path {
+            self.cached_path = Some(prepared_path);
+            Ok(())
+        } else {
+            Err(NodeClientError::BadRequest)
+        }
+    }
+
+    /// Targets an already prepared path.
+    pub fn from_prepared(path: String, bucket_id: Option<Uuid>) -> Self {
+        CopyEntityRequest {
+            to: path.clone(),
+            bucket_id,
+            cached_path: Some(path),
+        }
+    }
+
+    pub fn path(&self) -> String {
+        self.cached_path.as_ref().unwrap().clone()
+    }
+
+    pub fn bucket_id(&self, source_bucket_id: Uuid) -> Uuid {
+        self.bucket_id.unwrap_or(source_bucket_id)
+    }
+}
+
+impl FromRequest for CopyEntityRequest {
+    type Error = NodeClientError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let req_clone = req.clone();
+        let fs_limit = req
+            .app_data::<web::Data<FsLimitConfiguration>>()
+            .cloned()
+            .expect("FsLimitConfiguration not found");
+
+        let copy_request_fut = web::Json::<CopyEntityRequest>::from_request(&req_clone, payload);
+
+        Box::pin(async move {
+            let mut copy_request: CopyEntityRequest = copy_request_fut
+                .await
+                .map_err(|_| NodeClientError::BadRequest)?
+                .into_inner();
+
+            copy_request
+                .check_valid(&fs_limit)
+                .map_err(|_| NodeClientError::BadResourcePath)?;
+
+            Ok(copy_request)
+        })
+    }
+}
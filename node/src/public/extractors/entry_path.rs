@@ -35,6 +35,16 @@ impl EntryPath {
         }
     }
 
+    /// Targets an already prepared path, such as the destination of a copy.
+    pub fn from_prepared(app_id: Uuid, bucket_id: Uuid, path: String) -> Self {
+        EntryPath {
+            app_id,
+            bucket_id,
+            path: Some(path.clone()),
+            cached_path: Some(path),
+        }
+    }
+
     pub fn path(&self) -> String {
         self.cached_path.as_ref().unwrap().clone()
     }
@@ -1,2 +1,4 @@
+pub mod copy_request;
 pub mod entry_path;
+pub mod preconditions;
 pub mod rename_request;
@@ -0,0 +1,176 @@
+use actix_web::dev::Payload;
+use actix_web::http::header::{
+    EntityTag, Header, IfMatch, IfModifiedSince, IfNoneMatch, IF_MODIFIED_SINCE,
+};
+use actix_web::{FromRequest, HttpRequest};
+use chrono::{DateTime, Utc};
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use std::future::{ready, Ready};
+use std::time::SystemTime;
+
+/// The conditional request headers, evaluated against the etag of the targeted file.
+#[derive(Debug, Default)]
+pub struct Preconditions {
+    if_match: Option<IfMatch>,
+    if_none_match: Option<IfNoneMatch>,
+    if_modified_since: Option<SystemTime>,
+}
+
+impl Preconditions {
+    pub fn is_empty(&self) -> bool {
+        self.if_match.is_none() && self.if_none_match.is_none()
+    }
+
+    /// Drops `If-Match`, for operations where it has already been checked against a source file.
+    pub fn into_destination(self) -> Self {
+        Preconditions {
+            if_match: None,
+            ..self
+        }
+    }
+
+    /// Checks `If-Match` and `If-None-Match` before a file is written or deleted.
+    /// `current` is the etag of the file currently at the path, if there is one.
+    pub fn check_write(&self, current: Option<&str>) -> NodeClientResponse<()> {
+        self.check_match(current)?;
+        self.check_none_match(current)
+    }
+
+    pub fn check_match(&self, current: Option<&str>) -> NodeClientResponse<()> {
+        let current = current.map(|etag| EntityTag::new_strong(etag.to_string()));
+        let matches = match (&self.if_match, &current) {
+            (None, _) => true,
+            (Some(IfMatch::Any), current) => current.is_some(),
+            (Some(IfMatch::Items(tags)), Some(current)) => {
+                tags.iter().any(|tag| tag.strong_eq(current))
+            }
+            (Some(IfMatch::Items(_)), None) => false,
+        };
+        matches
+            .then_some(())
+            .ok_or(NodeClientError::PreconditionFailed)
+    }
+
+    pub fn check_none_match(&self, current: Option<&str>) -> NodeClientResponse<()> {
+        let current = current.map(|etag| EntityTag::new_strong(etag.to_string()));
+        let none_match = match (&self.if_none_match, &current) {
+            (None, _) => true,
+            (Some(IfNoneMatch::Any), current) => current.is_none(),
+            (Some(IfNoneMatch::Items(tags)), Some(current)) => {
+                !tags.iter().any(|tag| tag.weak_eq(current))
+            }
+            (Some(IfNoneMatch::Items(_)), None) => true,
+        };
+        none_match
+            .then_some(())
+            .ok_or(NodeClientError::PreconditionFailed)
+    }
+
+    /// Whether a download can be answered with `304 Not Modified`.
+    /// `If-Modified-Since` is ignored when `If-None-Match` is present.
+    pub fn not_modified(&self, etag: &str, last_modified: DateTime<Utc>) -> bool {
+        let etag = EntityTag::new_strong(etag.to_string());
+        match (&self.if_none_match, self.if_modified_since) {
+            (Some(IfNoneMatch::Any), _) => true,
+            (Some(IfNoneMatch::Items(tags)), _) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+            // http dates have a one second resolution
+            (None, Some(since)) => {
+                last_modified.timestamp() <= DateTime::<Utc>::from(since).timestamp()
+            }
+            (None, None) => false,
+        }
+    }
+}
+
+fn parse_present<H: Header>(req: &HttpRequest) -> NodeClientResponse<Option<H>> {
+    if !req.headers().contains_key(H::name()) {
+        return Ok(None);
+    }
+    H::parse(req)
+        .map(Some)
+        .map_err(|_| NodeClientError::BadRequest)
+}
+
+impl FromRequest for Preconditions {
+    type Error = NodeClientError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready((|| {
+            // An unparsable date is to be ignored, as per RFC 9110.
+            let if_modified_since = if req.headers().contains_key(IF_MODIFIED_SINCE) {
+                IfModifiedSince::parse(req)
+                    .ok()
+                    .map(|header| SystemTime::from(header.0))
+            } else {
+                None
+            };
+            Ok(Preconditions {
+                if_match: parse_present(req)?,
+                if_none_match: parse_present(req)?,
+                if_modified_since,
+            })
+        })())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::public::extractors::preconditions::Preconditions;
+    use actix_web::dev::Payload;
+    use actix_web::http::header::{IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH};
+    use actix_web::test::TestRequest;
+    use actix_web::FromRequest;
+    use chrono::{DateTime, Utc};
+
+    fn preconditions(headers: &[(&str, &str)]) -> Preconditions {
+        let mut req = TestRequest::default();
+        for header in headers {
+            req = req.insert_header(*header);
+        }
+        Preconditions::from_request(&req.to_http_request(), &mut Payload::None)
+            .into_inner()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_no_headers() {
+        let preconditions = preconditions(&[]);
+
+        assert!(preconditions.is_empty());
+        assert!(preconditions.check_write(None).is_ok());
+        assert!(preconditions.check_write(Some("abc")).is_ok());
+        assert!(!preconditions.not_modified("abc", Utc::now()));
+    }
+
+    #[test]
+    fn test_if_match() {
+        let preconditions = preconditions(&[(IF_MATCH.as_str(), "\"abc\", \"def\"")]);
+
+        assert!(preconditions.check_write(Some("def")).is_ok());
+        assert!(preconditions.check_write(Some("xyz")).is_err());
+        assert!(preconditions.check_write(None).is_err());
+    }
+
+    #[test]
+    fn test_if_none_match_any() {
+        let preconditions = preconditions(&[(IF_NONE_MATCH.as_str(), "*")]);
+
+        assert!(preconditions.check_write(None).is_ok());
+        assert!(preconditions.check_write(Some("abc")).is_err());
+    }
+
+    #[test]
+    fn test_not_modified() {
+        let etag = preconditions(&[(IF_NONE_MATCH.as_str(), "W/\"abc\"")]);
+        assert!(etag.not_modified("abc", Utc::now()));
+        assert!(!etag.not_modified("def", Utc::now()));
+
+        let date = preconditions(&[(IF_MODIFIED_SINCE.as_str(), "Sun, 06 Nov 1994 08:49:37 GMT")]);
+        let since = DateTime::parse_from_rfc2822("Sun, 06 Nov 1994 08:49:37 GMT")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert!(date.not_modified("abc", since));
+        assert!(!date.not_modified("abc", Utc::now()));
+    }
+}
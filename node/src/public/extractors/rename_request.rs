@@ -28,6 +28,14 @@ impl RenameEntityRequest {
         }
     }
 
+    /// Targets an already prepared path.
+    pub fn from_prepared(path: String) -> Self {
+        RenameEntityRequest {
+            to: path.clone(),
+            cached_path: Some(path),
+        }
+    }
+
     pub fn path(&self) -> String {
         self.cached_path.as_ref().unwrap().clone()
     }
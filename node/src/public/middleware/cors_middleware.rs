@@ -0,0 +1,159 @@
+use std::future::{ready, Ready};
+use std::rc::Rc;
+
+use actix_web::body::EitherBody;
+use actix_web::http::header::{
+    HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+    ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE,
+    ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+};
+use actix_web::http::Method;
+use actix_web::web::Data;
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpResponse,
+};
+use futures_util::future::LocalBoxFuture;
+
+use crate::caching::db::get_bucket_policy;
+use crate::public::middleware::bucket_of_path;
+use crate::AppState;
+use data::model::file_model::CorsRule;
+
+/// Applies the CORS rules of the bucket a request targets.
+/// Cross-origin requests to buckets without a matching rule get no CORS headers,
+/// and their preflight requests are refused.
+pub struct BucketCors;
+
+impl<S: 'static, B> Transform<S, ServiceRequest> for BucketCors
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = BucketCorsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(BucketCorsMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct BucketCorsMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for BucketCorsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let svc = self.service.clone();
+
+        Box::pin(async move {
+            let origin = match req.headers().get(ORIGIN) {
+                Some(origin) => origin.clone(),
+                None => return svc.call(req).await.map(ServiceResponse::map_into_left_body),
+            };
+            let app_data = req.app_data::<Data<AppState>>().unwrap().clone();
+            let rules = match bucket_of_path(req.path()) {
+                Some((app_id, bucket_id)) => {
+                    get_bucket_policy(app_id, bucket_id, &app_data.session)
+                        .await
+                        .map(|policy| policy.cors_rules)
+                        .unwrap_or_default()
+                }
+                None => vec![],
+            };
+
+            if req.method() == Method::OPTIONS
+                && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+            {
+                let response = preflight(&req, &origin, &rules);
+                return Ok(req.into_response(response).map_into_right_body());
+            }
+
+            let method = req.method().clone();
+            // the request must not be cloned before the call, as routing requires sole ownership of it
+            let mut res = svc.call(req).await?.map_into_left_body();
+            let headers = res.headers_mut();
+            headers.append(VARY, HeaderValue::from_static("Origin"));
+            let rule = origin.to_str().ok().and_then(|origin| {
+                rules
+                    .iter()
+                    .find(|rule| rule.allows_origin(origin) && rule.allows_method(method.as_str()))
+            });
+            if let Some(rule) = rule {
+                headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+                if !rule.expose_headers.is_empty() {
+                    if let Ok(expose) = HeaderValue::from_str(&rule.expose_headers.join(", ")) {
+                        headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, expose);
+                    }
+                }
+            }
+
+            Ok(res)
+        })
+    }
+}
+
+fn preflight(req: &ServiceRequest, origin: &HeaderValue, rules: &[CorsRule]) -> HttpResponse {
+    let header_str = |name| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+    };
+    let method = header_str(ACCESS_CONTROL_REQUEST_METHOD);
+    let requested_headers: Vec<String> = header_str(ACCESS_CONTROL_REQUEST_HEADERS)
+        .split(',')
+        .map(|header| header.trim().to_ascii_lowercase())
+        .filter(|header| !header.is_empty())
+        .collect();
+
+    let rule = origin.to_str().ok().and_then(|origin| {
+        rules.iter().find(|rule| {
+            rule.allows_origin(origin)
+                && rule.allows_method(method)
+                && requested_headers
+                    .iter()
+                    .all(|header| rule.allows_header(header))
+        })
+    });
+    let rule = match rule {
+        Some(rule) => rule,
+        None => return HttpResponse::Forbidden().finish(),
+    };
+
+    let mut response = HttpResponse::NoContent();
+    response
+        .insert_header((ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone()))
+        .insert_header((
+            ACCESS_CONTROL_ALLOW_METHODS,
+            rule.allowed_methods.join(", "),
+        ))
+        .insert_header((
+            VARY,
+            "Origin, Access-Control-Request-Method, Access-Control-Request-Headers",
+        ));
+    if !requested_headers.is_empty() {
+        response.insert_header((ACCESS_CONTROL_ALLOW_HEADERS, requested_headers.join(", ")));
+    }
+    if rule.max_age > 0 {
+        response.insert_header((ACCESS_CONTROL_MAX_AGE, rule.max_age.to_string()));
+    }
+    response.finish()
+}
@@ -1 +1,44 @@
+use uuid::Uuid;
+
+pub mod cors_middleware;
 pub mod user_middleware;
+
+/// The app and bucket a request targets, the first two consecutive ids of its path.
+pub(crate) fn bucket_of_path(path: &str) -> Option<(Uuid, Uuid)> {
+    let segments: Vec<&str> = path.split('/').collect();
+    segments.windows(2).find_map(|pair| {
+        Some((
+            Uuid::parse_str(pair[0]).ok()?,
+            Uuid::parse_str(pair[1]).ok()?,
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::public::middleware::bucket_of_path;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_bucket_of_path() {
+        let app_id = Uuid::new_v4();
+        let bucket_id = Uuid::new_v4();
+        let other = Uuid::new_v4();
+
+        assert_eq!(
+            bucket_of_path(&format!("/api/file/download/{app_id}/{bucket_id}/{other}")),
+            Some((app_id, bucket_id))
+        );
+        assert_eq!(
+            bucket_of_path(&format!(
+                "/api/bucket/snapshots/restore/{app_id}/{bucket_id}/{other}"
+            )),
+            Some((app_id, bucket_id))
+        );
+        assert_eq!(
+            bucket_of_path(&format!("/api/bucket/jobs/{app_id}/x/{bucket_id}")),
+            None
+        );
+        assert_eq!(bucket_of_path("/api/file/download"), None);
+    }
+}
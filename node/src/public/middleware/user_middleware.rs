@@ -1,6 +1,7 @@
 use std::future::{ready, Ready};
 use std::rc::Rc;
 
+use actix_web::body::EitherBody;
 use actix_web::dev::Payload;
 use actix_web::http::header::AUTHORIZATION;
 use actix_web::web::Data;
@@ -11,11 +12,14 @@ use actix_web::{
 use futures_util::future::{err, ok, LocalBoxFuture};
 use uuid::Uuid;
 
+use commons::audit::{AuditActor, PendingAudit};
 use commons::middleware_actions::remove_bearer_prefix;
 use commons::permission::check::check_permission;
-use commons::permission::AppTokenPermit;
+use commons::permission::{AppTokenData, AppTokenPermit};
 
-use crate::caching::db::validate_nonce;
+use crate::caching::db::{get_bucket_policy, validate_nonce};
+use crate::public::middleware::bucket_of_path;
+use crate::public::throttling::{throttle_for, Throttle};
 use crate::AppState;
 use commons::error::std_response::NodeClientError;
 
@@ -27,7 +31,7 @@ where
     S::Future: 'static,
     B: 'static,
 {
-    type Response = ServiceResponse<B>;
+    type Response = ServiceResponse<EitherBody<B>>;
     type Error = Error;
     type Transform = UserAuthenticateMiddleware<S>;
     type InitError = ();
@@ -50,7 +54,7 @@ where
     S::Future: 'static,
     B: 'static,
 {
-    type Response = ServiceResponse<B>;
+    type Response = ServiceResponse<EitherBody<B>>;
     type Error = Error;
     type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
 
@@ -60,44 +64,98 @@ where
         let svc = self.service.clone();
 
         Box::pin(async move {
-            let app_data = req.app_data::<Data<AppState>>().unwrap();
-            let token_header = req.headers().get(AUTHORIZATION);
-            if token_header.is_none() {
-                return Err(Error::from(NodeClientError::BadAuth));
-            }
-            let token_str = token_header.unwrap().to_str();
-            if token_str.is_err() {
-                return Err(Error::from(NodeClientError::BadAuth));
+            // failures are answered here, the outer middleware may not hold on to the request meanwhile
+            let claim_data = match verify_claims(&req).await {
+                Ok(claim_data) => claim_data,
+                Err(err) => return Ok(req.error_response(err).map_into_right_body()),
+            };
+            let app_data = req.app_data::<Data<AppState>>().unwrap().clone();
+            let audit = PendingAudit::start(
+                &req,
+                AuditActor::token(claim_data.issuer_id, claim_data.name.clone()),
+                Some(claim_data.app_id),
+            );
+
+            let res = match admit(&req, claim_data).await {
+                Ok(accessor) => {
+                    req.extensions_mut().insert(accessor);
+                    svc.call(req).await.map(ServiceResponse::map_into_left_body)
+                }
+                Err(err) => Ok(req.error_response(err).map_into_right_body()),
+            };
+            if let Some(audit) = audit {
+                audit.finish(&res, &app_data.session).await;
             }
+            res
+        })
+    }
+}
 
-            let clean_token = remove_bearer_prefix(token_str.unwrap());
+async fn verify_claims(req: &ServiceRequest) -> Result<AppTokenData, NodeClientError> {
+    let app_data = req.app_data::<Data<AppState>>().unwrap();
+    let token_header = req.headers().get(AUTHORIZATION);
+    if token_header.is_none() {
+        return Err(NodeClientError::BadAuth);
+    }
+    let token_str = token_header.unwrap().to_str();
+    if token_str.is_err() {
+        return Err(NodeClientError::BadAuth);
+    }
 
-            let claim_data = app_data.jwt_service.verify_token(clean_token.as_str());
+    let clean_token = remove_bearer_prefix(token_str.unwrap());
 
-            if claim_data.is_err() {
-                return Err(Error::from(NodeClientError::BadAuth));
-            }
+    let claim_data = app_data.jwt_service.verify_token(clean_token.as_str());
 
-            let claim_data = claim_data.unwrap();
-            let nonce_valid = validate_nonce(&claim_data, &app_data.session).await;
-            if !nonce_valid {
-                return Err(Error::from(NodeClientError::BadAuth));
-            }
+    if claim_data.is_err() {
+        return Err(NodeClientError::BadAuth);
+    }
 
-            req.extensions_mut().insert(BucketAccessor {
-                permits: claim_data.perms,
-                app_id: claim_data.app_id,
-            });
-            let fut = svc.call(req);
-            let res = fut.await?;
-            Ok(res)
-        })
+    let claim_data = claim_data.unwrap();
+    let nonce_valid = validate_nonce(&claim_data, &app_data.session).await;
+    if !nonce_valid {
+        return Err(NodeClientError::BadAuth);
     }
+    Ok(claim_data)
+}
+
+/// Lets the request of the verified token through, unless its bucket is being deleted or it is over its limits.
+async fn admit(
+    req: &ServiceRequest,
+    claim_data: AppTokenData,
+) -> Result<BucketAccessor, NodeClientError> {
+    let app_data = req.app_data::<Data<AppState>>().unwrap();
+    if let Some((app_id, bucket_id)) = bucket_of_path(req.path()) {
+        let policy = get_bucket_policy(app_id, bucket_id, &app_data.session).await?;
+        if policy.deleting {
+            return Err(NodeClientError::NotFound);
+        }
+    }
+
+    let holder = format!("{}/{}", claim_data.issuer_id, claim_data.name);
+    let throttle = throttle_for(
+        claim_data.app_id,
+        Some((&holder, claim_data.limits)),
+        app_data,
+    )
+    .await?;
+    throttle.check_request()?;
+
+    Ok(BucketAccessor {
+        permits: claim_data.perms,
+        app_id: claim_data.app_id,
+        holder,
+        throttle,
+    })
 }
 
+#[derive(Clone)]
 pub struct BucketAccessor {
     pub permits: Vec<AppTokenPermit>,
     pub app_id: Uuid,
+    /// Identifies the token across requests, as the owner of leases.
+    pub holder: String,
+    /// The limits of the app and the token, applied to the transfers of the request.
+    pub throttle: Throttle,
 }
 
 impl FromRequest for BucketAccessor {
@@ -1,4 +1,6 @@
 pub mod extractors;
 pub mod middleware;
+pub mod rate_limiter;
 pub mod routes;
 pub mod service;
+pub mod throttling;
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use commons::error::std_response::NodeClientError;
+
+/// Windows are swept once the table grows past this many keys.
+pub(crate) const SWEEP_THRESHOLD: usize = 4096;
+
+struct Window {
+    start: Instant,
+    count: u32,
+}
+
+/// Counts requests per key within fixed windows, local to the node.
+pub struct RateLimiter<K> {
+    window: Duration,
+    windows: Mutex<HashMap<K, Window>>,
+}
+
+impl<K: Hash + Eq + Clone> RateLimiter<K> {
+    pub fn new(window: Duration) -> Self {
+        RateLimiter {
+            window,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Counts a request against the key.
+    /// Once the limit of the current window is reached, returns the time left until the window resets.
+    pub fn try_acquire(&self, key: &K, limit: u32) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+        if windows.len() >= SWEEP_THRESHOLD {
+            windows.retain(|_, window| now.duration_since(window.start) < self.window);
+        }
+        let window = windows.entry(key.clone()).or_insert(Window {
+            start: now,
+            count: 0,
+        });
+        if now.duration_since(window.start) >= self.window {
+            window.start = now;
+            window.count = 0;
+        }
+        if window.count >= limit {
+            return Err(self.window - now.duration_since(window.start));
+        }
+        window.count += 1;
+        Ok(())
+    }
+}
+
+/// The rejection of a request that may be retried once the wait is over, rounded up to whole seconds.
+pub fn rate_limited(wait: Duration) -> NodeClientError {
+    let seconds = wait.as_millis().div_ceil(1000) as u64;
+    NodeClientError::RateLimited {
+        retry_after: seconds.max(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RateLimiter;
+    use std::time::Duration;
+
+    #[test]
+    fn test_limit() {
+        let limiter = RateLimiter::new(Duration::from_secs(60));
+        assert!(limiter.try_acquire(&1, 2).is_ok());
+        assert!(limiter.try_acquire(&1, 2).is_ok());
+        let wait = limiter.try_acquire(&1, 2).unwrap_err();
+        assert!(wait > Duration::from_secs(59) && wait <= Duration::from_secs(60));
+        assert!(limiter.try_acquire(&2, 2).is_ok());
+        assert!(limiter.try_acquire(&3, 0).is_err());
+    }
+
+    #[test]
+    fn test_window_reset() {
+        let limiter = RateLimiter::new(Duration::from_millis(50));
+        assert!(limiter.try_acquire(&1, 1).is_ok());
+        assert!(limiter.try_acquire(&1, 1).is_err());
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(limiter.try_acquire(&1, 1).is_ok());
+    }
+}
@@ -0,0 +1,21 @@
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::batch_service::do_batch;
+use crate::AppState;
+use actix_web::{post, web};
+use commons::error::std_response::NodeClientResponse;
+use data::dto::config::FsLimitConfiguration;
+use data::dto::entity::{BatchRequest, BatchResponse};
+use uuid::Uuid;
+
+#[post("/batch/{app_id}/{bucket_id}")]
+pub async fn batch(
+    path: web::Path<(Uuid, Uuid)>,
+    req: web::Json<BatchRequest>,
+    accessor: BucketAccessor,
+    fs_limit: web::Data<FsLimitConfiguration>,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<web::Json<BatchResponse>> {
+    do_batch(path.0, path.1, req.0, &fs_limit, accessor, app_data)
+        .await
+        .map(web::Json)
+}
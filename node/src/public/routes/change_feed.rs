@@ -0,0 +1,19 @@
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::change_feed_service::{do_list_changes, ChangeQuery};
+use crate::AppState;
+use actix_web::{get, web};
+use commons::error::std_response::NodeClientResponse;
+use data::dto::entity::BucketChangeList;
+use uuid::Uuid;
+
+#[get("/changes/{app_id}/{bucket_id}")]
+pub async fn list_changes(
+    path: web::Path<(Uuid, Uuid)>,
+    query: web::Query<ChangeQuery>,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<web::Json<BucketChangeList>> {
+    do_list_changes(path.0, path.1, query.0, accessor, app_data)
+        .await
+        .map(web::Json)
+}
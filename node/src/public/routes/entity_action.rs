@@ -1,22 +1,26 @@
+use crate::public::extractors::copy_request::CopyEntityRequest;
 use crate::public::extractors::entry_path::EntryPath;
+use crate::public::extractors::preconditions::Preconditions;
 pub(crate) use crate::public::extractors::rename_request::RenameEntityRequest;
 use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::copy_service::{do_copy_directory, do_copy_file};
 use crate::public::service::directory_action_service::{
     do_create_directory, do_delete_directory, do_rename_directory,
 };
 use crate::public::service::file_action_service::{delete_file_srv, rename_file_srv};
 use crate::AppState;
 use actix_web::{delete, post, web, HttpResponse};
-use commons::error::std_response::NodeClientResponse;
-use data::dto::entity::DeleteDirectoryRequest;
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use data::dto::entity::{BucketJobDto, DeleteDirectoryRequest};
 
 #[delete("/delete/{app_id}/{bucket_id}/{path:.*}")]
 pub async fn delete_file(
     path: EntryPath,
+    preconditions: Preconditions,
     accessor: BucketAccessor,
     app_data: web::Data<AppState>,
 ) -> NodeClientResponse<HttpResponse> {
-    delete_file_srv(path, accessor, app_data).await?;
+    delete_file_srv(path, preconditions, accessor, app_data).await?;
     Ok(HttpResponse::Ok().finish())
 }
 
@@ -24,10 +28,23 @@ pub async fn delete_file(
 pub async fn rename_file(
     path: EntryPath,
     req: RenameEntityRequest,
+    preconditions: Preconditions,
     accessor: BucketAccessor,
     app_data: web::Data<AppState>,
 ) -> NodeClientResponse<HttpResponse> {
-    rename_file_srv(path, req, accessor, app_data).await?;
+    rename_file_srv(path, req, preconditions, accessor, app_data).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[post("/copy/{app_id}/{bucket_id}/{path:.*}")]
+pub async fn copy_file(
+    path: EntryPath,
+    req: CopyEntityRequest,
+    preconditions: Preconditions,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<HttpResponse> {
+    do_copy_file(path, req, preconditions, accessor, app_data).await?;
     Ok(HttpResponse::Ok().finish())
 }
 
@@ -48,9 +65,11 @@ pub async fn delete_directory(
     accessor: BucketAccessor,
     app_data: web::Data<AppState>,
 ) -> NodeClientResponse<HttpResponse> {
-    do_delete_directory(path, req.0, accessor, app_data).await?;
-
-    Ok(HttpResponse::Ok().finish())
+    match do_delete_directory(path, req.0, accessor, app_data).await? {
+        Some(job) => Ok(HttpResponse::Accepted()
+            .json(BucketJobDto::try_from(job).map_err(|_| NodeClientError::InternalError)?)),
+        None => Ok(HttpResponse::Ok().finish()),
+    }
 }
 
 #[post("/rename/{app_id}/{bucket_id}/{path:.*}")]
@@ -64,3 +83,15 @@ pub async fn rename_directory(
 
     Ok(HttpResponse::Ok().finish())
 }
+
+#[post("/copy/{app_id}/{bucket_id}/{path:.*}")]
+pub async fn copy_directory(
+    path: EntryPath,
+    req: CopyEntityRequest,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<HttpResponse> {
+    do_copy_directory(path, req, accessor, app_data).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
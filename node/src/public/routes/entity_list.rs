@@ -2,12 +2,12 @@ use crate::public::extractors::entry_path::EntryPath;
 use crate::public::middleware::user_middleware::BucketAccessor;
 use crate::public::service::file_list_service::{
     do_fetch_bucket_info, do_list_bucket_directories, do_list_bucket_files, do_list_dir,
-    do_stat_file, PaginationInfo,
+    do_stat_file, ListQuery,
 };
 use crate::AppState;
 use actix_web::{get, web};
 use commons::error::std_response::NodeClientResponse;
-use data::dto::entity::{BucketDto, Entity, EntityList};
+use data::dto::entity::{BucketDto, Entity, EntityList, SnapshotSelector};
 use uuid::Uuid;
 
 #[get("/list/files/{app_id}/{bucket_id}")]
@@ -15,9 +15,9 @@ pub async fn list_bucket_files(
     path: web::Path<(Uuid, Uuid)>,
     accessor: BucketAccessor,
     app_data: web::Data<AppState>,
-    paginate: web::Query<PaginationInfo>,
+    query: web::Query<ListQuery>,
 ) -> NodeClientResponse<web::Json<EntityList>> {
-    do_list_bucket_files(path.0, path.1, accessor, app_data, paginate.0)
+    do_list_bucket_files(path.0, path.1, accessor, app_data, query.0)
         .await
         .map(web::Json)
 }
@@ -27,9 +27,9 @@ pub async fn list_bucket_directories(
     path: web::Path<(Uuid, Uuid)>,
     accessor: BucketAccessor,
     app_data: web::Data<AppState>,
-    paginate: web::Query<PaginationInfo>,
+    query: web::Query<ListQuery>,
 ) -> NodeClientResponse<web::Json<EntityList>> {
-    do_list_bucket_directories(path.0, path.1, accessor, app_data, paginate.0)
+    do_list_bucket_directories(path.0, path.1, accessor, app_data, query.0)
         .await
         .map(web::Json)
 }
@@ -39,9 +39,9 @@ pub async fn list_directory(
     path: EntryPath,
     accessor: BucketAccessor,
     app_data: web::Data<AppState>,
-    paginate: web::Query<PaginationInfo>,
+    query: web::Query<ListQuery>,
 ) -> NodeClientResponse<web::Json<EntityList>> {
-    do_list_dir(path, accessor, app_data, paginate.0)
+    do_list_dir(path, accessor, app_data, query.0)
         .await
         .map(web::Json)
 }
@@ -51,8 +51,9 @@ pub async fn stat_entity(
     path: EntryPath,
     accessor: BucketAccessor,
     app_data: web::Data<AppState>,
+    snapshot: web::Query<SnapshotSelector>,
 ) -> NodeClientResponse<web::Json<Entity>> {
-    do_stat_file(path, accessor, app_data).await
+    do_stat_file(path, snapshot.snapshot_id, accessor, app_data).await
 }
 
 #[get("/info/{app_id}/{bucket_id}")]
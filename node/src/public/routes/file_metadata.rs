@@ -0,0 +1,18 @@
+use crate::public::extractors::entry_path::EntryPath;
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::file_metadata_service::do_update_metadata;
+use crate::AppState;
+use actix_web::{patch, web, HttpResponse};
+use commons::error::std_response::NodeClientResponse;
+use data::dto::entity::FileMetadataDto;
+
+#[patch("/metadata/{app_id}/{bucket_id}/{path:.*}")]
+pub async fn update_metadata(
+    path: EntryPath,
+    req: web::Json<FileMetadataDto>,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<HttpResponse> {
+    do_update_metadata(path, req.0, accessor, app_data).await?;
+    Ok(HttpResponse::Ok().finish())
+}
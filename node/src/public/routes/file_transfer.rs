@@ -1,10 +1,16 @@
 use std::sync::Arc;
+use std::time::SystemTime;
 
-use actix_web::http::header::{ContentDisposition, ContentLength, Header, Range, CONTENT_LENGTH};
+use actix_web::http::header::{
+    ContentDisposition, ContentLength, ContentRange, ContentRangeSpec, ETag, EntityTag, Header,
+    LastModified, Range, ACCEPT_RANGES, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_LENGTH,
+    CONTENT_TYPE,
+};
+use actix_web::web::Bytes;
 use actix_web::{get, post, put, web, HttpRequest, HttpResponse};
-use futures_util::StreamExt;
+use futures_util::{Stream, StreamExt};
 use log::{trace, warn};
-use tokio::io::{AsyncWriteExt, BufReader, BufWriter};
+use tokio::io::{AsyncWriteExt, BufReader, BufWriter, DuplexStream};
 use tokio::select;
 use tokio::sync::Mutex;
 use tokio_util::io::ReaderStream;
@@ -13,39 +19,98 @@ use uuid::Uuid;
 
 use protocol::mdsftp::handler::{AbstractReadStream, AbstractWriteStream};
 
+use crate::archive::ArchiveFormat;
 use crate::public::extractors::entry_path::EntryPath;
+use crate::public::extractors::preconditions::Preconditions;
 use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::archive_service::{handle_archive, ArchiveQuery};
+use crate::public::service::extract_service::handle_extract;
 use crate::public::service::file_access_service::{
     handle_download, handle_upload_durable, handle_upload_oneshot, resume_upload_session,
-    start_upload_session,
+    start_upload_session, UploadSource,
 };
+use crate::public::service::file_metadata_service::{metadata_from_headers, USER_METADATA_PREFIX};
+use crate::public::service::partial_write_service::{handle_append, handle_range_write};
+use crate::public::throttling::{Direction, Transfer};
 use crate::AppState;
 use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use data::dto::config::FsLimitConfiguration;
 use data::dto::entity::{
-    UploadSessionRequest, UploadSessionResumeRequest, UploadSessionResumeResponse,
-    UploadSessionStartResponse,
+    ExtractionResult, FileVersionSelector, SnapshotSelector, UploadSessionRequest,
+    UploadSessionResumeRequest, UploadSessionResumeResponse, UploadSessionStartResponse,
 };
 
 const USER_TRANSFER_BUFFER: usize = 8 * 1024;
 
+fn content_length(req: &HttpRequest) -> NodeClientResponse<u64> {
+    req.headers()
+        .get(CONTENT_LENGTH)
+        .ok_or(NodeClientError::BadRequest)?
+        .to_str()
+        .map_err(|_| NodeClientError::BadRequest)?
+        .parse()
+        .map_err(|_| NodeClientError::BadRequest)
+}
+
+/// Forwards the request body into the sender until the body ends,
+/// or the token signals that the receiving side is done.
+/// The body is paced by the upload limits of the transfer.
+async fn forward_payload(
+    mut payload: web::Payload,
+    mut sender: DuplexStream,
+    token: CancellationToken,
+    transfer: Transfer,
+) -> NodeClientResponse<()> {
+    let send_res: NodeClientResponse<()> = async {
+        while let Some(item) = select! {
+            _ = token.cancelled() => {
+                return Ok(());
+            },
+            data = payload.next() => { data }
+        } {
+            let item = item?;
+            transfer.pace(Direction::Upload, item.len()).await;
+            sender.write_all(&item).await?;
+        }
+        Ok(())
+    }
+    .await;
+
+    sender.shutdown().await?;
+    send_res
+}
+
+/// Streams the receiver as a response body paced by the download limits of the transfer,
+/// which is held until the body is done.
+fn paced_body(
+    receiver: DuplexStream,
+    transfer: Transfer,
+) -> impl Stream<Item = std::io::Result<Bytes>> + 'static {
+    ReaderStream::new(receiver).then(move |chunk| {
+        let transfer = transfer.clone();
+        async move {
+            if let Ok(bytes) = &chunk {
+                transfer.pace(Direction::Download, bytes.len()).await;
+            }
+            chunk
+        }
+    })
+}
+
 #[post("/upload/oneshot/{app_id}/{bucket_id}/{path:.*}")]
 pub async fn upload_oneshot(
     path: EntryPath,
+    preconditions: Preconditions,
     accessor: BucketAccessor,
     req: HttpRequest,
     app_state: web::Data<AppState>,
-    mut payload: web::Payload,
+    payload: web::Payload,
 ) -> NodeClientResponse<HttpResponse> {
-    let content_size: u64 = req
-        .headers()
-        .get(CONTENT_LENGTH)
-        .ok_or(NodeClientError::BadRequest)?
-        .to_str()
-        .map_err(|_| NodeClientError::BadRequest)?
-        .parse()
-        .map_err(|_| NodeClientError::BadRequest)?;
+    let content_size = content_length(&req)?;
+    let metadata = metadata_from_headers(req.headers())?;
+    let transfer = accessor.throttle.start_transfer()?;
 
-    let (mut sender, receiver) = tokio::io::duplex(USER_TRANSFER_BUFFER);
+    let (sender, receiver) = tokio::io::duplex(USER_TRANSFER_BUFFER);
 
     let abstract_reader: AbstractReadStream =
         Arc::new(Mutex::new(Box::pin(BufReader::new(receiver))));
@@ -53,8 +118,16 @@ pub async fn upload_oneshot(
     let cancel_sender = token.clone();
 
     let channel_handle = tokio::spawn(async move {
-        let err =
-            handle_upload_oneshot(path, content_size, app_state, accessor, abstract_reader).await;
+        let err = handle_upload_oneshot(
+            path,
+            content_size,
+            app_state,
+            accessor,
+            UploadSource::Stream(abstract_reader),
+            metadata,
+            preconditions,
+        )
+        .await;
         cancel_sender.cancel();
         if let Err(err) = err {
             warn!("Oneshot upload error: {err:?}");
@@ -63,22 +136,107 @@ pub async fn upload_oneshot(
         Ok(())
     });
 
-    let send_res: NodeClientResponse<()> = async {
-        while let Some(item) = select! {
-            _ = token.cancelled() => {
-                return Ok(());
-            },
-            data = payload.next() => { data }
-        } {
-            let item = item?;
-            sender.write_all(&item).await?;
+    let send_res = forward_payload(payload, sender, token, transfer).await;
+
+    channel_handle.await??;
+
+    send_res.map(|_| HttpResponse::Ok().finish())
+}
+
+#[post("/append/{app_id}/{bucket_id}/{path:.*}")]
+pub async fn append(
+    path: EntryPath,
+    preconditions: Preconditions,
+    accessor: BucketAccessor,
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    payload: web::Payload,
+) -> NodeClientResponse<HttpResponse> {
+    let content_size = content_length(&req)?;
+    let transfer = accessor.throttle.start_transfer()?;
+
+    let (sender, receiver) = tokio::io::duplex(USER_TRANSFER_BUFFER);
+
+    let abstract_reader: AbstractReadStream =
+        Arc::new(Mutex::new(Box::pin(BufReader::new(receiver))));
+    let token = CancellationToken::new();
+    let cancel_sender = token.clone();
+
+    let channel_handle = tokio::spawn(async move {
+        let err = handle_append(
+            path,
+            content_size,
+            app_state,
+            accessor,
+            abstract_reader,
+            preconditions,
+        )
+        .await;
+        cancel_sender.cancel();
+        if let Err(err) = err {
+            warn!("Append error: {err:?}");
+            return Err(err);
         }
         Ok(())
+    });
+
+    let send_res = forward_payload(payload, sender, token, transfer).await;
+
+    channel_handle.await??;
+
+    send_res.map(|_| HttpResponse::Ok().finish())
+}
+
+/// Overwrites a part of an existing file, the part being given by the `Content-Range` header.
+#[put("/write/{app_id}/{bucket_id}/{path:.*}")]
+pub async fn write_range(
+    path: EntryPath,
+    preconditions: Preconditions,
+    accessor: BucketAccessor,
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    payload: web::Payload,
+) -> NodeClientResponse<HttpResponse> {
+    let content_size = content_length(&req)?;
+    let (range, total) = match ContentRange::parse(&req).map_err(|_| NodeClientError::BadRequest)? {
+        ContentRange(ContentRangeSpec::Bytes {
+            range: Some(range),
+            instance_length,
+        }) => (range, instance_length),
+        _ => return Err(NodeClientError::BadRequest),
+    };
+    if range.0 > range.1 || range.1 - range.0 + 1 != content_size {
+        return Err(NodeClientError::BadRequest);
     }
-    .await;
+    let transfer = accessor.throttle.start_transfer()?;
 
-    sender.shutdown().await?;
-    drop(sender);
+    let (sender, receiver) = tokio::io::duplex(USER_TRANSFER_BUFFER);
+
+    let abstract_reader: AbstractReadStream =
+        Arc::new(Mutex::new(Box::pin(BufReader::new(receiver))));
+    let token = CancellationToken::new();
+    let cancel_sender = token.clone();
+
+    let channel_handle = tokio::spawn(async move {
+        let err = handle_range_write(
+            path,
+            range,
+            total,
+            app_state,
+            accessor,
+            abstract_reader,
+            preconditions,
+        )
+        .await;
+        cancel_sender.cancel();
+        if let Err(err) = err {
+            warn!("Ranged write error: {err:?}");
+            return Err(err);
+        }
+        Ok(())
+    });
+
+    let send_res = forward_payload(payload, sender, token, transfer).await;
 
     channel_handle.await??;
 
@@ -88,11 +246,12 @@ pub async fn upload_oneshot(
 #[post("/upload/durable/{app_id}/{bucket_id}/{path:.*}")]
 pub async fn start_upload_durable(
     path: EntryPath,
+    preconditions: Preconditions,
     accessor: BucketAccessor,
     req: web::Json<UploadSessionRequest>,
     data: web::Data<AppState>,
 ) -> NodeClientResponse<web::Json<UploadSessionStartResponse>> {
-    start_upload_session(path, accessor, req.0, data).await
+    start_upload_session(path, accessor, req.0, preconditions, data).await
 }
 
 #[post("/upload/resume/{app_id}/{bucket_id}")]
@@ -117,6 +276,7 @@ pub async fn upload_durable(
     mut payload: web::Payload,
     data: web::Data<AppState>,
 ) -> NodeClientResponse<HttpResponse> {
+    let transfer = accessor.throttle.start_transfer()?;
     let (mut sender, receiver) = tokio::io::duplex(USER_TRANSFER_BUFFER);
 
     let abstract_reader: AbstractReadStream =
@@ -140,6 +300,7 @@ pub async fn upload_durable(
             data = payload.next() => { data }
         } {
             let item = item?;
+            transfer.pace(Direction::Upload, item.len()).await;
             sender.write_all(&item).await?;
         }
         Ok(())
@@ -159,25 +320,39 @@ pub async fn download(
     path: EntryPath,
     accessor: BucketAccessor,
     app_data: web::Data<AppState>,
+    version: web::Query<FileVersionSelector>,
+    snapshot: web::Query<SnapshotSelector>,
+    preconditions: Preconditions,
     req: HttpRequest,
 ) -> NodeClientResponse<HttpResponse> {
+    serve_download(
+        path,
+        accessor,
+        app_data,
+        version.version_id,
+        snapshot.snapshot_id,
+        &preconditions,
+        &req,
+    )
+    .await
+}
+
+/// Streams the file, or the requested ranges of it, as the response.
+pub(crate) async fn serve_download(
+    path: EntryPath,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+    version_id: Option<Uuid>,
+    snapshot_id: Option<Uuid>,
+    preconditions: &Preconditions,
+    req: &HttpRequest,
+) -> NodeClientResponse<HttpResponse> {
+    let transfer = accessor.throttle.start_transfer()?;
     let (sender, receiver) = tokio::io::duplex(USER_TRANSFER_BUFFER);
-    let range = Range::parse(&req);
-    let mut range_clone = None;
-    let mut byte_range = None;
-
-    if let Ok(range) = range {
-        match range {
-            Range::Bytes(range) => {
-                if range.len() != 1 {
-                    return Err(NodeClientError::RangeUnsatisfiable);
-                }
-                byte_range = Some(range[0].clone());
-                range_clone.clone_from(&byte_range);
-            }
-            Range::Unregistered(_, _) => {}
-        }
-    }
+    let ranges = match Range::parse(req) {
+        Ok(Range::Bytes(ranges)) => ranges,
+        _ => vec![],
+    };
 
     let abstract_writer: AbstractWriteStream =
         Arc::new(Mutex::new(Box::pin(BufWriter::new(sender))));
@@ -186,28 +361,130 @@ pub async fn download(
         accessor,
         abstract_writer,
         app_data,
-        byte_range.clone(),
+        ranges,
+        version_id,
+        snapshot_id,
+        preconditions,
     )
     .await?;
 
-    let response_stream = ReaderStream::new(receiver);
+    let etag = ETag(EntityTag::new_strong(info.etag));
+    let last_modified = LastModified(SystemTime::from(info.last_modified).into());
+    if info.not_modified {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(etag)
+            .insert_header(last_modified)
+            .finish());
+    }
 
-    let len = byte_range
-        .map(|x| {
-            x.to_satisfiable_range(info.size)
-                .map(|y| y.1 - y.0 + 1)
-                .unwrap_or(info.size)
-        })
-        .unwrap_or(info.size);
+    let response_stream = paced_body(receiver, transfer);
 
-    Ok(if range_clone.is_some() {
-        HttpResponse::PartialContent()
-    } else {
+    let mut response = if info.ranges.is_empty() {
         HttpResponse::Ok()
+    } else {
+        HttpResponse::PartialContent()
+    };
+    match (&info.boundary, info.ranges.first()) {
+        (Some(boundary), _) => {
+            response.insert_header((
+                CONTENT_TYPE,
+                format!("multipart/byteranges; boundary={boundary}"),
+            ));
+        }
+        (None, Some(range)) => {
+            response
+                .content_type(info.mime)
+                .insert_header(ContentRange(ContentRangeSpec::Bytes {
+                    range: Some(*range),
+                    instance_length: Some(info.size),
+                }));
+        }
+        (None, None) => {
+            response.content_type(info.mime);
+        }
     }
-    .content_type(info.mime)
-    .insert_header(ContentLength(len as usize))
-    .insert_header(("X-File-Content-Length", len as usize))
-    .insert_header(ContentDisposition::attachment(info.attachment_name))
-    .streaming(response_stream))
+    response
+        .insert_header(ContentLength(info.content_length as usize))
+        .insert_header(("X-File-Content-Length", info.content_length as usize))
+        .insert_header((ACCEPT_RANGES, "bytes"))
+        .insert_header(etag)
+        .insert_header(last_modified);
+    match info.metadata.content_disposition {
+        Some(disposition) => response.insert_header((CONTENT_DISPOSITION, disposition)),
+        None => response.insert_header(ContentDisposition::attachment(info.attachment_name)),
+    };
+    if let Some(cache_control) = info.metadata.cache_control {
+        response.insert_header((CACHE_CONTROL, cache_control));
+    }
+    for (key, value) in info.metadata.user.unwrap_or_default() {
+        response.insert_header((format!("{USER_METADATA_PREFIX}{key}"), value));
+    }
+
+    Ok(response.streaming(response_stream))
+}
+
+#[get("/archive/{app_id}/{bucket_id}/{path:.*}")]
+pub async fn download_archive(
+    path: EntryPath,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+    query: web::Query<ArchiveQuery>,
+) -> NodeClientResponse<HttpResponse> {
+    let transfer = accessor.throttle.start_transfer()?;
+    let (sender, receiver) = tokio::io::duplex(USER_TRANSFER_BUFFER);
+    let format = query.format;
+    let name = handle_archive(path, format, accessor, sender, app_data).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.mime())
+        .insert_header(ContentDisposition::attachment(format!(
+            "{name}.{}",
+            format.extension()
+        )))
+        .streaming(paced_body(receiver, transfer)))
+}
+
+#[post("/extract/{app_id}/{bucket_id}/{path:.*}")]
+pub async fn extract_archive(
+    path: EntryPath,
+    accessor: BucketAccessor,
+    query: web::Query<ArchiveQuery>,
+    fs_limits: web::Data<FsLimitConfiguration>,
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    payload: web::Payload,
+) -> NodeClientResponse<web::Json<ExtractionResult>> {
+    // zip archives are buffered whole, their size has to be known upfront
+    let archive_size = match query.format {
+        ArchiveFormat::Zip => Some(content_length(&req)?),
+        _ => None,
+    };
+    let transfer = accessor.throttle.start_transfer()?;
+    let (sender, receiver) = tokio::io::duplex(USER_TRANSFER_BUFFER);
+    let token = CancellationToken::new();
+    let cancel_sender = token.clone();
+    let format = query.format;
+
+    let extract_handle = tokio::spawn(async move {
+        let res = handle_extract(
+            path,
+            format,
+            archive_size,
+            &fs_limits,
+            accessor,
+            receiver,
+            app_state,
+        )
+        .await;
+        cancel_sender.cancel();
+        if let Err(err) = &res {
+            warn!("Archive extraction error: {err:?}");
+        }
+        res
+    });
+
+    let send_res = forward_payload(payload, sender, token, transfer).await;
+
+    let result = extract_handle.await??;
+    send_res.map(|_| web::Json(result))
 }
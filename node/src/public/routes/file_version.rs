@@ -0,0 +1,43 @@
+use crate::public::extractors::entry_path::EntryPath;
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::file_version_service::{
+    do_delete_version, do_list_versions, do_restore_version, VersionListQuery,
+};
+use crate::AppState;
+use actix_web::{delete, get, post, web, HttpResponse};
+use commons::error::std_response::NodeClientResponse;
+use data::dto::entity::{FileVersionList, FileVersionRequest};
+
+#[get("/versions/list/{app_id}/{bucket_id}/{path:.*}")]
+pub async fn list_versions(
+    path: EntryPath,
+    query: web::Query<VersionListQuery>,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<web::Json<FileVersionList>> {
+    do_list_versions(path, query.into_inner(), accessor, app_data)
+        .await
+        .map(web::Json)
+}
+
+#[post("/versions/restore/{app_id}/{bucket_id}/{path:.*}")]
+pub async fn restore_version(
+    path: EntryPath,
+    req: web::Json<FileVersionRequest>,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<HttpResponse> {
+    do_restore_version(path, req.0, accessor, app_data).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[delete("/versions/delete/{app_id}/{bucket_id}/{path:.*}")]
+pub async fn delete_version(
+    path: EntryPath,
+    req: web::Json<FileVersionRequest>,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<HttpResponse> {
+    do_delete_version(path, req.0, accessor, app_data).await?;
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,29 @@
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::job_service::{do_get_job, do_list_jobs};
+use crate::AppState;
+use actix_web::{get, web};
+use commons::error::std_response::NodeClientResponse;
+use data::dto::entity::{BucketJobDto, BucketJobList};
+use uuid::Uuid;
+
+#[get("/jobs/{app_id}/{bucket_id}")]
+pub async fn list_jobs(
+    path: web::Path<(Uuid, Uuid)>,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<web::Json<BucketJobList>> {
+    do_list_jobs(path.0, path.1, accessor, app_data)
+        .await
+        .map(web::Json)
+}
+
+#[get("/jobs/{app_id}/{bucket_id}/{job_id}")]
+pub async fn get_job(
+    path: web::Path<(Uuid, Uuid, Uuid)>,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<web::Json<BucketJobDto>> {
+    do_get_job(path.0, path.1, path.2, accessor, app_data)
+        .await
+        .map(web::Json)
+}
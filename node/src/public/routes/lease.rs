@@ -0,0 +1,42 @@
+use crate::public::extractors::entry_path::EntryPath;
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::lease_service::{do_acquire_lease, do_release_lease, do_renew_lease};
+use crate::AppState;
+use actix_web::{delete, post, web, HttpResponse};
+use commons::error::std_response::NodeClientResponse;
+use data::dto::entity::{LeaseAcquireRequest, LeaseDto, LeaseReleaseRequest, LeaseRenewRequest};
+
+#[post("/lease/acquire/{app_id}/{bucket_id}/{path:.*}")]
+pub async fn acquire_lease(
+    path: EntryPath,
+    req: web::Json<LeaseAcquireRequest>,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<web::Json<LeaseDto>> {
+    do_acquire_lease(path, req.0, accessor, app_data)
+        .await
+        .map(web::Json)
+}
+
+#[post("/lease/renew/{app_id}/{bucket_id}/{path:.*}")]
+pub async fn renew_lease(
+    path: EntryPath,
+    req: web::Json<LeaseRenewRequest>,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<web::Json<LeaseDto>> {
+    do_renew_lease(path, req.0, accessor, app_data)
+        .await
+        .map(web::Json)
+}
+
+#[delete("/lease/release/{app_id}/{bucket_id}/{path:.*}")]
+pub async fn release_lease(
+    path: EntryPath,
+    req: web::Json<LeaseReleaseRequest>,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<HttpResponse> {
+    do_release_lease(path, req.0, accessor, app_data).await?;
+    Ok(HttpResponse::Ok().finish())
+}
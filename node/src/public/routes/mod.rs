@@ -1,3 +1,13 @@
+pub mod batch;
+pub mod change_feed;
 pub mod entity_action;
 pub mod entity_list;
+pub mod file_metadata;
 pub mod file_transfer;
+pub mod file_version;
+pub mod job;
+pub mod lease;
+pub mod object_lock;
+pub mod public_access;
+pub mod snapshot;
+pub mod trash;
@@ -0,0 +1,18 @@
+use crate::public::extractors::entry_path::EntryPath;
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::object_lock_service::do_set_legal_hold;
+use crate::AppState;
+use actix_web::{put, web, HttpResponse};
+use commons::error::std_response::NodeClientResponse;
+use data::dto::entity::LegalHoldRequest;
+
+#[put("/hold/{app_id}/{bucket_id}/{path:.*}")]
+pub async fn set_legal_hold(
+    path: EntryPath,
+    req: web::Json<LegalHoldRequest>,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<HttpResponse> {
+    do_set_legal_hold(path, req.0, accessor, app_data).await?;
+    Ok(HttpResponse::Ok().finish())
+}
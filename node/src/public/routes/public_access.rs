@@ -0,0 +1,41 @@
+use crate::public::extractors::entry_path::EntryPath;
+use crate::public::extractors::preconditions::Preconditions;
+use crate::public::routes::file_transfer::serve_download;
+use crate::public::service::file_list_service::{do_list_dir, ListQuery};
+use crate::public::service::public_access_service::authorize_public;
+use crate::AppState;
+use actix_web::{get, route, web, HttpRequest, HttpResponse};
+use commons::error::std_response::NodeClientResponse;
+use data::dto::entity::EntityList;
+
+/// Anonymous download, versions and snapshots are not exposed.
+#[route(
+    "/download/{app_id}/{bucket_id}/{path:.*}",
+    method = "GET",
+    method = "HEAD"
+)]
+pub async fn public_download(
+    path: EntryPath,
+    app_data: web::Data<AppState>,
+    preconditions: Preconditions,
+    req: HttpRequest,
+) -> NodeClientResponse<HttpResponse> {
+    let accessor = authorize_public(&path, false, &app_data).await?;
+    serve_download(path, accessor, app_data, None, None, &preconditions, &req).await
+}
+
+#[get("/list/{app_id}/{bucket_id}/{path:.*}")]
+pub async fn public_list_directory(
+    path: EntryPath,
+    app_data: web::Data<AppState>,
+    query: web::Query<ListQuery>,
+) -> NodeClientResponse<web::Json<EntityList>> {
+    let accessor = authorize_public(&path, true, &app_data).await?;
+    let query = ListQuery {
+        snapshot_id: None,
+        ..query.0
+    };
+    do_list_dir(path, accessor, app_data, query)
+        .await
+        .map(web::Json)
+}
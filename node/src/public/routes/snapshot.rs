@@ -0,0 +1,55 @@
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::snapshot_service::{
+    do_create_snapshot, do_delete_snapshot, do_list_snapshots, do_restore_snapshot,
+};
+use crate::AppState;
+use actix_web::{delete, get, post, web, HttpResponse};
+use commons::error::std_response::NodeClientResponse;
+use data::dto::entity::{
+    BucketSnapshotDto, BucketSnapshotList, CreateSnapshotRequest, RestoreSnapshotRequest,
+};
+use uuid::Uuid;
+
+#[post("/snapshots/{app_id}/{bucket_id}")]
+pub async fn create_snapshot(
+    path: web::Path<(Uuid, Uuid)>,
+    req: web::Json<CreateSnapshotRequest>,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<web::Json<BucketSnapshotDto>> {
+    do_create_snapshot(path.0, path.1, req.0, accessor, app_data)
+        .await
+        .map(web::Json)
+}
+
+#[get("/snapshots/{app_id}/{bucket_id}")]
+pub async fn list_snapshots(
+    path: web::Path<(Uuid, Uuid)>,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<web::Json<BucketSnapshotList>> {
+    do_list_snapshots(path.0, path.1, accessor, app_data)
+        .await
+        .map(web::Json)
+}
+
+#[delete("/snapshots/{app_id}/{bucket_id}/{snapshot_id}")]
+pub async fn delete_snapshot(
+    path: web::Path<(Uuid, Uuid, Uuid)>,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<HttpResponse> {
+    do_delete_snapshot(path.0, path.1, path.2, accessor, app_data).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[post("/snapshots/restore/{app_id}/{bucket_id}/{snapshot_id}")]
+pub async fn restore_snapshot(
+    path: web::Path<(Uuid, Uuid, Uuid)>,
+    req: web::Json<RestoreSnapshotRequest>,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<HttpResponse> {
+    do_restore_snapshot(path.0, path.1, path.2, req.0, accessor, app_data).await?;
+    Ok(HttpResponse::Ok().finish())
+}
@@ -0,0 +1,31 @@
+use crate::public::extractors::entry_path::EntryPath;
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::trash_service::{do_list_trash, do_restore_trash, TrashListQuery};
+use crate::AppState;
+use actix_web::{get, post, web};
+use commons::error::std_response::NodeClientResponse;
+use data::dto::entity::{TrashList, TrashRestoreResponse};
+use uuid::Uuid;
+
+#[get("/trash/{app_id}/{bucket_id}")]
+pub async fn list_trash(
+    path: web::Path<(Uuid, Uuid)>,
+    query: web::Query<TrashListQuery>,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<web::Json<TrashList>> {
+    do_list_trash(path.0, path.1, query.into_inner(), accessor, app_data)
+        .await
+        .map(web::Json)
+}
+
+#[post("/trash/restore/{app_id}/{bucket_id}/{path:.*}")]
+pub async fn restore_trash(
+    path: EntryPath,
+    accessor: BucketAccessor,
+    app_data: web::Data<AppState>,
+) -> NodeClientResponse<web::Json<TrashRestoreResponse>> {
+    do_restore_trash(path, accessor, app_data)
+        .await
+        .map(web::Json)
+}
@@ -0,0 +1,197 @@
+use crate::archive::{ArchiveEntry, ArchiveFormat, ArchiveWriter, EntryKind};
+use crate::public::extractors::entry_path::EntryPath;
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::file_io_service::outbound_transfer;
+use crate::public::service::{DOWNLOAD_ALLOWANCE, LIST_DIR_ALLOWANCE};
+use crate::AppState;
+use actix_web::web::Data;
+use chrono::{DateTime, Utc};
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use data::access::file_access::{
+    get_bucket, get_directories_from_bucket, get_directory, get_files_from_bucket_and_directory,
+    DirectoryIterator,
+};
+use data::error::MeowithDataError;
+use data::model::file_model::{Directory, File, FileChunk};
+use data::pathlib::join_parent_name;
+use futures::pin_mut;
+use futures_util::StreamExt;
+use log::warn;
+use protocol::mdsftp::handler::AbstractWriteStream;
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter, DuplexStream};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+const ARCHIVE_TRANSFER_BUFFER: usize = 64 * 1024;
+
+#[derive(Deserialize, Debug, Default)]
+pub struct ArchiveQuery {
+    #[serde(default)]
+    pub format: ArchiveFormat,
+}
+
+/// A directory whose files go into the archive under `path`.
+/// Only the archived directory itself lacks `modified`, as it gets no entry of its own.
+struct ArchivedDirectory {
+    id: Option<Uuid>,
+    path: String,
+    modified: Option<DateTime<Utc>>,
+}
+
+/// Streams an archive of everything beneath the directory into the writer.
+/// The entries are relative to the directory, which is resolved before anything is written,
+/// the returned name is meant for the attachment.
+///
+/// An error while streaming can no longer be reported to the client,
+/// the archive ends abruptly instead.
+pub async fn handle_archive(
+    path: EntryPath,
+    format: ArchiveFormat,
+    accessor: BucketAccessor,
+    writer: DuplexStream,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<String> {
+    accessor.has_permission(&path.app_id, &path.bucket_id, *DOWNLOAD_ALLOWANCE)?;
+    accessor.has_permission(&path.app_id, &path.bucket_id, *LIST_DIR_ALLOWANCE)?;
+    let directory_path = path.path();
+
+    let (name, root, children) = if directory_path.is_empty() {
+        let bucket = get_bucket(path.app_id, path.bucket_id, &app_state.session).await?;
+        let mut stream = get_directories_from_bucket(path.bucket_id, &app_state.session).await?;
+        let mut children = vec![];
+        while let Some(directory) = stream.next().await {
+            children.push(directory.map_err(MeowithDataError::from)?);
+        }
+        (bucket.name, None, children)
+    } else {
+        let directory = get_directory(path.bucket_id, Some(directory_path), &app_state.session)
+            .await?
+            .unwrap(); // will not be None as it is not the root dir.
+        let child_stream = DirectoryIterator::from_parent(directory.clone(), &app_state.session);
+        pin_mut!(child_stream);
+        let mut children = vec![];
+        while let Some(res) = child_stream.next().await {
+            children.push(res?);
+        }
+        (directory.name.clone(), Some(directory), children)
+    };
+    let root_path = root.as_ref().map(Directory::full_path).unwrap_or_default();
+
+    let mut directories = vec![ArchivedDirectory {
+        id: root.map(|directory| directory.id),
+        path: String::new(),
+        modified: None,
+    }];
+    let mut children: Vec<(String, Directory)> = children
+        .into_iter()
+        .map(|directory| (relative_path(&directory.full_path(), &root_path), directory))
+        .collect();
+    // parents precede their children
+    children.sort_by(|a, b| a.0.cmp(&b.0));
+    directories.extend(
+        children
+            .into_iter()
+            .map(|(path, directory)| ArchivedDirectory {
+                id: Some(directory.id),
+                path,
+                modified: Some(directory.last_modified),
+            }),
+    );
+
+    let bucket_id = path.bucket_id;
+    tokio::spawn(async move {
+        if let Err(err) = write_archive(bucket_id, directories, format, writer, &app_state).await {
+            warn!("Archive download error: {err:?}");
+        }
+    });
+
+    Ok(name)
+}
+
+fn relative_path(full_path: &str, root_path: &str) -> String {
+    full_path[root_path.len()..]
+        .trim_start_matches('/')
+        .to_string()
+}
+
+async fn write_archive(
+    bucket_id: Uuid,
+    directories: Vec<ArchivedDirectory>,
+    format: ArchiveFormat,
+    writer: DuplexStream,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let mut writer = BufWriter::new(writer);
+    let mut archive = ArchiveWriter::new(format);
+
+    for directory in directories {
+        if let Some(modified) = directory.modified {
+            archive.start_entry(&ArchiveEntry {
+                path: directory.path.clone(),
+                kind: EntryKind::Directory,
+                size: 0,
+                modified,
+            });
+            archive.finish_entry()?;
+        }
+
+        let mut stream =
+            get_files_from_bucket_and_directory(bucket_id, directory.id, &app_state.session)
+                .await?;
+        while let Some(file) = stream.next().await {
+            let file = file.map_err(MeowithDataError::from)?;
+            archive.start_entry(&ArchiveEntry {
+                path: join_parent_name(&directory.path, &file.name),
+                kind: EntryKind::File,
+                size: file.size as u64,
+                modified: file.last_modified,
+            });
+            write_contents(&file, &mut archive, &mut writer, app_state).await?;
+            archive.finish_entry()?;
+        }
+        writer.write_all(&archive.take_output()).await?;
+    }
+
+    archive.finish()?;
+    writer.write_all(&archive.take_output()).await?;
+    writer.shutdown().await?;
+    Ok(())
+}
+
+/// Feeds the chunks of the file through the archive as they arrive.
+async fn write_contents(
+    file: &File,
+    archive: &mut ArchiveWriter,
+    writer: &mut BufWriter<DuplexStream>,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let mut chunks: Vec<&FileChunk> = file.chunk_ids.iter().collect();
+    chunks.sort_by_key(|chunk| chunk.chunk_order);
+
+    let (sender, mut receiver) = tokio::io::duplex(ARCHIVE_TRANSFER_BUFFER);
+    let sender: AbstractWriteStream = Arc::new(Mutex::new(Box::pin(sender)));
+
+    let transfer = async {
+        for chunk in chunks {
+            outbound_transfer(sender.clone(), chunk, app_state, None).await?;
+        }
+        sender.lock().await.shutdown().await?;
+        Ok::<(), NodeClientError>(())
+    };
+    let encode = async {
+        let mut buffer = vec![0u8; ARCHIVE_TRANSFER_BUFFER];
+        loop {
+            let read = receiver.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            archive.write_data(&buffer[..read]);
+            writer.write_all(&archive.take_output()).await?;
+        }
+        Ok::<(), NodeClientError>(())
+    };
+    tokio::try_join!(transfer, encode)?;
+    Ok(())
+}
@@ -0,0 +1,294 @@
+use crate::public::extractors::copy_request::CopyEntityRequest;
+use crate::public::extractors::entry_path::EntryPath;
+use crate::public::extractors::preconditions::Preconditions;
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::routes::entity_action::RenameEntityRequest;
+use crate::public::service::copy_service::do_copy_file;
+use crate::public::service::directory_action_service::do_create_directory;
+use crate::public::service::file_action_service::{delete_file_srv, rename_file_srv};
+use crate::public::service::lease_service::check_lease;
+use crate::public::service::object_lock_service::check_path_lock;
+use crate::public::service::{
+    CREATE_DIRECTORY_ALLOWANCE, DELETE_ALLOWANCE, DOWNLOAD_ALLOWANCE, RENAME_ALLOWANCE,
+    UPLOAD_ALLOWANCE, UPLOAD_OVERWRITE_ALLOWANCE,
+};
+use crate::AppState;
+use actix_web::http::StatusCode;
+use actix_web::web::Data;
+use actix_web::ResponseError;
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use data::access::file_access::{get_file_dir, maybe_get_file_dir};
+use data::dto::config::FsLimitConfiguration;
+use data::dto::entity::{BatchItemResult, BatchOperation, BatchRequest, BatchResponse};
+use data::pathlib::{prepare_path, split_path};
+use futures_util::{stream, StreamExt};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+const MAX_BATCH_OPERATIONS: usize = 1000;
+const BATCH_CONCURRENCY: usize = 16;
+
+/// A file in a bucket, used to find operations of an atomic batch that depend on each other.
+type BatchTarget = (Uuid, String);
+
+/// A batch operation with its paths prepared.
+enum PreparedOperation {
+    Delete(EntryPath),
+    Rename(EntryPath, RenameEntityRequest),
+    Mkdir(EntryPath),
+    Copy(EntryPath, CopyEntityRequest),
+}
+
+impl PreparedOperation {
+    fn prepare(
+        operation: BatchOperation,
+        app_id: Uuid,
+        bucket_id: Uuid,
+        fs_limit: &FsLimitConfiguration,
+    ) -> NodeClientResponse<Self> {
+        let prepare =
+            |path: &str| prepare_path(path, fs_limit).ok_or(NodeClientError::BadResourcePath);
+        let entry = |path: &str| -> NodeClientResponse<EntryPath> {
+            Ok(EntryPath::from_prepared(app_id, bucket_id, prepare(path)?))
+        };
+
+        Ok(match operation {
+            BatchOperation::Delete { path } => PreparedOperation::Delete(entry(&path)?),
+            BatchOperation::Rename { path, to } => PreparedOperation::Rename(
+                entry(&path)?,
+                RenameEntityRequest::from_prepared(prepare(&to)?),
+            ),
+            BatchOperation::Mkdir { path } => PreparedOperation::Mkdir(entry(&path)?),
+            BatchOperation::Copy {
+                path,
+                to,
+                bucket_id,
+            } => PreparedOperation::Copy(
+                entry(&path)?,
+                CopyEntityRequest::from_prepared(prepare(&to)?, bucket_id),
+            ),
+        })
+    }
+
+    fn writes(&self) -> Vec<BatchTarget> {
+        match self {
+            PreparedOperation::Delete(path) | PreparedOperation::Mkdir(path) => {
+                vec![(path.bucket_id, path.path())]
+            }
+            PreparedOperation::Rename(path, req) => {
+                vec![(path.bucket_id, path.path()), (path.bucket_id, req.path())]
+            }
+            PreparedOperation::Copy(path, req) => vec![(req.bucket_id(path.bucket_id), req.path())],
+        }
+    }
+
+    fn reads(&self) -> Vec<BatchTarget> {
+        match self {
+            PreparedOperation::Copy(path, _) => vec![(path.bucket_id, path.path())],
+            _ => vec![],
+        }
+    }
+
+    /// Checks the permissions, the existence, the leases and the object locks of the files involved,
+    /// without changing anything.
+    async fn validate(
+        &self,
+        accessor: &BucketAccessor,
+        app_state: &Data<AppState>,
+    ) -> NodeClientResponse<()> {
+        match self {
+            PreparedOperation::Delete(path) => {
+                accessor.has_permission(&path.app_id, &path.bucket_id, *DELETE_ALLOWANCE)?;
+                file_exists(path.bucket_id, path.path(), app_state).await?;
+            }
+            PreparedOperation::Rename(path, req) => {
+                accessor.has_permission(&path.app_id, &path.bucket_id, *RENAME_ALLOWANCE)?;
+                file_exists(path.bucket_id, path.path(), app_state).await?;
+                if path.path() != req.path()
+                    && maybe_file_exists(path.bucket_id, req.path(), app_state).await?
+                {
+                    accessor.has_permission(&path.app_id, &path.bucket_id, *DELETE_ALLOWANCE)?;
+                }
+            }
+            PreparedOperation::Mkdir(path) => {
+                accessor.has_permission(
+                    &path.app_id,
+                    &path.bucket_id,
+                    *CREATE_DIRECTORY_ALLOWANCE,
+                )?;
+            }
+            PreparedOperation::Copy(path, req) => {
+                let target_bucket = req.bucket_id(path.bucket_id);
+                accessor.has_permission(&path.app_id, &path.bucket_id, *DOWNLOAD_ALLOWANCE)?;
+                accessor.has_permission(&path.app_id, &target_bucket, *UPLOAD_ALLOWANCE)?;
+                file_exists(path.bucket_id, path.path(), app_state).await?;
+                if maybe_file_exists(target_bucket, req.path(), app_state).await? {
+                    accessor.has_permission(
+                        &path.app_id,
+                        &target_bucket,
+                        *UPLOAD_OVERWRITE_ALLOWANCE,
+                    )?;
+                }
+            }
+        }
+        if !matches!(self, PreparedOperation::Mkdir(_)) {
+            for (bucket_id, path) in self.writes() {
+                check_lease(bucket_id, &path, accessor, app_state).await?;
+                check_path_lock(bucket_id, &path, app_state).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn execute(
+        self,
+        accessor: BucketAccessor,
+        app_state: Data<AppState>,
+    ) -> NodeClientResponse<()> {
+        let preconditions = Preconditions::default();
+        match self {
+            PreparedOperation::Delete(path) => {
+                delete_file_srv(path, preconditions, accessor, app_state).await
+            }
+            PreparedOperation::Rename(path, req) => {
+                rename_file_srv(path, req, preconditions, accessor, app_state).await
+            }
+            PreparedOperation::Mkdir(path) => do_create_directory(path, accessor, app_state).await,
+            PreparedOperation::Copy(path, req) => {
+                do_copy_file(path, req, preconditions, accessor, app_state).await
+            }
+        }
+    }
+}
+
+async fn file_exists(
+    bucket_id: Uuid,
+    path: String,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let split_path = split_path(&path);
+    get_file_dir(bucket_id, split_path.0, split_path.1, &app_state.session).await?;
+    Ok(())
+}
+
+async fn maybe_file_exists(
+    bucket_id: Uuid,
+    path: String,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<bool> {
+    let split_path = split_path(&path);
+    let (file, _) =
+        maybe_get_file_dir(bucket_id, split_path.0, split_path.1, &app_state.session).await?;
+    Ok(file.is_some())
+}
+
+fn item_result(result: NodeClientResponse<()>) -> BatchItemResult {
+    match result {
+        Ok(()) => BatchItemResult {
+            status: StatusCode::OK.as_u16(),
+            error: None,
+        },
+        Err(err) => BatchItemResult {
+            status: err.status_code().as_u16(),
+            error: Some(err.to_string()),
+        },
+    }
+}
+
+/// Validates every operation of an atomic batch.
+/// Operations writing a file which another operation of the batch reads or writes are rejected,
+/// as the outcome of those would depend on the order of execution.
+async fn validate_atomic(
+    operations: &[NodeClientResponse<PreparedOperation>],
+    accessor: &BucketAccessor,
+    app_state: &Data<AppState>,
+) -> Vec<NodeClientResponse<()>> {
+    let mut writes: HashMap<BatchTarget, usize> = HashMap::new();
+    let mut reads: HashSet<BatchTarget> = HashSet::new();
+    for operation in operations.iter().flatten() {
+        for target in operation.writes() {
+            *writes.entry(target).or_default() += 1;
+        }
+        reads.extend(operation.reads());
+    }
+
+    stream::iter(operations)
+        .map(|operation| {
+            let writes = &writes;
+            let reads = &reads;
+            async move {
+                let operation = operation.as_ref().map_err(Clone::clone)?;
+                let conflicting = operation
+                    .writes()
+                    .iter()
+                    .any(|target| writes[target] > 1 || reads.contains(target))
+                    || operation
+                        .reads()
+                        .iter()
+                        .any(|target| writes.contains_key(target));
+                if conflicting {
+                    return Err(NodeClientError::BadRequest);
+                }
+                operation.validate(accessor, app_state).await
+            }
+        })
+        .buffered(BATCH_CONCURRENCY)
+        .collect()
+        .await
+}
+
+/// Executes the operations with a bounded concurrency, returning a result for each of them.
+/// In the atomic mode nothing is executed unless every operation passes validation.
+pub async fn do_batch(
+    app_id: Uuid,
+    bucket_id: Uuid,
+    req: BatchRequest,
+    fs_limit: &FsLimitConfiguration,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<BatchResponse> {
+    if req.operations.len() > MAX_BATCH_OPERATIONS {
+        return Err(NodeClientError::BadRequest);
+    }
+    let operations: Vec<NodeClientResponse<PreparedOperation>> = req
+        .operations
+        .into_iter()
+        .map(|operation| PreparedOperation::prepare(operation, app_id, bucket_id, fs_limit))
+        .collect();
+
+    if req.atomic {
+        let validation = validate_atomic(&operations, &accessor, &app_state).await;
+        if validation.iter().any(Result::is_err) {
+            let results = validation
+                .into_iter()
+                .map(|result| match result {
+                    Ok(()) => BatchItemResult {
+                        status: StatusCode::FAILED_DEPENDENCY.as_u16(),
+                        error: None,
+                    },
+                    Err(err) => item_result(Err(err)),
+                })
+                .collect();
+            return Ok(BatchResponse {
+                executed: false,
+                results,
+            });
+        }
+    }
+
+    let results = stream::iter(operations)
+        .map(|operation| {
+            let accessor = accessor.clone();
+            let app_state = app_state.clone();
+            async move { operation?.execute(accessor, app_state).await }
+        })
+        .buffered(BATCH_CONCURRENCY)
+        .map(item_result)
+        .collect()
+        .await;
+
+    Ok(BatchResponse {
+        executed: true,
+        results,
+    })
+}
@@ -0,0 +1,178 @@
+use crate::public::service::file_action_service::delete_chunks;
+use crate::public::service::job_service::record_job_progress;
+use crate::public::service::object_lock_service::check_object_lock;
+use crate::public::service::snapshot_service::delete_snapshot;
+use crate::AppState;
+use actix_web::web::Data;
+use commons::error::std_response::NodeClientResponse;
+use data::access::app_access::{delete_app, get_app_by_id};
+use data::access::file_access::{
+    delete_bucket, delete_bucket_leases, delete_directory, delete_file, delete_file_version,
+    delete_upload_session, get_bucket, get_bucket_file_versions_after, get_bucket_snapshots,
+    get_directories_from_bucket_after, get_directory_path_cached, get_files_from_bucket_after,
+    get_trashed_files, get_trashed_files_after, get_upload_sessions, maybe_get_first_bucket,
+    purge_trashed_file, read_page,
+};
+use data::error::MeowithDataError;
+use data::model::file_model::{
+    Bucket, BucketJob, BucketSnapshot, BucketUploadSession, Directory, File, FileVersion,
+    TrashedFile,
+};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// The entries read and deleted at a time, keeping the memory used bounded for large buckets.
+const DELETE_PAGE_SIZE: usize = 500;
+
+/// Removes everything stored in the bucket of the job, then the bucket itself,
+/// regardless of its versioning and trash.
+/// Files protected by the object lock are kept, leaving the bucket marked as deleting until a retry.
+/// Once the last bucket of an app being deleted is gone, the app is removed as well.
+pub async fn delete_bucket_contents(
+    job: &mut BucketJob,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let session = &app_state.session;
+    let bucket = match get_bucket(job.app_id, job.bucket_id, session).await {
+        Ok(bucket) => bucket,
+        // Already removed by an earlier attempt
+        Err(MeowithDataError::NotFound) => return delete_drained_app(job.app_id, app_state).await,
+        Err(err) => return Err(err.into()),
+    };
+
+    let uploads: Vec<BucketUploadSession> = get_upload_sessions(bucket.app_id, bucket.id, session)
+        .await?
+        .try_collect()
+        .await
+        .map_err(MeowithDataError::from)?;
+    for upload in uploads {
+        delete_upload_session(&upload, session).await?;
+        delete_chunks(bucket.id, &upload.fragments, app_state).await;
+    }
+
+    // Released first, so that no chunk is kept for a snapshot once the files using it are gone.
+    let snapshots: Vec<BucketSnapshot> = get_bucket_snapshots(bucket.id, session)
+        .await?
+        .try_collect()
+        .await
+        .map_err(MeowithDataError::from)?;
+    for snapshot in snapshots {
+        delete_snapshot(snapshot, app_state).await?;
+    }
+
+    // Each page continues after the last entry of the previous one, the entries which could not
+    // be deleted are not read again.
+    let mut after = None;
+    loop {
+        let stream = get_files_from_bucket_after(bucket.id, after, false, session).await?;
+        let files: Vec<File> = read_page(stream, DELETE_PAGE_SIZE).await?;
+        let Some(last) = files.last() else { break };
+        after = Some((last.directory, last.name.clone()));
+        count_page(job, files.len());
+
+        let mut parents = HashMap::new();
+        for file in files {
+            let parent =
+                get_directory_path_cached(bucket.id, file.directory, &mut parents, session).await?;
+            let path = file.full_path(&parent);
+            let result = delete_bucket_file(&file, &path, &bucket, app_state).await;
+            record_job_progress(job, &path, result, true, app_state).await?;
+        }
+    }
+
+    let mut after = None;
+    loop {
+        let stream = get_bucket_file_versions_after(bucket.id, after, session).await?;
+        let versions: Vec<FileVersion> = read_page(stream, DELETE_PAGE_SIZE).await?;
+        let Some(last) = versions.last() else { break };
+        after = Some((last.path.clone(), last.version_id));
+        count_page(job, versions.len());
+
+        for version in versions {
+            let result = delete_file_version(&version, &bucket, session)
+                .await
+                .map(|_| version.size)
+                .map_err(Into::into);
+            if result.is_ok() {
+                delete_chunks(bucket.id, &version.chunk_ids, app_state).await;
+            }
+            record_job_progress(job, &version.path, result, true, app_state).await?;
+        }
+    }
+
+    let mut after = None;
+    loop {
+        let stream = match after {
+            Some(after) => get_trashed_files_after(bucket.id, after, session).await?,
+            None => get_trashed_files(bucket.id, session).await?,
+        };
+        let trashed: Vec<TrashedFile> = read_page(stream, DELETE_PAGE_SIZE).await?;
+        let Some(last) = trashed.last() else { break };
+        after = Some((last.path.clone(), last.id));
+        count_page(job, trashed.len());
+
+        for entry in trashed {
+            let result = purge_trashed_file(&entry, Some(&bucket), session)
+                .await
+                .map(|_| entry.size)
+                .map_err(Into::into);
+            if result.is_ok() {
+                delete_chunks(bucket.id, &entry.chunk_ids, app_state).await;
+            }
+            record_job_progress(job, &entry.path, result, true, app_state).await?;
+        }
+    }
+
+    if job.failed_files > 0 {
+        return Ok(());
+    }
+    // Removed last, as the paths of the files above are resolved through them.
+    let mut after = None;
+    loop {
+        let stream = get_directories_from_bucket_after(bucket.id, after, false, session).await?;
+        let directories: Vec<Directory> = read_page(stream, DELETE_PAGE_SIZE).await?;
+        let Some(last) = directories.last() else {
+            break;
+        };
+        after = Some((last.parent.clone(), last.name.clone()));
+        for directory in &directories {
+            delete_directory(directory, session).await?;
+        }
+    }
+    delete_bucket_leases(bucket.id, session).await?;
+    delete_bucket(&bucket, session).await?;
+
+    delete_drained_app(bucket.app_id, app_state).await
+}
+
+/// The total of the job only covers the entries read so far, as the size of the bucket is not known ahead.
+fn count_page(job: &mut BucketJob, len: usize) {
+    job.total_files = job.total_files.max(job.processed_files + len as i64);
+}
+
+/// Deletes the file without archiving or trashing it, returning its size.
+async fn delete_bucket_file(
+    file: &File,
+    path: &str,
+    bucket: &Bucket,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<i64> {
+    check_object_lock(file, path)?;
+    delete_file(file, bucket, &app_state.session).await?;
+    delete_chunks(bucket.id, &file.chunk_ids, app_state).await;
+    Ok(file.size)
+}
+
+/// Removes the app, its tokens, roles and members, once its forced deletion has no buckets left.
+async fn delete_drained_app(app_id: Uuid, app_state: &Data<AppState>) -> NodeClientResponse<()> {
+    let session = &app_state.session;
+    let app = match get_app_by_id(app_id, session).await {
+        Ok(app) => app,
+        Err(MeowithDataError::NotFound) => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+    if app.is_deleting() && maybe_get_first_bucket(app_id, session).await?.is_none() {
+        delete_app(&app, session).await?;
+    }
+    Ok(())
+}
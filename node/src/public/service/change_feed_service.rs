@@ -0,0 +1,178 @@
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::notification_service::BucketEvent;
+use crate::public::service::LIST_BUCKET_ALLOWANCE;
+use crate::AppState;
+use actix_web::web::Data;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, SubsecRound, TimeDelta, Utc};
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use data::access::file_access::{get_bucket_changes_after, insert_bucket_change};
+use data::dto::entity::{BucketChangeDto, BucketChangeList};
+use data::error::MeowithDataError;
+use data::model::file_model::{BucketChange, CHANGE_PERIOD_SECS, CHANGE_RETENTION_SECS};
+use futures_util::StreamExt;
+use logging::log_err;
+use scylla::client::caching_session::CachingSession;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const MAX_CHANGE_LIMIT: usize = 1000;
+/// Changes are only handed out once they are this old, as the nodes recording them
+/// may be slightly behind each other, and a change must not appear behind a returned cursor.
+const CHANGE_SETTLE_MILLIS: i64 = 5000;
+/// The amount of hourly partitions read by a single poll, so that a poll starting at the oldest
+/// retained change does not go through the whole retention of a quiet bucket at once.
+const MAX_POLLED_PERIODS: i64 = 24;
+
+#[derive(Deserialize)]
+pub struct ChangeQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// The position in the change feed, handed out to the client as an opaque cursor.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, PartialOrd)]
+struct ChangeCursor {
+    occurred: DateTime<Utc>,
+    id: Uuid,
+}
+
+impl ChangeCursor {
+    fn encode(&self) -> NodeClientResponse<String> {
+        let bytes = serde_cbor::to_vec(self).map_err(|_| NodeClientError::InternalError)?;
+        Ok(URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    fn decode(cursor: &str) -> NodeClientResponse<Self> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(cursor)
+            .map_err(|_| NodeClientError::BadRequest)?;
+        serde_cbor::from_slice(&bytes).map_err(|_| NodeClientError::BadRequest)
+    }
+}
+
+pub async fn record_change(
+    bucket_id: Uuid,
+    event: &BucketEvent,
+    occurred: DateTime<Utc>,
+    session: &CachingSession,
+) -> Result<(), MeowithDataError> {
+    let change = BucketChange {
+        bucket_id,
+        period: BucketChange::period_of(occurred),
+        occurred,
+        id: Uuid::new_v4(),
+        kind: event.kind.into(),
+        path: event.path.clone(),
+        old_path: event.old_path.clone(),
+        directory: event.directory,
+        size: event.size,
+        etag: event.etag.clone(),
+    };
+    insert_bucket_change(&change, session).await
+}
+
+/// Records a change which is not announced to the webhooks of the bucket.
+/// The change has already been made, so failures are only logged.
+pub async fn log_change(bucket_id: Uuid, event: BucketEvent, app_state: &Data<AppState>) {
+    log_err(
+        "Failed to record a change",
+        record_change(bucket_id, &event, Utc::now(), &app_state.session).await,
+    );
+}
+
+/// Returns the changes following the cursor, or all the retained ones without a cursor.
+/// A cursor older than the retention fails with [NodeClientError::CursorExpired],
+/// as the changes following it may already be gone.
+pub async fn do_list_changes(
+    app_id: Uuid,
+    bucket_id: Uuid,
+    query: ChangeQuery,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<BucketChangeList> {
+    accessor.has_permission(&app_id, &bucket_id, *LIST_BUCKET_ALLOWANCE)?;
+    let limit = query.limit.unwrap_or(MAX_CHANGE_LIMIT);
+    if limit == 0 || limit > MAX_CHANGE_LIMIT {
+        return Err(NodeClientError::BadRequest);
+    }
+
+    let now = Utc::now();
+    let retained_since = now - TimeDelta::seconds(CHANGE_RETENTION_SECS);
+    let mut position = match &query.cursor {
+        Some(cursor) => {
+            let cursor = ChangeCursor::decode(cursor)?;
+            if cursor.occurred < retained_since {
+                return Err(NodeClientError::CursorExpired);
+            }
+            cursor
+        }
+        None => ChangeCursor {
+            occurred: retained_since,
+            id: Uuid::nil(),
+        },
+    };
+    // changes are stored with millisecond precision
+    let horizon = (now - TimeDelta::milliseconds(CHANGE_SETTLE_MILLIS)).trunc_subsecs(3);
+
+    let first_period = BucketChange::period_of(position.occurred);
+    let horizon_period = BucketChange::period_of(horizon);
+    let last_period = horizon_period.min(first_period + MAX_POLLED_PERIODS - 1);
+
+    let mut changes = vec![];
+    let mut has_more = false;
+    'periods: for period in first_period..=last_period {
+        let mut stream = get_bucket_changes_after(
+            bucket_id,
+            period,
+            (position.occurred, position.id),
+            &app_state.session,
+        )
+        .await?;
+        while let Some(change) = stream.next().await {
+            let change = change.map_err(MeowithDataError::from)?;
+            if change.occurred > horizon {
+                break 'periods;
+            }
+            if changes.len() == limit {
+                has_more = true;
+                break 'periods;
+            }
+            position = ChangeCursor {
+                occurred: change.occurred,
+                id: change.id,
+            };
+            changes.push(change);
+        }
+    }
+
+    if !has_more {
+        let seen = if last_period < horizon_period {
+            // the remaining periods are left to the following polls
+            has_more = true;
+            let next_period = DateTime::from_timestamp((last_period + 1) * CHANGE_PERIOD_SECS, 0)
+                .ok_or(NodeClientError::InternalError)?;
+            next_period - TimeDelta::milliseconds(1)
+        } else {
+            // everything up to the horizon has been seen, so that later polls start from there
+            horizon
+        };
+        let seen = ChangeCursor {
+            occurred: seen,
+            id: Uuid::max(),
+        };
+        if seen > position {
+            position = seen;
+        }
+    }
+
+    Ok(BucketChangeList {
+        changes: changes
+            .into_iter()
+            .filter_map(|change| BucketChangeDto::try_from(change).ok())
+            .collect(),
+        cursor: position.encode()?,
+        has_more,
+    })
+}
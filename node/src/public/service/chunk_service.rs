@@ -1,5 +1,6 @@
 use crate::AppState;
 use actix_web::web::Data;
+use commons::error::io_error::MeowithIoError;
 use commons::error::mdsftp_error::MDSFTPError;
 use commons::error::std_response::{NodeClientError, NodeClientResponse};
 use log::trace;
@@ -44,6 +45,69 @@ pub async fn commit_chunk(
     }
 }
 
+/// Appends the uncommitted chunk to the end of the target chunk, both residing on the given node.
+/// See [crate::io::fragment_ledger::FragmentLedger::merge_chunk].
+pub async fn merge_chunk(
+    node_id: Uuid,
+    chunk_id: Uuid,
+    target_id: Uuid,
+    target_size: u64,
+    state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    if node_id == state.req_ctx.id {
+        trace!("Trying to merge local chunk {chunk_id} into {target_id}");
+        state
+            .fragment_ledger
+            .merge_chunk(&chunk_id, &target_id, target_size)
+            .await?;
+    } else {
+        trace!("Trying to merge remote chunk {chunk_id} into {target_id}");
+        let pool = state.mdsftp_server.pool();
+        let channel = pool.channel(&node_id).await?;
+        channel
+            .merge(chunk_id, target_id, target_size)
+            .await
+            .map_err(NodeClientError::from)?;
+    }
+    Ok(())
+}
+
+/// Copies the chunk into a new, uncommitted chunk of the given file on the node holding it,
+/// so that the contents never leave that node. Returns the id of the copy.
+/// See [crate::io::fragment_ledger::FragmentLedger::duplicate_chunk].
+pub async fn duplicate_chunk(
+    node_id: Uuid,
+    chunk_id: Uuid,
+    size: u64,
+    bucket_id: Uuid,
+    file_id: Uuid,
+    state: &Data<AppState>,
+) -> NodeClientResponse<Uuid> {
+    if node_id == state.req_ctx.id {
+        trace!("Trying to duplicate local chunk {chunk_id}");
+        match state
+            .fragment_ledger
+            .duplicate_chunk(&chunk_id, size, bucket_id, file_id)
+            .await
+        {
+            Ok(id) => Ok(id),
+            Err(MeowithIoError::InsufficientDiskSpace) => {
+                Err(MDSFTPError::ReserveError(state.fragment_ledger.get_available_space()).into())
+            }
+            Err(err) => Err(err.into()),
+        }
+    } else {
+        trace!("Trying to duplicate remote chunk {chunk_id}");
+        let pool = state.mdsftp_server.pool();
+        let channel = pool.channel(&node_id).await?;
+        let res = channel
+            .duplicate(chunk_id, size, file_id, bucket_id)
+            .await
+            .map_err(NodeClientError::from)?;
+        Ok(res.chunk_id)
+    }
+}
+
 pub struct ChunkInfo {
     pub chunk_buffer: u16,
     pub size: u64,
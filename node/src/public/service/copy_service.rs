@@ -0,0 +1,238 @@
+use crate::public::extractors::copy_request::CopyEntityRequest;
+use crate::public::extractors::entry_path::EntryPath;
+use crate::public::extractors::preconditions::Preconditions;
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::change_feed_service::log_change;
+use crate::public::service::file_access_service::{handle_upload_oneshot, try_mkdir, UploadSource};
+use crate::public::service::file_action_service::delete_chunks;
+use crate::public::service::notification_service::{emit_event, BucketEvent};
+use crate::public::service::{CREATE_DIRECTORY_ALLOWANCE, DOWNLOAD_ALLOWANCE, UPLOAD_ALLOWANCE};
+use crate::AppState;
+use actix_web::web::Data;
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use data::access::file_access::{
+    delete_directory, delete_file, get_bucket, get_directory, get_file_dir,
+    get_files_from_bucket_and_directory, DirectoryIterator,
+};
+use data::error::MeowithDataError;
+use data::model::file_model::{Bucket, BucketEventKind, File, FileChunk};
+use data::pathlib::{join_parent_name, normalize, split_path};
+use futures::pin_mut;
+use futures_util::StreamExt;
+use log::{debug, warn};
+use logging::log_err;
+
+/// `If-Match` applies to the copied file, `If-None-Match` to the one at the destination.
+pub async fn do_copy_file(
+    path: EntryPath,
+    req: CopyEntityRequest,
+    preconditions: Preconditions,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<()> {
+    let target_bucket = req.bucket_id(path.bucket_id);
+    accessor.has_permission(&path.app_id, &path.bucket_id, *DOWNLOAD_ALLOWANCE)?;
+    accessor.has_permission(&path.app_id, &target_bucket, *UPLOAD_ALLOWANCE)?;
+    if target_bucket == path.bucket_id && path.path() == req.path() {
+        // The file would replace itself, no work needs to be done
+        return Ok(());
+    }
+
+    let split_path = split_path(&path.path());
+    let (file, _) = get_file_dir(
+        path.bucket_id,
+        split_path.0,
+        split_path.1,
+        &app_state.session,
+    )
+    .await?;
+    preconditions.check_match(Some(&file.etag()))?;
+
+    copy_file(
+        &file,
+        EntryPath::from_prepared(path.app_id, target_bucket, req.path()),
+        preconditions.into_destination(),
+        accessor,
+        &app_state,
+    )
+    .await
+}
+
+/// Copies the directory along with all of its subdirectories and files.
+/// The destination must not exist yet.
+pub async fn do_copy_directory(
+    path: EntryPath,
+    req: CopyEntityRequest,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<()> {
+    let target_bucket = req.bucket_id(path.bucket_id);
+    accessor.has_permission(&path.app_id, &path.bucket_id, *DOWNLOAD_ALLOWANCE)?;
+    accessor.has_permission(&path.app_id, &target_bucket, *CREATE_DIRECTORY_ALLOWANCE)?;
+    let source_path = path.path();
+    let target_path = req.path();
+
+    if source_path.is_empty() || target_path.is_empty() {
+        // no touching the root "dir"
+        return Err(NodeClientError::BadRequest);
+    }
+    if target_bucket == path.bucket_id
+        && (target_path == source_path || target_path.starts_with(&format!("{source_path}/")))
+    {
+        // a directory cannot be copied into itself
+        return Err(NodeClientError::BadRequest);
+    }
+
+    let directory = get_directory(path.bucket_id, Some(source_path), &app_state.session)
+        .await?
+        .unwrap(); // will not be None as it will not be the root dir.
+
+    if get_directory(target_bucket, Some(target_path.clone()), &app_state.session)
+        .await
+        .is_ok()
+    {
+        return Err(NodeClientError::EntityExists);
+    }
+
+    let mut directories = vec![directory.clone()];
+    let child_stream = DirectoryIterator::from_parent(directory.clone(), &app_state.session);
+    pin_mut!(child_stream);
+    while let Some(res) = child_stream.next().await {
+        directories.push(res?);
+    }
+
+    // Collect everything first, so that the quota can be checked before anything is written.
+    let source_root = directory.full_path();
+    let mut target_directories = vec![];
+    let mut files: Vec<(File, String)> = vec![];
+    for dir in &directories {
+        let target_dir = normalize(&format!(
+            "{target_path}/{}",
+            &dir.full_path()[source_root.len()..]
+        ));
+        let mut stream =
+            get_files_from_bucket_and_directory(path.bucket_id, Some(dir.id), &app_state.session)
+                .await?;
+        while let Some(file) = stream.next().await {
+            let file = file.map_err(MeowithDataError::from)?;
+            let target = join_parent_name(&target_dir, &file.name);
+            files.push((file, target));
+        }
+        target_directories.push(target_dir);
+    }
+
+    let bucket = get_bucket(path.app_id, target_bucket, &app_state.session).await?;
+    if bucket.is_deleting() {
+        return Err(NodeClientError::NotFound);
+    }
+    let size: i64 = files.iter().map(|(file, _)| file.size).sum();
+    let reserved = app_state
+        .upload_manager
+        .get_reserved_space(path.app_id, target_bucket)
+        .await?;
+    if bucket.space_taken + size + reserved > bucket.quota {
+        return Err(NodeClientError::InsufficientStorage {
+            message: format!(
+                "Insufficient space in bucket. quota={}, size={}, taken={}, reserved={}",
+                bucket.quota, size, bucket.space_taken, reserved
+            ),
+        });
+    }
+
+    let mut copied = vec![];
+    let result: NodeClientResponse<()> = async {
+        for target_dir in &target_directories {
+            try_mkdir(target_bucket, target_dir.clone(), &app_state.session).await?;
+        }
+        for (file, target) in files {
+            debug!("Copying {} to {target_bucket} {target}", file.id);
+            copy_file(
+                &file,
+                EntryPath::from_prepared(path.app_id, target_bucket, target.clone()),
+                Preconditions::default(),
+                accessor.clone(),
+                &app_state,
+            )
+            .await?;
+            copied.push(target);
+        }
+        Ok(())
+    }
+    .await;
+
+    if result.is_err() {
+        remove_partial_copy(&bucket, &copied, &target_directories, &app_state).await;
+    }
+    result
+}
+
+/// Removes what a failed directory copy created, the copied files along with the copied tree,
+/// so that the copy either completes or leaves the destination as it was.
+/// The tree did not exist before, nothing else is removed along with it.
+async fn remove_partial_copy(
+    bucket: &Bucket,
+    files: &[String],
+    directories: &[String],
+    app_state: &Data<AppState>,
+) {
+    debug!("Removing the partial copy into {}", bucket.id);
+    for path in files {
+        let (directory, name) = split_path(path);
+        let Ok((file, _)) = get_file_dir(bucket.id, directory, name, &app_state.session).await
+        else {
+            continue;
+        };
+        if let Err(err) = delete_file(&file, bucket, &app_state.session).await {
+            warn!("Failed to remove the copy {path} {err:?}");
+            continue;
+        }
+        delete_chunks(bucket.id, &file.chunk_ids, app_state).await;
+        emit_event(
+            bucket,
+            BucketEvent::file(BucketEventKind::Deleted, path.clone()),
+            app_state,
+        )
+        .await;
+    }
+    // Children first, the directories were collected with the parents preceding them.
+    for path in directories.iter().rev() {
+        if let Ok(Some(directory)) =
+            get_directory(bucket.id, Some(path.clone()), &app_state.session).await
+        {
+            log_err(
+                "Failed to remove a copied directory",
+                delete_directory(&directory, &app_state.session).await,
+            );
+            log_change(
+                bucket.id,
+                BucketEvent::directory(BucketEventKind::Deleted, path.clone()),
+                app_state,
+            )
+            .await;
+        }
+    }
+}
+
+/// Copies the file into a regular upload at the target path.
+/// The upload applies the quota and overwrite checks of the destination bucket,
+/// while the chunks are duplicated by the nodes holding them, their contents never leave those nodes.
+pub(crate) async fn copy_file(
+    file: &File,
+    target: EntryPath,
+    preconditions: Preconditions,
+    accessor: BucketAccessor,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let mut chunks: Vec<FileChunk> = file.chunk_ids.iter().cloned().collect();
+    chunks.sort_by_key(|chunk| chunk.chunk_order);
+    handle_upload_oneshot(
+        target,
+        file.size as u64,
+        app_state.clone(),
+        accessor,
+        UploadSource::Chunks(chunks),
+        file.metadata.clone(),
+        preconditions,
+    )
+    .await
+}
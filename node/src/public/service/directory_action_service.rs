@@ -1,7 +1,13 @@
 use crate::public::extractors::entry_path::EntryPath;
 use crate::public::middleware::user_middleware::BucketAccessor;
 use crate::public::routes::entity_action::RenameEntityRequest;
+use crate::public::service::change_feed_service::log_change;
 use crate::public::service::file_access_service::try_mkdir;
+use crate::public::service::file_action_service::do_delete_file;
+use crate::public::service::job_service::{record_job_progress, start_job};
+use crate::public::service::lease_service::check_directory_leases;
+use crate::public::service::notification_service::{emit_event, BucketEvent};
+use crate::public::service::object_lock_service::check_object_lock;
 use crate::public::service::{
     CREATE_DIRECTORY_ALLOWANCE, DELETE_ALLOWANCE, RENAME_DIRECTORY_ALLOWANCE,
 };
@@ -9,15 +15,20 @@ use crate::AppState;
 use actix_web::web::Data;
 use commons::error::std_response::{NodeClientError, NodeClientResponse};
 use data::access::file_access::{
-    delete_directory, get_directory, maybe_get_first_child_from_directory,
-    maybe_get_first_file_from_directory, update_directory_path, DirectoryIterator,
+    delete_directory, get_bucket, get_directory, get_files_from_bucket_and_directory,
+    maybe_get_first_child_from_directory, maybe_get_first_file_from_directory,
+    update_directory_path, DirectoryIterator,
 };
 use data::dto::entity::DeleteDirectoryRequest;
-use data::model::file_model::Directory;
+use data::error::MeowithDataError;
+use data::model::file_model::{BucketEventKind, BucketJob, Directory, File, JobKind};
 use data::pathlib::{join_parent_name, normalize, split_path};
 use futures::pin_mut;
 use futures_util::StreamExt;
 
+/// Recursive deletes of up to this many files are carried out within the request.
+const INLINE_DELETE_LIMIT: i64 = 100;
+
 pub async fn do_create_directory(
     path: EntryPath,
     bucket_accessor: BucketAccessor,
@@ -28,42 +39,163 @@ pub async fn do_create_directory(
     Ok(())
 }
 
+/// Deletes the directory. A recursive delete removes all the files within it as well,
+/// small trees are removed right away while larger ones are handed off to a background job.
 pub async fn do_delete_directory(
     e_path: EntryPath,
     req: DeleteDirectoryRequest,
     bucket_accessor: BucketAccessor,
     app_state: Data<AppState>,
-) -> NodeClientResponse<()> {
+) -> NodeClientResponse<Option<BucketJob>> {
     bucket_accessor.has_permission(&e_path.app_id, &e_path.bucket_id, *DELETE_ALLOWANCE)?;
     let path = e_path.path();
 
     if path.is_empty() {
         return Err(NodeClientError::BadRequest);
     }
+    check_directory_leases(e_path.bucket_id, &path, &bucket_accessor, &app_state).await?;
 
     let directory = get_directory(e_path.bucket_id, Some(path.clone()), &app_state.session)
         .await?
         .unwrap(); // will not be None as it will not be the root dir.
 
     if req.recursive {
-        let child_stream = DirectoryIterator::from_parent(directory.clone(), &app_state.session);
-        pin_mut!(child_stream);
-        let mut children = Vec::new();
+        let mut job = BucketJob::new(
+            JobKind::DeleteDirectory,
+            e_path.app_id,
+            e_path.bucket_id,
+            path,
+            app_state.req_ctx.id,
+        );
+        let directories = collect_tree(directory, &app_state).await?;
+        // Counting stops past the inline limit, the job finds out the rest as it goes.
+        job.total_files = count_files(&directories, Some(INLINE_DELETE_LIMIT), &app_state).await?;
 
-        while let Some(res) = child_stream.next().await {
-            children.push(res?);
+        if job.total_files > INLINE_DELETE_LIMIT {
+            return Ok(Some(start_job(job, app_state).await?));
         }
+        delete_directory_tree(&mut job, false, &app_state).await?;
+        return Ok(None);
+    }
 
-        for dir in children {
-            dir_empty(&directory, &app_state, false).await?;
+    dir_empty(&directory, &app_state, true).await?;
+    delete_directory(&directory, &app_state.session).await?;
+    log_change(
+        e_path.bucket_id,
+        BucketEvent::directory(BucketEventKind::Deleted, path),
+        &app_state,
+    )
+    .await;
+
+    Ok(None)
+}
+
+/// The directory followed by all of its descendants, parents always preceding their children.
+async fn collect_tree(
+    directory: Directory,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<Vec<Directory>> {
+    let mut directories = vec![directory.clone()];
+    let child_stream = DirectoryIterator::from_parent(directory, &app_state.session);
+    pin_mut!(child_stream);
+    while let Some(res) = child_stream.next().await {
+        directories.push(res?);
+    }
+    Ok(directories)
+}
 
-            delete_directory(&dir, &app_state.session).await?;
+/// Counts the files within the directories, or just past the `limit` when there are more,
+/// failing if any of the counted files is protected by the object lock so that the tree is left untouched.
+async fn count_files(
+    directories: &[Directory],
+    limit: Option<i64>,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<i64> {
+    let mut count = 0;
+    for directory in directories {
+        let parent = directory.full_path();
+        let mut stream = get_files_from_bucket_and_directory(
+            directory.bucket_id,
+            Some(directory.id),
+            &app_state.session,
+        )
+        .await?;
+        while let Some(file) = stream.next().await {
+            let file = file.map_err(MeowithDataError::from)?;
+            check_object_lock(&file, &file.full_path(&parent))?;
+            count += 1;
+            if limit.is_some_and(|limit| count > limit) {
+                return Ok(count);
+            }
         }
-    } else {
-        dir_empty(&directory, &app_state, true).await?;
     }
+    Ok(count)
+}
 
-    delete_directory(&directory, &app_state.session).await?;
+/// Deletes every file beneath the directory of the job, honoring the bucket versioning and trash.
+/// The directories themselves are removed once all the files are gone.
+/// Being idempotent, a job can be re-run to continue where a previous attempt stopped.
+/// When not persisted, the delete is a part of a request and stops at the first failure, returning it.
+pub async fn delete_directory_tree(
+    job: &mut BucketJob,
+    persist: bool,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let bucket = get_bucket(job.app_id, job.bucket_id, &app_state.session).await?;
+    let directory =
+        match get_directory(job.bucket_id, Some(job.path.clone()), &app_state.session).await {
+            Ok(directory) => directory.unwrap(),
+            // Already removed by an earlier attempt
+            Err(MeowithDataError::NotFound) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+    let directories = collect_tree(directory, app_state).await?;
+
+    for directory in &directories {
+        // The files of the directory are removed below, list them before the first one goes.
+        let files: Vec<File> = get_files_from_bucket_and_directory(
+            directory.bucket_id,
+            Some(directory.id),
+            &app_state.session,
+        )
+        .await?
+        .try_collect()
+        .await
+        .map_err(MeowithDataError::from)?;
+
+        let parent = directory.full_path();
+        for file in files {
+            let path = file.full_path(&parent);
+            let result = do_delete_file(&file, &path, &bucket, app_state)
+                .await
+                .map(|_| file.size);
+            match &result {
+                Ok(_) => {
+                    let event = BucketEvent {
+                        size: Some(file.size),
+                        etag: Some(file.etag()),
+                        ..BucketEvent::file(BucketEventKind::Deleted, path.clone())
+                    };
+                    log_change(bucket.id, event, app_state).await;
+                }
+                Err(err) if !persist => return Err(err.clone()),
+                Err(_) => {}
+            }
+            record_job_progress(job, &path, result, persist, app_state).await?;
+        }
+    }
+
+    if job.failed_files == 0 {
+        for directory in directories.iter().rev() {
+            delete_directory(directory, &app_state.session).await?;
+            log_change(
+                bucket.id,
+                BucketEvent::directory(BucketEventKind::Deleted, directory.full_path()),
+                app_state,
+            )
+            .await;
+        }
+    }
 
     Ok(())
 }
@@ -121,6 +253,10 @@ pub async fn do_rename_directory(
         // no touching the root "dir"
         return Err(NodeClientError::BadRequest);
     }
+    check_directory_leases(e_path.bucket_id, &path, &bucket_accessor, &app_state).await?;
+    // the files moved in would replace anything leased beneath the destination
+    check_directory_leases(e_path.bucket_id, &req.path(), &bucket_accessor, &app_state).await?;
+    let bucket = get_bucket(e_path.app_id, e_path.bucket_id, &app_state.session).await?;
 
     let original_directory = get_directory(e_path.bucket_id, Some(path), &app_state.session)
         .await?
@@ -137,6 +273,11 @@ pub async fn do_rename_directory(
         return Err(NodeClientError::EntityExists); // a directory with this name already exists
     }
 
+    let mut to_rename = collect_tree(original_directory.clone(), &app_state).await?;
+    // renaming the directory changes the paths of the files within it
+    count_files(&to_rename, None, &app_state).await?;
+    to_rename.remove(0);
+
     let (new_parent, new_name) = split_path(req.path().as_str());
     let new_parent = try_mkdir(
         e_path.bucket_id,
@@ -145,15 +286,6 @@ pub async fn do_rename_directory(
     )
     .await?;
 
-    let child_stream =
-        DirectoryIterator::from_parent(original_directory.clone(), &app_state.session);
-    pin_mut!(child_stream);
-
-    let mut to_rename = Vec::new();
-    while let Some(res) = child_stream.next().await {
-        to_rename.push(res?);
-    }
-
     let old_parent_path = original_directory.full_path();
     let new_parent_path = join_parent_name(
         &new_parent
@@ -185,5 +317,15 @@ pub async fn do_rename_directory(
     )
     .await?;
 
+    emit_event(
+        &bucket,
+        BucketEvent {
+            old_path: Some(e_path.path()),
+            ..BucketEvent::directory(BucketEventKind::Renamed, req.path())
+        },
+        &app_state,
+    )
+    .await;
+
     Ok(())
 }
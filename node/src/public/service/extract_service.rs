@@ -0,0 +1,453 @@
+use crate::archive::tar::{padding, parse_header, parse_pax_records, TarEntryType, BLOCK_SIZE};
+use crate::archive::zip::{
+    parse_central_directory, parse_end_record, parse_local_header, parse_zip64_end_record,
+    EndRecord, LOCAL_HEADER_LENGTH, MAX_END_DISTANCE, METHOD_DEFLATED, METHOD_STORED,
+    ZIP64_END_LENGTH,
+};
+use crate::archive::ArchiveFormat;
+use crate::public::extractors::entry_path::EntryPath;
+use crate::public::extractors::preconditions::Preconditions;
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::file_access_service::{handle_upload_oneshot, try_mkdir, UploadSource};
+use crate::public::service::{CREATE_DIRECTORY_ALLOWANCE, UPLOAD_ALLOWANCE};
+use crate::AppState;
+use actix_web::web::Data;
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use data::access::file_access::get_bucket;
+use data::dto::config::FsLimitConfiguration;
+use data::dto::entity::ExtractionResult;
+use data::pathlib::{join_parent_name, normalize, prepare_path};
+use flate2::write::{DeflateDecoder, GzDecoder};
+use log::debug;
+use protocol::mdsftp::handler::AbstractReadStream;
+use std::io::{SeekFrom, Write};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, DuplexStream};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+const EXTRACT_TRANSFER_BUFFER: usize = 64 * 1024;
+/// The largest pax header or GNU long name read into memory.
+const MAX_TAR_METADATA_SIZE: u64 = 1024 * 1024;
+
+/// Expands the archive read from the reader beneath the directory of the path.
+/// Every file entry goes through a regular oneshot upload, parent directories are created as needed.
+///
+/// Tar archives are extracted while they are being received, an invalid entry fails the request
+/// with the preceding entries already in place. Zip archives are buffered in a temporary file,
+/// as their central directory comes last, and are validated as a whole before anything is written.
+/// Their `archive_size` is required, so that oversized archives are rejected before being buffered.
+pub async fn handle_extract(
+    path: EntryPath,
+    format: ArchiveFormat,
+    archive_size: Option<u64>,
+    fs_limits: &FsLimitConfiguration,
+    accessor: BucketAccessor,
+    reader: DuplexStream,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<ExtractionResult> {
+    accessor.has_permission(&path.app_id, &path.bucket_id, *UPLOAD_ALLOWANCE)?;
+    accessor.has_permission(&path.app_id, &path.bucket_id, *CREATE_DIRECTORY_ALLOWANCE)?;
+
+    let mut extraction = Extraction {
+        app_id: path.app_id,
+        bucket_id: path.bucket_id,
+        target: path.path(),
+        fs_limits,
+        accessor,
+        app_state,
+        result: ExtractionResult::default(),
+    };
+    try_mkdir(
+        extraction.bucket_id,
+        extraction.target.clone(),
+        &extraction.app_state.session,
+    )
+    .await?;
+
+    match format {
+        ArchiveFormat::Tar => extract_tar(reader, &mut extraction).await?,
+        ArchiveFormat::TarGz => extract_tar(gunzip(reader), &mut extraction).await?,
+        ArchiveFormat::Zip => {
+            let archive_size = archive_size.ok_or(NodeClientError::BadRequest)?;
+            extract_zip(reader, archive_size, &mut extraction).await?
+        }
+    }
+    Ok(extraction.result)
+}
+
+struct Extraction<'a> {
+    app_id: Uuid,
+    bucket_id: Uuid,
+    target: String,
+    fs_limits: &'a FsLimitConfiguration,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+    result: ExtractionResult,
+}
+
+impl Extraction<'_> {
+    /// Resolves the entry name beneath the target directory, `None` stands for the target itself.
+    /// Names which are absolute or climb out of the target directory are rejected.
+    fn entry_path(&self, name: &[u8]) -> NodeClientResponse<Option<String>> {
+        let name = std::str::from_utf8(name).map_err(|_| NodeClientError::BadResourcePath)?;
+        if name.starts_with(['/', '\\']) {
+            return Err(NodeClientError::BadResourcePath);
+        }
+        let normalized = normalize(name);
+        let mut components = vec![];
+        for component in normalized.split('/') {
+            match component {
+                "" | "." => {}
+                ".." => return Err(NodeClientError::BadResourcePath),
+                component => components.push(component),
+            }
+        }
+        if components.is_empty() {
+            return Ok(None);
+        }
+
+        let path = join_parent_name(&self.target, &components.join("/"));
+        prepare_path(&path, self.fs_limits)
+            .map(Some)
+            .ok_or(NodeClientError::BadResourcePath)
+    }
+
+    async fn directory(&mut self, path: String) -> NodeClientResponse<()> {
+        try_mkdir(self.bucket_id, path, &self.app_state.session).await?;
+        self.result.directories += 1;
+        Ok(())
+    }
+
+    async fn file<R: AsyncRead + Unpin>(
+        &mut self,
+        path: String,
+        size: u64,
+        source: R,
+        verification: Option<Verification>,
+    ) -> NodeClientResponse<()> {
+        debug!("Extracting {path} into {}", self.bucket_id);
+        let (sender, receiver) = tokio::io::duplex(EXTRACT_TRANSFER_BUFFER);
+        let reader: AbstractReadStream = Arc::new(Mutex::new(Box::pin(receiver)));
+
+        let upload = handle_upload_oneshot(
+            EntryPath::from_prepared(self.app_id, self.bucket_id, path),
+            size,
+            self.app_state.clone(),
+            self.accessor.clone(),
+            UploadSource::Stream(reader),
+            None,
+            Preconditions::default(),
+        );
+        let feed = feed_entry(source, size, verification, sender);
+        let (upload_res, feed_res) = tokio::join!(upload, feed);
+        // an upload rejected early breaks the feed, so its error is the telling one
+        upload_res?;
+        feed_res?;
+
+        self.result.files += 1;
+        Ok(())
+    }
+}
+
+async fn extract_tar<R: AsyncRead + Unpin>(
+    mut reader: R,
+    extraction: &mut Extraction<'_>,
+) -> NodeClientResponse<()> {
+    let mut long_name: Option<Vec<u8>> = None;
+    let mut pax_records = vec![];
+    let mut block = [0u8; BLOCK_SIZE];
+
+    loop {
+        reader.read_exact(&mut block).await.map_err(malformed)?;
+        let Some(header) = parse_header(&block).map_err(malformed)? else {
+            // the end of the archive is usually followed by padding up to the record size
+            tokio::io::copy(&mut reader, &mut tokio::io::sink()).await?;
+            break;
+        };
+
+        if matches!(
+            header.entry_type,
+            TarEntryType::PaxHeader | TarEntryType::GlobalPaxHeader | TarEntryType::LongName
+        ) {
+            if header.size > MAX_TAR_METADATA_SIZE {
+                return Err(NodeClientError::BadRequest);
+            }
+            let mut data = vec![0u8; header.size as usize];
+            reader.read_exact(&mut data).await.map_err(malformed)?;
+            skip(&mut reader, padding(header.size)).await?;
+            match header.entry_type {
+                TarEntryType::PaxHeader => {
+                    pax_records = parse_pax_records(&data).map_err(malformed)?
+                }
+                TarEntryType::LongName => {
+                    let end = data.iter().position(|byte| *byte == 0);
+                    data.truncate(end.unwrap_or(data.len()));
+                    long_name = Some(data);
+                }
+                // global headers carry nothing extraction cares about
+                _ => {}
+            }
+            continue;
+        }
+
+        let mut name = long_name.take().unwrap_or(header.name);
+        let mut size = header.size;
+        for (key, value) in pax_records.drain(..) {
+            match key.as_str() {
+                "path" => name = value,
+                "size" => {
+                    size = std::str::from_utf8(&value)
+                        .ok()
+                        .and_then(|size| size.parse().ok())
+                        .ok_or(NodeClientError::BadRequest)?
+                }
+                _ => {}
+            }
+        }
+
+        match header.entry_type {
+            TarEntryType::File => match extraction.entry_path(&name)? {
+                Some(path) => {
+                    extraction
+                        .file(path, size, (&mut reader).take(size), None)
+                        .await?;
+                    skip(&mut reader, padding(size)).await?;
+                }
+                // a file cannot replace the target directory
+                None => return Err(NodeClientError::BadResourcePath),
+            },
+            TarEntryType::Directory => {
+                if let Some(path) = extraction.entry_path(&name)? {
+                    extraction.directory(path).await?;
+                }
+                skip(&mut reader, size + padding(size)).await?;
+            }
+            _ => {
+                extraction.result.skipped += 1;
+                skip(&mut reader, size + padding(size)).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fails if the bucket has no room left for `size` more bytes.
+async fn check_quota(size: u64, extraction: &Extraction<'_>) -> NodeClientResponse<()> {
+    let bucket = get_bucket(
+        extraction.app_id,
+        extraction.bucket_id,
+        &extraction.app_state.session,
+    )
+    .await?;
+    let reserved = extraction
+        .app_state
+        .upload_manager
+        .get_reserved_space(extraction.app_id, extraction.bucket_id)
+        .await?;
+    if bucket.space_taken + size as i64 + reserved > bucket.quota {
+        return Err(NodeClientError::InsufficientStorage {
+            message: format!(
+                "Insufficient space in bucket. quota={}, size={}, taken={}, reserved={}",
+                bucket.quota, size, bucket.space_taken, reserved
+            ),
+        });
+    }
+    Ok(())
+}
+
+async fn extract_zip(
+    reader: DuplexStream,
+    archive_size: u64,
+    extraction: &mut Extraction<'_>,
+) -> NodeClientResponse<()> {
+    if archive_size > extraction.fs_limits.max_zip_archive_size {
+        return Err(NodeClientError::BadRequest);
+    }
+    // The entries take at least as much space as the archive, unless it is bogus.
+    check_quota(archive_size, extraction).await?;
+
+    let mut file = tokio::fs::File::from_std(tempfile::tempfile()?);
+    let length = tokio::io::copy(&mut reader.take(archive_size), &mut file).await?;
+    if length != archive_size {
+        return Err(NodeClientError::BadRequest);
+    }
+
+    let tail_length = length.min(MAX_END_DISTANCE);
+    let tail = read_at(&mut file, length - tail_length, tail_length).await?;
+    let directory = match parse_end_record(&tail).map_err(malformed)? {
+        EndRecord::Directory(directory) => directory,
+        EndRecord::Zip64(offset) => {
+            let record = read_at(&mut file, offset, ZIP64_END_LENGTH as u64).await?;
+            parse_zip64_end_record(&record).map_err(malformed)?
+        }
+    };
+    if directory.offset.saturating_add(directory.size) > length {
+        return Err(NodeClientError::BadRequest);
+    }
+    let central_directory = read_at(&mut file, directory.offset, directory.size).await?;
+    let entries =
+        parse_central_directory(&central_directory, directory.entries).map_err(malformed)?;
+
+    // Validate everything first, so that nothing is written for an invalid archive.
+    let mut paths = vec![];
+    let mut size = 0u64;
+    for entry in &entries {
+        if entry.is_encrypted() || ![METHOD_STORED, METHOD_DEFLATED].contains(&entry.method) {
+            return Err(NodeClientError::BadRequest);
+        }
+        let path = extraction.entry_path(&entry.name)?;
+        if path.is_none() && !entry.is_directory() {
+            return Err(NodeClientError::BadResourcePath);
+        }
+        paths.push(path);
+        size = size.saturating_add(entry.size);
+    }
+    check_quota(size, extraction).await?;
+
+    for (entry, path) in entries.into_iter().zip(paths) {
+        let Some(path) = path else {
+            continue;
+        };
+        if entry.is_directory() {
+            extraction.directory(path).await?;
+            continue;
+        }
+        let header = read_at(&mut file, entry.offset, LOCAL_HEADER_LENGTH as u64).await?;
+        let data_offset = parse_local_header(&header, entry.offset).map_err(malformed)?;
+        file.seek(SeekFrom::Start(data_offset)).await?;
+        let verification = Verification {
+            crc: entry.crc,
+            deflated: entry.method == METHOD_DEFLATED,
+        };
+        extraction
+            .file(
+                path,
+                entry.size,
+                (&mut file).take(entry.compressed_size),
+                Some(verification),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+struct Verification {
+    crc: u32,
+    deflated: bool,
+}
+
+/// Copies the contents of an entry into the upload.
+/// With a verification, the last piece is held back until the checksum matches,
+/// so that a corrupt entry never completes its upload.
+async fn feed_entry<R: AsyncRead + Unpin>(
+    mut source: R,
+    size: u64,
+    verification: Option<Verification>,
+    mut sender: DuplexStream,
+) -> NodeClientResponse<()> {
+    let Some(verification) = verification else {
+        let copied = tokio::io::copy(&mut source, &mut sender).await?;
+        if copied != size {
+            return Err(NodeClientError::BadRequest);
+        }
+        sender.shutdown().await?;
+        return Ok(());
+    };
+
+    let mut decoder = verification
+        .deflated
+        .then(|| DeflateDecoder::new(Vec::new()));
+    let mut hasher = crc32fast::Hasher::new();
+    let mut pending = vec![];
+    let mut sent = 0u64;
+    let mut buffer = vec![0u8; EXTRACT_TRANSFER_BUFFER];
+    loop {
+        let read = source.read(&mut buffer).await?;
+        let output = match &mut decoder {
+            Some(decoder) => {
+                if read == 0 {
+                    decoder.try_finish().map_err(malformed)?;
+                } else {
+                    decoder.write_all(&buffer[..read]).map_err(malformed)?;
+                }
+                std::mem::take(decoder.get_mut())
+            }
+            None => buffer[..read].to_vec(),
+        };
+        if !output.is_empty() {
+            hasher.update(&output);
+            sender.write_all(&pending).await?;
+            sent += pending.len() as u64;
+            pending = output;
+            if sent + pending.len() as u64 > size {
+                return Err(NodeClientError::BadRequest);
+            }
+        }
+        if read == 0 {
+            break;
+        }
+    }
+
+    if sent + pending.len() as u64 != size || hasher.finalize() != verification.crc {
+        return Err(NodeClientError::BadRequest);
+    }
+    sender.write_all(&pending).await?;
+    sender.shutdown().await?;
+    Ok(())
+}
+
+/// Decompresses the gzip stream on a separate task, a corrupt stream ends the output early.
+fn gunzip(mut source: DuplexStream) -> DuplexStream {
+    let (mut sender, receiver) = tokio::io::duplex(EXTRACT_TRANSFER_BUFFER);
+    tokio::spawn(async move {
+        let res: std::io::Result<()> = async {
+            let mut decoder = GzDecoder::new(Vec::new());
+            let mut buffer = vec![0u8; EXTRACT_TRANSFER_BUFFER];
+            loop {
+                let read = source.read(&mut buffer).await?;
+                if read == 0 {
+                    break;
+                }
+                decoder.write_all(&buffer[..read])?;
+                sender.write_all(&std::mem::take(decoder.get_mut())).await?;
+            }
+            decoder.try_finish()?;
+            sender.write_all(&std::mem::take(decoder.get_mut())).await?;
+            sender.shutdown().await
+        }
+        .await;
+        if let Err(err) = res {
+            debug!("Archive decompression stopped: {err}");
+        }
+    });
+    receiver
+}
+
+async fn skip<R: AsyncRead + Unpin>(reader: &mut R, length: u64) -> NodeClientResponse<()> {
+    let skipped = tokio::io::copy(&mut reader.take(length), &mut tokio::io::sink()).await?;
+    if skipped != length {
+        return Err(NodeClientError::BadRequest);
+    }
+    Ok(())
+}
+
+async fn read_at(
+    file: &mut tokio::fs::File,
+    offset: u64,
+    length: u64,
+) -> NodeClientResponse<Vec<u8>> {
+    file.seek(SeekFrom::Start(offset)).await?;
+    let mut data = vec![];
+    file.take(length).read_to_end(&mut data).await?;
+    if data.len() as u64 != length {
+        return Err(NodeClientError::BadRequest);
+    }
+    Ok(data)
+}
+
+/// Errors caused by the contents of the archive are the client's.
+fn malformed(err: std::io::Error) -> NodeClientError {
+    debug!("Malformed archive: {err}");
+    NodeClientError::BadRequest
+}
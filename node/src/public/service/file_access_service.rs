@@ -5,9 +5,10 @@ use std::time::Duration;
 use actix_web::http::header::{ByteRangeSpec, ContentType};
 use actix_web::web;
 use actix_web::web::Data;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use futures_util::future::try_join_all;
 use log::{debug, error, trace, warn};
+use logging::log_err;
 use mime_guess::mime;
 use scylla::client::caching_session::CachingSession;
 use tokio::io::AsyncWriteExt;
@@ -17,33 +18,124 @@ use tokio::time;
 use uuid::Uuid;
 
 use data::access::file_access::{
-    get_bucket, get_directory, get_file, get_file_dir, insert_directory, insert_file, ROOT_DIR,
+    get_bucket, get_directory, get_file, get_file_dir, get_file_version, insert_directory,
+    insert_file, try_insert_file, ROOT_DIR,
 };
+use data::error::MeowithDataError;
 use data::model::file_model::{
-    Bucket, BucketUploadSession, Directory, File, FileChunk, SessionState,
+    Bucket, BucketEventKind, BucketUploadSession, Directory, File, FileChunk, FileMetadata,
+    SessionState,
 };
 use protocol::mdsftp::data::{ChunkRange, CommitFlags, ReserveFlags};
 use protocol::mdsftp::handler::{AbstractReadStream, AbstractWriteStream};
 
 use crate::public::extractors::entry_path::EntryPath;
+use crate::public::extractors::preconditions::Preconditions;
 use crate::public::middleware::user_middleware::BucketAccessor;
-use crate::public::service::chunk_service::{commit_chunk, query_chunk, ChunkInfo};
+use crate::public::service::change_feed_service::record_change;
+use crate::public::service::chunk_service::{
+    commit_chunk, duplicate_chunk, query_chunk, ChunkInfo,
+};
 use crate::public::service::durable_transfer_session_manager::DURABLE_UPLOAD_SESSION_VALIDITY_TIME_SECS;
-use crate::public::service::file_action_service::do_delete_file;
+use crate::public::service::file_action_service::{
+    delete_chunks, do_delete_file, do_delete_file_if_unchanged,
+};
 use crate::public::service::file_io_service::{inbound_transfer, outbound_transfer};
+use crate::public::service::file_metadata_service::validate_metadata;
+use crate::public::service::lease_service::check_lease;
+use crate::public::service::notification_service::{emit_event, BucketEvent};
+use crate::public::service::object_lock_service::check_object_lock;
 use crate::public::service::reservation_service::{
     reserve_chunks, reserve_info_to_file_chunks, ReservationMode,
 };
+use crate::public::service::snapshot_service::snapshot_partition;
 use crate::public::service::{DOWNLOAD_ALLOWANCE, UPLOAD_ALLOWANCE, UPLOAD_OVERWRITE_ALLOWANCE};
 use crate::AppState;
 use commons::error::std_response::{NodeClientError, NodeClientResponse};
 use data::dto::entity::{UploadSessionRequest, UploadSessionStartResponse};
-use data::pathlib::split_path;
+use data::pathlib::{join_parent_name, split_path};
 
 pub struct DlInfo {
     pub size: u64,
     pub attachment_name: String,
     pub mime: ContentType,
+    pub metadata: FileMetadata,
+    pub etag: String,
+    pub last_modified: DateTime<Utc>,
+    /// Set when the preconditions allow skipping the transfer, nothing is written then.
+    pub not_modified: bool,
+    /// The satisfiable requested ranges, end inclusive. Empty if the whole file is sent.
+    pub ranges: Vec<(u64, u64)>,
+    /// Set for `multipart/byteranges` responses, that is when more than one range was requested.
+    pub boundary: Option<String>,
+    pub content_length: u64,
+}
+
+/// Bounds the amount of parts in a `multipart/byteranges` response.
+const MAX_DOWNLOAD_RANGES: usize = 64;
+
+fn part_header(boundary: &str, mime: &ContentType, range: (u64, u64), size: u64) -> String {
+    format!(
+        "\r\n--{boundary}\r\nContent-Type: {mime}\r\nContent-Range: bytes {}-{}/{size}\r\n\r\n",
+        range.0, range.1
+    )
+}
+
+fn closing_boundary(boundary: &str) -> String {
+    format!("\r\n--{boundary}--\r\n")
+}
+
+/// Coalesces overlapping and adjacent ranges (RFC 7233 4.1), so that no byte is sent twice.
+/// The ranges are end inclusive and come out ordered by their start.
+fn merge_ranges(mut ranges: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    ranges.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.0 <= last.1.saturating_add(1) => last.1 = max(last.1, range.1),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Maps a range of the file onto the chunks holding it, `None` marking chunks outside of it.
+fn resolve_chunk_ranges(
+    chunks: &[FileChunk],
+    range: Option<(u64, u64)>,
+) -> NodeClientResponse<Vec<Option<ChunkRange>>> {
+    let mut chunk_ranges: Vec<Option<ChunkRange>> = vec![Some(ChunkRange::default()); chunks.len()];
+
+    if let Some(range) = range {
+        let mut start = 0i64;
+        for (chunk, i) in chunks.iter().zip(0..) {
+            let end = start + chunk.chunk_size - 1;
+
+            let in_range_start = max(start, range.0 as i64);
+            let in_range_end = min(end, range.1 as i64);
+
+            if in_range_end < in_range_start {
+                chunk_ranges[i] = None;
+            } else if in_range_end != end || in_range_start != start {
+                chunk_ranges[i] = Some(ChunkRange::new(
+                    (in_range_start - start) as u64,
+                    (in_range_end + 1 - start) as u64,
+                )?);
+            } // else leave default
+
+            start += chunk.chunk_size;
+        }
+    }
+
+    Ok(chunk_ranges)
+}
+
+/// Where the contents of a oneshot upload come from.
+pub enum UploadSource {
+    /// Transferred from the stream into chunks reserved like for any upload.
+    Stream(AbstractReadStream),
+    /// Duplicated from the chunks of another file, in order, by the nodes holding them.
+    Chunks(Vec<FileChunk>),
 }
 
 pub async fn handle_upload_oneshot(
@@ -51,10 +143,13 @@ pub async fn handle_upload_oneshot(
     size: u64,
     app_state: Data<AppState>,
     accessor: BucketAccessor,
-    reader: AbstractReadStream,
+    source: UploadSource,
+    metadata: Option<FileMetadata>,
+    preconditions: Preconditions,
 ) -> NodeClientResponse<()> {
     // quit early if the user cannot upload at all.
     accessor.has_permission(&path.app_id, &path.bucket_id, *UPLOAD_ALLOWANCE)?;
+    check_lease(path.bucket_id, &path.path(), &accessor, &app_state).await?;
     let split_path = split_path(&path.path());
 
     let bucket = get_bucket(path.app_id, path.bucket_id, &app_state.session).await?;
@@ -67,14 +162,25 @@ pub async fn handle_upload_oneshot(
         &app_state.session,
     )
     .await;
+    preconditions.check_write(file.as_ref().ok().map(|file| file.0.etag()).as_deref())?;
+    // The file expected at the path once the upload completes, verified if preconditions are given.
+    let mut expected_id = file.as_ref().ok().map(|file| file.0.id);
     let mut old_file: Option<File> = None;
     let overwrite = if file.is_ok() {
         accessor.has_permission(&path.app_id, &path.bucket_id, *UPLOAD_OVERWRITE_ALLOWANCE)?;
         old_file = Some(file?.0);
+        check_object_lock(old_file.as_ref().unwrap(), &path.path())?;
         if !bucket.atomic_upload {
             trace!("Overwriting old file {}", path.path());
-            do_delete_file(old_file.as_ref().unwrap(), &bucket, &app_state).await?;
+            do_delete_file(
+                old_file.as_ref().unwrap(),
+                &path.path(),
+                &bucket,
+                &app_state,
+            )
+            .await?;
             old_file = None;
+            expected_id = None;
         }
         true
     } else {
@@ -95,20 +201,26 @@ pub async fn handle_upload_oneshot(
     }
 
     let file_id = Uuid::new_v4();
-    let reservation = reserve_chunks(
-        size,
-        ReserveFlags {
-            auto_start: true,
-            durable: false,
-            temp: false,
-            overwrite,
-        },
-        bucket.id,
-        file_id,
-        ReservationMode::PreferSelfThenMostFree,
-        &app_state,
-    )
-    .await?;
+    let reservation = match &source {
+        UploadSource::Stream(_) => Some(
+            reserve_chunks(
+                size,
+                ReserveFlags {
+                    auto_start: true,
+                    durable: false,
+                    temp: false,
+                    overwrite,
+                },
+                bucket.id,
+                file_id,
+                ReservationMode::PreferSelfThenMostFree,
+                &app_state,
+            )
+            .await?,
+        ),
+        // The copies are recorded on the session as they are made.
+        UploadSource::Chunks(_) => None,
+    };
 
     let bucket_upload_session = BucketUploadSession {
         app_id: path.app_id,
@@ -118,9 +230,14 @@ pub async fn handle_upload_oneshot(
         path: path.path(),
         size: size as i64,
         durable: false,
-        fragments: reserve_info_to_file_chunks(&reservation),
+        fragments: reservation
+            .as_ref()
+            .map(reserve_info_to_file_chunks)
+            .unwrap_or_default(),
         last_access: Utc::now(),
         state: SessionState::Writing.into(),
+        metadata,
+        expected_file: (!preconditions.is_empty()).then(|| expected_id.unwrap_or_default()),
     };
 
     let session_id = app_state
@@ -131,26 +248,50 @@ pub async fn handle_upload_oneshot(
     let bucket_upload_session = Arc::new(Mutex::new(bucket_upload_session));
     let notifier = create_commit_notifier(bucket_upload_session.clone(), app_state.clone());
 
-    let transfer_result: NodeClientResponse<()> = async {
-        for space in reservation.fragments.into_iter() {
-            inbound_transfer(
-                reader.clone(),
-                0,
-                space.node_id,
-                space.chunk_id,
-                space.channel,
-                ChunkInfo {
-                    chunk_buffer: space.chunk_buffer,
-                    size: space.size,
-                    append: false, // always the case for non-durable uploads.
-                },
-                &app_state,
-            )
-            .await?;
+    let transfer_result: NodeClientResponse<()> = match source {
+        UploadSource::Stream(reader) => {
+            async {
+                let fragments = reservation.into_iter().flat_map(|info| info.fragments);
+                for space in fragments {
+                    inbound_transfer(
+                        reader.clone(),
+                        0,
+                        space.node_id,
+                        space.chunk_id,
+                        space.channel,
+                        ChunkInfo {
+                            chunk_buffer: space.chunk_buffer,
+                            size: space.size,
+                            append: false, // always the case for non-durable uploads.
+                        },
+                        &app_state,
+                    )
+                    .await?;
+                }
+                Ok(())
+            }
+            .await
         }
-        Ok(())
-    }
-    .await;
+        UploadSource::Chunks(chunks) => {
+            async {
+                for chunk in chunks {
+                    let chunk_id = duplicate_chunk(
+                        chunk.server_id,
+                        chunk.chunk_id,
+                        chunk.chunk_size as u64,
+                        bucket.id,
+                        file_id,
+                        &app_state,
+                    )
+                    .await?;
+                    let copy = FileChunk { chunk_id, ..chunk };
+                    bucket_upload_session.lock().await.fragments.insert(copy);
+                }
+                Ok(())
+            }
+            .await
+        }
+    };
 
     trace!("Aborting the notifier");
     // We are getting the session BEFORE the .abort call to ensure it is not in the middle of
@@ -189,6 +330,7 @@ pub async fn handle_upload_oneshot(
         (path.app_id, session_id),
         Some(old_file),
         file_id,
+        overwrite,
     )
     .await
 }
@@ -197,11 +339,17 @@ pub async fn start_upload_session(
     e_path: EntryPath,
     accessor: BucketAccessor,
     req: UploadSessionRequest,
+    preconditions: Preconditions,
     app_state: Data<AppState>,
 ) -> NodeClientResponse<web::Json<UploadSessionStartResponse>> {
     accessor
         .has_permission(&e_path.app_id, &e_path.bucket_id, *UPLOAD_ALLOWANCE)
         .map_err(|_| NodeClientError::BadRequest)?;
+    check_lease(e_path.bucket_id, &e_path.path(), &accessor, &app_state).await?;
+    let metadata = req.metadata.map(FileMetadata::from);
+    if let Some(metadata) = &metadata {
+        validate_metadata(metadata)?;
+    }
 
     let path = split_path(&e_path.path());
 
@@ -209,6 +357,9 @@ pub async fn start_upload_session(
 
     // check if the file will be overwritten and if the user can do that.
     let file = get_file_dir(e_path.bucket_id, path.0, path.1, &app_state.session).await;
+    preconditions.check_write(file.as_ref().ok().map(|file| file.0.etag()).as_deref())?;
+    // The file expected at the path once the upload completes, verified if preconditions are given.
+    let mut expected_id = file.as_ref().ok().map(|file| file.0.id);
     let overwrite = if file.is_ok() {
         accessor.has_permission(
             &e_path.app_id,
@@ -216,8 +367,10 @@ pub async fn start_upload_session(
             *UPLOAD_OVERWRITE_ALLOWANCE,
         )?;
         let file = file?;
+        check_object_lock(&file.0, &e_path.path())?;
         if !bucket.atomic_upload {
-            do_delete_file(&file.0, &bucket, &app_state).await?;
+            do_delete_file(&file.0, &e_path.path(), &bucket, &app_state).await?;
+            expected_id = None;
         }
         true
     } else {
@@ -264,6 +417,8 @@ pub async fn start_upload_session(
         fragments: reserve_info_to_file_chunks(&reservation),
         last_access: Utc::now(),
         state: SessionState::AwaitingData.into(),
+        metadata,
+        expected_file: (!preconditions.is_empty()).then(|| expected_id.unwrap_or_default()),
     };
 
     trace!("Starting upload session {bucket_upload_session:?}");
@@ -283,7 +438,7 @@ pub async fn handle_upload_durable(
     session_id: Uuid,
     app_id: Uuid,
     bucket_id: Uuid,
-    _accessor: BucketAccessor,
+    accessor: BucketAccessor,
     reader: AbstractReadStream,
     app_state: Data<AppState>,
 ) -> NodeClientResponse<()> {
@@ -292,6 +447,8 @@ pub async fn handle_upload_durable(
         .upload_manager
         .get_session(app_id, bucket_id, session_id)
         .await?;
+    // the lease may have been taken after the session started
+    check_lease(bucket_id, &session.path, &accessor, &app_state).await?;
     let bucket = get_bucket(app_id, bucket_id, &app_state.session).await?;
     trace!("Durable try lock {}", session.last_access);
     app_state
@@ -329,6 +486,7 @@ pub async fn handle_upload_durable(
             (app_id, session_id),
             None,
             file_id,
+            false,
         )
         .await;
     }
@@ -407,6 +565,7 @@ pub async fn handle_upload_durable(
         (app_id, session_id),
         None,
         file_id,
+        false,
     )
     .await
 }
@@ -460,6 +619,8 @@ pub async fn end_session(
     app_session_ids: (Uuid, Uuid),
     old_file: Option<Option<File>>,
     file_id: Uuid,
+    // the upload was started in place of a file, which may have been removed already
+    replaced: bool,
 ) -> NodeClientResponse<()> {
     debug!("Committing chunks {:?}", &bucket_upload_session.fragments);
     let mut futures = vec![];
@@ -474,6 +635,7 @@ pub async fn end_session(
     try_join_all(futures).await?;
 
     let now = Utc::now();
+    let full_path = join_parent_name(split_path.0.as_deref().unwrap_or_default(), &split_path.1);
     let directory = if let Some(directory) = split_path.0 {
         try_mkdir(bucket.id, directory, &app_state.session)
             .await?
@@ -495,30 +657,109 @@ pub async fn end_session(
         chunk_ids: bucket_upload_session.fragments.clone(),
         created: now,
         last_modified: now,
+        metadata: bucket_upload_session.metadata.clone(),
+        etag: Some(File::content_etag(file_id)),
+        retain_until: bucket.retain_until(now),
+        legal_hold: None,
     };
-    let old_file = old_file.unwrap_or(
-        get_file(bucket.id, directory, split_path.1, &app_state.session)
-            .await
-            .ok(),
-    );
-
-    if old_file.is_some() {
-        do_delete_file(old_file.as_ref().unwrap(), &bucket, &app_state).await?;
-        trace!("Deleted residual file");
-    }
+    let old_file = match bucket_upload_session.expected_file {
+        Some(expected_id) => {
+            let committed =
+                commit_guarded_file(&file, &full_path, expected_id, &bucket, &app_state).await;
+            match committed {
+                Ok(old_file) => old_file,
+                Err(err) => {
+                    debug!("Conditional upload no longer applies, deleting. {err}");
+                    delete_chunks(bucket.id, &bucket_upload_session.fragments, &app_state).await;
+                    app_state
+                        .upload_manager
+                        .end_session(app_session_ids.0, bucket.id, app_session_ids.1)
+                        .await;
+                    return Err(err);
+                }
+            }
+        }
+        None => {
+            let old_file = old_file.unwrap_or(
+                get_file(bucket.id, directory, split_path.1, &app_state.session)
+                    .await
+                    .ok(),
+            );
+
+            if old_file.is_some() {
+                do_delete_file(old_file.as_ref().unwrap(), &full_path, &bucket, &app_state).await?;
+                trace!("Deleted residual file");
+            }
 
-    trace!("Inserting file record");
-    insert_file(&file, &bucket, &app_state.session).await?;
+            trace!("Inserting file record");
+            insert_file(&file, &bucket, &app_state.session).await?;
+            old_file
+        }
+    };
     app_state
         .upload_manager
         .end_session(app_session_ids.0, bucket.id, app_session_ids.1)
         .await;
 
+    let kind = if old_file.is_some() || replaced {
+        BucketEventKind::Overwritten
+    } else {
+        BucketEventKind::Created
+    };
+    emit_event(
+        &bucket,
+        BucketEvent {
+            size: Some(size),
+            etag: file.etag.clone(),
+            ..BucketEvent::file(kind, full_path)
+        },
+        &app_state,
+    )
+    .await;
+
     trace!("End session complete");
 
     Ok(())
 }
 
+/// Puts the file of a conditional upload in place, guarding against another upload having
+/// replaced the file at the path since the preconditions were checked.
+/// The replaced file is only removed if unchanged, and the new one only inserted if the path
+/// is still free, so that a concurrent write in between fails the upload instead of being lost.
+/// Returns the replaced file.
+async fn commit_guarded_file(
+    file: &File,
+    path: &str,
+    expected_id: Uuid,
+    bucket: &Bucket,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<Option<File>> {
+    let current = match get_file(
+        bucket.id,
+        Some(file.directory),
+        file.name.clone(),
+        &app_state.session,
+    )
+    .await
+    {
+        Ok(current) => Some(current),
+        Err(MeowithDataError::NotFound) => None,
+        Err(err) => return Err(err.into()),
+    };
+    if current.as_ref().map(|file| file.id).unwrap_or_default() != expected_id {
+        return Err(NodeClientError::PreconditionFailed);
+    }
+    if let Some(current) = &current {
+        do_delete_file_if_unchanged(current, path, bucket, app_state).await?;
+    }
+
+    trace!("Inserting file record");
+    if !try_insert_file(file, bucket, &app_state.session).await? {
+        return Err(NodeClientError::PreconditionFailed);
+    }
+    Ok(current)
+}
+
 pub async fn try_mkdir(
     bucket_id: Uuid,
     path: String,
@@ -561,6 +802,16 @@ pub async fn try_mkdir(
         };
 
         insert_directory(&new_dir, session).await?;
+        log_err(
+            "Failed to record a change",
+            record_change(
+                bucket_id,
+                &BucketEvent::directory(BucketEventKind::Created, new_dir.full_path()),
+                new_dir.created,
+                session,
+            )
+            .await,
+        );
 
         if i == directories_len - 1 {
             return Ok(Some(new_dir));
@@ -570,6 +821,21 @@ pub async fn try_mkdir(
     unreachable!("Something went very wrong when creating the directory")
 }
 
+/// Ensures the parent directory exists, returning its id.
+pub async fn ensure_directory(
+    bucket_id: Uuid,
+    directory: Option<String>,
+    session: &CachingSession,
+) -> NodeClientResponse<Uuid> {
+    Ok(match directory {
+        Some(directory) => try_mkdir(bucket_id, directory, session)
+            .await?
+            .map(|dir| dir.id)
+            .unwrap_or(ROOT_DIR),
+        None => ROOT_DIR,
+    })
+}
+
 pub fn create_commit_notifier(
     bucket_upload_session: Arc<Mutex<BucketUploadSession>>,
     data: Data<AppState>,
@@ -599,80 +865,180 @@ pub fn create_commit_notifier(
     })
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_download(
     e_path: EntryPath,
     accessor: BucketAccessor,
     writer: AbstractWriteStream,
     app_state: Data<AppState>,
-    range: Option<ByteRangeSpec>,
-) -> NodeClientResponse<(DlInfo, JoinHandle<NodeClientResponse<()>>)> {
+    ranges: Vec<ByteRangeSpec>,
+    version_id: Option<Uuid>,
+    snapshot_id: Option<Uuid>,
+    preconditions: &Preconditions,
+) -> NodeClientResponse<(DlInfo, Option<JoinHandle<NodeClientResponse<()>>>)> {
     accessor.has_permission(&e_path.app_id, &e_path.bucket_id, *DOWNLOAD_ALLOWANCE)?;
+    if version_id.is_some() && snapshot_id.is_some() {
+        // versions are not a part of snapshots
+        return Err(NodeClientError::BadRequest);
+    }
+    let partition = snapshot_partition(e_path.bucket_id, snapshot_id, &app_state).await?;
+    let requested_ranges = ranges.len();
     let path = split_path(&e_path.path());
     let attachment_name = path.1.clone();
-    let file = get_file_dir(e_path.bucket_id, path.0, path.1, &app_state.session).await?;
-
-    let range = if let Some(range) = range {
-        // end inclusive
-        Some(
-            range
-                .to_satisfiable_range(file.0.size as u64)
-                .ok_or(NodeClientError::RangeUnsatisfiable)?,
-        )
-    } else {
-        None
+    let (size, chunks, metadata, etag, last_modified) = match version_id {
+        Some(version_id) => {
+            let version = get_file_version(
+                e_path.bucket_id,
+                e_path.path(),
+                version_id,
+                &app_state.session,
+            )
+            .await?;
+            let etag = version.etag();
+            (
+                version.size,
+                version.chunk_ids,
+                version.metadata,
+                etag,
+                version.last_modified,
+            )
+        }
+        None => {
+            let file = get_file_dir(partition, path.0, path.1, &app_state.session).await?;
+            let etag = file.0.etag();
+            (
+                file.0.size,
+                file.0.chunk_ids,
+                file.0.metadata,
+                etag,
+                file.0.last_modified,
+            )
+        }
     };
 
-    let mut chunk_ids: Vec<FileChunk> = file.0.chunk_ids.iter().cloned().collect();
-    chunk_ids.sort_by_key(|chunk| chunk.chunk_order);
-    let mut chunk_ranges: Vec<Option<ChunkRange>> =
-        vec![Some(ChunkRange::default()); chunk_ids.len()];
+    let metadata = metadata.unwrap_or_default();
+    // The stored content type is validated on write, the guess only applies to files without one.
+    let mime = metadata
+        .content_type
+        .as_ref()
+        .and_then(|content_type| content_type.parse().ok())
+        .unwrap_or_else(|| {
+            mime_guess::from_path(&attachment_name).first_or(mime::APPLICATION_OCTET_STREAM)
+        });
+    let not_modified = preconditions.not_modified(&etag, last_modified);
+    let size = size as u64;
+    if ranges.len() > MAX_DOWNLOAD_RANGES {
+        return Err(NodeClientError::RangeUnsatisfiable { size });
+    }
 
-    if let Some(range) = range {
-        let mut start = 0i64;
-        for (chunk, i) in chunk_ids.iter().zip(0..) {
-            let end = start + chunk.chunk_size - 1;
+    // end inclusive, unsatisfiable ranges are skipped unless none of them can be satisfied.
+    let ranges: Vec<(u64, u64)> = ranges
+        .iter()
+        .filter_map(|range| range.to_satisfiable_range(size))
+        .collect();
+    if ranges.len() != requested_ranges {
+        if ranges.is_empty() {
+            return Err(NodeClientError::RangeUnsatisfiable { size });
+        }
+        debug!("Skipping unsatisfiable ranges of {}", e_path.path());
+    }
+    let ranges = merge_ranges(ranges);
+    let mime = ContentType(mime);
+    let boundary = (ranges.len() > 1).then(|| Uuid::new_v4().simple().to_string());
+    let content_length = match &boundary {
+        Some(boundary) => {
+            ranges
+                .iter()
+                .map(|range| {
+                    part_header(boundary, &mime, *range, size).len() as u64 + range.1 - range.0 + 1
+                })
+                .sum::<u64>()
+                + closing_boundary(boundary).len() as u64
+        }
+        None => ranges
+            .first()
+            .map(|range| range.1 - range.0 + 1)
+            .unwrap_or(size),
+    };
 
-            let in_range_start = max(start, range.0 as i64);
-            let in_range_end = min(end, range.1 as i64);
+    let info = DlInfo {
+        size,
+        mime,
+        attachment_name,
+        metadata,
+        etag,
+        last_modified,
+        not_modified,
+        ranges,
+        boundary,
+        content_length,
+    };
+    if not_modified {
+        return Ok((info, None));
+    }
 
-            if in_range_end < in_range_start {
-                chunk_ranges[i] = None;
-            } else if in_range_end != end || in_range_start != start {
-                chunk_ranges[i] = Some(ChunkRange::new(
-                    (in_range_start - start) as u64,
-                    (in_range_end + 1 - start) as u64,
-                )?);
-            } // else leave default
+    let mut chunk_ids: Vec<FileChunk> = chunks.into_iter().collect();
+    chunk_ids.sort_by_key(|chunk| chunk.chunk_order);
 
-            start += chunk.chunk_size;
-        }
-    }
+    // Each part is resolved to the sections of the chunks holding it, so that only those are fetched.
+    let parts: Vec<Vec<Option<ChunkRange>>> = if info.ranges.is_empty() {
+        vec![resolve_chunk_ranges(&chunk_ids, None)?]
+    } else {
+        info.ranges
+            .iter()
+            .map(|range| resolve_chunk_ranges(&chunk_ids, Some(*range)))
+            .collect::<NodeClientResponse<_>>()?
+    };
+    let part_headers: Vec<String> = match &info.boundary {
+        Some(boundary) => info
+            .ranges
+            .iter()
+            .map(|range| part_header(boundary, &info.mime, *range, info.size))
+            .collect(),
+        None => vec![],
+    };
+    let closing = info.boundary.as_deref().map(closing_boundary);
 
     let handle: JoinHandle<NodeClientResponse<()>> = tokio::spawn(async move {
-        for (chunk, range) in chunk_ids.iter().zip(chunk_ranges) {
-            if let Some(range) = range {
-                outbound_transfer(
-                    writer.clone(),
-                    chunk.server_id,
-                    chunk.chunk_id,
-                    &app_state,
-                    range.into_option(),
-                )
-                .await?;
+        for (i, chunk_ranges) in parts.into_iter().enumerate() {
+            if let Some(header) = part_headers.get(i) {
+                writer.lock().await.write_all(header.as_bytes()).await?;
             }
+            for (chunk, range) in chunk_ids.iter().zip(chunk_ranges) {
+                if let Some(range) = range {
+                    outbound_transfer(writer.clone(), chunk, &app_state, range.into_option())
+                        .await?;
+                }
+            }
+        }
+        if let Some(closing) = closing {
+            writer.lock().await.write_all(closing.as_bytes()).await?;
         }
         writer.lock().await.shutdown().await?;
         Ok(())
     });
 
-    Ok((
-        DlInfo {
-            size: file.0.size as u64,
-            mime: ContentType(
-                mime_guess::from_path(&attachment_name).first_or(mime::APPLICATION_OCTET_STREAM),
-            ),
-            attachment_name,
-        },
-        handle,
-    ))
+    Ok((info, Some(handle)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge_ranges;
+
+    #[test]
+    fn merges_overlapping_and_adjacent_ranges() {
+        assert_eq!(merge_ranges(vec![(0, 99), (0, 99), (0, 99)]), vec![(0, 99)]);
+        assert_eq!(merge_ranges(vec![(10, 19), (20, 29)]), vec![(10, 29)]);
+        assert_eq!(
+            merge_ranges(vec![(50, 60), (0, 10), (5, 55)]),
+            vec![(0, 60)]
+        );
+        assert_eq!(merge_ranges(vec![(0, 99), (10, 19)]), vec![(0, 99)]);
+    }
+
+    #[test]
+    fn keeps_disjoint_ranges() {
+        assert_eq!(merge_ranges(vec![(50, 51), (0, 1)]), vec![(0, 1), (50, 51)]);
+        assert_eq!(merge_ranges(vec![(0, 1), (3, 4)]), vec![(0, 1), (3, 4)]);
+    }
 }
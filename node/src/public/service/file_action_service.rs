@@ -1,24 +1,35 @@
 use crate::public::extractors::entry_path::EntryPath;
+use crate::public::extractors::preconditions::Preconditions;
 use crate::public::middleware::user_middleware::BucketAccessor;
 use crate::public::routes::entity_action::RenameEntityRequest;
-use crate::public::service::DELETE_ALLOWANCE;
+use crate::public::service::lease_service::check_lease;
+use crate::public::service::notification_service::{emit_event, BucketEvent};
+use crate::public::service::object_lock_service::check_object_lock;
+use crate::public::service::snapshot_service::snapshot_holds;
+use crate::public::service::{DELETE_ALLOWANCE, RENAME_ALLOWANCE};
 use crate::AppState;
 use actix_web::web::Data;
-use commons::error::std_response::NodeClientResponse;
+use chrono::Utc;
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
 use data::access::file_access::{
-    delete_file, get_bucket, get_file_dir, maybe_get_file_dir, update_file_path, DID,
+    archive_file, delete_file, get_bucket, get_file_dir, maybe_get_file_dir, trash_file,
+    try_archive_file, try_delete_file, try_trash_file, try_update_file_path, update_file_path, DID,
 };
-use data::model::file_model::{Bucket, File};
+use data::model::file_model::{Bucket, BucketEventKind, File, FileChunk, FileVersion, TrashedFile};
 use data::pathlib::split_path;
+use log::warn;
 use logging::log_err;
 use tokio::try_join;
+use uuid::Uuid;
 
 pub async fn delete_file_srv(
     path: EntryPath,
+    preconditions: Preconditions,
     bucket_accessor: BucketAccessor,
     app_state: Data<AppState>,
 ) -> NodeClientResponse<()> {
     bucket_accessor.has_permission(&path.app_id, &path.bucket_id, *DELETE_ALLOWANCE)?;
+    check_lease(path.bucket_id, &path.path(), &bucket_accessor, &app_state).await?;
     let split_path = split_path(&path.path());
     let (bucket, file) = try_join!(
         get_bucket(path.app_id, path.bucket_id, &app_state.session),
@@ -29,16 +40,118 @@ pub async fn delete_file_srv(
             &app_state.session
         )
     )?;
-    do_delete_file(&file.0, &bucket, &app_state).await?;
+    preconditions.check_write(Some(&file.0.etag()))?;
+    if preconditions.is_empty() {
+        do_delete_file(&file.0, &path.path(), &bucket, &app_state).await?;
+    } else {
+        do_delete_file_if_unchanged(&file.0, &path.path(), &bucket, &app_state).await?;
+    }
+    emit_event(
+        &bucket,
+        BucketEvent {
+            size: Some(file.0.size),
+            etag: Some(file.0.etag()),
+            ..BucketEvent::file(BucketEventKind::Deleted, path.path())
+        },
+        &app_state,
+    )
+    .await;
     Ok(())
 }
 
+/// Removes the file from the bucket, unless it is protected by the object lock.
+/// If the bucket has versioning enabled, the file is kept as a non-current version instead.
+/// Otherwise, if the bucket has a trash, the file is moved there.
 pub async fn do_delete_file(
     file: &File,
+    path: &str,
+    bucket: &Bucket,
+    state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    remove_file(file, path, bucket, false, state).await?;
+    Ok(())
+}
+
+/// Like [do_delete_file], but fails if the file changed after it was read,
+/// so that the preconditions checked against it still hold.
+pub async fn do_delete_file_if_unchanged(
+    file: &File,
+    path: &str,
     bucket: &Bucket,
     state: &Data<AppState>,
 ) -> NodeClientResponse<()> {
-    for chunk in &file.chunk_ids {
+    remove_file(file, path, bucket, true, state)
+        .await?
+        .then_some(())
+        .ok_or(NodeClientError::PreconditionFailed)
+}
+
+/// Returns whether the file was removed, which only a `guarded` removal may not be.
+async fn remove_file(
+    file: &File,
+    path: &str,
+    bucket: &Bucket,
+    guarded: bool,
+    state: &Data<AppState>,
+) -> NodeClientResponse<bool> {
+    check_object_lock(file, path)?;
+    if bucket.versioning_enabled() {
+        let version = FileVersion::of(file, path.to_string(), Uuid::now_v7(), Utc::now());
+        if guarded {
+            return Ok(try_archive_file(file, &version, bucket, &state.session).await?);
+        }
+        archive_file(file, &version, bucket, &state.session).await?;
+        return Ok(true);
+    }
+    if bucket.trash_enabled() {
+        let entry = TrashedFile::of(file, bucket, path.to_string(), Uuid::new_v4(), Utc::now());
+        if guarded {
+            return Ok(try_trash_file(file, &entry, bucket, &state.session).await?);
+        }
+        trash_file(file, &entry, bucket, &state.session).await?;
+        return Ok(true);
+    }
+
+    if guarded {
+        // The record goes first, the chunks of a changed file must stay.
+        if !try_delete_file(file, bucket, &state.session).await? {
+            return Ok(false);
+        }
+        delete_chunks(bucket.id, &file.chunk_ids, state).await;
+        return Ok(true);
+    }
+    delete_chunks(bucket.id, &file.chunk_ids, state).await;
+    delete_file(file, bucket, &state.session).await?;
+
+    Ok(true)
+}
+
+/// Deletes the chunks released by the bucket, except for those still held by a snapshot.
+/// See [snapshot_holds] for the chunks released while a snapshot is being taken.
+pub async fn delete_chunks<'a>(
+    bucket_id: Uuid,
+    chunks: impl IntoIterator<Item = &'a FileChunk>,
+    state: &Data<AppState>,
+) {
+    let chunks: Vec<&FileChunk> = chunks.into_iter().collect();
+    if chunks.is_empty() {
+        return;
+    }
+    let held = match snapshot_holds(bucket_id, &chunks, state).await {
+        Ok(held) => held,
+        Err(err) => {
+            // Kept, a leaked chunk being preferable to a snapshot losing its contents
+            warn!(
+                "Failed to check the snapshots of {} chunks {err:?}",
+                chunks.len()
+            );
+            return;
+        }
+    };
+    for chunk in chunks {
+        if held.contains(&chunk.chunk_id) {
+            continue;
+        }
         if chunk.server_id == state.req_ctx.id {
             log_err(
                 "file delete mdsftp_error",
@@ -51,26 +164,28 @@ pub async fn do_delete_file(
             );
         }
     }
-
-    delete_file(file, bucket, &state.session).await?;
-
-    Ok(())
 }
 
+/// `If-Match` applies to the renamed file, `If-None-Match` to the one at the destination.
 pub async fn rename_file_srv(
     path: EntryPath,
     req: RenameEntityRequest,
+    preconditions: Preconditions,
     bucket_accessor: BucketAccessor,
     app_state: Data<AppState>,
 ) -> NodeClientResponse<()> {
+    bucket_accessor.has_permission(&path.app_id, &path.bucket_id, *RENAME_ALLOWANCE)?;
     if path.path() == req.path() {
         // The paths equal, no work needs to be done
         return Ok(());
     }
+    check_lease(path.bucket_id, &path.path(), &bucket_accessor, &app_state).await?;
+    check_lease(path.bucket_id, &req.path(), &bucket_accessor, &app_state).await?;
 
     let split_old_path = split_path(&path.path());
     let split_new_path = split_path(&req.path());
-    let (old_file, new_file) = try_join!(
+    let (bucket, old_file, new_file) = try_join!(
+        get_bucket(path.app_id, path.bucket_id, &app_state.session),
         get_file_dir(
             path.bucket_id,
             split_old_path.0,
@@ -84,32 +199,50 @@ pub async fn rename_file_srv(
             &app_state.session
         )
     )?;
+    preconditions.check_match(Some(&old_file.0.etag()))?;
+    preconditions.check_none_match(new_file.0.as_ref().map(File::etag).as_deref())?;
+    check_object_lock(&old_file.0, &path.path())?;
 
-    match new_file.0 {
-        None => {
-            // update the path
-            let _new_file = update_file_path(
-                &old_file.0,
-                DID::of(new_file.1).0,
-                split_new_path.1,
-                &app_state.session,
-            )
-            .await?;
-        }
-        Some(new_file_file) => {
-            // if a file already exists in the new destination, and the user possesses the required allowance, delete it
-            bucket_accessor.has_permission(&path.app_id, &path.bucket_id, *DELETE_ALLOWANCE)?;
-            let bucket = get_bucket(path.app_id, path.bucket_id, &app_state.session).await?;
-            do_delete_file(&new_file_file, &bucket, &app_state).await?;
-            let _new_file = update_file_path(
-                &old_file.0,
-                DID::of(new_file.1).0,
-                split_new_path.1,
-                &app_state.session,
-            )
-            .await?;
+    if let Some(new_file_file) = new_file.0 {
+        // if a file already exists in the new destination, and the user possesses the required allowance, delete it
+        bucket_accessor.has_permission(&path.app_id, &path.bucket_id, *DELETE_ALLOWANCE)?;
+        if preconditions.is_empty() {
+            do_delete_file(&new_file_file, &req.path(), &bucket, &app_state).await?;
+        } else {
+            do_delete_file_if_unchanged(&new_file_file, &req.path(), &bucket, &app_state).await?;
         }
     }
+    // update the path
+    if preconditions.is_empty() {
+        update_file_path(
+            &old_file.0,
+            DID::of(new_file.1).0,
+            split_new_path.1,
+            &app_state.session,
+        )
+        .await?;
+    } else {
+        try_update_file_path(
+            &old_file.0,
+            DID::of(new_file.1).0,
+            split_new_path.1,
+            &app_state.session,
+        )
+        .await?
+        .ok_or(NodeClientError::PreconditionFailed)?;
+    }
+
+    emit_event(
+        &bucket,
+        BucketEvent {
+            old_path: Some(path.path()),
+            size: Some(old_file.0.size),
+            etag: Some(old_file.0.etag()),
+            ..BucketEvent::file(BucketEventKind::Renamed, req.path())
+        },
+        &app_state,
+    )
+    .await;
 
     Ok(())
 }
@@ -3,6 +3,7 @@ use crate::public::service::chunk_service::ChunkInfo;
 use crate::AppState;
 use actix_web::web::Data;
 use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use data::model::file_model::FileChunk;
 use log::trace;
 use protocol::mdsftp::channel::MDSFTPChannel;
 use protocol::mdsftp::data::{ChunkRange, PutFlags};
@@ -105,13 +106,20 @@ pub async fn inbound_transfer(
     }
 }
 
+/// Streams the given range of the chunk, or the whole of it, into the writer.
+/// Reads never go past the size of the chunk, as its file can be extended by appends in the meantime.
 pub async fn outbound_transfer(
     writer: AbstractWriteStream,
-    node_id: Uuid,
-    chunk_id: Uuid,
+    chunk: &FileChunk,
     state: &Data<AppState>,
     range: Option<ChunkRange>,
 ) -> NodeClientResponse<()> {
+    let (node_id, chunk_id) = (chunk.server_id, chunk.chunk_id);
+    let range = match range {
+        Some(range) => range,
+        None if chunk.chunk_size > 0 => ChunkRange::new(0, chunk.chunk_size as u64)?,
+        None => return Ok(()),
+    };
     if node_id == state.req_ctx.id {
         // send local chunk, no need for net io
         let mut reader = state
@@ -121,16 +129,10 @@ pub async fn outbound_transfer(
             .map_err(|_| NodeClientError::NotFound)?;
         let mut writer = writer.lock().await;
 
-        if let Some(range) = range {
-            reader.seek(SeekFrom::Start(range.start)).await?;
-            io::copy(&mut reader.take(range.size()), &mut *writer)
-                .await
-                .map_err(|_| NodeClientError::InternalError)?;
-        } else {
-            io::copy(&mut reader, &mut *writer)
-                .await
-                .map_err(|_| NodeClientError::InternalError)?;
-        }
+        reader.seek(SeekFrom::Start(range.start)).await?;
+        io::copy(&mut reader.take(range.size()), &mut *writer)
+            .await
+            .map_err(|_| NodeClientError::InternalError)?;
 
         Ok(())
     } else {
@@ -147,7 +149,7 @@ pub async fn outbound_transfer(
             .retrieve_content(writer, handler, false) // there might be more chunks to send!
             .await?;
 
-        channel.retrieve_req(chunk_id, 16, range).await?;
+        channel.retrieve_req(chunk_id, 16, Some(range)).await?;
 
         handle
             .await
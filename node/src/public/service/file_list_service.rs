@@ -1,82 +1,378 @@
 use crate::public::extractors::entry_path::EntryPath;
 use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::snapshot_service::snapshot_partition;
 use crate::public::service::{
     DOWNLOAD_ALLOWANCE, FETCH_BUCKET_INFO_ALLOWANCE, LIST_BUCKET_ALLOWANCE, LIST_DIR_ALLOWANCE,
 };
 use crate::AppState;
 use actix_web::web;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
 use commons::error::std_response::{NodeClientError, NodeClientResponse};
 use data::access::file_access::{
-    get_bucket, get_directories_from_bucket, get_directories_from_bucket_paginated, get_directory,
-    get_file_dir, get_files_from_bucket, get_files_from_bucket_and_directory,
-    get_files_from_bucket_paginated, get_sub_dirs, DirectoryListItem, FileItem,
+    get_bucket, get_directories_from_bucket_after, get_directory, get_directory_path_cached,
+    get_file_dir, get_files_from_bucket_after, get_files_from_directory_bounded,
+    get_sub_dirs_bounded,
 };
-use data::dto::entity::{BucketDto, Entity, EntityList};
+use data::dto::entity::{BucketDto, Entity, EntityList, FileMetadataDto};
 use data::error::MeowithDataError;
+use data::model::file_model::{Directory, File};
 use data::pathlib::split_path;
 use futures::Stream;
 use futures_util::StreamExt;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::pin::Pin;
 use tokio::join;
 use uuid::Uuid;
 
+const MAX_LIST_LIMIT: usize = 1000;
+/// The rows a single request examines at most, so that a selective filter or prefix does not walk
+/// the whole bucket at once. Past it, the page is cut short and returned with a cursor.
+const MAX_EXAMINED_ROWS: usize = 10 * MAX_LIST_LIMIT;
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ListOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
 #[derive(Deserialize)]
-pub struct PaginationInfo {
-    pub start: Option<u64>,
-    pub end: Option<u64>,
+pub struct ListQuery {
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+    pub prefix: Option<String>,
+    pub delimiter: Option<String>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub modified_after: Option<DateTime<Utc>>,
+    pub modified_before: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub order: ListOrder,
+    /// Lists a snapshot of the bucket instead of its current contents.
+    pub snapshot_id: Option<Uuid>,
+}
+
+/// The position of a listing, handed out to the client as an opaque cursor.
+#[derive(Serialize, Deserialize)]
+struct ListCursor {
+    descending: bool,
+    key: CursorKey,
+    /// The last common prefix returned, the entities grouped under it are not reported again.
+    common_prefix: Option<String>,
+}
+
+/// The clustering key of the last entity visited by a listing.
+#[derive(Serialize, Deserialize)]
+enum CursorKey {
+    File { directory: Uuid, name: String },
+    Directory { parent: String, name: String },
+    Entry { is_dir: bool, name: String },
 }
 
-impl PaginationInfo {
+impl ListCursor {
+    fn encode(&self) -> NodeClientResponse<String> {
+        let bytes = serde_cbor::to_vec(self).map_err(|_| NodeClientError::InternalError)?;
+        Ok(URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    fn decode(cursor: &str) -> NodeClientResponse<Self> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(cursor)
+            .map_err(|_| NodeClientError::BadRequest)?;
+        serde_cbor::from_slice(&bytes).map_err(|_| NodeClientError::BadRequest)
+    }
+}
+
+impl ListQuery {
     fn validate(&self) -> NodeClientResponse<()> {
-        if self.is_paginated() {
-            let valid = self.completed();
-            if valid.start.unwrap() >= valid.end.unwrap() {
-                Err(NodeClientError::BadRequest)
-            } else {
-                Ok(())
-            }
-        } else {
+        let limit_valid = self
+            .limit
+            .is_none_or(|limit| limit > 0 && limit <= MAX_LIST_LIMIT);
+        let size_valid = match (self.min_size, self.max_size) {
+            (Some(min), Some(max)) => min <= max,
+            _ => true,
+        };
+        if limit_valid && size_valid {
             Ok(())
+        } else {
+            Err(NodeClientError::BadRequest)
+        }
+    }
+
+    fn limit(&self) -> usize {
+        self.limit.unwrap_or(MAX_LIST_LIMIT)
+    }
+
+    fn descending(&self) -> bool {
+        self.order == ListOrder::Desc
+    }
+
+    fn prefix(&self) -> &str {
+        self.prefix.as_deref().unwrap_or_default()
+    }
+
+    fn cursor(&self) -> NodeClientResponse<Option<ListCursor>> {
+        let Some(cursor) = &self.cursor else {
+            return Ok(None);
+        };
+        let cursor = ListCursor::decode(cursor)?;
+        if cursor.descending != self.descending() {
+            // a cursor can only continue the listing it was returned by
+            return Err(NodeClientError::BadRequest);
         }
+        Ok(Some(cursor))
     }
 
-    fn is_paginated(&self) -> bool {
-        self.start.is_some() || self.end.is_some()
+    fn matches_filters(&self, entity: &Entity) -> bool {
+        self.min_size.is_none_or(|min| entity.size >= min)
+            && self.max_size.is_none_or(|max| entity.size <= max)
+            && self
+                .modified_after
+                .is_none_or(|after| entity.last_modified > after)
+            && self
+                .modified_before
+                .is_none_or(|before| entity.last_modified < before)
     }
 
-    fn completed(&self) -> Self {
-        PaginationInfo {
-            start: Some(self.start.unwrap_or(0)),
-            end: Some(self.start.unwrap_or(usize::MAX as u64)),
+    /// The bound of a listing of a single directory, which is sorted by name,
+    /// see [get_files_from_directory_bounded].
+    /// Names following `after` which match the prefix and are not grouped under `skip` are kept.
+    fn name_bound(&self, after: Option<String>, skip: Option<&str>) -> Option<String> {
+        let prefix = Some(self.prefix()).filter(|prefix| !prefix.is_empty());
+        if self.descending() {
+            [
+                after,
+                prefix.and_then(prefix_successor),
+                skip.map(str::to_string),
+            ]
+            .into_iter()
+            .flatten()
+            .min()
+        } else {
+            [
+                after.map(|name| format!("{name}\0")),
+                prefix.map(str::to_string),
+                skip.and_then(prefix_successor),
+            ]
+            .into_iter()
+            .flatten()
+            .max()
         }
     }
 }
 
+/// The smallest string greater than every string starting with the prefix.
+fn prefix_successor(prefix: &str) -> Option<String> {
+    let mut chars: Vec<char> = prefix.chars().collect();
+    while let Some(last) = chars.pop() {
+        if let Some(next) = (last as u32 + 1..=char::MAX as u32).find_map(char::from_u32) {
+            chars.push(next);
+            return Some(chars.into_iter().collect());
+        }
+    }
+    None
+}
+
+/// Collects a single page of a listing, applying the filters and the delimiter of the query.
+struct Listing<'a> {
+    query: &'a ListQuery,
+    entities: Vec<Entity>,
+    common_prefixes: Vec<String>,
+    last_prefix: Option<String>,
+    last_key: Option<CursorKey>,
+    examined: usize,
+}
+
+impl<'a> Listing<'a> {
+    fn new(query: &'a ListQuery, cursor: Option<&ListCursor>) -> Self {
+        Listing {
+            query,
+            entities: vec![],
+            common_prefixes: vec![],
+            last_prefix: cursor.and_then(|cursor| cursor.common_prefix.clone()),
+            last_key: None,
+            examined: 0,
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.entities.len() + self.common_prefixes.len() >= self.query.limit()
+    }
+
+    /// Whether the page is full or the request examined as many rows as it may.
+    fn is_done(&self) -> bool {
+        self.is_full() || self.examined >= MAX_EXAMINED_ROWS
+    }
+
+    /// Whether no further name of a listing sorted by name can match the prefix.
+    fn past_prefix(&self, name: &str) -> bool {
+        let prefix = self.query.prefix();
+        if prefix.is_empty() {
+            false
+        } else if self.query.descending() {
+            name < prefix
+        } else {
+            name > prefix && !name.starts_with(prefix)
+        }
+    }
+
+    /// Adds the entity unless filtered out, the prefix and the delimiter applying to `path`.
+    fn push(&mut self, entity: Entity, path: &str, key: CursorKey) {
+        self.last_key = Some(key);
+        self.examined += 1;
+        let prefix = self.query.prefix();
+        if !path.starts_with(prefix) || !self.query.matches_filters(&entity) {
+            return;
+        }
+
+        let delimiter = self.query.delimiter.as_deref().unwrap_or_default();
+        if !delimiter.is_empty() {
+            if let Some(index) = path[prefix.len()..].find(delimiter) {
+                let common_prefix = &path[..prefix.len() + index + delimiter.len()];
+                if self.last_prefix.as_deref() != Some(common_prefix)
+                    && !self
+                        .common_prefixes
+                        .iter()
+                        .any(|seen| seen == common_prefix)
+                {
+                    self.common_prefixes.push(common_prefix.to_string());
+                }
+                self.last_prefix = Some(common_prefix.to_string());
+                return;
+            }
+        }
+        self.entities.push(entity);
+    }
+
+    /// Feeds the stream into the listing until the page is done, matching the names of the entities.
+    /// Returns whether the stream may hold more entities.
+    async fn fill<T, E, S>(
+        &mut self,
+        stream: S,
+        sorted_by_name: bool,
+        entry: impl Fn(T) -> (Entity, CursorKey),
+    ) -> NodeClientResponse<bool>
+    where
+        S: Stream<Item = Result<T, E>> + Unpin,
+        MeowithDataError: From<E>,
+    {
+        let mut stream = stream.peekable();
+        while !self.is_done() {
+            let Some(item) = stream.next().await else {
+                return Ok(false);
+            };
+            let (entity, key) = entry(item.map_err(MeowithDataError::from)?);
+            if sorted_by_name && self.past_prefix(&entity.name) {
+                return Ok(false);
+            }
+            let name = entity.name.clone();
+            self.push(entity, &name, key);
+        }
+        Ok(Pin::new(&mut stream).peek().await.is_some())
+    }
+
+    fn finish(self, more: bool) -> NodeClientResponse<EntityList> {
+        let next_cursor = match self.last_key {
+            Some(key) if more => Some(
+                ListCursor {
+                    descending: self.query.descending(),
+                    key,
+                    common_prefix: self.last_prefix,
+                }
+                .encode()?,
+            ),
+            _ => None,
+        };
+        Ok(EntityList {
+            entities: self.entities,
+            common_prefixes: self.common_prefixes,
+            next_cursor,
+        })
+    }
+}
+
+fn directory_entity(dir: Directory, name: String) -> Entity {
+    Entity {
+        name,
+        dir: None,
+        dir_id: Some(dir.id),
+        size: 0,
+        is_dir: true,
+        created: dir.created,
+        last_modified: dir.last_modified,
+        metadata: None,
+        etag: None,
+        retain_until: None,
+        legal_hold: None,
+    }
+}
+
+fn file_entity(file: File, include_dir: bool) -> Entity {
+    Entity {
+        name: file.name,
+        dir: if include_dir {
+            Some(file.directory)
+        } else {
+            None
+        },
+        dir_id: None,
+        size: file.size as u64,
+        is_dir: false,
+        created: file.created,
+        last_modified: file.last_modified,
+        metadata: None,
+        etag: None,
+        retain_until: None,
+        legal_hold: None,
+    }
+}
+
 pub async fn do_list_bucket_files(
     app_id: Uuid,
     bucket_id: Uuid,
     accessor: BucketAccessor,
     app_data: web::Data<AppState>,
-    pagination_info: PaginationInfo,
+    query: ListQuery,
 ) -> NodeClientResponse<EntityList> {
-    pagination_info.validate()?;
+    query.validate()?;
     accessor.has_permission(&app_id, &bucket_id, *LIST_BUCKET_ALLOWANCE)?;
-    let files: Box<dyn Stream<Item = FileItem> + Unpin> = if pagination_info.is_paginated() {
-        let complete = pagination_info.completed();
-        Box::new(
-            get_files_from_bucket_paginated(
-                bucket_id,
-                &app_data.session,
-                complete.start.unwrap(),
-                complete.end.unwrap(),
-            )
-            .await?,
-        )
-    } else {
-        Box::new(get_files_from_bucket(bucket_id, &app_data.session).await?)
+    let cursor = query.cursor()?;
+    let after = match cursor.as_ref().map(|cursor| &cursor.key) {
+        None => None,
+        Some(CursorKey::File { directory, name }) => Some((*directory, name.clone())),
+        Some(_) => return Err(NodeClientError::BadRequest),
     };
 
-    collect_files(files, true).await
+    let partition = snapshot_partition(bucket_id, query.snapshot_id, &app_data).await?;
+    let files =
+        get_files_from_bucket_after(partition, after, query.descending(), &app_data.session)
+            .await?;
+    let mut listing = Listing::new(&query, cursor.as_ref());
+    // The files are sorted by the id of their directory, the prefix and the delimiter
+    // apply to their full paths.
+    let mut files = files.peekable();
+    let mut parents = HashMap::new();
+    while !listing.is_done() {
+        let Some(file) = files.next().await else {
+            break;
+        };
+        let file = file.map_err(MeowithDataError::from)?;
+        let parent =
+            get_directory_path_cached(partition, file.directory, &mut parents, &app_data.session)
+                .await?;
+        let path = file.full_path(&parent);
+        let key = CursorKey::File {
+            directory: file.directory,
+            name: file.name.clone(),
+        };
+        listing.push(file_entity(file, true), &path, key);
+    }
+    let more = listing.is_done() && Pin::new(&mut files).peek().await.is_some();
+    listing.finish(more)
 }
 
 pub async fn do_list_bucket_directories(
@@ -84,50 +380,50 @@ pub async fn do_list_bucket_directories(
     bucket_id: Uuid,
     accessor: BucketAccessor,
     app_data: web::Data<AppState>,
-    pagination_info: PaginationInfo,
+    query: ListQuery,
 ) -> NodeClientResponse<EntityList> {
-    pagination_info.validate()?;
+    query.validate()?;
     accessor.has_permission(&app_id, &bucket_id, *LIST_BUCKET_ALLOWANCE)?;
-    let mut sub_dirs: Box<dyn Stream<Item = DirectoryListItem> + Unpin> =
-        if pagination_info.is_paginated() {
-            let complete = pagination_info.completed();
-            Box::new(
-                get_directories_from_bucket_paginated(
-                    bucket_id,
-                    &app_data.session,
-                    complete.start.unwrap(),
-                    complete.end.unwrap(),
-                )
-                .await?,
-            )
-        } else {
-            Box::new(get_directories_from_bucket(bucket_id, &app_data.session).await?)
-        };
+    let cursor = query.cursor()?;
+    let after = match cursor.as_ref().map(|cursor| &cursor.key) {
+        None => None,
+        Some(CursorKey::Directory { parent, name }) => Some((parent.clone(), name.clone())),
+        Some(_) => return Err(NodeClientError::BadRequest),
+    };
 
-    let mut entries = Vec::new();
-    while let Some(dir) = sub_dirs.next().await {
-        let dir = dir.map_err(MeowithDataError::from)?;
-        entries.push(Entity {
-            name: dir.full_path(),
-            dir: None,
-            dir_id: Some(dir.id),
-            size: 0,
-            is_dir: true,
-            created: dir.created,
-            last_modified: dir.last_modified,
+    let partition = snapshot_partition(bucket_id, query.snapshot_id, &app_data).await?;
+    let directories =
+        get_directories_from_bucket_after(partition, after, query.descending(), &app_data.session)
+            .await?;
+    let mut listing = Listing::new(&query, cursor.as_ref());
+    let more = listing
+        .fill(directories, false, |dir| {
+            let key = CursorKey::Directory {
+                parent: dir.parent.clone(),
+                name: dir.name.clone(),
+            };
+            let name = dir.full_path();
+            (directory_entity(dir, name), key)
         })
-    }
-    Ok(EntityList { entities: entries })
+        .await?;
+    listing.finish(more)
 }
 
+/// Lists the subdirectories followed by the files, or the other way around when descending.
 pub async fn do_list_dir(
     e_path: EntryPath,
     accessor: BucketAccessor,
     app_data: web::Data<AppState>,
-    pagination_info: PaginationInfo,
+    query: ListQuery,
 ) -> NodeClientResponse<EntityList> {
-    pagination_info.validate()?;
+    query.validate()?;
     accessor.has_permission(&e_path.app_id, &e_path.bucket_id, *LIST_DIR_ALLOWANCE)?;
+    let cursor = query.cursor()?;
+    let mut after = match cursor.as_ref().map(|cursor| &cursor.key) {
+        None => None,
+        Some(CursorKey::Entry { is_dir, name }) => Some((*is_dir, name.clone())),
+        Some(_) => return Err(NodeClientError::BadRequest),
+    };
 
     let path = if e_path.path().is_empty() {
         None
@@ -135,89 +431,84 @@ pub async fn do_list_dir(
         Some(e_path.path())
     };
 
-    let dir = get_directory(e_path.bucket_id, path, &app_data.session).await?;
+    let partition = snapshot_partition(e_path.bucket_id, query.snapshot_id, &app_data).await?;
+    let dir = get_directory(partition, path, &app_data.session).await?;
 
-    let mut sub_dirs = get_sub_dirs(e_path.bucket_id, e_path.path(), &app_data.session).await?;
-
-    let complete = pagination_info.completed();
-
-    let mut length = complete
-        .end
-        .unwrap()
-        .saturating_sub(complete.start.unwrap());
-
-    let mut entries = Vec::new();
-    while let Some(dir) = sub_dirs.next().await {
-        if length == 0 {
+    let phases = if query.descending() {
+        [false, true]
+    } else {
+        [true, false]
+    };
+    let mut listing = Listing::new(&query, cursor.as_ref());
+    let mut more = false;
+    for is_dir in phases {
+        let phase_after = match after.take() {
+            // the cursor points into the next phase
+            Some((cursor_is_dir, name)) if cursor_is_dir != is_dir => {
+                after = Some((cursor_is_dir, name));
+                continue;
+            }
+            phase_after => phase_after.map(|(_, name)| name),
+        };
+        if listing.is_done() {
+            more = true;
             break;
         }
-        let dir = dir.map_err(MeowithDataError::from)?;
-
-        entries.push(Entity {
-            name: dir.name,
-            dir: None,
-            dir_id: Some(dir.id),
-            size: 0,
-            is_dir: true,
-            created: dir.created,
-            last_modified: dir.last_modified,
-        });
-        length -= 1;
-    }
 
-    if length > 0 {
-        let files: Box<dyn Stream<Item = FileItem> + Unpin> = Box::new(
-            get_files_from_bucket_and_directory(
-                e_path.bucket_id,
-                dir.map(|dir| dir.id),
+        let bound = query.name_bound(phase_after, listing.last_prefix.as_deref());
+        more = if is_dir {
+            let sub_dirs = get_sub_dirs_bounded(
+                partition,
+                e_path.path(),
+                bound,
+                query.descending(),
                 &app_data.session,
             )
-            .await?,
-        );
-        for entity in collect_files(files, false).await?.entities {
-            if length == 0 {
-                break;
-            }
-            entries.push(entity);
-            length -= 1;
-        }
-    }
-    Ok(EntityList { entities: entries })
-}
-
-async fn collect_files(
-    mut files: Box<dyn Stream<Item = FileItem> + Unpin>,
-    include_dir: bool,
-) -> NodeClientResponse<EntityList> {
-    let mut entities = Vec::new();
-
-    while let Some(item) = files.next().await {
-        if let Ok(item) = item {
-            entities.push(Entity {
-                name: item.name,
-                dir: if include_dir {
-                    Some(item.directory)
-                } else {
-                    None
-                },
-                dir_id: None,
-                size: item.size as u64,
-                is_dir: false,
-                created: item.created,
-                last_modified: item.last_modified,
-            });
+            .await?;
+            listing
+                .fill(sub_dirs, true, |dir| {
+                    let key = CursorKey::Entry {
+                        is_dir: true,
+                        name: dir.name.clone(),
+                    };
+                    let name = dir.name.clone();
+                    (directory_entity(dir, name), key)
+                })
+                .await?
+        } else {
+            let files = get_files_from_directory_bounded(
+                partition,
+                dir.as_ref().map(|dir| dir.id),
+                bound,
+                query.descending(),
+                &app_data.session,
+            )
+            .await?;
+            listing
+                .fill(files, true, |file| {
+                    let key = CursorKey::Entry {
+                        is_dir: false,
+                        name: file.name.clone(),
+                    };
+                    (file_entity(file, false), key)
+                })
+                .await?
+        };
+        if more {
+            break;
         }
     }
-
-    Ok(EntityList { entities })
+    listing.finish(more)
 }
 
 pub async fn do_stat_file(
     e_path: EntryPath,
+    snapshot_id: Option<Uuid>,
     accessor: BucketAccessor,
     app_data: web::Data<AppState>,
 ) -> NodeClientResponse<web::Json<Entity>> {
     accessor.has_permission(&e_path.app_id, &e_path.bucket_id, *DOWNLOAD_ALLOWANCE)?;
+    let partition = snapshot_partition(e_path.bucket_id, snapshot_id, &app_data).await?;
 
     if e_path.path().is_empty() {
         // No stating the root dir
@@ -228,8 +519,8 @@ pub async fn do_stat_file(
     let (maybe_dir, filename) = split_path(&e_path.path());
 
     // Optimistically fetch both of these at the same time to reduce latency.
-    let dir_future = get_directory(e_path.bucket_id, path, &app_data.session);
-    let file_future = get_file_dir(e_path.bucket_id, maybe_dir, filename, &app_data.session);
+    let dir_future = get_directory(partition, path, &app_data.session);
+    let file_future = get_file_dir(partition, maybe_dir, filename, &app_data.session);
 
     let (dir_result, file_result) = join!(dir_future, file_future);
 
@@ -244,8 +535,13 @@ pub async fn do_stat_file(
             is_dir: true,
             created: dir.created,
             last_modified: dir.last_modified,
+            metadata: None,
+            etag: None,
+            retain_until: None,
+            legal_hold: None,
         }))
     } else if let Ok((file, dir)) = file_result {
+        let etag = file.etag();
         Ok(web::Json(Entity {
             name: file.name,
             dir: dir.map(|dir| dir.id),
@@ -254,6 +550,10 @@ pub async fn do_stat_file(
             is_dir: false,
             created: file.created,
             last_modified: file.last_modified,
+            etag: Some(etag),
+            metadata: Some(file.metadata.map(FileMetadataDto::from).unwrap_or_default()),
+            retain_until: file.retain_until,
+            legal_hold: file.legal_hold,
         }))
     } else {
         Err(NodeClientError::InternalError)
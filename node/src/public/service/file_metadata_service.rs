@@ -0,0 +1,123 @@
+use crate::public::extractors::entry_path::EntryPath;
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::UPLOAD_OVERWRITE_ALLOWANCE;
+use crate::AppState;
+use actix_web::http::header::{
+    HeaderMap, HeaderName, HeaderValue, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE,
+};
+use actix_web::web::Data;
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use data::access::file_access::{get_file_dir, update_file_metadata};
+use data::dto::entity::FileMetadataDto;
+use data::model::file_model::FileMetadata;
+use data::pathlib::split_path;
+use mime_guess::Mime;
+use std::collections::HashMap;
+
+pub const USER_METADATA_PREFIX: &str = "x-meowith-meta-";
+/// The combined size limit of all the metadata keys and values of a file.
+const MAX_METADATA_SIZE: usize = 8 * 1024;
+
+fn header_string(headers: &HeaderMap, name: &HeaderName) -> NodeClientResponse<Option<String>> {
+    headers
+        .get(name)
+        .map(|value| {
+            value
+                .to_str()
+                .map(str::to_string)
+                .map_err(|_| NodeClientError::BadRequest)
+        })
+        .transpose()
+}
+
+/// Reads the metadata of an upload from its request headers.
+pub fn metadata_from_headers(headers: &HeaderMap) -> NodeClientResponse<Option<FileMetadata>> {
+    let mut user = HashMap::new();
+    for (name, value) in headers.iter() {
+        if let Some(key) = name.as_str().strip_prefix(USER_METADATA_PREFIX) {
+            let value = value.to_str().map_err(|_| NodeClientError::BadRequest)?;
+            user.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    let metadata = FileMetadata {
+        content_type: header_string(headers, &CONTENT_TYPE)?,
+        content_disposition: header_string(headers, &CONTENT_DISPOSITION)?,
+        cache_control: header_string(headers, &CACHE_CONTROL)?,
+        user: if user.is_empty() { None } else { Some(user) },
+    };
+    validate_metadata(&metadata)?;
+
+    Ok(if metadata == FileMetadata::default() {
+        None
+    } else {
+        Some(metadata)
+    })
+}
+
+/// Ensures the metadata can be stored and returned as response headers.
+pub fn validate_metadata(metadata: &FileMetadata) -> NodeClientResponse<()> {
+    let mut size = 0;
+    if let Some(content_type) = &metadata.content_type {
+        content_type
+            .parse::<Mime>()
+            .map_err(|_| NodeClientError::BadRequest)?;
+    }
+    for value in [
+        &metadata.content_type,
+        &metadata.content_disposition,
+        &metadata.cache_control,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        HeaderValue::from_str(value).map_err(|_| NodeClientError::BadRequest)?;
+        size += value.len();
+    }
+    for (key, value) in metadata.user.iter().flatten() {
+        // Header names are case-insensitive, only lowercase keys survive the round trip.
+        if key.is_empty() || *key != key.to_ascii_lowercase() {
+            return Err(NodeClientError::BadRequest);
+        }
+        HeaderName::try_from(format!("{USER_METADATA_PREFIX}{key}"))
+            .map_err(|_| NodeClientError::BadRequest)?;
+        HeaderValue::from_str(value).map_err(|_| NodeClientError::BadRequest)?;
+        size += key.len() + value.len();
+    }
+
+    if size > MAX_METADATA_SIZE {
+        return Err(NodeClientError::BadRequest);
+    }
+    Ok(())
+}
+
+/// Replaces the metadata of a file, the contents are left untouched.
+pub async fn do_update_metadata(
+    path: EntryPath,
+    req: FileMetadataDto,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<()> {
+    accessor.has_permission(&path.app_id, &path.bucket_id, *UPLOAD_OVERWRITE_ALLOWANCE)?;
+    let metadata = FileMetadata::from(req);
+    validate_metadata(&metadata)?;
+
+    let split_path = split_path(&path.path());
+    let (mut file, _) = get_file_dir(
+        path.bucket_id,
+        split_path.0,
+        split_path.1,
+        &app_state.session,
+    )
+    .await?;
+    file.metadata = if metadata == FileMetadata::default() {
+        None
+    } else {
+        Some(metadata)
+    };
+    if !update_file_metadata(&file, &app_state.session).await? {
+        return Err(NodeClientError::NotFound);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use crate::public::extractors::entry_path::EntryPath;
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::file_access_service::ensure_directory;
+use crate::public::service::file_action_service::{delete_chunks, do_delete_file};
+use crate::public::service::lease_service::check_lease;
+use crate::public::service::notification_service::{emit_event, BucketEvent};
+use crate::public::service::{
+    DELETE_ALLOWANCE, LIST_VERSIONS_ALLOWANCE, UPLOAD_OVERWRITE_ALLOWANCE,
+};
+use crate::AppState;
+use actix_web::web::Data;
+use chrono::Utc;
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use data::access::file_access::{
+    delete_file_version, get_bucket, get_file_version, get_file_versions, get_file_versions_before,
+    maybe_get_file_dir, restore_file_version,
+};
+use data::dto::entity::{FileVersionDto, FileVersionList, FileVersionRequest};
+use data::error::MeowithDataError;
+use data::model::file_model::{BucketEventKind, File, FileVersion};
+use data::pathlib::split_path;
+use futures_util::StreamExt;
+use serde::Deserialize;
+use tokio::try_join;
+use uuid::Uuid;
+
+const MAX_VERSION_LIMIT: usize = 1000;
+
+#[derive(Deserialize)]
+pub struct VersionListQuery {
+    pub limit: Option<usize>,
+    /// The `next_cursor` of the previous page.
+    pub cursor: Option<Uuid>,
+}
+
+/// Lists the versions of the path, newest first.
+pub async fn do_list_versions(
+    path: EntryPath,
+    query: VersionListQuery,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<FileVersionList> {
+    accessor.has_permission(&path.app_id, &path.bucket_id, *LIST_VERSIONS_ALLOWANCE)?;
+    let limit = query.limit.unwrap_or(MAX_VERSION_LIMIT);
+    if limit == 0 || limit > MAX_VERSION_LIMIT {
+        return Err(NodeClientError::BadRequest);
+    }
+
+    let session = &app_state.session;
+    let mut stream = match query.cursor {
+        Some(cursor) => {
+            get_file_versions_before(path.bucket_id, path.path(), cursor, session).await?
+        }
+        None => get_file_versions(path.bucket_id, path.path(), session).await?,
+    };
+    let mut versions: Vec<FileVersion> = vec![];
+    let mut next_cursor = None;
+    while let Some(version) = stream.next().await {
+        let version = version.map_err(MeowithDataError::from)?;
+        if versions.len() == limit {
+            next_cursor = versions.last().map(|last| last.version_id);
+            break;
+        }
+        versions.push(version);
+    }
+
+    Ok(FileVersionList {
+        versions: versions.into_iter().map(FileVersionDto::from).collect(),
+        next_cursor,
+    })
+}
+
+/// Makes the selected version the current file again.
+/// The file currently residing at the path is replaced, the same way an overwrite would.
+pub async fn do_restore_version(
+    path: EntryPath,
+    req: FileVersionRequest,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<()> {
+    accessor.has_permission(&path.app_id, &path.bucket_id, *UPLOAD_OVERWRITE_ALLOWANCE)?;
+    check_lease(path.bucket_id, &path.path(), &accessor, &app_state).await?;
+    let split_path = split_path(&path.path());
+
+    let (bucket, version, current) = try_join!(
+        get_bucket(path.app_id, path.bucket_id, &app_state.session),
+        get_file_version(
+            path.bucket_id,
+            path.path(),
+            req.version_id,
+            &app_state.session
+        ),
+        maybe_get_file_dir(
+            path.bucket_id,
+            split_path.0.clone(),
+            split_path.1.clone(),
+            &app_state.session
+        )
+    )?;
+
+    let replaced = current.0.is_some();
+    if let Some(current) = current.0 {
+        do_delete_file(&current, &path.path(), &bucket, &app_state).await?;
+    }
+
+    let directory = ensure_directory(bucket.id, split_path.0, &app_state.session).await?;
+
+    // The restored file counts as newly written for the object lock.
+    let file = File {
+        retain_until: bucket.retain_until(Utc::now()),
+        ..version.clone().into_file(directory, split_path.1)
+    };
+    restore_file_version(&version, &file, &bucket, &app_state.session).await?;
+
+    let kind = if replaced {
+        BucketEventKind::Overwritten
+    } else {
+        BucketEventKind::Created
+    };
+    let event = BucketEvent {
+        size: Some(file.size),
+        etag: Some(file.etag()),
+        ..BucketEvent::file(kind, path.path())
+    };
+    emit_event(&bucket, event, &app_state).await;
+
+    Ok(())
+}
+
+/// Permanently removes the version along with its chunks.
+pub async fn do_delete_version(
+    path: EntryPath,
+    req: FileVersionRequest,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<()> {
+    accessor.has_permission(&path.app_id, &path.bucket_id, *DELETE_ALLOWANCE)?;
+
+    let (bucket, version) = try_join!(
+        get_bucket(path.app_id, path.bucket_id, &app_state.session),
+        get_file_version(
+            path.bucket_id,
+            path.path(),
+            req.version_id,
+            &app_state.session
+        )
+    )?;
+
+    delete_chunks(path.bucket_id, &version.chunk_ids, &app_state).await;
+    delete_file_version(&version, &bucket, &app_state.session).await?;
+
+    Ok(())
+}
@@ -0,0 +1,238 @@
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::bucket_deletion_service::delete_bucket_contents;
+use crate::public::service::directory_action_service::delete_directory_tree;
+use crate::public::service::snapshot_service::create_snapshot_entries;
+use crate::public::service::FETCH_BUCKET_INFO_ALLOWANCE;
+use crate::AppState;
+use actix_web::web::Data;
+use chrono::{TimeDelta, Utc};
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use data::access::file_access::{
+    delete_running_bucket_job, get_bucket_job, get_bucket_jobs, get_running_bucket_jobs,
+    save_bucket_job, try_replace_bucket_job, try_save_held_bucket_job,
+};
+use data::dto::entity::{BucketJobDto, BucketJobList};
+use data::error::MeowithDataError;
+use data::model::file_model::{BucketJob, JobKind, JobState};
+use futures_util::StreamExt;
+use log::{info, warn};
+use logging::log_err;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// The amount of failures kept for the final report of a job.
+const MAX_JOB_ERRORS: usize = 16;
+/// The amount of processed files between two progress updates of a job.
+const JOB_PROGRESS_INTERVAL: i64 = 100;
+/// Running jobs not updated for this long are considered abandoned by their node.
+const JOB_STALE_AFTER: TimeDelta = TimeDelta::minutes(5);
+/// How often the node running a job confirms it is still alive, well within [JOB_STALE_AFTER].
+const JOB_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Records a processed file on the job, persisting the progress every so often.
+/// Jobs which are not persisted are executed inline, as a part of a request.
+/// Fails once the job was taken over by another node, which the runner must stop for.
+pub async fn record_job_progress(
+    job: &mut BucketJob,
+    path: &str,
+    result: NodeClientResponse<i64>,
+    persist: bool,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    job.processed_files += 1;
+    match result {
+        Ok(size) => job.deleted_size += size,
+        Err(err) => {
+            job.failed_files += 1;
+            let errors = job.errors.get_or_insert_with(Vec::new);
+            if errors.len() < MAX_JOB_ERRORS {
+                errors.push(format!("{path}: {err}"));
+            }
+        }
+    }
+
+    if persist && job.processed_files % JOB_PROGRESS_INTERVAL == 0 {
+        job.last_update = Utc::now();
+        match try_save_held_bucket_job(job, &app_state.session).await {
+            Ok(true) => {}
+            Ok(false) => return Err(job_taken_over(job)),
+            Err(err) => warn!("Job progress update error - {err:?}"),
+        }
+    }
+    Ok(())
+}
+
+fn job_taken_over(job: &BucketJob) -> NodeClientError {
+    NodeClientError::Locked {
+        message: format!("Job {} was taken over by another node", job.id),
+    }
+}
+
+/// Persists the job and starts executing it in the background on this node.
+pub async fn start_job(job: BucketJob, app_state: Data<AppState>) -> NodeClientResponse<BucketJob> {
+    save_bucket_job(&job, &app_state.session).await?;
+    tokio::spawn(run_job(job.clone(), app_state));
+    Ok(job)
+}
+
+/// Persists the job and executes it in the background on this node, waiting for it to finish.
+/// The job is not tied to the caller, it carries on should the caller go away.
+pub async fn run_job_to_completion(
+    job: BucketJob,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<BucketJob> {
+    save_bucket_job(&job, &app_state.session).await?;
+    Ok(tokio::spawn(run_job(job, app_state)).await?)
+}
+
+/// Runs the job until it is done, or until another node takes it over.
+async fn run_job(mut job: BucketJob, app_state: Data<AppState>) -> BucketJob {
+    info!("Running job {} {} {}", job.bucket_id, job.id, job.path);
+    let (bucket_id, id, holder) = (job.bucket_id, job.id, job.holder);
+    tokio::select! {
+        _ = execute_job(&mut job, &app_state) => {}
+        _ = job_heartbeat(bucket_id, id, holder, &app_state) => {
+            warn!("Job {id} was taken over by another node, stopping");
+        }
+    }
+    job
+}
+
+async fn execute_job(job: &mut BucketJob, app_state: &Data<AppState>) {
+    let result = match JobKind::try_from(job.kind) {
+        Ok(JobKind::DeleteDirectory) => delete_directory_tree(job, true, app_state).await,
+        Ok(JobKind::DeleteBucket) => delete_bucket_contents(job, app_state).await,
+        Ok(JobKind::CreateSnapshot) => create_snapshot_entries(job, app_state).await,
+        Err(_) => Err(NodeClientError::InternalError),
+    };
+
+    if let Err(err) = result {
+        warn!("Job {} failed {err:?}", job.id);
+        job.errors
+            .get_or_insert_with(Vec::new)
+            .push(err.to_string());
+    }
+    job.state = if job.failed_files == 0 && job.errors.is_none() {
+        JobState::Completed
+    } else {
+        JobState::Failed
+    }
+    .into();
+    job.last_update = Utc::now();
+    match try_save_held_bucket_job(job, &app_state.session).await {
+        Ok(true) => {}
+        Ok(false) => warn!("Job {} was taken over by another node", job.id),
+        Err(err) => warn!("Job completion update error - {err:?}"),
+    }
+}
+
+/// Bumps the last update of the job while this node holds it, so that it is not considered stale
+/// even when the runner makes no progress for a while. Returns once the job changed hands.
+/// The job is rewritten whole from a fresh read, progress saved by the runner in between wins.
+async fn job_heartbeat(bucket_id: Uuid, id: Uuid, holder: Uuid, app_state: &Data<AppState>) {
+    let mut interval = tokio::time::interval(JOB_HEARTBEAT_INTERVAL);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let job = match get_bucket_job(bucket_id, id, &app_state.session).await {
+            Ok(job) => job,
+            Err(err) => {
+                warn!("Job heartbeat error - {err:?}");
+                continue;
+            }
+        };
+        if job.holder != holder {
+            return;
+        }
+        let touched = BucketJob {
+            last_update: Utc::now(),
+            ..job.clone()
+        };
+        log_err(
+            "Job heartbeat error",
+            try_replace_bucket_job(&touched, &job, &app_state.session).await,
+        );
+    }
+}
+
+/// Takes over the running jobs whose node stopped reporting progress,
+/// as well as the jobs enqueued by the dashboard which no node holds yet.
+/// Jobs are idempotent, the new holder simply continues with whatever is left.
+/// Only the running jobs are read, through their index.
+pub async fn resume_stale_jobs(app_state: &Data<AppState>) -> NodeClientResponse<()> {
+    let session = &app_state.session;
+    let stale_before = Utc::now() - JOB_STALE_AFTER;
+    let mut stale = vec![];
+    for kind in [
+        JobKind::DeleteDirectory,
+        JobKind::DeleteBucket,
+        JobKind::CreateSnapshot,
+    ] {
+        let mut stream = get_running_bucket_jobs(kind, session).await?;
+        while let Some(running) = stream.next().await {
+            let running = running.map_err(MeowithDataError::from)?;
+            let job = match get_bucket_job(running.bucket_id, running.id, session).await {
+                Ok(job) if job.is_running() => job,
+                // Finished without its index entry being removed.
+                Ok(_) => {
+                    delete_running_bucket_job(&running, session).await?;
+                    continue;
+                }
+                // Indexed ahead of being written, or expired along with its index entry.
+                Err(MeowithDataError::NotFound) => continue,
+                Err(err) => return Err(err.into()),
+            };
+            if job.holder.is_nil() || job.last_update < stale_before {
+                stale.push(job);
+            }
+        }
+    }
+
+    for job in stale {
+        let claimed = BucketJob {
+            holder: app_state.req_ctx.id,
+            last_update: Utc::now(),
+            ..job.clone()
+        };
+        // Only one node wins the job, and only if its holder did not report in since it was read.
+        if try_replace_bucket_job(&claimed, &job, session).await? {
+            info!("Resuming stale job {} held by {}", job.id, job.holder);
+            tokio::spawn(run_job(claimed, app_state.clone()));
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn do_list_jobs(
+    app_id: Uuid,
+    bucket_id: Uuid,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<BucketJobList> {
+    accessor.has_permission(&app_id, &bucket_id, *FETCH_BUCKET_INFO_ALLOWANCE)?;
+
+    let mut jobs = vec![];
+    let mut stream = get_bucket_jobs(bucket_id, &app_state.session).await?;
+    while let Some(job) = stream.next().await {
+        let job = job.map_err(MeowithDataError::from)?;
+        if let Ok(job) = BucketJobDto::try_from(job) {
+            jobs.push(job);
+        }
+    }
+
+    Ok(BucketJobList { jobs })
+}
+
+pub async fn do_get_job(
+    app_id: Uuid,
+    bucket_id: Uuid,
+    job_id: Uuid,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<BucketJobDto> {
+    accessor.has_permission(&app_id, &bucket_id, *FETCH_BUCKET_INFO_ALLOWANCE)?;
+
+    let job = get_bucket_job(bucket_id, job_id, &app_state.session).await?;
+    BucketJobDto::try_from(job).map_err(|_| NodeClientError::InternalError)
+}
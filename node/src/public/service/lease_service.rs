@@ -0,0 +1,229 @@
+use crate::public::extractors::entry_path::EntryPath;
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::LEASE_ALLOWANCE;
+use crate::AppState;
+use actix_web::web::Data;
+use chrono::{DateTime, TimeDelta, Utc};
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use data::access::file_access::{
+    get_file_leases_within, maybe_get_file_leases, try_delete_file_leases, try_save_file_leases,
+};
+use data::dto::entity::{
+    LeaseAcquireRequest, LeaseDto, LeaseMode, LeaseReleaseRequest, LeaseRenewRequest,
+};
+use data::error::MeowithDataError;
+use data::model::file_model::{FileLeases, Lease};
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Longer leases have to be renewed, so that a crashed client does not block the path for long.
+const MAX_LEASE_TTL: u32 = 3600;
+/// Concurrent changes to the leases of a single path are retried this many times.
+const LEASE_WRITE_ATTEMPTS: usize = 8;
+
+/// Leases the path, which does not have to exist yet.
+/// An exclusive lease conflicts with every other lease, a shared one only with an exclusive lease,
+/// regardless of the token holding it.
+pub async fn do_acquire_lease(
+    path: EntryPath,
+    req: LeaseAcquireRequest,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<LeaseDto> {
+    accessor.has_permission(&path.app_id, &path.bucket_id, *LEASE_ALLOWANCE)?;
+    let ttl = lease_ttl(req.ttl)?;
+    let exclusive = req.mode == LeaseMode::Exclusive;
+
+    let id = Uuid::new_v4();
+    let lease = modify_leases(&path, &app_state, |leases, now| {
+        if let Some(conflict) = leases.values().find(|lease| exclusive || lease.exclusive) {
+            return Err(locked(&path.path(), conflict));
+        }
+        let lease = Lease {
+            holder: accessor.holder.clone(),
+            exclusive,
+            expires: now + ttl,
+        };
+        leases.insert(id, lease.clone());
+        Ok(lease)
+    })
+    .await?;
+
+    Ok(LeaseDto {
+        id,
+        mode: req.mode,
+        expires: lease.expires,
+    })
+}
+
+/// Extends a lease of the same token, counting the ttl from now.
+pub async fn do_renew_lease(
+    path: EntryPath,
+    req: LeaseRenewRequest,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<LeaseDto> {
+    accessor.has_permission(&path.app_id, &path.bucket_id, *LEASE_ALLOWANCE)?;
+    let ttl = lease_ttl(req.ttl)?;
+
+    let lease = modify_leases(&path, &app_state, |leases, now| {
+        let lease = leases
+            .get_mut(&req.id)
+            .filter(|lease| lease.holder == accessor.holder)
+            .ok_or(NodeClientError::NotFound)?;
+        lease.expires = now + ttl;
+        Ok(lease.clone())
+    })
+    .await?;
+
+    Ok(LeaseDto {
+        id: req.id,
+        mode: lease_mode(&lease),
+        expires: lease.expires,
+    })
+}
+
+pub async fn do_release_lease(
+    path: EntryPath,
+    req: LeaseReleaseRequest,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<()> {
+    accessor.has_permission(&path.app_id, &path.bucket_id, *LEASE_ALLOWANCE)?;
+
+    // nothing is written on failure, so the lease may be taken out before checking its holder
+    modify_leases(&path, &app_state, |leases, _| {
+        match leases.remove(&req.id) {
+            Some(lease) if lease.holder == accessor.holder => Ok(()),
+            _ => Err(NodeClientError::NotFound),
+        }
+    })
+    .await
+}
+
+/// Rejects writing to the path while another token holds an exclusive lease on it.
+pub async fn check_lease(
+    bucket_id: Uuid,
+    path: &str,
+    accessor: &BucketAccessor,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    match maybe_get_file_leases(bucket_id, path, &app_state.session).await? {
+        Some(leases) => check_leases(&leases, accessor, Utc::now()),
+        None => Ok(()),
+    }
+}
+
+/// [check_lease] for every path beneath the directory.
+pub async fn check_directory_leases(
+    bucket_id: Uuid,
+    directory: &str,
+    accessor: &BucketAccessor,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let now = Utc::now();
+    let mut stream = get_file_leases_within(bucket_id, directory, &app_state.session).await?;
+    while let Some(leases) = stream.next().await {
+        check_leases(&leases.map_err(MeowithDataError::from)?, accessor, now)?;
+    }
+    Ok(())
+}
+
+fn check_leases(
+    leases: &FileLeases,
+    accessor: &BucketAccessor,
+    now: DateTime<Utc>,
+) -> NodeClientResponse<()> {
+    match leases
+        .leases
+        .values()
+        .find(|lease| lease.exclusive && lease.is_active(now) && lease.holder != accessor.holder)
+    {
+        Some(lease) => Err(locked(&leases.path, lease)),
+        None => Ok(()),
+    }
+}
+
+/// Applies the change to the active leases of the path,
+/// retrying from a fresh read if they were changed concurrently.
+async fn modify_leases<T>(
+    path: &EntryPath,
+    app_state: &Data<AppState>,
+    mut modify: impl FnMut(&mut HashMap<Uuid, Lease>, DateTime<Utc>) -> NodeClientResponse<T>,
+) -> NodeClientResponse<T> {
+    let lease_path = path.path();
+    for _ in 0..LEASE_WRITE_ATTEMPTS {
+        let current =
+            maybe_get_file_leases(path.bucket_id, &lease_path, &app_state.session).await?;
+        let expected_version = current.as_ref().map(|leases| leases.version);
+        let mut leases = current.map(|leases| leases.leases).unwrap_or_default();
+
+        let now = Utc::now();
+        leases.retain(|_, lease| lease.is_active(now));
+        let result = modify(&mut leases, now)?;
+
+        let applied = match (
+            leases.values().map(|lease| lease.expires).max(),
+            expected_version,
+        ) {
+            (Some(expires), _) => {
+                // the row lives as long as the longest lease, rounded up
+                let ttl = (expires - now).num_seconds() as i32 + 1;
+                let leases = FileLeases {
+                    bucket_id: path.bucket_id,
+                    path: lease_path.clone(),
+                    leases,
+                    version: Uuid::new_v4(),
+                };
+                try_save_file_leases(&leases, expected_version, ttl, &app_state.session).await?
+            }
+            (None, Some(expected_version)) => {
+                try_delete_file_leases(
+                    path.bucket_id,
+                    &lease_path,
+                    expected_version,
+                    &app_state.session,
+                )
+                .await?
+            }
+            (None, None) => true,
+        };
+        if applied {
+            return Ok(result);
+        }
+    }
+
+    Err(NodeClientError::Locked {
+        message: format!("The leases of {lease_path} are under contention, try again"),
+    })
+}
+
+fn lease_ttl(ttl: u32) -> NodeClientResponse<TimeDelta> {
+    if ttl == 0 || ttl > MAX_LEASE_TTL {
+        return Err(NodeClientError::BadRequest);
+    }
+    Ok(TimeDelta::seconds(ttl as i64))
+}
+
+fn lease_mode(lease: &Lease) -> LeaseMode {
+    if lease.exclusive {
+        LeaseMode::Exclusive
+    } else {
+        LeaseMode::Shared
+    }
+}
+
+fn locked(path: &str, lease: &Lease) -> NodeClientError {
+    let access = if lease.exclusive {
+        "exclusively"
+    } else {
+        "for shared access"
+    };
+    NodeClientError::Locked {
+        message: format!(
+            "{path} is leased {access} until {}",
+            lease.expires.to_rfc3339()
+        ),
+    }
+}
@@ -4,15 +4,27 @@ use crate::public::service::reservation_service::try_reserve_chunk;
 use crate::AppState;
 use actix_web::web::Data;
 use commons::error::std_response::{NodeClientError, NodeClientResponse};
-use data::access::file_access::{get_all_files, update_file_chunks};
+use data::access::file_access::{
+    delete_snapshot_chunk, delete_snapshot_pending_chunk, get_all_file_versions, get_all_files,
+    get_all_snapshot_pending_chunks, get_all_trashed_files, get_snapshot_chunks,
+    insert_snapshot_chunk, insert_snapshot_pending_chunk, update_file_chunks,
+    update_file_version_chunks, update_trashed_file_chunks,
+};
 use data::error::MeowithDataError;
+use data::model::file_model::{FileChunk, SnapshotChunk, SnapshotPendingChunk};
 use futures_util::StreamExt;
 use log::error;
 use protocol::mdsftp::data::ReserveFlags;
 use rand::prelude::SliceRandom;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
+/// Where a local chunk was copied to.
+struct MigratedChunk {
+    server_id: Uuid,
+    chunk_id: Uuid,
+}
+
 /// Copies over a local chunk to another node
 pub async fn move_chunk(
     id: Uuid,
@@ -76,63 +88,154 @@ pub async fn move_chunk(
     Ok(space.chunk_id)
 }
 
+/// Moves every local chunk over to one of the targets, then points each row referencing it
+/// at the copy: the files along with the entries of the snapshots, which share their table,
+/// the versions, the trash and the chunks held for snapshots being taken.
+/// The local chunks are deleted only once no row references them anymore.
 pub async fn migrate_chunks(
     state: &Data<AppState>,
     targets: HashSet<Uuid>,
 ) -> NodeClientResponse<()> {
     state.pause().await;
+    let mut migrated = HashMap::new();
 
     let mut file_stream = get_all_files(&state.session).await?;
     while let Some(file) = file_stream.next().await {
         let mut file = file.map_err(MeowithDataError::from)?;
-        let mut new_chunks = HashSet::new();
-        let mut changed = false;
-        let storage_map = state.node_storage_map.write().await;
-        for mut chunk in file.chunk_ids {
-            if state.fragment_ledger.fragment_exists(&chunk.chunk_id).await {
-                let mut candidates = vec![];
-                for potential_target in &targets {
-                    if *storage_map.get(potential_target).unwrap_or(&0u64) as i64
-                        >= chunk.chunk_size
-                    {
-                        candidates.push(*potential_target);
-                    }
-                }
-                let target = *candidates.choose(&mut rand::thread_rng()).ok_or(
+        if let Some(chunks) =
+            migrate_referenced(&file.chunk_ids, &targets, &mut migrated, state).await?
+        {
+            file.chunk_ids = chunks;
+            update_file_chunks(&file, &state.session).await?;
+        }
+    }
+
+    let mut version_stream = get_all_file_versions(&state.session).await?;
+    while let Some(version) = version_stream.next().await {
+        let mut version = version.map_err(MeowithDataError::from)?;
+        if let Some(chunks) =
+            migrate_referenced(&version.chunk_ids, &targets, &mut migrated, state).await?
+        {
+            version.chunk_ids = chunks;
+            update_file_version_chunks(&version, &state.session).await?;
+        }
+    }
+
+    let mut trash_stream = get_all_trashed_files(&state.session).await?;
+    while let Some(entry) = trash_stream.next().await {
+        let mut entry = entry.map_err(MeowithDataError::from)?;
+        if let Some(chunks) =
+            migrate_referenced(&entry.chunk_ids, &targets, &mut migrated, state).await?
+        {
+            entry.chunk_ids = chunks;
+            update_trashed_file_chunks(&entry, &state.session).await?;
+        }
+    }
+
+    let pending: Vec<SnapshotPendingChunk> = get_all_snapshot_pending_chunks(&state.session)
+        .await?
+        .try_collect()
+        .await
+        .map_err(MeowithDataError::from)?;
+    for pending in pending {
+        let chunks = HashSet::from([pending.chunk.clone()]);
+        let Some(chunks) = migrate_referenced(&chunks, &targets, &mut migrated, state).await?
+        else {
+            continue;
+        };
+        let chunk = chunks.into_iter().next().unwrap();
+        let moved = SnapshotPendingChunk {
+            snapshot_id: pending.snapshot_id,
+            chunk_id: chunk.chunk_id,
+            chunk,
+        };
+        insert_snapshot_pending_chunk(&moved, &state.session).await?;
+        delete_snapshot_pending_chunk(&pending, &state.session).await?;
+    }
+
+    for (chunk_id, moved) in &migrated {
+        let holds: Vec<SnapshotChunk> = get_snapshot_chunks(vec![*chunk_id], &state.session)
+            .await?
+            .try_collect()
+            .await
+            .map_err(MeowithDataError::from)?;
+        for hold in holds {
+            let moved_hold = SnapshotChunk {
+                chunk_id: moved.chunk_id,
+                snapshot_id: hold.snapshot_id,
+            };
+            insert_snapshot_chunk(&moved_hold, &state.session).await?;
+            delete_snapshot_chunk(&hold, &state.session).await?;
+        }
+
+        let _guard = state.fragment_ledger.lock_table().write(*chunk_id).await;
+        state.fragment_ledger.delete_chunk(chunk_id).await?;
+    }
+
+    Ok(())
+}
+
+/// The chunks with the local ones replaced by their copies on the targets,
+/// moving those not migrated yet. None when none of the chunks is local.
+async fn migrate_referenced(
+    chunks: &HashSet<FileChunk>,
+    targets: &HashSet<Uuid>,
+    migrated: &mut HashMap<Uuid, MigratedChunk>,
+    state: &Data<AppState>,
+) -> NodeClientResponse<Option<HashSet<FileChunk>>> {
+    let mut new_chunks = HashSet::new();
+    let mut changed = false;
+    for chunk in chunks {
+        if !migrated.contains_key(&chunk.chunk_id)
+            && state.fragment_ledger.fragment_exists(&chunk.chunk_id).await
+        {
+            let target = {
+                let storage_map = state.node_storage_map.read().await;
+                let candidates: Vec<Uuid> = targets
+                    .iter()
+                    .filter(|target| {
+                        *storage_map.get(*target).unwrap_or(&0u64) as i64 >= chunk.chunk_size
+                    })
+                    .copied()
+                    .collect();
+                *candidates.choose(&mut rand::thread_rng()).ok_or(
                     NodeClientError::InsufficientStorage {
                         message: "No suitable candidate".to_string(),
                     },
-                )?;
-
-                // In case any transfers are still ongoing, check up with the locking table.
-                {
-                    let _guard = state
-                        .fragment_ledger
-                        .lock_table()
-                        .read(chunk.chunk_id)
-                        .await;
-                    move_chunk(chunk.chunk_id, target, state).await?;
-                }
-                {
-                    let _guard = state
-                        .fragment_ledger
-                        .lock_table()
-                        .write(chunk.chunk_id)
-                        .await;
-                    state.fragment_ledger.delete_chunk(&chunk.chunk_id).await?;
-                }
-                chunk.server_id = target;
-                new_chunks.insert(chunk);
+                )?
+            };
+
+            // In case any transfers are still ongoing, check up with the locking table.
+            let chunk_id = {
+                let _guard = state
+                    .fragment_ledger
+                    .lock_table()
+                    .read(chunk.chunk_id)
+                    .await;
+                move_chunk(chunk.chunk_id, target, state).await?
+            };
+            migrated.insert(
+                chunk.chunk_id,
+                MigratedChunk {
+                    server_id: target,
+                    chunk_id,
+                },
+            );
+        }
+
+        match migrated.get(&chunk.chunk_id) {
+            Some(moved) => {
+                new_chunks.insert(FileChunk {
+                    server_id: moved.server_id,
+                    chunk_id: moved.chunk_id,
+                    ..chunk.clone()
+                });
                 changed = true;
-            } else {
-                new_chunks.insert(chunk);
             }
-        }
-        if changed {
-            file.chunk_ids = new_chunks;
-            update_file_chunks(&file, &state.session).await?;
+            None => {
+                new_chunks.insert(chunk.clone());
+            }
         }
     }
-
-    Ok(())
+    Ok(changed.then_some(new_chunks))
 }
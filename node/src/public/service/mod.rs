@@ -2,15 +2,31 @@ use commons::permission::PermissionList;
 use data::model::permission_model::UserPermission;
 use lazy_static::lazy_static;
 
+pub mod archive_service;
+pub mod batch_service;
+pub mod bucket_deletion_service;
+pub mod change_feed_service;
 pub mod chunk_service;
+pub mod copy_service;
 pub(crate) mod directory_action_service;
 pub mod durable_transfer_session_manager;
+pub mod extract_service;
 pub mod file_access_service;
 pub mod file_action_service;
 pub mod file_io_service;
 pub mod file_list_service;
+pub mod file_metadata_service;
+pub mod file_version_service;
+pub mod job_service;
+pub mod lease_service;
 pub mod migration_service;
+pub mod notification_service;
+pub mod object_lock_service;
+pub mod partial_write_service;
+pub mod public_access_service;
 pub mod reservation_service;
+pub mod snapshot_service;
+pub mod trash_service;
 
 lazy_static! {
     static ref DELETE_ALLOWANCE: u64 =
@@ -23,12 +39,20 @@ lazy_static! {
         UserPermission::Overwrite,
     ])
     .into();
+    static ref MODIFY_ALLOWANCE: u64 =
+        PermissionList(vec![UserPermission::Write, UserPermission::Overwrite]).into();
     static ref DOWNLOAD_ALLOWANCE: u64 = PermissionList(vec![UserPermission::Read]).into();
     static ref LIST_BUCKET_ALLOWANCE: u64 = PermissionList(vec![UserPermission::ListBucket]).into();
     static ref LIST_DIR_ALLOWANCE: u64 = PermissionList(vec![UserPermission::ListDirectory]).into();
     static ref CREATE_DIRECTORY_ALLOWANCE: u64 = PermissionList(vec![UserPermission::Write]).into();
     static ref RENAME_DIRECTORY_ALLOWANCE: u64 =
         PermissionList(vec![UserPermission::Write, UserPermission::Rename]).into();
+    static ref LIST_VERSIONS_ALLOWANCE: u64 = PermissionList(vec![UserPermission::Read]).into();
+    static ref LEASE_ALLOWANCE: u64 = PermissionList(vec![UserPermission::Write]).into();
+    static ref LEGAL_HOLD_ALLOWANCE: u64 =
+        PermissionList(vec![UserPermission::Write, UserPermission::Delete]).into();
+    static ref SNAPSHOT_ALLOWANCE: u64 =
+        PermissionList(vec![UserPermission::ListBucket, UserPermission::Write]).into();
     static ref FETCH_BUCKET_INFO_ALLOWANCE: u64 =
         PermissionList(vec![UserPermission::FetchBucketInfo]).into();
 }
@@ -0,0 +1,271 @@
+use crate::public::service::change_feed_service::record_change;
+use crate::AppState;
+use actix_web::web::Data;
+use chrono::{TimeDelta, Utc};
+use commons::error::std_response::NodeClientResponse;
+use commons::webhook::validate_webhook_url;
+use data::access::file_access::{
+    delete_bucket_notification, get_bucket, get_due_bucket_notifications,
+    reschedule_bucket_notification, save_bucket_notification,
+};
+use data::dto::entity::BucketEventDto;
+use data::error::MeowithDataError;
+use data::model::file_model::{
+    Bucket, BucketEventKind, BucketNotification, NotificationConfig, NOTIFICATION_SHARDS,
+};
+use futures_util::StreamExt;
+use log::{debug, warn};
+use logging::log_err;
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// The first attempt is made right away, the worker only picks up the event after this delay.
+const DELIVERY_GRACE_SECS: i64 = 30;
+/// Doubled after every failed attempt, up to [MAX_RETRY_DELAY_SECS].
+const RETRY_DELAY_SECS: i64 = 30;
+const MAX_RETRY_DELAY_SECS: i64 = 3600;
+/// Undeliverable events are dropped after this many attempts.
+const MAX_DELIVERY_ATTEMPTS: i32 = 10;
+/// Bounds a single run of the worker for each shard, the rest is delivered on the next one.
+const DELIVERIES_PER_SHARD: i32 = 32;
+const CONCURRENT_DELIVERIES: usize = 16;
+
+pub const EVENT_HEADER: &str = "X-Meowith-Event";
+pub const DELIVERY_HEADER: &str = "X-Meowith-Delivery";
+/// `sha256=` followed by the hex encoded HMAC-SHA256 of the body, keyed with the config secret.
+pub const SIGNATURE_HEADER: &str = "X-Meowith-Signature";
+
+pub struct BucketEvent {
+    pub kind: BucketEventKind,
+    pub path: String,
+    /// The path a renamed entity was moved from.
+    pub old_path: Option<String>,
+    pub directory: bool,
+    pub size: Option<i64>,
+    pub etag: Option<String>,
+}
+
+impl BucketEvent {
+    pub fn file(kind: BucketEventKind, path: String) -> Self {
+        BucketEvent {
+            kind,
+            path,
+            old_path: None,
+            directory: false,
+            size: None,
+            etag: None,
+        }
+    }
+
+    pub fn directory(kind: BucketEventKind, path: String) -> Self {
+        BucketEvent {
+            directory: true,
+            ..BucketEvent::file(kind, path)
+        }
+    }
+}
+
+/// Records the event in the change feed of the bucket, then queues it for every matching webhook
+/// and attempts to deliver it.
+/// The change the event describes has already been made, so failures are only logged.
+pub async fn emit_event(bucket: &Bucket, event: BucketEvent, app_state: &Data<AppState>) {
+    let now = Utc::now();
+    log_err(
+        "Failed to record a change",
+        record_change(bucket.id, &event, now, &app_state.session).await,
+    );
+    let Some(configs) = &bucket.notifications else {
+        return;
+    };
+    for config in configs {
+        // a rename is of interest to both the source and the destination prefix
+        let matches = config.matches(event.kind, &event.path)
+            || event
+                .old_path
+                .as_ref()
+                .is_some_and(|old_path| config.matches(event.kind, old_path));
+        if !matches {
+            continue;
+        }
+
+        let id = Uuid::new_v4();
+        let notification = BucketNotification {
+            shard: BucketNotification::shard_of(id),
+            bucket_id: bucket.id,
+            id,
+            app_id: bucket.app_id,
+            config_id: config.id,
+            kind: event.kind.into(),
+            path: event.path.clone(),
+            old_path: event.old_path.clone(),
+            directory: event.directory,
+            size: event.size,
+            etag: event.etag.clone(),
+            occurred: now,
+            attempts: 0,
+            next_attempt: now + TimeDelta::seconds(DELIVERY_GRACE_SECS),
+            last_error: None,
+        };
+        // persisted first, so that the event survives the node going down mid delivery
+        if let Err(err) = save_bucket_notification(&notification, &app_state.session).await {
+            warn!("Failed to queue a {:?} event {err:?}", event.kind);
+            continue;
+        }
+
+        let config = config.clone();
+        let app_state = app_state.clone();
+        tokio::spawn(async move {
+            log_err(
+                "Notification delivery error",
+                deliver(notification, &config, &app_state).await,
+            );
+        });
+    }
+}
+
+/// Retries the queued events which are due, dropping those of removed webhooks.
+pub async fn deliver_pending_notifications(state: &Data<AppState>) -> NodeClientResponse<()> {
+    let now = Utc::now();
+    let mut buckets: HashMap<Uuid, Option<Bucket>> = HashMap::new();
+    let mut due = vec![];
+
+    for shard in 0..NOTIFICATION_SHARDS {
+        let mut notifications =
+            get_due_bucket_notifications(shard, now, DELIVERIES_PER_SHARD, &state.session).await?;
+        while let Some(notification) = notifications.next().await {
+            let notification = notification.map_err(MeowithDataError::from)?;
+            let bucket = match buckets.entry(notification.bucket_id) {
+                Entry::Occupied(occupied) => occupied.into_mut(),
+                Entry::Vacant(vacant) => vacant.insert(
+                    get_bucket(notification.app_id, notification.bucket_id, &state.session)
+                        .await
+                        .ok(),
+                ),
+            };
+            let config = bucket
+                .as_ref()
+                .and_then(|bucket| bucket.notifications.as_ref())
+                .and_then(|configs| {
+                    configs
+                        .iter()
+                        .find(|config| config.id == notification.config_id)
+                });
+            match config {
+                Some(config) => due.push((notification, config.clone())),
+                None => {
+                    debug!(
+                        "Dropping the event {} of a removed webhook",
+                        notification.id
+                    );
+                    delete_bucket_notification(&notification, &state.session).await?;
+                }
+            }
+        }
+    }
+
+    futures::stream::iter(due)
+        .for_each_concurrent(CONCURRENT_DELIVERIES, |(notification, config)| async move {
+            log_err(
+                "Notification delivery error",
+                deliver(notification, &config, state).await,
+            );
+        })
+        .await;
+
+    Ok(())
+}
+
+/// Attempts a delivery, removing the event once it is acknowledged
+/// or rescheduling it with an exponential backoff.
+async fn deliver(
+    mut notification: BucketNotification,
+    config: &NotificationConfig,
+    state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let error = match send(&notification, config, state).await {
+        Ok(()) => {
+            delete_bucket_notification(&notification, &state.session).await?;
+            return Ok(());
+        }
+        Err(error) => error,
+    };
+
+    notification.attempts += 1;
+    if notification.attempts >= MAX_DELIVERY_ATTEMPTS {
+        warn!(
+            "Dropping the event {} to {} after {} attempts, last error: {error}",
+            notification.id, config.url, notification.attempts
+        );
+        delete_bucket_notification(&notification, &state.session).await?;
+        return Ok(());
+    }
+
+    debug!(
+        "Delivery of the event {} to {} failed: {error}",
+        notification.id, config.url
+    );
+    let delay = RETRY_DELAY_SECS
+        .saturating_mul(1 << (notification.attempts - 1).min(16))
+        .min(MAX_RETRY_DELAY_SECS);
+    let previous_attempt = notification.next_attempt;
+    notification.next_attempt = Utc::now() + TimeDelta::seconds(delay);
+    notification.last_error = Some(error);
+    reschedule_bucket_notification(previous_attempt, &notification, &state.session).await?;
+    Ok(())
+}
+
+async fn send(
+    notification: &BucketNotification,
+    config: &NotificationConfig,
+    state: &Data<AppState>,
+) -> Result<(), String> {
+    let event = BucketEventDto::try_from(notification.clone())
+        .map_err(|_| format!("Unknown event kind {}", notification.kind))?;
+    let body = serde_json::to_vec(&event).map_err(|err| err.to_string())?;
+    let signature = sign(&config.secret, &body).map_err(|err| err.to_string())?;
+
+    // checked again, as the url may predate the validation or the configuration may have changed
+    let url = validate_webhook_url(&config.url, state.allow_private_webhooks)?;
+    let response = state
+        .webhook_client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, event_name(event.event))
+        .header(DELIVERY_HEADER, notification.id.to_string())
+        .header(SIGNATURE_HEADER, format!("sha256={signature}"))
+        .body(body)
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("Responded with {}", response.status()));
+    }
+    Ok(())
+}
+
+/// The name the kind is serialized as.
+fn event_name(kind: BucketEventKind) -> &'static str {
+    match kind {
+        BucketEventKind::Created => "created",
+        BucketEventKind::Overwritten => "overwritten",
+        BucketEventKind::Deleted => "deleted",
+        BucketEventKind::Renamed => "renamed",
+    }
+}
+
+/// Hex encoded HMAC-SHA256 of the body.
+pub fn sign(secret: &str, body: &[u8]) -> Result<String, ErrorStack> {
+    let key = PKey::hmac(secret.as_bytes())?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(body)?;
+    Ok(signer
+        .sign_to_vec()?
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
@@ -0,0 +1,66 @@
+use crate::public::extractors::entry_path::EntryPath;
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::LEGAL_HOLD_ALLOWANCE;
+use crate::AppState;
+use actix_web::web::Data;
+use chrono::Utc;
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use data::access::file_access::{get_file_dir, maybe_get_file_dir, update_file_legal_hold};
+use data::dto::entity::LegalHoldRequest;
+use data::model::file_model::File;
+use data::pathlib::split_path;
+use uuid::Uuid;
+
+/// Places or removes the legal hold of the file.
+/// A held file cannot be overwritten, renamed or deleted, regardless of its retention.
+pub async fn do_set_legal_hold(
+    path: EntryPath,
+    req: LegalHoldRequest,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<()> {
+    accessor.has_permission(&path.app_id, &path.bucket_id, *LEGAL_HOLD_ALLOWANCE)?;
+    let split_path = split_path(&path.path());
+    let (mut file, _) = get_file_dir(
+        path.bucket_id,
+        split_path.0,
+        split_path.1,
+        &app_state.session,
+    )
+    .await?;
+    file.legal_hold = Some(req.hold);
+    update_file_legal_hold(&file, &app_state.session).await?;
+
+    Ok(())
+}
+
+/// Rejects changing the file while it is held or within its retention.
+/// Applies to every token, the app owner included.
+pub fn check_object_lock(file: &File, path: &str) -> NodeClientResponse<()> {
+    if file.has_legal_hold() {
+        return Err(NodeClientError::ObjectLocked {
+            message: format!("{path} is under a legal hold"),
+        });
+    }
+    match file.retain_until {
+        Some(until) if file.is_protected(Utc::now()) => Err(NodeClientError::ObjectLocked {
+            message: format!("{path} is retained until {}", until.to_rfc3339()),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// [check_object_lock] for the file at the path, if there is one.
+pub async fn check_path_lock(
+    bucket_id: Uuid,
+    path: &str,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let split_path = split_path(path);
+    let (file, _) =
+        maybe_get_file_dir(bucket_id, split_path.0, split_path.1, &app_state.session).await?;
+    match file {
+        Some(file) => check_object_lock(&file, path),
+        None => Ok(()),
+    }
+}
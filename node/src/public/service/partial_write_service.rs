@@ -0,0 +1,687 @@
+use crate::locking::file_write_guard::FileWriteGuard;
+use crate::public::extractors::entry_path::EntryPath;
+use crate::public::extractors::preconditions::Preconditions;
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::chunk_service::{
+    commit_chunk, duplicate_chunk, merge_chunk, query_chunk, ChunkInfo,
+};
+use crate::public::service::file_access_service::create_commit_notifier;
+use crate::public::service::file_action_service::delete_chunks;
+use crate::public::service::file_io_service::{inbound_transfer, outbound_transfer};
+use crate::public::service::lease_service::check_lease;
+use crate::public::service::notification_service::{emit_event, BucketEvent};
+use crate::public::service::object_lock_service::check_object_lock;
+use crate::public::service::reservation_service::{
+    reserve_chunks, reserve_info_to_file_chunks, try_reserve_chunk, ReservationMode,
+    ReservedFragment,
+};
+use crate::public::service::MODIFY_ALLOWANCE;
+use crate::AppState;
+use actix_web::web::Data;
+use chrono::Utc;
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use data::access::file_access::{
+    archive_file_contents, get_bucket, get_file_dir, trash_file_contents, update_bucket_space,
+    update_file_contents,
+};
+use data::model::file_model::{
+    Bucket, BucketEventKind, BucketUploadSession, File, FileChunk, FileVersion, SessionState,
+    TrashedFile,
+};
+use data::pathlib::split_path;
+use futures_util::future::try_join_all;
+use log::{debug, trace};
+use protocol::mdsftp::data::{ChunkRange, CommitFlags, ReserveFlags};
+use protocol::mdsftp::handler::{AbstractReadStream, AbstractWriteStream};
+use std::collections::HashSet;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::io;
+use tokio::io::AsyncReadExt;
+use tokio::sync::Mutex;
+use tokio::try_join;
+use uuid::Uuid;
+
+/// Appended data extends the last chunk of the file in place, on the node holding it,
+/// as long as the chunk stays within this size. Past it, the data goes into new chunks.
+const APPEND_CHUNK_LIMIT: i64 = 64 * 1024 * 1024;
+const SPLICE_TRANSFER_BUFFER: usize = 64 * 1024;
+
+const REWRITE_RESERVE_FLAGS: ReserveFlags = ReserveFlags {
+    auto_start: true,
+    durable: false,
+    temp: false,
+    overwrite: false,
+};
+
+/// A part of the contents written into the new chunks.
+enum Segment {
+    /// A range of an existing chunk.
+    Chunk(FileChunk, ChunkRange),
+    /// The given amount of bytes read from the request.
+    Request(u64),
+}
+
+/// Appends `size` bytes read from the reader to the end of an existing file.
+///
+/// If the last chunk is small, the appended data is transferred to its node and merged into it,
+/// only the data being moved. Otherwise, or if the merge fails, the data goes into new chunks.
+/// A bucket keeping the previous contents gets a copy of the file instead, see [replace_contents].
+pub async fn handle_append(
+    path: EntryPath,
+    size: u64,
+    app_state: Data<AppState>,
+    accessor: BucketAccessor,
+    reader: AbstractReadStream,
+    preconditions: Preconditions,
+) -> NodeClientResponse<()> {
+    accessor.has_permission(&path.app_id, &path.bucket_id, *MODIFY_ALLOWANCE)?;
+    check_lease(path.bucket_id, &path.path(), &accessor, &app_state).await?;
+    let (_guard, bucket, file) = lock_file(&path, &app_state).await?;
+    preconditions.check_write(Some(&file.etag()))?;
+    check_object_lock(&file, &path.path())?;
+    if size == 0 {
+        return Ok(());
+    }
+    let keeps_previous = keeps_previous(&bucket);
+    let added = if keeps_previous {
+        file.size as u64 + size
+    } else {
+        size
+    };
+    check_quota(&bucket, added, &app_state).await?;
+
+    let last_chunk = file
+        .chunk_ids
+        .iter()
+        .max_by_key(|chunk| chunk.chunk_order)
+        .cloned();
+    let next_order = match &last_chunk {
+        Some(last) => last.chunk_order.checked_add(1),
+        None => Some(0),
+    };
+    let extended = match last_chunk
+        .filter(|chunk| !keeps_previous && chunk.chunk_size + size as i64 <= APPEND_CHUNK_LIMIT)
+    {
+        Some(last) => try_reserve_chunk(
+            last.server_id,
+            size,
+            bucket.id,
+            file.id,
+            &REWRITE_RESERVE_FLAGS,
+            &app_state,
+        )
+        .await
+        // Without space on its node, the chunk cannot be extended.
+        .map_err(|err| debug!("Append reservation failure {err}"))
+        .ok()
+        .map(|fragment| (last, fragment)),
+        None => None,
+    };
+
+    let mut appended = file.clone();
+    match extended {
+        Some((last, fragment)) => {
+            let chunk = FileChunk {
+                server_id: fragment.node_id,
+                chunk_id: fragment.chunk_id,
+                chunk_size: size as i64,
+                chunk_order: last.chunk_order,
+            };
+            let chunks = HashSet::from([chunk.clone()]);
+            write_chunks(
+                &path,
+                &file,
+                vec![fragment],
+                &chunks,
+                reader,
+                &[],
+                &app_state,
+            )
+            .await?;
+            let extended = extend_chunk(&last, chunk, next_order, &app_state).await?;
+            if extended.chunk_id == last.chunk_id {
+                appended.chunk_ids.remove(&last);
+            }
+            appended.chunk_ids.insert(extended);
+        }
+        None => {
+            let first_order = next_order.ok_or(NodeClientError::BadRequest)?;
+            let reservation = reserve_chunks(
+                size,
+                REWRITE_RESERVE_FLAGS,
+                bucket.id,
+                file.id,
+                ReservationMode::PreferSelfThenMostFree,
+                &app_state,
+            )
+            .await?;
+            let reserved_chunks = reserve_info_to_file_chunks(&reservation);
+            let chunks: NodeClientResponse<HashSet<FileChunk>> = reserved_chunks
+                .iter()
+                .map(|chunk| {
+                    Ok(FileChunk {
+                        chunk_order: chunk
+                            .chunk_order
+                            .checked_add(first_order)
+                            .ok_or(NodeClientError::BadRequest)?,
+                        ..chunk.clone()
+                    })
+                })
+                .collect();
+            let chunks = match chunks {
+                Ok(chunks) => chunks,
+                Err(err) => {
+                    reject_chunks(&reserved_chunks, &app_state).await?;
+                    return Err(err);
+                }
+            };
+
+            // The previous contents stay with their chunks, the file continues in copies of them.
+            let copied: Vec<FileChunk> = if keeps_previous {
+                file.chunk_ids.iter().cloned().collect()
+            } else {
+                vec![]
+            };
+            let copies = write_chunks(
+                &path,
+                &file,
+                reservation.fragments,
+                &chunks,
+                reader,
+                &copied,
+                &app_state,
+            )
+            .await?;
+            commit_chunks(chunks.iter().chain(&copies), &app_state).await?;
+            if keeps_previous {
+                appended.chunk_ids = copies;
+            }
+            appended.chunk_ids.extend(chunks);
+        }
+    }
+
+    appended.size += size as i64;
+    replace_contents(&path.path(), &file, appended, bucket, &[], &app_state).await
+}
+
+/// Merges the transferred chunk into the last chunk of the file, on the node holding both,
+/// returning the grown last chunk.
+///
+/// The merge fails if the last chunk holds more data than the file accounts for,
+/// left over by an append whose file update did not go through. The transferred chunk
+/// is then committed as the next chunk of the file instead.
+async fn extend_chunk(
+    last: &FileChunk,
+    chunk: FileChunk,
+    next_order: Option<i8>,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<FileChunk> {
+    let merged = merge_chunk(
+        chunk.server_id,
+        chunk.chunk_id,
+        last.chunk_id,
+        last.chunk_size as u64,
+        app_state,
+    )
+    .await;
+    if merged.is_ok() {
+        return Ok(FileChunk {
+            chunk_size: last.chunk_size + chunk.chunk_size,
+            ..last.clone()
+        });
+    }
+
+    debug!("Failed to merge into {} {merged:?}", last.chunk_id);
+    // An interrupted merge could have consumed the chunk all the same.
+    if query_chunk(chunk.chunk_id, chunk.server_id, app_state)
+        .await?
+        .is_none()
+    {
+        return Err(NodeClientError::InternalError);
+    }
+    let Some(chunk_order) = next_order else {
+        reject_chunks([&chunk], app_state).await?;
+        return Err(NodeClientError::BadRequest);
+    };
+    commit_chunks([&chunk], app_state).await?;
+    Ok(FileChunk {
+        chunk_order,
+        ..chunk
+    })
+}
+
+/// Overwrites the given range of an existing file, end inclusive, with the data read from the reader.
+/// The size of the file, when given, must match the current one.
+///
+/// Each chunk overlapping the range is rewritten into a new chunk on the same node,
+/// combining the untouched parts of the old chunk with the data read.
+/// A bucket keeping the previous contents gets a copy of the remaining chunks too,
+/// see [replace_contents].
+pub async fn handle_range_write(
+    path: EntryPath,
+    range: (u64, u64),
+    total: Option<u64>,
+    app_state: Data<AppState>,
+    accessor: BucketAccessor,
+    reader: AbstractReadStream,
+    preconditions: Preconditions,
+) -> NodeClientResponse<()> {
+    accessor.has_permission(&path.app_id, &path.bucket_id, *MODIFY_ALLOWANCE)?;
+    check_lease(path.bucket_id, &path.path(), &accessor, &app_state).await?;
+    let (_guard, bucket, file) = lock_file(&path, &app_state).await?;
+    preconditions.check_write(Some(&file.etag()))?;
+    check_object_lock(&file, &path.path())?;
+    let size = file.size as u64;
+    if range.0 > range.1 || range.1 >= size || total.is_some_and(|total| total != size) {
+        // The size of a file cannot be changed by a ranged write, appends do that.
+        return Err(NodeClientError::RangeUnsatisfiable { size });
+    }
+    let keeps_previous = keeps_previous(&bucket);
+    if keeps_previous {
+        check_quota(&bucket, size, &app_state).await?;
+    }
+
+    let mut sorted_chunks: Vec<&FileChunk> = file.chunk_ids.iter().collect();
+    sorted_chunks.sort_by_key(|chunk| chunk.chunk_order);
+
+    // Every affected chunk along with the part of it which gets overwritten, end exclusive.
+    let mut affected: Vec<(FileChunk, u64, u64)> = vec![];
+    let mut chunk_start = 0u64;
+    for chunk in sorted_chunks {
+        let chunk_end = chunk_start + chunk.chunk_size as u64;
+        if chunk_start <= range.1 && range.0 < chunk_end {
+            affected.push((
+                chunk.clone(),
+                range.0.saturating_sub(chunk_start),
+                (range.1 + 1).min(chunk_end) - chunk_start,
+            ));
+        }
+        chunk_start = chunk_end;
+    }
+
+    let mut segments = vec![];
+    for (chunk, start, end) in &affected {
+        if *start > 0 {
+            segments.push(Segment::Chunk(chunk.clone(), ChunkRange::new(0, *start)?));
+        }
+        segments.push(Segment::Request(end - start));
+        if *end < chunk.chunk_size as u64 {
+            segments.push(Segment::Chunk(
+                chunk.clone(),
+                ChunkRange::new(*end, chunk.chunk_size as u64)?,
+            ));
+        }
+    }
+    let mut fragments: Vec<ReservedFragment> = vec![];
+    for (chunk, _, _) in &affected {
+        let fragment = try_reserve_chunk(
+            chunk.server_id,
+            chunk.chunk_size as u64,
+            bucket.id,
+            file.id,
+            &REWRITE_RESERVE_FLAGS,
+            &app_state,
+        )
+        .await;
+        match fragment {
+            Ok(fragment) => fragments.push(fragment),
+            Err(err) => {
+                debug!("Ranged write reservation failure {err}");
+                let reserved: Vec<FileChunk> = affected
+                    .iter()
+                    .zip(&fragments)
+                    .map(|((chunk, _, _), fragment)| rewritten_chunk(chunk, fragment))
+                    .collect();
+                reject_chunks(&reserved, &app_state).await?;
+                return Err(NodeClientError::InsufficientStorage {
+                    message: format!("Failed to reserve space on {}", chunk.server_id),
+                });
+            }
+        }
+    }
+    let chunks: HashSet<FileChunk> = affected
+        .iter()
+        .zip(&fragments)
+        .map(|((chunk, _, _), fragment)| rewritten_chunk(chunk, fragment))
+        .collect();
+
+    let replaced: Vec<FileChunk> = affected.into_iter().map(|(chunk, _, _)| chunk).collect();
+    let copied: Vec<FileChunk> = if keeps_previous {
+        file.chunk_ids
+            .iter()
+            .filter(|chunk| !replaced.contains(chunk))
+            .cloned()
+            .collect()
+    } else {
+        vec![]
+    };
+
+    let source = spliced_source(segments, reader, &app_state);
+    let copies = write_chunks(
+        &path, &file, fragments, &chunks, source, &copied, &app_state,
+    )
+    .await?;
+    commit_chunks(chunks.iter().chain(&copies), &app_state).await?;
+
+    let mut rewritten = file.clone();
+    for chunk in replaced.iter().chain(&copied) {
+        rewritten.chunk_ids.remove(chunk);
+    }
+    rewritten.chunk_ids.extend(chunks);
+    rewritten.chunk_ids.extend(copies);
+    replace_contents(
+        &path.path(),
+        &file,
+        rewritten,
+        bucket,
+        &replaced,
+        &app_state,
+    )
+    .await
+}
+
+/// Whether the bucket keeps the contents a file is overwritten with, as a version or in the trash.
+fn keeps_previous(bucket: &Bucket) -> bool {
+    bucket.versioning_enabled() || bucket.trash_enabled()
+}
+
+/// Fails unless the bucket has room for `size` more bytes, besides the space reserved by uploads.
+async fn check_quota(
+    bucket: &Bucket,
+    size: u64,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let reserved = app_state
+        .upload_manager
+        .get_reserved_space(bucket.app_id, bucket.id)
+        .await?;
+    if bucket.space_taken + size as i64 + reserved > bucket.quota {
+        return Err(NodeClientError::InsufficientStorage {
+            message: format!(
+                "Insufficient space in bucket. quota={}, size={}, taken={}, reserved={}",
+                bucket.quota, size, bucket.space_taken, reserved
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Takes the write lock of the file, serializing its modifications handled by this node.
+/// Those handled by other nodes are covered by the conditional update of the file.
+async fn lock_file(
+    path: &EntryPath,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<(FileWriteGuard<Uuid>, Bucket, File)> {
+    let split_path = split_path(&path.path());
+    let (file, _) = get_file_dir(
+        path.bucket_id,
+        split_path.0.clone(),
+        split_path.1.clone(),
+        &app_state.session,
+    )
+    .await?;
+    let guard = app_state
+        .fragment_ledger
+        .lock_table()
+        .write(file.id)
+        .await
+        .map_err(|_| NodeClientError::InternalError)?;
+
+    // Read the file again, as another modification could have completed while waiting for the lock.
+    let (bucket, (file, _)) = try_join!(
+        get_bucket(path.app_id, path.bucket_id, &app_state.session),
+        get_file_dir(
+            path.bucket_id,
+            split_path.0,
+            split_path.1,
+            &app_state.session
+        )
+    )?;
+    Ok((guard, bucket, file))
+}
+
+fn rewritten_chunk(chunk: &FileChunk, fragment: &ReservedFragment) -> FileChunk {
+    FileChunk {
+        server_id: fragment.node_id,
+        chunk_id: fragment.chunk_id,
+        chunk_size: chunk.chunk_size,
+        chunk_order: chunk.chunk_order,
+    }
+}
+
+/// Streams the segments one after another.
+/// A failure leaves the stream short, which fails the transfer reading from it.
+fn spliced_source(
+    segments: Vec<Segment>,
+    reader: AbstractReadStream,
+    app_state: &Data<AppState>,
+) -> AbstractReadStream {
+    let (sender, receiver) = io::duplex(SPLICE_TRANSFER_BUFFER);
+    let writer: AbstractWriteStream = Arc::new(Mutex::new(Box::pin(sender)));
+    let app_state = app_state.clone();
+
+    tokio::spawn(async move {
+        for segment in segments {
+            match segment {
+                Segment::Chunk(chunk, range) => {
+                    // Remote chunks are read locked by their node for the duration of the transfer.
+                    let _guard = if chunk.server_id == app_state.req_ctx.id {
+                        Some(
+                            app_state
+                                .fragment_ledger
+                                .lock_table()
+                                .read(chunk.chunk_id)
+                                .await
+                                .map_err(|_| NodeClientError::InternalError)?,
+                        )
+                    } else {
+                        None
+                    };
+                    outbound_transfer(writer.clone(), &chunk, &app_state, Some(range)).await?;
+                }
+                Segment::Request(size) => {
+                    let mut writer = writer.lock().await;
+                    let reader = reader.lock().await;
+                    let mut reader = Pin::new(reader).take(size);
+                    let copied = io::copy(&mut reader, &mut *writer)
+                        .await
+                        .map_err(|_| NodeClientError::InternalError)?;
+                    if copied != size {
+                        return Err(NodeClientError::BadRequest);
+                    }
+                }
+            }
+        }
+        Ok(())
+    });
+
+    Arc::new(Mutex::new(Box::pin(receiver)))
+}
+
+/// Transfers the source into the reserved chunks, then duplicates the `copied` chunks on their
+/// nodes, keeping the new chunks alive for the duration of it and returning the copies.
+/// The new chunks are rejected on failure, committing them is left to the caller otherwise.
+async fn write_chunks(
+    path: &EntryPath,
+    file: &File,
+    fragments: Vec<ReservedFragment>,
+    chunks: &HashSet<FileChunk>,
+    source: AbstractReadStream,
+    copied: &[FileChunk],
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<HashSet<FileChunk>> {
+    let session = BucketUploadSession {
+        app_id: path.app_id,
+        bucket: path.bucket_id,
+        file_id: file.id,
+        id: Uuid::new_v4(),
+        path: path.path(),
+        size: chunks.iter().map(|chunk| chunk.chunk_size).sum(),
+        durable: false,
+        fragments: chunks.clone(),
+        last_access: Utc::now(),
+        state: SessionState::Writing.into(),
+        metadata: None,
+        expected_file: None,
+    };
+    let session_id = app_state.upload_manager.start_session(&session).await?;
+    let session = Arc::new(Mutex::new(session));
+    let notifier = create_commit_notifier(session.clone(), app_state.clone());
+
+    let transfer_result: NodeClientResponse<HashSet<FileChunk>> = async {
+        for space in fragments.into_iter() {
+            inbound_transfer(
+                source.clone(),
+                0,
+                space.node_id,
+                space.chunk_id,
+                space.channel,
+                ChunkInfo {
+                    chunk_buffer: space.chunk_buffer,
+                    size: space.size,
+                    append: false,
+                },
+                app_state,
+            )
+            .await?;
+        }
+        let mut copies = HashSet::new();
+        for chunk in copied {
+            let chunk_id = duplicate_chunk(
+                chunk.server_id,
+                chunk.chunk_id,
+                chunk.chunk_size as u64,
+                file.bucket_id,
+                file.id,
+                app_state,
+            )
+            .await?;
+            let copy = FileChunk {
+                chunk_id,
+                ..chunk.clone()
+            };
+            session.lock().await.fragments.insert(copy.clone());
+            copies.insert(copy);
+        }
+        Ok(copies)
+    }
+    .await;
+
+    // Taken before the abort, to ensure the notifier is not in the middle of updating the session.
+    let session = session.lock().await;
+    notifier.abort();
+    app_state
+        .upload_manager
+        .end_session(path.app_id, path.bucket_id, session_id)
+        .await;
+
+    if let Err(err) = &transfer_result {
+        debug!("Partial write failure, deleting. {err}");
+        reject_chunks(&session.fragments, app_state).await?;
+    }
+    transfer_result
+}
+
+async fn commit_chunks<'a>(
+    chunks: impl IntoIterator<Item = &'a FileChunk>,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let mut futures = vec![];
+    for chunk in chunks {
+        futures.push(commit_chunk(
+            CommitFlags::r#final(),
+            chunk.server_id,
+            chunk.chunk_id,
+            app_state,
+        ));
+    }
+    try_join_all(futures).await?;
+    Ok(())
+}
+
+async fn reject_chunks<'a>(
+    chunks: impl IntoIterator<Item = &'a FileChunk>,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let mut futures = vec![];
+    for chunk in chunks {
+        futures.push(commit_chunk(
+            CommitFlags::reject(),
+            chunk.server_id,
+            chunk.chunk_id,
+            app_state,
+        ));
+    }
+    try_join_all(futures).await?;
+    Ok(())
+}
+
+/// Swaps the contents of the file for the new ones, unless the file changed in the meantime.
+/// The chunks which got replaced are deleted afterward, or the new ones if the swap fails.
+/// The change is announced as the file at the path being overwritten.
+///
+/// Like an upload over the file, the previous contents are kept as a version if the bucket has
+/// versioning enabled, or else moved to the trash if it has one. They keep all of their chunks then,
+/// the new contents being expected not to share any with them.
+async fn replace_contents(
+    path: &str,
+    file: &File,
+    mut modified: File,
+    bucket: Bucket,
+    replaced: &[FileChunk],
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let now = Utc::now();
+    modified.last_modified = now;
+    modified.etag = Some(File::content_etag(Uuid::new_v4()));
+
+    trace!("Replacing the contents of {}", file.id);
+    let expected_etag = file.etag.clone();
+    let swapped = if bucket.versioning_enabled() {
+        let version = FileVersion::of(file, path.to_string(), Uuid::now_v7(), now);
+        archive_file_contents(&modified, expected_etag, &version, &app_state.session).await?
+    } else if bucket.trash_enabled() {
+        let entry = TrashedFile::of(file, &bucket, path.to_string(), Uuid::new_v4(), now);
+        trash_file_contents(&modified, expected_etag, &entry, &app_state.session).await?
+    } else {
+        update_file_contents(&modified, expected_etag, &app_state.session).await?
+    };
+    if !swapped {
+        // Another node modified, or replaced the file in the meantime.
+        // A chunk grown by an append is kept, its readers never go past the previous size.
+        let added: Vec<&FileChunk> = modified
+            .chunk_ids
+            .iter()
+            .filter(|chunk| {
+                !file
+                    .chunk_ids
+                    .iter()
+                    .any(|previous| previous.chunk_id == chunk.chunk_id)
+            })
+            .collect();
+        delete_chunks(file.bucket_id, added, app_state).await;
+        return Err(NodeClientError::PreconditionFailed);
+    }
+    let keeps_previous = keeps_previous(&bucket);
+    let size_delta = if keeps_previous {
+        modified.size
+    } else {
+        modified.size - file.size
+    };
+    if size_delta != 0 {
+        update_bucket_space(bucket.clone(), 0, size_delta, &app_state.session).await?;
+    }
+    if !keeps_previous {
+        delete_chunks(file.bucket_id, replaced, app_state).await;
+    }
+
+    let event = BucketEvent {
+        size: Some(modified.size),
+        etag: modified.etag.clone(),
+        ..BucketEvent::file(BucketEventKind::Overwritten, path.to_string())
+    };
+    emit_event(&bucket, event, app_state).await;
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use crate::caching::db::get_bucket_policy;
+use crate::public::extractors::entry_path::EntryPath;
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::rate_limiter::rate_limited;
+use crate::public::service::{DOWNLOAD_ALLOWANCE, LIST_DIR_ALLOWANCE};
+use crate::public::throttling::throttle_for;
+use crate::AppState;
+use actix_web::web::Data;
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use commons::permission::AppTokenPermit;
+
+/// The holder of anonymous requests, distinct from any token holder as those contain a slash.
+pub const PUBLIC_HOLDER: &str = "public";
+
+/// Grants an anonymous request the access the bucket exposes for the path,
+/// a download of a file or a listing of a directory.
+/// Every anonymous request to a public bucket counts towards its limit, rejected ones included,
+/// and towards the limits of the app.
+pub async fn authorize_public(
+    path: &EntryPath,
+    listing: bool,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<BucketAccessor> {
+    let public_access = get_bucket_policy(path.app_id, path.bucket_id, &app_state.session)
+        .await?
+        .public_access
+        .ok_or(NodeClientError::BadAuth)?;
+    app_state
+        .public_rate_limiter
+        .try_acquire(&path.bucket_id, public_access.requests_per_minute as u32)
+        .map_err(rate_limited)?;
+    let throttle = throttle_for(path.app_id, None, app_state).await?;
+    throttle.check_request()?;
+
+    let (allowed, allowance) = if listing {
+        (
+            public_access.listing && public_access.matches_directory(&path.path()),
+            *LIST_DIR_ALLOWANCE,
+        )
+    } else {
+        (public_access.matches(&path.path()), *DOWNLOAD_ALLOWANCE)
+    };
+    if !allowed {
+        return Err(NodeClientError::BadAuth);
+    }
+
+    Ok(BucketAccessor {
+        permits: vec![AppTokenPermit {
+            bucket_id: path.bucket_id,
+            allowance,
+        }],
+        app_id: path.app_id,
+        holder: PUBLIC_HOLDER.to_string(),
+        throttle,
+    })
+}
@@ -0,0 +1,453 @@
+use crate::public::extractors::entry_path::EntryPath;
+use crate::public::extractors::preconditions::Preconditions;
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::copy_service::copy_file;
+use crate::public::service::file_access_service::try_mkdir;
+use crate::public::service::file_action_service::delete_chunks;
+use crate::public::service::job_service::{record_job_progress, run_job_to_completion};
+use crate::public::service::{
+    DELETE_ALLOWANCE, DOWNLOAD_ALLOWANCE, LIST_BUCKET_ALLOWANCE, SNAPSHOT_ALLOWANCE,
+    UPLOAD_ALLOWANCE,
+};
+use crate::AppState;
+use actix_web::web::Data;
+use chrono::Utc;
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use data::access::file_access::{
+    delete_bucket_snapshot, delete_directory, delete_snapshot_chunk, delete_snapshot_file,
+    delete_snapshot_pending_chunk, get_bucket, get_bucket_snapshot, get_bucket_snapshots,
+    get_directories_from_bucket, get_files_from_bucket, get_snapshot_chunks,
+    get_snapshot_pending_chunks, insert_directory, insert_snapshot_chunk, insert_snapshot_file,
+    insert_snapshot_pending_chunk, maybe_get_file_by_id, maybe_get_file_version_by_file_id,
+    maybe_get_first_child_from_directory, maybe_get_first_file_from_directory,
+    maybe_get_first_snapshot_chunk, maybe_get_trashed_file_by_file_id, save_bucket_snapshot,
+    ROOT_DIR,
+};
+use data::dto::entity::{
+    BucketSnapshotDto, BucketSnapshotList, CreateSnapshotRequest, RestoreSnapshotRequest,
+};
+use data::error::MeowithDataError;
+use data::model::file_model::{
+    BucketJob, BucketSnapshot, Directory, File, FileChunk, JobKind, JobState, SnapshotChunk,
+    SnapshotPendingChunk, SnapshotState,
+};
+use futures_util::StreamExt;
+use log::debug;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use tokio::try_join;
+use uuid::Uuid;
+
+const MAX_SNAPSHOTS: usize = 64;
+const MAX_SNAPSHOT_NAME_LENGTH: usize = 255;
+/// The amount of chunks checked for holds per query, within the partition key restrictions of the cluster.
+const HOLD_QUERY_BATCH: usize = 100;
+
+/// The partition the entries are read from, the bucket itself or one of its snapshots.
+/// Only snapshots which finished copying can be browsed.
+pub async fn snapshot_partition(
+    bucket_id: Uuid,
+    snapshot_id: Option<Uuid>,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<Uuid> {
+    let Some(snapshot_id) = snapshot_id else {
+        return Ok(bucket_id);
+    };
+    let snapshot = get_bucket_snapshot(bucket_id, snapshot_id, &app_state.session).await?;
+    if !snapshot.is_ready() {
+        return Err(NodeClientError::NotFound);
+    }
+    Ok(snapshot.id)
+}
+
+/// Creates a snapshot of the bucket, copying its entries with a [JobKind::CreateSnapshot] job.
+/// The chunks are shared with the bucket, so that a snapshot only takes up metadata.
+/// The request waits for the copy, which carries on should the client go away
+/// and is resumed by another node should this one go down.
+pub async fn do_create_snapshot(
+    app_id: Uuid,
+    bucket_id: Uuid,
+    req: CreateSnapshotRequest,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<BucketSnapshotDto> {
+    accessor.has_permission(&app_id, &bucket_id, *SNAPSHOT_ALLOWANCE)?;
+    if req.name.is_empty() || req.name.len() > MAX_SNAPSHOT_NAME_LENGTH {
+        return Err(NodeClientError::BadRequest);
+    }
+    let bucket = get_bucket(app_id, bucket_id, &app_state.session).await?;
+
+    let existing: Vec<BucketSnapshot> = get_bucket_snapshots(bucket.id, &app_state.session)
+        .await?
+        .try_collect()
+        .await
+        .map_err(MeowithDataError::from)?;
+    if existing.iter().any(|snapshot| snapshot.name == req.name) {
+        return Err(NodeClientError::EntityExists);
+    }
+    if existing.len() >= MAX_SNAPSHOTS {
+        return Err(NodeClientError::BadRequest);
+    }
+
+    let snapshot = BucketSnapshot {
+        bucket_id: bucket.id,
+        id: Uuid::new_v4(),
+        app_id,
+        name: req.name,
+        state: SnapshotState::Creating.into(),
+        file_count: 0,
+        space_taken: 0,
+        created: Utc::now(),
+    };
+    // Written before anything is read, from here on the chunks released by the bucket are held.
+    save_bucket_snapshot(&snapshot, &app_state.session).await?;
+
+    let job = BucketJob::new(
+        JobKind::CreateSnapshot,
+        app_id,
+        bucket.id,
+        snapshot.id.to_string(),
+        app_state.req_ctx.id,
+    );
+    let job = run_job_to_completion(job, app_state.clone()).await?;
+    if job.state == i8::from(JobState::Failed) {
+        return Err(NodeClientError::InternalError);
+    }
+
+    let snapshot = get_bucket_snapshot(bucket.id, snapshot.id, &app_state.session).await?;
+    BucketSnapshotDto::try_from(snapshot).map_err(|_| NodeClientError::InternalError)
+}
+
+/// Copies the directories and files of the bucket into the snapshot named by the job.
+/// Entries changed while the snapshot is being taken are captured as they were when copied,
+/// the chunks they referenced being held by [snapshot_holds] until the copy is done.
+/// Copying an entry again is harmless, so that the job can be re-run after an interruption.
+pub async fn create_snapshot_entries(
+    job: &mut BucketJob,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let snapshot_id = Uuid::from_str(&job.path).map_err(|_| NodeClientError::InternalError)?;
+    let mut snapshot =
+        match get_bucket_snapshot(job.bucket_id, snapshot_id, &app_state.session).await {
+            Ok(snapshot) => snapshot,
+            // Deleted before the copy finished
+            Err(MeowithDataError::NotFound) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+    if snapshot.is_creating() {
+        let mut directories =
+            get_directories_from_bucket(job.bucket_id, &app_state.session).await?;
+        while let Some(directory) = directories.next().await {
+            let directory = directory.map_err(MeowithDataError::from)?;
+            let copy = Directory {
+                bucket_id: snapshot.id,
+                ..directory
+            };
+            insert_directory(&copy, &app_state.session).await?;
+        }
+
+        let mut files = get_files_from_bucket(job.bucket_id, &app_state.session).await?;
+        while let Some(file) = files.next().await {
+            let file = file.map_err(MeowithDataError::from)?;
+            // The chunks are held before the entry referencing them exists.
+            for chunk in &file.chunk_ids {
+                let hold = SnapshotChunk {
+                    chunk_id: chunk.chunk_id,
+                    snapshot_id: snapshot.id,
+                };
+                insert_snapshot_chunk(&hold, &app_state.session).await?;
+            }
+            let name = file.name.clone();
+            let copy = File {
+                bucket_id: snapshot.id,
+                ..file
+            };
+            let result = insert_snapshot_file(&copy, &app_state.session)
+                .await
+                .map(|_| 0)
+                .map_err(Into::into);
+            record_job_progress(job, &name, result, true, app_state).await?;
+        }
+        if job.failed_files > 0 {
+            return Ok(());
+        }
+    }
+
+    // Counted from the copies, an interrupted attempt may have copied files deleted since.
+    let mut referenced = HashSet::new();
+    let (mut file_count, mut space_taken) = (0, 0);
+    let mut copies = get_files_from_bucket(snapshot.id, &app_state.session).await?;
+    while let Some(copy) = copies.next().await {
+        let copy = copy.map_err(MeowithDataError::from)?;
+        file_count += 1;
+        space_taken += copy.size;
+        referenced.extend(copy.chunk_ids.iter().map(|chunk| chunk.chunk_id));
+    }
+
+    if snapshot.is_creating() {
+        // Re-read, so that a snapshot deleted in the meantime is not written back.
+        let current = get_bucket_snapshot(job.bucket_id, snapshot_id, &app_state.session).await?;
+        if !current.is_creating() {
+            return Ok(());
+        }
+        snapshot.file_count = file_count;
+        snapshot.space_taken = space_taken;
+        snapshot.state = SnapshotState::Ready.into();
+        save_bucket_snapshot(&snapshot, &app_state.session).await?;
+    }
+
+    // Once ready, the snapshot holds the chunks of its entries only.
+    release_pending_chunks(&snapshot, &referenced, app_state).await
+}
+
+/// The chunks released by the bucket which are held by one of its snapshots and have to be kept.
+/// While a snapshot is being taken every released chunk is held for it,
+/// as an entry copied before it changed may still reference the chunk.
+/// The entries are changed before their chunks are released, so a copy made afterwards
+/// sees the change, while one made before finds the chunk held.
+pub async fn snapshot_holds(
+    bucket_id: Uuid,
+    chunks: &[&FileChunk],
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<HashSet<Uuid>> {
+    let mut creating = false;
+    let mut snapshots = get_bucket_snapshots(bucket_id, &app_state.session).await?;
+    while let Some(snapshot) = snapshots.next().await {
+        let snapshot = snapshot.map_err(MeowithDataError::from)?;
+        if !snapshot.is_creating() {
+            continue;
+        }
+        creating = true;
+        for chunk in chunks {
+            // Recorded before the hold, so that the hold is always found and released.
+            let pending = SnapshotPendingChunk {
+                snapshot_id: snapshot.id,
+                chunk_id: chunk.chunk_id,
+                chunk: (*chunk).clone(),
+            };
+            insert_snapshot_pending_chunk(&pending, &app_state.session).await?;
+            let hold = SnapshotChunk {
+                chunk_id: chunk.chunk_id,
+                snapshot_id: snapshot.id,
+            };
+            insert_snapshot_chunk(&hold, &app_state.session).await?;
+        }
+    }
+    if creating {
+        return Ok(chunks.iter().map(|chunk| chunk.chunk_id).collect());
+    }
+
+    let mut held = HashSet::new();
+    for batch in chunks.chunks(HOLD_QUERY_BATCH) {
+        let ids = batch.iter().map(|chunk| chunk.chunk_id).collect();
+        let mut holds = get_snapshot_chunks(ids, &app_state.session).await?;
+        while let Some(hold) = holds.next().await {
+            held.insert(hold.map_err(MeowithDataError::from)?.chunk_id);
+        }
+    }
+    Ok(held)
+}
+
+/// Drops the holds placed on the chunks released while the snapshot was being taken,
+/// except for the `referenced` ones, deleting the chunks no other snapshot holds.
+async fn release_pending_chunks(
+    snapshot: &BucketSnapshot,
+    referenced: &HashSet<Uuid>,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let pending: Vec<SnapshotPendingChunk> =
+        get_snapshot_pending_chunks(snapshot.id, &app_state.session)
+            .await?
+            .try_collect()
+            .await
+            .map_err(MeowithDataError::from)?;
+    for pending in pending {
+        if !referenced.contains(&pending.chunk_id) {
+            let hold = SnapshotChunk {
+                chunk_id: pending.chunk_id,
+                snapshot_id: snapshot.id,
+            };
+            delete_snapshot_chunk(&hold, &app_state.session).await?;
+            // Released by the bucket already, nothing but another snapshot can be using it.
+            delete_chunks(snapshot.bucket_id, [&pending.chunk], app_state).await;
+        }
+        delete_snapshot_pending_chunk(&pending, &app_state.session).await?;
+    }
+    Ok(())
+}
+
+pub async fn do_list_snapshots(
+    app_id: Uuid,
+    bucket_id: Uuid,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<BucketSnapshotList> {
+    accessor.has_permission(&app_id, &bucket_id, *LIST_BUCKET_ALLOWANCE)?;
+
+    let mut snapshots = vec![];
+    let mut stream = get_bucket_snapshots(bucket_id, &app_state.session).await?;
+    while let Some(snapshot) = stream.next().await {
+        let snapshot = snapshot.map_err(MeowithDataError::from)?;
+        if let Ok(snapshot) = BucketSnapshotDto::try_from(snapshot) {
+            snapshots.push(snapshot);
+        }
+    }
+    snapshots.sort_by_key(|snapshot| snapshot.created);
+
+    Ok(BucketSnapshotList { snapshots })
+}
+
+/// Removes the snapshot along with its entries.
+/// Chunks no longer referenced by any snapshot or by the bucket are deleted.
+pub async fn do_delete_snapshot(
+    app_id: Uuid,
+    bucket_id: Uuid,
+    snapshot_id: Uuid,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<()> {
+    accessor.has_permission(&app_id, &bucket_id, *DELETE_ALLOWANCE)?;
+    let snapshot = get_bucket_snapshot(bucket_id, snapshot_id, &app_state.session).await?;
+    delete_snapshot(snapshot, &app_state).await
+}
+
+/// Removes the snapshot along with its entries, releasing its holds on the chunks.
+pub async fn delete_snapshot(
+    mut snapshot: BucketSnapshot,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    snapshot.state = SnapshotState::Deleting.into();
+    save_bucket_snapshot(&snapshot, &app_state.session).await?;
+
+    // Read every snapshot row up front, the loop below deletes them as it goes.
+    let files: Vec<File> = get_files_from_bucket(snapshot.id, &app_state.session)
+        .await?
+        .try_collect()
+        .await
+        .map_err(MeowithDataError::from)?;
+    let mut referenced = HashSet::new();
+    for file in files {
+        for chunk in &file.chunk_ids {
+            release_chunk(&snapshot, file.id, chunk, app_state).await?;
+            referenced.insert(chunk.chunk_id);
+        }
+        delete_snapshot_file(&file, &app_state.session).await?;
+    }
+    release_pending_chunks(&snapshot, &referenced, app_state).await?;
+
+    let directories: Vec<Directory> = get_directories_from_bucket(snapshot.id, &app_state.session)
+        .await?
+        .try_collect()
+        .await
+        .map_err(MeowithDataError::from)?;
+    for directory in directories {
+        delete_directory(&directory, &app_state.session).await?;
+    }
+
+    delete_bucket_snapshot(&snapshot, &app_state.session).await?;
+    Ok(())
+}
+
+/// Drops the hold of the snapshot on the chunk, deleting the chunk if nothing else uses it.
+async fn release_chunk(
+    snapshot: &BucketSnapshot,
+    file_id: Uuid,
+    chunk: &FileChunk,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let hold = SnapshotChunk {
+        chunk_id: chunk.chunk_id,
+        snapshot_id: snapshot.id,
+    };
+    delete_snapshot_chunk(&hold, &app_state.session).await?;
+    if maybe_get_first_snapshot_chunk(chunk.chunk_id, &app_state.session)
+        .await?
+        .is_some()
+    {
+        return Ok(());
+    }
+
+    let (file, version, trashed) = try_join!(
+        maybe_get_file_by_id(snapshot.bucket_id, file_id, &app_state.session),
+        maybe_get_file_version_by_file_id(snapshot.bucket_id, file_id, &app_state.session),
+        maybe_get_trashed_file_by_file_id(snapshot.bucket_id, file_id, &app_state.session)
+    )?;
+    let in_use = file.is_some_and(|file| file.chunk_ids.contains(chunk))
+        || version.is_some_and(|version| version.chunk_ids.contains(chunk))
+        || trashed.is_some_and(|trashed| trashed.chunk_ids.contains(chunk));
+    if !in_use {
+        debug!("Deleting chunk {} released by a snapshot", chunk.chunk_id);
+        delete_chunks(snapshot.bucket_id, [chunk], app_state).await;
+    }
+    Ok(())
+}
+
+/// Copies the contents of the snapshot into an empty bucket of the same app.
+/// The chunks are duplicated like for a copy, the restored bucket shares none with the snapshot.
+pub async fn do_restore_snapshot(
+    app_id: Uuid,
+    bucket_id: Uuid,
+    snapshot_id: Uuid,
+    req: RestoreSnapshotRequest,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<()> {
+    accessor.has_permission(&app_id, &bucket_id, *DOWNLOAD_ALLOWANCE)?;
+    accessor.has_permission(&app_id, &req.bucket_id, *UPLOAD_ALLOWANCE)?;
+    if req.bucket_id == bucket_id {
+        return Err(NodeClientError::BadRequest);
+    }
+
+    let (snapshot, target) = try_join!(
+        get_bucket_snapshot(bucket_id, snapshot_id, &app_state.session),
+        get_bucket(app_id, req.bucket_id, &app_state.session)
+    )?;
+    if !snapshot.is_ready() || target.is_deleting() {
+        return Err(NodeClientError::NotFound);
+    }
+    let (first_file, first_child) = try_join!(
+        maybe_get_first_file_from_directory(target.id, None, &app_state.session),
+        maybe_get_first_child_from_directory(target.id, None, &app_state.session)
+    )?;
+    if first_file.is_some() || first_child.is_some() {
+        return Err(NodeClientError::NotEmpty);
+    }
+    if snapshot.space_taken > target.quota {
+        return Err(NodeClientError::InsufficientStorage {
+            message: format!(
+                "Insufficient space in bucket. quota={}, size={}",
+                target.quota, snapshot.space_taken
+            ),
+        });
+    }
+
+    let mut paths = HashMap::from([(ROOT_DIR, String::new())]);
+    let mut directories = get_directories_from_bucket(snapshot.id, &app_state.session).await?;
+    while let Some(directory) = directories.next().await {
+        let directory = directory.map_err(MeowithDataError::from)?;
+        let path = directory.full_path();
+        try_mkdir(target.id, path.clone(), &app_state.session).await?;
+        paths.insert(directory.id, path);
+    }
+
+    let mut files = get_files_from_bucket(snapshot.id, &app_state.session).await?;
+    while let Some(file) = files.next().await {
+        let file = file.map_err(MeowithDataError::from)?;
+        let Some(parent) = paths.get(&file.directory) else {
+            continue;
+        };
+        let path = file.full_path(parent);
+        debug!("Restoring {} {path} into {}", snapshot.id, target.id);
+        copy_file(
+            &file,
+            EntryPath::from_prepared(app_id, target.id, path),
+            Preconditions::default(),
+            accessor.clone(),
+            &app_state,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,190 @@
+use crate::public::extractors::entry_path::EntryPath;
+use crate::public::middleware::user_middleware::BucketAccessor;
+use crate::public::service::file_access_service::ensure_directory;
+use crate::public::service::lease_service::{check_directory_leases, check_lease};
+use crate::public::service::notification_service::{emit_event, BucketEvent};
+use crate::public::service::{LIST_BUCKET_ALLOWANCE, UPLOAD_ALLOWANCE};
+use crate::AppState;
+use actix_web::web::Data;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::Utc;
+use commons::error::std_response::{NodeClientError, NodeClientResponse};
+use data::access::file_access::{
+    get_bucket, get_trashed_files, get_trashed_files_after, get_trashed_files_at,
+    get_trashed_files_within, maybe_get_file_dir, restore_trashed_file,
+};
+use data::dto::entity::{TrashList, TrashRestoreResponse, TrashedFileDto};
+use data::error::MeowithDataError;
+use data::model::file_model::{Bucket, BucketEventKind, File, TrashedFile};
+use data::pathlib::split_path;
+use futures_util::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const MAX_TRASH_LIMIT: usize = 1000;
+
+#[derive(Deserialize)]
+pub struct TrashListQuery {
+    pub limit: Option<usize>,
+    /// The `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+}
+
+/// The clustering key of the last entry of a page, handed out to the client as an opaque cursor.
+#[derive(Serialize, Deserialize)]
+struct TrashCursor {
+    path: String,
+    id: Uuid,
+}
+
+impl TrashCursor {
+    fn encode(&self) -> NodeClientResponse<String> {
+        let bytes = serde_cbor::to_vec(self).map_err(|_| NodeClientError::InternalError)?;
+        Ok(URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    fn decode(cursor: &str) -> NodeClientResponse<Self> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(cursor)
+            .map_err(|_| NodeClientError::BadRequest)?;
+        serde_cbor::from_slice(&bytes).map_err(|_| NodeClientError::BadRequest)
+    }
+}
+
+/// Lists the trash of the bucket, ordered by path.
+pub async fn do_list_trash(
+    app_id: Uuid,
+    bucket_id: Uuid,
+    query: TrashListQuery,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<TrashList> {
+    accessor.has_permission(&app_id, &bucket_id, *LIST_BUCKET_ALLOWANCE)?;
+    let limit = query.limit.unwrap_or(MAX_TRASH_LIMIT);
+    if limit == 0 || limit > MAX_TRASH_LIMIT {
+        return Err(NodeClientError::BadRequest);
+    }
+
+    let session = &app_state.session;
+    let mut stream = match query.cursor {
+        Some(cursor) => {
+            let cursor = TrashCursor::decode(&cursor)?;
+            get_trashed_files_after(bucket_id, (cursor.path, cursor.id), session).await?
+        }
+        None => get_trashed_files(bucket_id, session).await?,
+    };
+    let mut entries: Vec<TrashedFile> = vec![];
+    let mut next_cursor = None;
+    while let Some(entry) = stream.next().await {
+        let entry = entry.map_err(MeowithDataError::from)?;
+        if entries.len() == limit {
+            if let Some(last) = entries.last() {
+                next_cursor = Some(
+                    TrashCursor {
+                        path: last.path.clone(),
+                        id: last.id,
+                    }
+                    .encode()?,
+                );
+            }
+            break;
+        }
+        entries.push(entry);
+    }
+
+    Ok(TrashList {
+        entries: entries.into_iter().map(TrashedFileDto::from).collect(),
+        next_cursor,
+    })
+}
+
+/// Restores the trashed file at the given path, or every trashed file beneath it if it is a directory.
+/// When a path has been trashed multiple times, the most recently deleted entry is restored.
+pub async fn do_restore_trash(
+    path: EntryPath,
+    accessor: BucketAccessor,
+    app_state: Data<AppState>,
+) -> NodeClientResponse<TrashRestoreResponse> {
+    accessor.has_permission(&path.app_id, &path.bucket_id, *UPLOAD_ALLOWANCE)?;
+    let bucket = get_bucket(path.app_id, path.bucket_id, &app_state.session).await?;
+    let target = path.path();
+    if !target.is_empty() {
+        check_lease(bucket.id, &target, &accessor, &app_state).await?;
+    }
+    check_directory_leases(bucket.id, &target, &accessor, &app_state).await?;
+
+    let at = if target.is_empty() {
+        None
+    } else {
+        Some(get_trashed_files_at(bucket.id, target.clone(), &app_state.session).await?)
+    };
+    let within = get_trashed_files_within(bucket.id, &target, &app_state.session).await?;
+    let mut entries = stream::iter(at).flatten().chain(within);
+
+    let mut response = TrashRestoreResponse {
+        restored: 0,
+        skipped: 0,
+    };
+    // The entries of a path follow each other, its latest one is known once the next path comes up.
+    let mut latest: Option<TrashedFile> = None;
+    while let Some(entry) = entries.next().await {
+        let entry = entry.map_err(MeowithDataError::from)?;
+        match &latest {
+            Some(current) if current.path == entry.path => {
+                if entry.deleted > current.deleted {
+                    latest = Some(entry);
+                }
+            }
+            _ => {
+                if let Some(previous) = latest.replace(entry) {
+                    restore_entry(previous, &bucket, &mut response, &app_state).await?;
+                }
+            }
+        }
+    }
+    if let Some(last) = latest {
+        restore_entry(last, &bucket, &mut response, &app_state).await?;
+    }
+
+    if response.restored + response.skipped == 0 {
+        return Err(NodeClientError::NotFound);
+    }
+    Ok(response)
+}
+
+/// Puts the entry back at its path, unless that is occupied by a file.
+async fn restore_entry(
+    entry: TrashedFile,
+    bucket: &Bucket,
+    response: &mut TrashRestoreResponse,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let split_path = split_path(&entry.path);
+    let existing = maybe_get_file_dir(
+        bucket.id,
+        split_path.0.clone(),
+        split_path.1.clone(),
+        &app_state.session,
+    )
+    .await?;
+    if existing.0.is_some() {
+        response.skipped += 1;
+        return Ok(());
+    }
+
+    let directory = ensure_directory(bucket.id, split_path.0, &app_state.session).await?;
+    let file = File {
+        retain_until: bucket.retain_until(Utc::now()),
+        ..entry.clone().into_file(directory, split_path.1)
+    };
+    restore_trashed_file(&entry, &file, bucket, &app_state.session).await?;
+    response.restored += 1;
+    let event = BucketEvent {
+        size: Some(file.size),
+        etag: Some(file.etag()),
+        ..BucketEvent::file(BucketEventKind::Created, entry.path)
+    };
+    emit_event(bucket, event, app_state).await;
+    Ok(())
+}
@@ -0,0 +1,387 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::web::Data;
+use uuid::Uuid;
+
+use crate::caching::db::get_app_limits;
+use crate::public::rate_limiter::{rate_limited, RateLimiter, SWEEP_THRESHOLD};
+use crate::AppState;
+use commons::error::std_response::NodeClientResponse;
+use data::dto::entity::RateLimitsDto;
+
+/// How long a client turned away for its concurrent transfers is asked to wait.
+const TRANSFER_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// What a set of limits applies to.
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub enum LimitKey {
+    App(Uuid),
+    /// A token, by its app and holder.
+    Token(Uuid, String),
+}
+
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum Direction {
+    Upload,
+    Download,
+}
+
+/// Refilled at the rate, holding up to a second worth of bytes.
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, now: Instant) -> Self {
+        TokenBucket {
+            rate: rate as f64,
+            tokens: rate as f64,
+            last: now,
+        }
+    }
+
+    /// Takes the bytes out of the bucket, which may go into debt.
+    /// Returns the time it takes to pay the debt off.
+    fn take(&mut self, bytes: usize, rate: u64, now: Instant) -> Duration {
+        self.rate = rate as f64;
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate) - bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+
+    fn is_full(&self, now: Instant) -> bool {
+        self.tokens + now.duration_since(self.last).as_secs_f64() * self.rate >= self.rate
+    }
+}
+
+/// The traffic of apps and tokens passing through this node.
+pub struct TrafficLimiter {
+    requests: RateLimiter<LimitKey>,
+    transfers: Mutex<HashMap<LimitKey, u32>>,
+    bandwidth: Mutex<HashMap<(LimitKey, Direction), TokenBucket>>,
+}
+
+impl Default for TrafficLimiter {
+    fn default() -> Self {
+        TrafficLimiter {
+            requests: RateLimiter::new(Duration::from_secs(1)),
+            transfers: Mutex::new(HashMap::new()),
+            bandwidth: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// The part of a set of limits enforced by this node, `None` leaving the traffic unlimited.
+/// A count of zero turns the traffic away, as the other nodes hold the whole limit.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct NodeLimits {
+    requests_per_second: Option<u32>,
+    concurrent_transfers: Option<u32>,
+    upload_bytes_per_second: Option<u64>,
+    download_bytes_per_second: Option<u64>,
+}
+
+/// The limits a request is subject to on this node.
+#[derive(Clone)]
+pub struct Throttle {
+    limiter: Arc<TrafficLimiter>,
+    scopes: Vec<(LimitKey, NodeLimits)>,
+}
+
+impl Throttle {
+    /// Counts the request against the requests per second of every scope.
+    pub fn check_request(&self) -> NodeClientResponse<()> {
+        for (key, limits) in &self.scopes {
+            if let Some(limit) = limits.requests_per_second {
+                self.limiter
+                    .requests
+                    .try_acquire(key, limit)
+                    .map_err(rate_limited)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes a transfer slot in every scope, given back once the transfer and all its clones are dropped.
+    pub fn start_transfer(&self) -> NodeClientResponse<Transfer> {
+        let keys: Vec<LimitKey> = {
+            let mut transfers = self.limiter.transfers.lock().unwrap();
+            let limited: Vec<(&LimitKey, u32)> = self
+                .scopes
+                .iter()
+                .filter_map(|(key, limits)| Some((key, limits.concurrent_transfers?)))
+                .collect();
+            if limited
+                .iter()
+                .any(|(key, limit)| transfers.get(*key).copied().unwrap_or(0) >= *limit)
+            {
+                return Err(rate_limited(TRANSFER_RETRY_AFTER));
+            }
+            for (key, _) in &limited {
+                *transfers.entry((*key).clone()).or_insert(0) += 1;
+            }
+            limited.into_iter().map(|(key, _)| key.clone()).collect()
+        };
+
+        Ok(Transfer {
+            throttle: self.clone(),
+            _slot: Arc::new(TransferSlot {
+                limiter: self.limiter.clone(),
+                keys,
+            }),
+        })
+    }
+
+    fn bandwidth_wait(&self, direction: Direction, bytes: usize) -> Duration {
+        let now = Instant::now();
+        let mut wait = Duration::ZERO;
+        let mut buckets = None;
+        for (key, limits) in &self.scopes {
+            let rate = match direction {
+                Direction::Upload => limits.upload_bytes_per_second,
+                Direction::Download => limits.download_bytes_per_second,
+            };
+            let Some(rate) = rate else {
+                continue;
+            };
+            let buckets = buckets.get_or_insert_with(|| self.limiter.bandwidth.lock().unwrap());
+            if buckets.len() >= SWEEP_THRESHOLD {
+                buckets.retain(|_, bucket| !bucket.is_full(now));
+            }
+            let bucket = buckets
+                .entry((key.clone(), direction))
+                .or_insert_with(|| TokenBucket::new(rate, now));
+            wait = wait.max(bucket.take(bytes, rate, now));
+        }
+        wait
+    }
+}
+
+/// A transfer in progress, holding its slots.
+#[derive(Clone)]
+pub struct Transfer {
+    throttle: Throttle,
+    _slot: Arc<TransferSlot>,
+}
+
+impl Transfer {
+    /// Waits until the bytes fit within the bandwidth limits of the direction.
+    pub async fn pace(&self, direction: Direction, bytes: usize) {
+        let wait = self.throttle.bandwidth_wait(direction, bytes);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+struct TransferSlot {
+    limiter: Arc<TrafficLimiter>,
+    keys: Vec<LimitKey>,
+}
+
+impl Drop for TransferSlot {
+    fn drop(&mut self) {
+        let mut transfers = self.limiter.transfers.lock().unwrap();
+        for key in self.keys.drain(..) {
+            if let Entry::Occupied(mut entry) = transfers.entry(key) {
+                *entry.get_mut() -= 1;
+                if *entry.get() == 0 {
+                    entry.remove();
+                }
+            }
+        }
+    }
+}
+
+/// The part of the cluster wide limits falling to the node of the given rank.
+/// The limits are split between the nodes so that their shares add up to the limit exactly,
+/// the nodes of the lowest ranks taking the remainder. When a limit is below the number of nodes,
+/// the others turn the traffic away. Rates only go as low as a byte per second,
+/// the nodes could otherwise not pace a transfer at all.
+fn node_share(limits: RateLimitsDto, rank: u64, nodes: u64) -> NodeLimits {
+    let share = |limit: u64| match limit {
+        0 => None,
+        limit => Some(limit / nodes + u64::from(rank < limit % nodes)),
+    };
+    NodeLimits {
+        requests_per_second: share(limits.requests_per_second as u64).map(|share| share as u32),
+        concurrent_transfers: share(limits.concurrent_transfers as u64).map(|share| share as u32),
+        upload_bytes_per_second: share(limits.upload_bytes_per_second).map(|share| share.max(1)),
+        download_bytes_per_second: share(limits.download_bytes_per_second)
+            .map(|share| share.max(1)),
+    }
+}
+
+/// The throttle of a request to the app, made with the token of the given holder and limits, if any.
+/// The limits are split between the storage nodes the controller announced to this node,
+/// regardless of how the traffic is spread over them. This only approximates the limits,
+/// a client whose traffic lands on a few nodes is held below them.
+pub async fn throttle_for(
+    app_id: Uuid,
+    token: Option<(&str, Option<RateLimitsDto>)>,
+    app_state: &Data<AppState>,
+) -> NodeClientResponse<Throttle> {
+    let mut limits = vec![];
+    if let Some(app_limits) = get_app_limits(app_id, &app_state.session).await? {
+        limits.push((LimitKey::App(app_id), app_limits));
+    }
+    if let Some((holder, Some(token_limits))) = token {
+        limits.push((LimitKey::Token(app_id, holder.to_string()), token_limits));
+    }
+    limits.retain(|(_, limits)| !limits.is_unlimited());
+    let mut scopes = vec![];
+    if !limits.is_empty() {
+        let (rank, nodes) = node_rank(app_state).await;
+        scopes = limits
+            .into_iter()
+            .map(|(key, limits)| (key, node_share(limits, rank, nodes)))
+            .collect();
+    }
+
+    Ok(Throttle {
+        limiter: app_state.traffic_limiter.clone(),
+        scopes,
+    })
+}
+
+/// The position of this node among the storage nodes ordered by id, and their number.
+/// A node not announced yet counts itself last.
+async fn node_rank(app_state: &Data<AppState>) -> (u64, u64) {
+    let storage_map = app_state.node_storage_map.read().await;
+    let id = app_state.req_ctx.id;
+    let below = storage_map.keys().filter(|node| **node < id).count() as u64;
+    if storage_map.contains_key(&id) {
+        (below, storage_map.len() as u64)
+    } else {
+        (storage_map.len() as u64, storage_map.len() as u64 + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        node_share, Direction, LimitKey, NodeLimits, Throttle, TokenBucket, TrafficLimiter,
+    };
+    use data::dto::entity::RateLimitsDto;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use uuid::Uuid;
+
+    fn throttle(limiter: &Arc<TrafficLimiter>, limits: NodeLimits) -> Throttle {
+        Throttle {
+            limiter: limiter.clone(),
+            scopes: vec![(LimitKey::App(Uuid::nil()), limits)],
+        }
+    }
+
+    #[test]
+    fn test_node_share() {
+        let limits = RateLimitsDto {
+            requests_per_second: 5,
+            concurrent_transfers: 0,
+            upload_bytes_per_second: 1000,
+            download_bytes_per_second: 1,
+        };
+        let share = node_share(limits, 0, 2);
+        assert_eq!(share.requests_per_second, Some(3));
+        assert_eq!(share.concurrent_transfers, None);
+        assert_eq!(share.upload_bytes_per_second, Some(500));
+        assert_eq!(share.download_bytes_per_second, Some(1));
+        let share = node_share(limits, 1, 2);
+        assert_eq!(share.requests_per_second, Some(2));
+        assert_eq!(share.download_bytes_per_second, Some(1));
+
+        let share = node_share(limits, 2, 3);
+        assert_eq!(share.requests_per_second, Some(1));
+        assert_eq!(share.upload_bytes_per_second, Some(333));
+    }
+
+    #[test]
+    fn test_node_share_sums_to_limit() {
+        // a single transfer is allowed on one of the nodes only
+        let limits = RateLimitsDto {
+            requests_per_second: 7,
+            concurrent_transfers: 1,
+            ..Default::default()
+        };
+        let shares: Vec<NodeLimits> = (0..3).map(|rank| node_share(limits, rank, 3)).collect();
+        let transfers: Vec<_> = shares.iter().map(|s| s.concurrent_transfers).collect();
+        assert_eq!(transfers, vec![Some(1), Some(0), Some(0)]);
+        let requests: u32 = shares.iter().filter_map(|s| s.requests_per_second).sum();
+        assert_eq!(requests, 7);
+    }
+
+    #[test]
+    fn test_refused_share() {
+        let limiter = Arc::new(TrafficLimiter::default());
+        let throttle = throttle(
+            &limiter,
+            NodeLimits {
+                requests_per_second: Some(0),
+                concurrent_transfers: Some(0),
+                ..Default::default()
+            },
+        );
+        assert!(throttle.check_request().is_err());
+        assert!(throttle.start_transfer().is_err());
+    }
+
+    #[test]
+    fn test_transfer_slots() {
+        let limiter = Arc::new(TrafficLimiter::default());
+        let throttle = throttle(
+            &limiter,
+            NodeLimits {
+                concurrent_transfers: Some(1),
+                ..Default::default()
+            },
+        );
+        let transfer = throttle.start_transfer().unwrap();
+        let clone = transfer.clone();
+        assert!(throttle.start_transfer().is_err());
+        drop(transfer);
+        assert!(throttle.start_transfer().is_err());
+        drop(clone);
+        assert!(throttle.start_transfer().is_ok());
+        assert!(limiter.transfers.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(1000, now);
+        assert_eq!(bucket.take(1000, 1000, now), Duration::ZERO);
+        assert_eq!(bucket.take(500, 1000, now), Duration::from_millis(500));
+        assert!(!bucket.is_full(now + Duration::from_millis(1000)));
+        assert!(bucket.is_full(now + Duration::from_millis(1500)));
+        let later = now + Duration::from_millis(1500);
+        assert_eq!(bucket.take(1000, 1000, later), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_unlimited_direction() {
+        let limiter = Arc::new(TrafficLimiter::default());
+        let throttle = throttle(
+            &limiter,
+            NodeLimits {
+                upload_bytes_per_second: Some(10),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            throttle.bandwidth_wait(Direction::Download, 1000),
+            Duration::ZERO
+        );
+        assert!(throttle.bandwidth_wait(Direction::Upload, 20) > Duration::ZERO);
+    }
+}
@@ -0,0 +1,351 @@
+use crate::public::service::change_feed_service::log_change;
+use crate::public::service::file_action_service::{delete_chunks, do_delete_file};
+use crate::public::service::notification_service::BucketEvent;
+use crate::worker::{DeleteThrottle, LeaseHeartbeat, LIFECYCLE_WORKER};
+use crate::AppState;
+use actix_web::web::Data;
+use chrono::Utc;
+use commons::error::std_response::NodeClientResponse;
+use data::access::file_access::{
+    delete_file_version, delete_upload_session, get_all_buckets, get_bucket_file_versions,
+    get_directory_path_cached, get_files_from_bucket_after, get_upload_sessions, read_page,
+    ROOT_DIR,
+};
+use data::error::MeowithDataError;
+use data::model::file_model::{
+    Bucket, BucketEventKind, BucketUploadSession, File, FileChunk, FileVersion, LifecycleAction,
+    LifecycleRule,
+};
+use futures_util::StreamExt;
+use log::debug;
+use logging::log_err;
+use std::collections::HashMap;
+
+/// The files read at a time, keeping the memory used bounded for large buckets.
+const EXPIRE_PAGE_SIZE: usize = 500;
+
+fn rules_for(bucket: &Bucket, action: LifecycleAction) -> Vec<&LifecycleRule> {
+    let action: i8 = action.into();
+    bucket
+        .lifecycle_rules
+        .iter()
+        .flatten()
+        .filter(|rule| rule.action == action)
+        .collect()
+}
+
+/// Applies the lifecycle rules of every bucket.
+pub async fn apply_lifecycle_rules(state: &Data<AppState>) -> NodeClientResponse<()> {
+    let buckets: Vec<Bucket> = get_all_buckets(&state.session)
+        .await?
+        .try_collect()
+        .await
+        .map_err(MeowithDataError::from)?;
+
+    let mut throttle = DeleteThrottle::new();
+    let mut heartbeat = LeaseHeartbeat::new(LIFECYCLE_WORKER, state);
+    for bucket in buckets.iter().filter(|bucket| {
+        bucket
+            .lifecycle_rules
+            .as_ref()
+            .is_some_and(|r| !r.is_empty())
+    }) {
+        if !heartbeat.beat().await {
+            break;
+        }
+        expire_files(bucket, &mut throttle, &mut heartbeat, state).await?;
+        if !heartbeat.beat().await {
+            break;
+        }
+        purge_versions(bucket, &mut throttle, state).await?;
+        abort_uploads(bucket, &mut throttle, state).await?;
+        if throttle.remaining == 0 {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+async fn expire_files(
+    bucket: &Bucket,
+    throttle: &mut DeleteThrottle,
+    heartbeat: &mut LeaseHeartbeat<'_>,
+    state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let rules = rules_for(bucket, LifecycleAction::Expire);
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    // Each page continues after the last file of the previous one, expiring the files of a page
+    // does not shift the ones after it.
+    let now = Utc::now();
+    let mut after = None;
+    loop {
+        if !heartbeat.beat().await {
+            return Ok(());
+        }
+        let stream = get_files_from_bucket_after(bucket.id, after, false, &state.session).await?;
+        let files: Vec<File> = read_page(stream, EXPIRE_PAGE_SIZE).await?;
+        let Some(last) = files.last() else { break };
+        after = Some((last.directory, last.name.clone()));
+
+        let mut parents = HashMap::new();
+        for file in files {
+            if file.is_protected(now) {
+                // expired once the object lock allows it
+                continue;
+            }
+            let parent =
+                get_directory_path_cached(bucket.id, file.directory, &mut parents, &state.session)
+                    .await?;
+            if parent.is_empty() && file.directory != ROOT_DIR {
+                // left behind by a directory being removed
+                continue;
+            }
+            let path = file.full_path(&parent);
+            if !rules.iter().any(|rule| {
+                rule.matches(&path) && rule.is_due(now, file.created, file.last_modified)
+            }) {
+                continue;
+            }
+            if !throttle.acquire().await {
+                return Ok(());
+            }
+            debug!("Lifecycle expiring {} {path}", bucket.id);
+            let result = do_delete_file(&file, &path, bucket, state).await;
+            if result.is_ok() {
+                let event = BucketEvent {
+                    size: Some(file.size),
+                    etag: Some(file.etag()),
+                    ..BucketEvent::file(BucketEventKind::Deleted, path)
+                };
+                log_change(bucket.id, event, state).await;
+            }
+            log_err("Lifecycle expire error", result);
+        }
+    }
+
+    Ok(())
+}
+
+async fn purge_versions(
+    bucket: &Bucket,
+    throttle: &mut DeleteThrottle,
+    state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let rules = rules_for(bucket, LifecycleAction::PurgeVersions);
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let mut due: Vec<FileVersion> = vec![];
+    let mut stream = get_bucket_file_versions(bucket.id, &state.session).await?;
+    while let Some(version) = stream.next().await {
+        let version = version.map_err(MeowithDataError::from)?;
+        if rules.iter().any(|rule| {
+            rule.matches(&version.path) && rule.is_due(now, version.archived, version.last_modified)
+        }) {
+            due.push(version);
+            if due.len() >= throttle.remaining {
+                break;
+            }
+        }
+    }
+
+    for version in due {
+        if !throttle.acquire().await {
+            break;
+        }
+        debug!("Lifecycle purging version {} {}", bucket.id, version.path);
+        delete_chunks(bucket.id, &version.chunk_ids, state).await;
+        log_err(
+            "Lifecycle version purge error",
+            delete_file_version(&version, bucket, &state.session).await,
+        );
+    }
+
+    Ok(())
+}
+
+async fn abort_uploads(
+    bucket: &Bucket,
+    throttle: &mut DeleteThrottle,
+    state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    let rules = rules_for(bucket, LifecycleAction::AbortUploads);
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let mut due: Vec<BucketUploadSession> = vec![];
+    let mut stream = get_upload_sessions(bucket.app_id, bucket.id, &state.session).await?;
+    while let Some(session) = stream.next().await {
+        let session = session.map_err(MeowithDataError::from)?;
+        // Sessions carry no creation date, both conditions use the last access.
+        if session.durable
+            && rules.iter().any(|rule| {
+                rule.matches(&session.path)
+                    && rule.is_due(now, session.last_access, session.last_access)
+            })
+        {
+            due.push(session);
+            if due.len() >= throttle.remaining {
+                break;
+            }
+        }
+    }
+
+    for session in due {
+        if !throttle.acquire().await {
+            break;
+        }
+        debug!("Lifecycle aborting upload {} {}", bucket.id, session.path);
+        log_err(
+            "Lifecycle upload abort error",
+            delete_upload_session(&session, &state.session).await,
+        );
+        cancel_reservations(&session.fragments, state).await;
+        delete_chunks(bucket.id, &session.fragments, state).await;
+    }
+
+    Ok(())
+}
+
+/// Frees the space the nodes still hold for the fragments of an unfinished upload,
+/// the fragments only reaching the committed set once the upload ends.
+async fn cancel_reservations<'a>(
+    fragments: impl IntoIterator<Item = &'a FileChunk>,
+    state: &Data<AppState>,
+) {
+    for chunk in fragments {
+        if chunk.server_id == state.req_ctx.id {
+            log_err(
+                "Lifecycle reservation cancel error",
+                state
+                    .fragment_ledger
+                    .cancel_reservation(&chunk.chunk_id)
+                    .await,
+            );
+        } else if let Ok(channel) = state.mdsftp_server.pool().channel(&chunk.server_id).await {
+            log_err(
+                "Lifecycle reservation cancel error",
+                channel.cancel_reserve(chunk.chunk_id).await,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::worker::lifecycle::rules_for;
+    use chrono::{TimeDelta, Utc};
+    use data::model::file_model::{Bucket, LifecycleAction, LifecycleCondition, LifecycleRule};
+    use uuid::Uuid;
+
+    fn rule(prefix: &str, action: LifecycleAction, condition: i8, age: i64) -> LifecycleRule {
+        LifecycleRule {
+            id: Uuid::new_v4(),
+            prefix: prefix.to_string(),
+            action: action.into(),
+            condition,
+            age,
+        }
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        let age: i8 = LifecycleCondition::Age.into();
+        let all = rule("", LifecycleAction::Expire, age, 0);
+        assert!(all.matches(""));
+        assert!(all.matches("logs/a.txt"));
+
+        let logs = rule("logs/", LifecycleAction::Expire, age, 0);
+        assert!(logs.matches("logs/a.txt"));
+        assert!(logs.matches("logs/nested/a.txt"));
+        assert!(!logs.matches("logs"));
+        assert!(!logs.matches("other/logs/a.txt"));
+        assert!(!logs.matches("Logs/a.txt"));
+    }
+
+    #[test]
+    fn test_rule_is_due() {
+        let now = Utc::now();
+        let day = TimeDelta::days(1);
+        let age = rule(
+            "",
+            LifecycleAction::Expire,
+            LifecycleCondition::Age.into(),
+            day.num_seconds(),
+        );
+        assert!(age.is_due(now, now - day, now));
+        assert!(age.is_due(now, now - day * 2, now));
+        assert!(!age.is_due(now, now - day + TimeDelta::seconds(1), now - day * 2));
+
+        let modified = rule(
+            "",
+            LifecycleAction::Expire,
+            LifecycleCondition::LastModified.into(),
+            day.num_seconds(),
+        );
+        assert!(modified.is_due(now, now, now - day));
+        assert!(!modified.is_due(now, now - day * 2, now));
+
+        let immediate = rule(
+            "",
+            LifecycleAction::Expire,
+            LifecycleCondition::Age.into(),
+            0,
+        );
+        assert!(immediate.is_due(now, now, now));
+        // dates from the future, as left by clock skew between nodes, are not due yet
+        assert!(!immediate.is_due(now, now + day, now + day));
+
+        let invalid = rule("", LifecycleAction::Expire, 0, 0);
+        assert!(!invalid.is_due(now, now - day, now - day));
+    }
+
+    #[test]
+    fn test_version_is_due() {
+        // versions age from the moment they were archived
+        let now = Utc::now();
+        let week = TimeDelta::weeks(1);
+        let purge = rule(
+            "docs/",
+            LifecycleAction::PurgeVersions,
+            LifecycleCondition::Age.into(),
+            week.num_seconds(),
+        );
+        let archived = now - TimeDelta::days(3);
+        let last_modified = now - week * 4;
+        assert!(purge.matches("docs/report.pdf"));
+        assert!(!purge.is_due(now, archived, last_modified));
+        assert!(purge.is_due(now + TimeDelta::days(4), archived, last_modified));
+    }
+
+    #[test]
+    fn test_rules_for() {
+        let age: i8 = LifecycleCondition::Age.into();
+        let expire = rule("", LifecycleAction::Expire, age, 0);
+        let purge = rule("", LifecycleAction::PurgeVersions, age, 0);
+        let abort = rule("", LifecycleAction::AbortUploads, age, 0);
+        let bucket = Bucket {
+            lifecycle_rules: Some(vec![expire.clone(), purge.clone(), abort.clone()]),
+            ..Default::default()
+        };
+
+        assert_eq!(rules_for(&bucket, LifecycleAction::Expire), vec![&expire]);
+        assert_eq!(
+            rules_for(&bucket, LifecycleAction::PurgeVersions),
+            vec![&purge]
+        );
+        assert_eq!(
+            rules_for(&bucket, LifecycleAction::AbortUploads),
+            vec![&abort]
+        );
+        assert!(rules_for(&Bucket::default(), LifecycleAction::Expire).is_empty());
+    }
+}
@@ -0,0 +1,147 @@
+use crate::public::service::job_service::resume_stale_jobs;
+use crate::public::service::notification_service::deliver_pending_notifications;
+use crate::worker::lifecycle::apply_lifecycle_rules;
+use crate::worker::trash::purge_expired_trash;
+use crate::AppState;
+use actix_web::web::Data;
+use data::access::microservice_node_access::try_acquire_worker_lease;
+use log::warn;
+use logging::log_err;
+use std::time::{Duration, Instant};
+use tokio::task::AbortHandle;
+use tokio::time;
+use tokio::time::{Interval, MissedTickBehavior};
+
+mod lifecycle;
+mod trash;
+
+const WORKER_TASK_INTERVAL: u64 = 60;
+/// Outlives a couple of intervals, so that the holder keeps the lease between runs.
+const WORKER_LEASE_TTL: i32 = 3 * WORKER_TASK_INTERVAL as i32;
+
+const TRASH_PURGE_WORKER: &str = "trash_purge";
+const LIFECYCLE_WORKER: &str = "lifecycle";
+const JOB_RECOVERY_WORKER: &str = "job_recovery";
+const NOTIFICATION_WORKER: &str = "notifications";
+/// Jobs enqueued by the dashboard are picked up within this many seconds.
+const JOB_QUEUE_INTERVAL: u64 = 10;
+/// Lifecycle rules scan whole buckets, so they are evaluated less often.
+/// Their lease is still renewed every tick, so that it does not lapse between the passes.
+const LIFECYCLE_EVERY_TICKS: u64 = 10;
+
+/// The maximum amount of objects removed in a single run, the remainder is left for the next one.
+const DELETES_PER_RUN: usize = 1000;
+const DELETE_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Spaces out the deletes of a run, so that the worker does not starve the cluster.
+struct DeleteThrottle {
+    remaining: usize,
+    interval: Interval,
+}
+
+impl DeleteThrottle {
+    fn new() -> Self {
+        let mut interval = time::interval(DELETE_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        DeleteThrottle {
+            remaining: DELETES_PER_RUN,
+            interval,
+        }
+    }
+
+    /// Splits what is left of the run evenly between the buckets still to be visited,
+    /// so that a bucket with a large backlog cannot starve those after it.
+    fn share(&self, buckets_left: usize) -> usize {
+        self.remaining.div_ceil(buckets_left.max(1))
+    }
+
+    async fn acquire(&mut self) -> bool {
+        if self.remaining == 0 {
+            return false;
+        }
+        self.remaining -= 1;
+        self.interval.tick().await;
+        true
+    }
+}
+
+/// Starts the cluster-wide background workers.
+/// Each of them runs on a single node at a time, the one currently holding its lease.
+pub fn initialize_workers(state: Data<AppState>) -> AbortHandle {
+    tokio::spawn(async move {
+        let mut interval = time::interval(Duration::from_secs(WORKER_TASK_INTERVAL));
+        let mut job_interval = time::interval(Duration::from_secs(JOB_QUEUE_INTERVAL));
+        let mut tick = 0u64;
+        loop {
+            tokio::select! {
+                _ = job_interval.tick() => {
+                    if holds_lease(JOB_RECOVERY_WORKER, &state).await {
+                        log_err("Job recovery error", resume_stale_jobs(&state).await);
+                    }
+                }
+                _ = interval.tick() => {
+                    run_periodic_workers(tick, &state).await;
+                    tick += 1;
+                }
+            }
+        }
+    })
+    .abort_handle()
+}
+
+async fn run_periodic_workers(tick: u64, state: &Data<AppState>) {
+    if holds_lease(TRASH_PURGE_WORKER, state).await {
+        log_err("Trash purge error", purge_expired_trash(state).await);
+    }
+    if holds_lease(LIFECYCLE_WORKER, state).await && tick.is_multiple_of(LIFECYCLE_EVERY_TICKS) {
+        log_err("Lifecycle error", apply_lifecycle_rules(state).await);
+    }
+    if holds_lease(NOTIFICATION_WORKER, state).await {
+        log_err(
+            "Notification delivery error",
+            deliver_pending_notifications(state).await,
+        );
+    }
+}
+
+/// Renews the lease of a worker while a run is in progress, as a run may outlast the lease.
+struct LeaseHeartbeat<'a> {
+    name: &'static str,
+    state: &'a Data<AppState>,
+    renewed: Instant,
+    held: bool,
+}
+
+impl<'a> LeaseHeartbeat<'a> {
+    fn new(name: &'static str, state: &'a Data<AppState>) -> Self {
+        LeaseHeartbeat {
+            name,
+            state,
+            renewed: Instant::now(),
+            held: true,
+        }
+    }
+
+    /// Returns whether the run may go on, renewing the lease once an interval has passed since the last renewal.
+    /// Once lost, the lease is left to its new holder for the rest of the run.
+    async fn beat(&mut self) -> bool {
+        if self.held && self.renewed.elapsed() >= Duration::from_secs(WORKER_TASK_INTERVAL) {
+            self.held = holds_lease(self.name, self.state).await;
+            self.renewed = Instant::now();
+            if !self.held {
+                warn!("Lost the {} worker lease during a run", self.name);
+            }
+        }
+        self.held
+    }
+}
+
+async fn holds_lease(name: &str, state: &Data<AppState>) -> bool {
+    match try_acquire_worker_lease(name, state.req_ctx.id, WORKER_LEASE_TTL, &state.session).await {
+        Ok(held) => held,
+        Err(err) => {
+            warn!("Failed to acquire the {name} worker lease {err:?}");
+            false
+        }
+    }
+}
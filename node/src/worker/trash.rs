@@ -0,0 +1,83 @@
+use crate::public::service::file_action_service::delete_chunks;
+use crate::worker::DeleteThrottle;
+use crate::AppState;
+use actix_web::web::Data;
+use chrono::Utc;
+use commons::error::std_response::NodeClientResponse;
+use data::access::file_access::{get_all_buckets, get_trashed_files, purge_trashed_file};
+use data::error::MeowithDataError;
+use data::model::file_model::{Bucket, TrashedFile};
+use futures_util::StreamExt;
+use log::debug;
+use rand::Rng;
+
+/// Permanently removes trashed files past their retention, along with their chunks.
+/// Only buckets which had a trash at some point are visited,
+/// those whose trash got disabled still hold the entries trashed before.
+pub async fn purge_expired_trash(state: &Data<AppState>) -> NodeClientResponse<()> {
+    let buckets: Vec<Bucket> = get_all_buckets(&state.session)
+        .await?
+        .try_collect()
+        .await
+        .map_err(MeowithDataError::from)?;
+
+    let buckets: Vec<&Bucket> = buckets
+        .iter()
+        .filter(|bucket| bucket.trash_retention.is_some())
+        .collect();
+    if buckets.is_empty() {
+        return Ok(());
+    }
+
+    // Starting at a random bucket, the ones left over when the run ends differ from run to run.
+    let start = rand::thread_rng().gen_range(0..buckets.len());
+    let mut throttle = DeleteThrottle::new();
+    for (visited, bucket) in buckets
+        .iter()
+        .cycle()
+        .skip(start)
+        .take(buckets.len())
+        .enumerate()
+    {
+        let share = throttle.share(buckets.len() - visited);
+        purge_bucket_trash(bucket, share, &mut throttle, state).await?;
+        if throttle.remaining == 0 {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Purges up to `share` of the expired entries of the bucket.
+async fn purge_bucket_trash(
+    bucket: &Bucket,
+    share: usize,
+    throttle: &mut DeleteThrottle,
+    state: &Data<AppState>,
+) -> NodeClientResponse<()> {
+    // Only the due entries are gathered, at most the share of the bucket.
+    let now = Utc::now();
+    let mut due: Vec<TrashedFile> = vec![];
+    let mut stream = get_trashed_files(bucket.id, &state.session).await?;
+    while let Some(entry) = stream.next().await {
+        let entry = entry.map_err(MeowithDataError::from)?;
+        if entry.expires <= now {
+            due.push(entry);
+            if due.len() >= share {
+                break;
+            }
+        }
+    }
+
+    for entry in due {
+        if !throttle.acquire().await {
+            break;
+        }
+        debug!("Purging trashed file {} {}", entry.bucket_id, entry.path);
+        delete_chunks(bucket.id, &entry.chunk_ids, state).await;
+        purge_trashed_file(&entry, Some(bucket), &state.session).await?;
+    }
+
+    Ok(())
+}
@@ -116,6 +116,36 @@ impl MDSFTPChannel {
         self._internal_channel.commit(flags, id).await
     }
 
+    /// Appends the contents of an uncommitted chunk to the committed target chunk,
+    /// provided the target still holds exactly `target_size` bytes.
+    /// The merged chunk is removed, the contents of the target below `target_size` stay untouched.
+    #[inline(always)]
+    pub async fn merge(
+        &self,
+        id: Uuid,
+        target_id: Uuid,
+        target_size: u64,
+    ) -> MDSFTPResult<CommitResult> {
+        self._internal_channel
+            .merge(id, target_id, target_size)
+            .await
+    }
+
+    /// Copies the first `size` bytes of a committed chunk into a new, uncommitted chunk
+    /// on the same node, associated with the given file. The copy is committed like an upload.
+    #[inline(always)]
+    pub async fn duplicate(
+        &self,
+        id: Uuid,
+        size: u64,
+        associated_file_id: Uuid,
+        associated_bucket_id: Uuid,
+    ) -> MDSFTPResult<ReserveResult> {
+        self._internal_channel
+            .duplicate(id, size, associated_file_id, associated_bucket_id)
+            .await
+    }
+
     #[inline(always)]
     pub async fn request_put(
         &self,
@@ -366,6 +396,31 @@ impl InternalMDSFTPChannel {
         { lock.recv().await.ok_or(MDSFTPError::Interrupted)? }
     });
 
+    internal_sender_method!(payload_buffer this lock merge(MDSFTPPacketType::Merge, chunk_id: Uuid, target_id: Uuid, target_size: u64) -> MDSFTPResult<CommitResult> {
+        {
+            let _ = payload_buffer.write(chunk_id.as_bytes().as_slice());
+            let _ = payload_buffer.write(target_id.as_bytes().as_slice());
+            let _ = payload_buffer.write(&target_size.to_be_bytes());
+            let (tx, rx) = mpsc::channel(1);
+            *this.commit_sender.lock().await = Some(tx);
+            rx
+        }
+        { lock.recv().await.ok_or(MDSFTPError::Interrupted)? }
+    });
+
+    internal_sender_method!(payload_buffer this lock duplicate(MDSFTPPacketType::Duplicate, chunk_id: Uuid, size: u64, associated_file_id: Uuid, associated_bucket_id: Uuid) -> MDSFTPResult<ReserveResult> {
+        {
+            let _ = payload_buffer.write(chunk_id.as_bytes().as_slice());
+            let _ = payload_buffer.write(&size.to_be_bytes());
+            let _ = payload_buffer.write(associated_file_id.as_bytes().as_slice());
+            let _ = payload_buffer.write(associated_bucket_id.as_bytes().as_slice());
+            let (tx, rx) = mpsc::channel(1);
+            *this.reserve_sender.lock().await = Some(tx);
+            rx
+        }
+        { lock.recv().await.ok_or(MDSFTPError::Interrupted)? }
+    });
+
     internal_sender_method!(payload_buffer this none respond_commit_err(MDSFTPPacketType::CommitErr, err: ChunkErrorKind) -> MDSFTPResult<()> {
         {
             let kind: u8 = err.into();
@@ -725,6 +780,45 @@ impl InternalMDSFTPChannel {
                         .handle_commit(handler_channel, chunk_id, flags)
                         .await?;
                 }
+                MDSFTPPacketType::Merge => {
+                    let chunk_id = Uuid::from_bytes(
+                        Bytes::try_from(&packet.payload.as_slice()[0..16])
+                            .map_err(MDSFTPError::from)?,
+                    );
+                    let target_id = Uuid::from_bytes(
+                        Bytes::try_from(&packet.payload.as_slice()[16..32])
+                            .map_err(MDSFTPError::from)?,
+                    );
+                    let target_size =
+                        u64::from_be_bytes(packet.payload[32..40].try_into().unwrap());
+                    handler
+                        .handle_merge(handler_channel, chunk_id, target_id, target_size)
+                        .await?;
+                }
+                MDSFTPPacketType::Duplicate => {
+                    let chunk_id = Uuid::from_bytes(
+                        Bytes::try_from(&packet.payload.as_slice()[0..16])
+                            .map_err(MDSFTPError::from)?,
+                    );
+                    let size = u64::from_be_bytes(packet.payload[16..24].try_into().unwrap());
+                    let associated_file_id = Uuid::from_bytes(
+                        Bytes::try_from(&packet.payload.as_slice()[24..40])
+                            .map_err(MDSFTPError::from)?,
+                    );
+                    let associated_bucket_id = Uuid::from_bytes(
+                        Bytes::try_from(&packet.payload.as_slice()[40..56])
+                            .map_err(MDSFTPError::from)?,
+                    );
+                    handler
+                        .handle_duplicate(
+                            handler_channel,
+                            chunk_id,
+                            size,
+                            associated_bucket_id,
+                            associated_file_id,
+                        )
+                        .await?;
+                }
                 MDSFTPPacketType::Query => {
                     let chunk_id = Uuid::from_bytes(
                         Bytes::try_from(&packet.payload.as_slice()[0..16])
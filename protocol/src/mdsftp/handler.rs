@@ -68,6 +68,23 @@ pub trait ChannelPacketHandler: Send {
         flags: CommitFlags,
     ) -> MDSFTPResult<()>;
 
+    async fn handle_merge(
+        &mut self,
+        channel: Channel,
+        chunk_id: Uuid,
+        target_id: Uuid,
+        target_size: u64,
+    ) -> MDSFTPResult<()>;
+
+    async fn handle_duplicate(
+        &mut self,
+        channel: Channel,
+        chunk_id: Uuid,
+        size: u64,
+        associated_bucket_id: Uuid,
+        associated_file_id: Uuid,
+    ) -> MDSFTPResult<()>;
+
     async fn handle_query(&mut self, channel: Channel, chunk_id: Uuid) -> MDSFTPResult<()>;
 
     async fn handle_interrupt(&mut self) -> MDSFTPResult<()>;
@@ -22,6 +22,8 @@ pub(crate) enum MDSFTPPacketType {
     CommitErr = 17u8,
     Query = 18u8,
     QueryResponse = 19u8,
+    Merge = 20u8,
+    Duplicate = 21u8,
     ChannelOpen = 128u8,
     ChannelClose = 129u8,
     ChannelErr = 130u8,
@@ -57,6 +59,8 @@ impl MDSFTPPacketType {
             MDSFTPPacketType::QueryResponse => 9,
             MDSFTPPacketType::CommitOk => 0,
             MDSFTPPacketType::CommitErr => 1,
+            MDSFTPPacketType::Merge => 40,
+            MDSFTPPacketType::Duplicate => 16 + 8 + 16 + 16,
         }
     }
 }
@@ -209,6 +209,31 @@ mod int_tests {
             Ok(())
         }
 
+        async fn handle_merge(
+            &mut self,
+            channel: Channel,
+            _chunk_id: Uuid,
+            _target_id: Uuid,
+            _target_size: u64,
+        ) -> MDSFTPResult<()> {
+            channel.respond_commit_ok().await?;
+            channel.close(Ok(())).await;
+            Ok(())
+        }
+
+        async fn handle_duplicate(
+            &mut self,
+            channel: Channel,
+            _chunk_id: Uuid,
+            _size: u64,
+            _associated_bucket_id: Uuid,
+            _associated_file_id: Uuid,
+        ) -> MDSFTPResult<()> {
+            channel.respond_reserve_ok(Uuid::new_v4(), 0).await?;
+            channel.close(Ok(())).await;
+            Ok(())
+        }
+
         async fn handle_query(&mut self, channel: Channel, _chunk_id: Uuid) -> MDSFTPResult<()> {
             channel.respond_query(123456789, true).await?;
             channel.close(Ok(())).await;
@@ -344,6 +369,15 @@ mod int_tests {
             assert!(put_req.is_ok());
         }
 
+        {
+            debug!("Test Duplicate");
+            let channel = client_pool.channel(&id1).await.unwrap();
+            let duplicate_req = channel
+                .duplicate(Uuid::new_v4(), 1024, Uuid::new_v4(), Uuid::new_v4())
+                .await;
+            assert!(duplicate_req.is_ok());
+        }
+
         client_pool.shutdown().await;
         server.shutdown().await;
     }
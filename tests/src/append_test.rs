@@ -0,0 +1,122 @@
+use crate::directory_test::{create_file, stat_entity, NodeArgs, FILE_SIZE};
+use crate::file_transfer_test::fetch_bucket_info;
+use crate::utils::Logger;
+use crate::versioning_test::{delete_version, list_versions, restore_version, set_versioning};
+use data::dto::entity::{AppDto, BucketDto};
+use http::header::{AUTHORIZATION, CONTENT_LENGTH, IF_MATCH};
+use http::StatusCode;
+use log::info;
+use reqwest_middleware::ClientBuilder;
+
+const APPENDED: &[u8] = b"appended";
+
+pub(crate) async fn append(name: &str, if_match: Option<&str>, args: &NodeArgs<'_>) -> StatusCode {
+    let mut req = args
+        .client
+        .post(format!(
+            "http://{}/api/file/append/{}/{}/{}",
+            args.node, args.app_id, args.bucket_id, name,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .header(CONTENT_LENGTH, APPENDED.len().to_string());
+    if let Some(etag) = if_match {
+        req = req.header(IF_MATCH, etag);
+    }
+    req.body(APPENDED.to_vec()).send().await.expect("").status()
+}
+
+async fn download(name: &str, args: &NodeArgs<'_>) -> Vec<u8> {
+    args.client
+        .get(format!(
+            "http://{}/api/file/download/{}/{}/{}",
+            args.node, args.app_id, args.bucket_id, name,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .send()
+        .await
+        .expect("")
+        .bytes()
+        .await
+        .unwrap()
+        .to_vec()
+}
+
+pub async fn append_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: &user_token,
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    create_file("append", &args).await;
+    let initial = fetch_bucket_info(args.token, args.app_id, args.bucket_id, args.client).await;
+    let original = stat_entity("append", &args).await;
+    header!("Created test file");
+
+    assert!(append("append", None, &args).await.is_success());
+    assert!(append("append", None, &args).await.is_success());
+    let appended = stat_entity("append", &args).await;
+    assert_eq!(appended.size, (FILE_SIZE + 2 * APPENDED.len()) as u64);
+    assert_ne!(appended.etag, original.etag);
+    assert!(appended.last_modified > original.last_modified);
+
+    let mut expected = vec![0u8; FILE_SIZE];
+    expected.extend_from_slice(APPENDED);
+    expected.extend_from_slice(APPENDED);
+    assert_eq!(download("append", &args).await, expected);
+
+    let bucket = fetch_bucket_info(args.token, args.app_id, args.bucket_id, args.client).await;
+    assert_eq!(bucket.file_count, initial.file_count);
+    assert_eq!(
+        bucket.space_taken,
+        initial.space_taken + 2 * APPENDED.len() as i64
+    );
+    header!("Appended to the file");
+
+    let stale = format!("\"{}\"", original.etag.unwrap());
+    assert_eq!(
+        append("append", Some(&stale), &args).await,
+        StatusCode::PRECONDITION_FAILED
+    );
+    let current = format!("\"{}\"", appended.etag.unwrap());
+    assert!(append("append", Some(&current), &args).await.is_success());
+    expected.extend_from_slice(APPENDED);
+    header!("Appended with preconditions");
+
+    set_versioning(true, &args).await;
+    let before = fetch_bucket_info(args.token, args.app_id, args.bucket_id, args.client).await;
+    assert!(append("append", None, &args).await.is_success());
+    let mut versioned = expected.clone();
+    versioned.extend_from_slice(APPENDED);
+    assert_eq!(download("append", &args).await, versioned);
+    let versions = list_versions("append", &args).await;
+    assert_eq!(versions.versions.len(), 1);
+    let bucket = fetch_bucket_info(args.token, args.app_id, args.bucket_id, args.client).await;
+    assert_eq!(bucket.file_count, before.file_count);
+    assert_eq!(
+        bucket.space_taken,
+        before.space_taken + versioned.len() as i64
+    );
+    header!("Append retained a version");
+
+    restore_version("append", versions.versions[0].version_id, &args).await;
+    assert_eq!(download("append", &args).await, expected);
+    let versions = list_versions("append", &args).await;
+    set_versioning(false, &args).await;
+    delete_version("append", versions.versions[0].version_id, &args).await;
+    assert!(list_versions("append", &args).await.versions.is_empty());
+    header!("Restored the version before the append");
+
+    assert_eq!(
+        append("append_missing", None, &args).await,
+        StatusCode::NOT_FOUND
+    );
+    header!("Rejected append to a missing file");
+}
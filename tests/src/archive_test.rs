@@ -0,0 +1,98 @@
+use crate::directory_test::{create_dir, create_file, NodeArgs, FILE_SIZE};
+use crate::utils::Logger;
+use data::dto::entity::{AppDto, BucketDto};
+use http::header::{AUTHORIZATION, CONTENT_TYPE};
+use http::StatusCode;
+use log::info;
+use reqwest_middleware::ClientBuilder;
+
+async fn archive(path: &str, format: &str, args: &NodeArgs<'_>) -> Result<Vec<u8>, StatusCode> {
+    let response = args
+        .client
+        .get(format!(
+            "http://{}/api/directory/archive/{}/{}/{path}?format={format}",
+            args.node, args.app_id, args.bucket_id,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .send()
+        .await
+        .unwrap();
+    if !response.status().is_success() {
+        return Err(response.status());
+    }
+    let content_type = response.headers()[CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .to_string();
+    info!("Archive content type {content_type}");
+    Ok(response.bytes().await.unwrap().to_vec())
+}
+
+/// The names and sizes of the tar entries.
+fn tar_entries(archive: &[u8]) -> Vec<(String, u64)> {
+    let mut entries = vec![];
+    let mut offset = 0;
+    while archive[offset] != 0 {
+        let header = &archive[offset..offset + 512];
+        let name_length = header[..100].iter().position(|byte| *byte == 0).unwrap();
+        let name = String::from_utf8(header[..name_length].to_vec()).unwrap();
+        let size = std::str::from_utf8(&header[124..135]).unwrap();
+        let size = u64::from_str_radix(size, 8).unwrap();
+        entries.push((name, size));
+        offset += 512 + size.div_ceil(512) as usize * 512;
+    }
+    entries
+}
+
+pub async fn archive_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, _user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: "",
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    create_file("archive/a", &args).await;
+    create_file("archive/sub/b", &args).await;
+    create_dir("archive/empty", &args).await;
+    header!("Created test tree");
+
+    let tar = archive("archive", "tar", &args).await.unwrap();
+    assert_eq!(
+        tar_entries(&tar),
+        vec![
+            ("a".to_string(), FILE_SIZE as u64),
+            ("empty/".to_string(), 0),
+            ("sub/".to_string(), 0),
+            ("sub/b".to_string(), FILE_SIZE as u64),
+        ]
+    );
+    header!("Downloaded tar archive");
+
+    let gzip = archive("archive", "tar.gz", &args).await.unwrap();
+    assert_eq!(gzip[..2], [0x1f, 0x8b]);
+    header!("Downloaded gzipped tar archive");
+
+    let zip = archive("archive", "zip", &args).await.unwrap();
+    assert_eq!(zip[..4], [0x50, 0x4b, 0x03, 0x04]);
+    let end = zip.len() - 22;
+    assert_eq!(zip[end..end + 4], [0x50, 0x4b, 0x05, 0x06]);
+    assert_eq!(u16::from_le_bytes([zip[end + 10], zip[end + 11]]), 4);
+    header!("Downloaded zip archive");
+
+    assert_eq!(
+        archive("archive/missing", "tar", &args).await,
+        Err(StatusCode::NOT_FOUND)
+    );
+    assert_eq!(
+        archive("archive", "rar", &args).await,
+        Err(StatusCode::BAD_REQUEST)
+    );
+    header!("Rejected invalid archive requests");
+}
@@ -0,0 +1,111 @@
+use crate::directory_test::{create_file, NodeArgs};
+use crate::file_transfer_test::{delete_file, issue_token};
+use crate::utils::Logger;
+use data::dto::entity::{AppDto, AuditEventList, BucketDto};
+use data::model::audit_model::AuditActorKind;
+use http::header::AUTHORIZATION;
+use http::StatusCode;
+use log::info;
+use reqwest_middleware::ClientBuilder;
+
+async fn query_audit(query: &[(&str, &str)], args: &NodeArgs<'_>) -> reqwest::Response {
+    args.client
+        .get(format!(
+            "http://127.0.0.4:4002/api/app/audit/{}",
+            args.app_id
+        ))
+        .query(query)
+        .header(AUTHORIZATION, format!("Bearer {}", args.user_token))
+        .send()
+        .await
+        .expect("")
+}
+
+async fn audit_events(query: &[(&str, &str)], args: &NodeArgs<'_>) -> AuditEventList {
+    let response = query_audit(query, args).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    response.json().await.unwrap()
+}
+
+pub async fn audit_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: &user_token,
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    create_file("audit/file1", &args).await;
+    create_file("audit/file2", &args).await;
+    delete_file("audit/file1", args.node, &args).await;
+    delete_file("audit/missing", args.node, &args).await;
+
+    let list = audit_events(&[("path", "audit/")], &args).await;
+    assert_eq!(list.events.len(), 4);
+    assert!(list.cursor.is_none());
+    let deletion = &list.events[1];
+    assert_eq!(deletion.path.as_deref(), Some("audit/file1"));
+    assert!(deletion.action.starts_with("DELETE /api/file/delete/"));
+    assert_eq!(deletion.actor_kind, AuditActorKind::Token);
+    assert_eq!(deletion.bucket_id, Some(bucket_dto.id));
+    assert_eq!(deletion.status, 200);
+    assert!(deletion.source_ip.is_some());
+    // the most recent first
+    assert_eq!(list.events[0].path.as_deref(), Some("audit/missing"));
+    assert!(list.events[0].time >= deletion.time);
+    header!("File deletion recorded");
+
+    let list = audit_events(&[("path", "audit/"), ("outcome", "failure")], &args).await;
+    assert_eq!(list.events.len(), 1);
+    assert_eq!(list.events[0].status, 404);
+    header!("Outcome filtered");
+
+    let first = audit_events(&[("path", "audit/"), ("limit", "3")], &args).await;
+    assert_eq!(first.events.len(), 3);
+    let cursor = first.cursor.expect("Missing cursor");
+    let rest = audit_events(
+        &[("path", "audit/"), ("limit", "3"), ("cursor", &cursor)],
+        &args,
+    )
+    .await;
+    assert_eq!(rest.events.len(), 1);
+    assert_eq!(rest.events[0].path.as_deref(), Some("audit/file1"));
+    assert!(rest.cursor.is_none());
+    header!("Events paginated");
+
+    issue_token(
+        &app_dto,
+        bucket_dto.id,
+        "audited".to_string(),
+        &user_token,
+        &client,
+    )
+    .await;
+    let list = audit_events(&[("actor_id", &app_dto.owner_id.to_string())], &args).await;
+    let issue = list
+        .events
+        .iter()
+        .find(|event| event.action == "POST /api/app/token/issue")
+        .expect("Missing token issue");
+    assert_eq!(issue.actor_kind, AuditActorKind::User);
+    assert_eq!(issue.app_id, app_dto.id);
+    header!("Token issue recorded");
+
+    assert_eq!(
+        query_audit(&[("limit", "0")], &args).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        query_audit(&[("cursor", "invalid")], &args).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+    header!("Invalid queries rejected");
+
+    delete_file("audit/file2", args.node, &args).await;
+}
@@ -0,0 +1,99 @@
+use crate::directory_test::{create_file, stat_entity, NodeArgs};
+use crate::utils::Logger;
+use data::dto::entity::{AppDto, BatchOperation, BatchRequest, BatchResponse, BucketDto};
+use http::header::AUTHORIZATION;
+use log::info;
+use reqwest_middleware::ClientBuilder;
+
+async fn batch(
+    operations: Vec<BatchOperation>,
+    atomic: bool,
+    args: &NodeArgs<'_>,
+) -> BatchResponse {
+    let req = BatchRequest { operations, atomic };
+
+    args.client
+        .post(format!(
+            "http://{}/api/bucket/batch/{}/{}",
+            args.node, args.app_id, args.bucket_id,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .json(&req)
+        .send()
+        .await
+        .expect("")
+        .json()
+        .await
+        .expect("")
+}
+
+fn delete(path: &str) -> BatchOperation {
+    BatchOperation::Delete {
+        path: path.to_string(),
+    }
+}
+
+pub async fn batch_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, _user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: "",
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    create_file("batch/a", &args).await;
+    create_file("batch/b", &args).await;
+    create_file("batch/c", &args).await;
+    header!("Created test files");
+
+    let response = batch(
+        vec![
+            delete("batch/a"),
+            BatchOperation::Rename {
+                path: "batch/b".to_string(),
+                to: "batch/renamed".to_string(),
+            },
+            BatchOperation::Mkdir {
+                path: "batch/dir".to_string(),
+            },
+            delete("batch/missing"),
+        ],
+        false,
+        &args,
+    )
+    .await;
+    assert!(response.executed);
+    let statuses: Vec<u16> = response.results.iter().map(|r| r.status).collect();
+    assert_eq!(statuses, vec![200, 200, 200, 404]);
+    assert_eq!(stat_entity("batch/renamed", &args).await.name, "renamed");
+    assert!(stat_entity("batch/dir", &args).await.is_dir);
+    header!("Executed batch");
+
+    let response = batch(
+        vec![delete("batch/c"), delete("batch/missing")],
+        true,
+        &args,
+    )
+    .await;
+    assert!(!response.executed);
+    let statuses: Vec<u16> = response.results.iter().map(|r| r.status).collect();
+    assert_eq!(statuses, vec![424, 404]);
+    assert_eq!(stat_entity("batch/c", &args).await.name, "c");
+    header!("Rejected atomic batch");
+
+    let response = batch(
+        vec![delete("batch/c"), delete("batch/renamed")],
+        true,
+        &args,
+    )
+    .await;
+    assert!(response.executed);
+    assert!(response.results.iter().all(|r| r.status == 200));
+    header!("Executed atomic batch");
+}
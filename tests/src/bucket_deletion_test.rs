@@ -0,0 +1,159 @@
+use crate::directory_test::{create_file, NodeArgs};
+use crate::file_transfer_test::{create_bucket, issue_token};
+use crate::utils::Logger;
+use dashboard_lib::public::routes::application::{
+    CreateApplicationRequest, DeleteApplicationRequest,
+};
+use data::dto::entity::{AppDto, BucketDto, BucketJobDto, BucketJobList};
+use data::model::file_model::{JobKind, JobState};
+use http::header::AUTHORIZATION;
+use http::StatusCode;
+use log::info;
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use std::time::Duration;
+use uuid::Uuid;
+
+async fn create_app(user_token: &str, client: &ClientWithMiddleware) -> AppDto {
+    let req = CreateApplicationRequest {
+        name: "doomed".to_string(),
+        quota: 512 * 1024 * 1024,
+    };
+
+    client
+        .post("http://127.0.0.4:4002/api/app/create")
+        .json(&req)
+        .header(AUTHORIZATION, format!("Bearer {user_token}"))
+        .send()
+        .await
+        .expect("")
+        .json::<AppDto>()
+        .await
+        .expect("")
+}
+
+async fn delete_app(
+    id: Uuid,
+    force: bool,
+    user_token: &str,
+    client: &ClientWithMiddleware,
+) -> reqwest::Response {
+    client
+        .delete("http://127.0.0.4:4002/api/app/delete")
+        .header(AUTHORIZATION, format!("Bearer {user_token}"))
+        .json(&DeleteApplicationRequest { id, force })
+        .send()
+        .await
+        .expect("")
+}
+
+async fn app_deletion(
+    id: Uuid,
+    user_token: &str,
+    client: &ClientWithMiddleware,
+) -> reqwest::Response {
+    client
+        .get(format!("http://127.0.0.4:4002/api/app/deletion/{id}"))
+        .header(AUTHORIZATION, format!("Bearer {user_token}"))
+        .send()
+        .await
+        .expect("")
+}
+
+async fn stat_status(path: &str, args: &NodeArgs<'_>) -> StatusCode {
+    args.client
+        .get(format!(
+            "http://{}/api/bucket/stat/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.token))
+        .send()
+        .await
+        .expect("")
+        .status()
+}
+
+pub async fn bucket_deletion_test(data: (AppDto, BucketDto, String, String)) {
+    let (_, _, _, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let app_dto = create_app(&user_token, &client).await;
+    let bucket_dto = create_bucket(&user_token, &app_dto, "doomed".to_string(), &client).await;
+    let token = issue_token(
+        &app_dto,
+        bucket_dto.id,
+        "doomed".to_string(),
+        &user_token,
+        &client,
+    )
+    .await
+    .token;
+    let mut args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: &user_token,
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+    create_file("deep/tree/file1", &args).await;
+    create_file("file2", &args).await;
+    args.node = "127.0.0.3:4001";
+    create_file("deep/file3", &args).await;
+    assert_eq!(stat_status("file2", &args).await, StatusCode::OK);
+
+    let response = delete_app(app_dto.id, false, &user_token, &client).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    header!("Non-empty app kept");
+
+    let response = delete_app(app_dto.id, true, &user_token, &client).await;
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let jobs: BucketJobList = response.json().await.unwrap();
+    assert_eq!(jobs.jobs.len(), 1);
+    assert_eq!(jobs.jobs[0].kind, JobKind::DeleteBucket);
+    assert_eq!(jobs.jobs[0].state, JobState::Running);
+
+    let response = client
+        .get(format!(
+            "http://127.0.0.4:4002/api/bucket/deletion/{}/{}",
+            app_dto.id, bucket_dto.id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {user_token}"))
+        .send()
+        .await
+        .expect("");
+    // the job may already have removed the app
+    if response.status() != StatusCode::NOT_FOUND {
+        let job: BucketJobDto = response.json().await.unwrap();
+        assert_eq!(job.id, jobs.jobs[0].id);
+    }
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(stat_status("file2", &args).await, StatusCode::NOT_FOUND);
+    args.node = "127.0.0.2:4000";
+    assert_eq!(stat_status("file2", &args).await, StatusCode::NOT_FOUND);
+    header!("Deleting bucket inaccessible");
+
+    let mut deleted = false;
+    for _ in 0..40 {
+        let response = app_deletion(app_dto.id, &user_token, &client).await;
+        if response.status() == StatusCode::NOT_FOUND {
+            deleted = true;
+            break;
+        }
+        assert_eq!(response.status(), StatusCode::OK);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    assert!(deleted);
+    let response = client
+        .get(format!(
+            "http://127.0.0.4:4002/api/app/buckets/{}",
+            app_dto.id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {user_token}"))
+        .send()
+        .await
+        .expect("");
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    header!("App deleted");
+}
@@ -0,0 +1,158 @@
+use crate::directory_test::{create_file, delete_dir, NodeArgs};
+use crate::file_transfer_test::{create_bucket, delete_file, fetch_bucket_info};
+use crate::utils::Logger;
+use dashboard_lib::public::routes::bucket::{
+    DelReq, EditBucketPublicAccessRequest, EditBucketRequest,
+};
+use data::dto::entity::{AppDto, BucketDto};
+use http::header::AUTHORIZATION;
+use http::StatusCode;
+use log::info;
+use reqwest_middleware::ClientBuilder;
+use std::time::Duration;
+
+async fn edit_bucket(req: &EditBucketRequest, args: &NodeArgs<'_>) -> reqwest::Response {
+    args.client
+        .patch(format!(
+            "http://127.0.0.4:4002/api/bucket/update/{}/{}",
+            args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.user_token))
+        .json(req)
+        .send()
+        .await
+        .expect("")
+}
+
+async fn set_public(prefixes: Vec<String>, args: &NodeArgs<'_>) {
+    let req = EditBucketPublicAccessRequest {
+        prefixes,
+        listing: false,
+        requests_per_minute: 100,
+    };
+    assert!(args
+        .client
+        .patch(format!(
+            "http://127.0.0.4:4002/api/bucket/public-access/{}/{}",
+            args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.user_token))
+        .json(&req)
+        .send()
+        .await
+        .expect("")
+        .status()
+        .is_success());
+}
+
+async fn public_download(args: &NodeArgs<'_>) -> StatusCode {
+    args.client
+        .get(format!(
+            "http://{}/api/public/download/{}/{}/settings/file",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .send()
+        .await
+        .expect("")
+        .status()
+}
+
+pub async fn bucket_settings_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: &user_token,
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    let response = edit_bucket(
+        &EditBucketRequest {
+            name: Some("renamed".to_string()),
+            atomic_upload: Some(true),
+            ..Default::default()
+        },
+        &args,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let edited: BucketDto = response.json().await.unwrap();
+    assert_eq!(edited.name, "renamed");
+    assert!(edited.atomic_upload);
+    assert_eq!(edited.quota, bucket_dto.quota);
+    let info = fetch_bucket_info(args.token, args.app_id, args.bucket_id, &client).await;
+    assert_eq!(info.name, "renamed");
+    assert!(info.atomic_upload);
+    header!("Bucket edited");
+
+    let other = create_bucket(&user_token, &app_dto, "other".to_string(), &client).await;
+    let response = edit_bucket(
+        &EditBucketRequest {
+            name: Some("other".to_string()),
+            ..Default::default()
+        },
+        &args,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = edit_bucket(
+        &EditBucketRequest {
+            name: Some("ab".to_string()),
+            ..Default::default()
+        },
+        &args,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = edit_bucket(
+        &EditBucketRequest {
+            name: Some("bad/name".to_string()),
+            ..Default::default()
+        },
+        &args,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(client
+        .delete("http://127.0.0.4:4002/api/bucket/delete")
+        .header(AUTHORIZATION, format!("Bearer {user_token}"))
+        .json(&DelReq {
+            app_id: app_dto.id,
+            bucket_id: other.id,
+            force: false,
+        })
+        .send()
+        .await
+        .expect("")
+        .status()
+        .is_success());
+    header!("Invalid edits rejected");
+
+    // the policy cached by the node is dropped as soon as it changes
+    create_file("settings/file", &args).await;
+    set_public(vec!["settings/".to_string()], &args).await;
+    assert_eq!(public_download(&args).await, StatusCode::OK);
+    set_public(vec![], &args).await;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(public_download(&args).await, StatusCode::UNAUTHORIZED);
+    header!("Node caches invalidated");
+
+    let response = edit_bucket(
+        &EditBucketRequest {
+            name: Some(bucket_dto.name.clone()),
+            atomic_upload: Some(bucket_dto.atomic_upload),
+            ..Default::default()
+        },
+        &args,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    delete_file("settings/file", args.node, &args).await;
+    assert_eq!(delete_dir("settings", false, &args).await, StatusCode::OK);
+    header!("Bucket restored");
+}
@@ -0,0 +1,185 @@
+use crate::append_test::append;
+use crate::directory_test::{create_dir, create_file, delete_dir, rename_dir, NodeArgs};
+use crate::file_transfer_test::delete_file;
+use crate::move_test::rename_file;
+use crate::range_write_test::write_range;
+use crate::trash_test::{restore_trash, set_trash_retention};
+use crate::utils::Logger;
+use crate::versioning_test::{delete_version, list_versions, restore_version, set_versioning};
+use data::dto::entity::{AppDto, BucketChangeDto, BucketChangeList, BucketDto};
+use data::model::file_model::BucketEventKind;
+use http::header::AUTHORIZATION;
+use http::StatusCode;
+use log::info;
+use reqwest_middleware::ClientBuilder;
+use std::time::Duration;
+
+async fn list_changes(
+    cursor: Option<&str>,
+    limit: Option<usize>,
+    args: &NodeArgs<'_>,
+) -> reqwest::Response {
+    let mut query = vec![];
+    if let Some(cursor) = cursor {
+        query.push(("cursor", cursor.to_string()));
+    }
+    if let Some(limit) = limit {
+        query.push(("limit", limit.to_string()));
+    }
+    args.client
+        .get(format!(
+            "http://{}/api/bucket/changes/{}/{}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .query(&query)
+        .header(AUTHORIZATION, format!("Bearer {}", args.token))
+        .send()
+        .await
+        .expect("")
+}
+
+/// Pages through the feed until no more changes are available right away.
+async fn drain_changes(
+    mut cursor: Option<String>,
+    limit: Option<usize>,
+    args: &NodeArgs<'_>,
+) -> (Vec<BucketChangeDto>, String) {
+    let mut changes = vec![];
+    loop {
+        let response = list_changes(cursor.as_deref(), limit, args).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let list: BucketChangeList = response.json().await.unwrap();
+        changes.extend(list.changes);
+        cursor = Some(list.cursor);
+        if !list.has_more {
+            return (changes, cursor.unwrap());
+        }
+    }
+}
+
+pub async fn change_feed_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: &user_token,
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    // Changes made by the earlier tests are still being settled
+    tokio::time::sleep(Duration::from_secs(6)).await;
+    let (_, cursor) = drain_changes(None, None, &args).await;
+    header!("Fetched the initial cursor");
+
+    create_dir("feed/dir", &args).await;
+    create_file("feed/a", &args).await;
+    create_file("feed/a", &args).await;
+    rename_file("feed/a", "feed/b", &args).await;
+    rename_dir("feed/dir", "feed/moved", &args).await;
+    delete_file("feed/b", "127.0.0.3:4001", &args).await;
+    create_file("feed/moved/c", &args).await;
+    assert!(append("feed/moved/c", None, &args).await.is_success());
+    assert!(write_range("feed/moved/c", "bytes 0-1/*", b"ab", &args)
+        .await
+        .is_success());
+
+    set_trash_retention(3600, &args).await;
+    create_file("feed/t", &args).await;
+    delete_file("feed/t", args.node, &args).await;
+    assert_eq!(restore_trash("feed/t", &args).await.restored, 1);
+    set_trash_retention(0, &args).await;
+
+    set_versioning(true, &args).await;
+    create_file("feed/v", &args).await;
+    create_file("feed/v", &args).await;
+    let version = list_versions("feed/v", &args).await.versions[0].version_id;
+    restore_version("feed/v", version, &args).await;
+    set_versioning(false, &args).await;
+    for version in list_versions("feed/v", &args).await.versions {
+        delete_version("feed/v", version.version_id, &args).await;
+    }
+
+    assert_eq!(delete_dir("feed", true, &args).await, StatusCode::OK);
+
+    // changes only appear once settled
+    let response = list_changes(Some(&cursor), None, &args).await;
+    let list: BucketChangeList = response.json().await.unwrap();
+    assert!(list.changes.is_empty());
+    tokio::time::sleep(Duration::from_secs(6)).await;
+
+    let (changes, cursor) = drain_changes(Some(cursor), Some(2), &args).await;
+    let expected = [
+        (BucketEventKind::Created, "feed", None, true),
+        (BucketEventKind::Created, "feed/dir", None, true),
+        (BucketEventKind::Created, "feed/a", None, false),
+        (BucketEventKind::Overwritten, "feed/a", None, false),
+        (BucketEventKind::Renamed, "feed/b", Some("feed/a"), false),
+        (
+            BucketEventKind::Renamed,
+            "feed/moved",
+            Some("feed/dir"),
+            true,
+        ),
+        (BucketEventKind::Deleted, "feed/b", None, false),
+        (BucketEventKind::Created, "feed/moved/c", None, false),
+        // appended, then written by range
+        (BucketEventKind::Overwritten, "feed/moved/c", None, false),
+        (BucketEventKind::Overwritten, "feed/moved/c", None, false),
+        (BucketEventKind::Created, "feed/t", None, false),
+        (BucketEventKind::Deleted, "feed/t", None, false),
+        // restored from the trash
+        (BucketEventKind::Created, "feed/t", None, false),
+        (BucketEventKind::Created, "feed/v", None, false),
+        (BucketEventKind::Overwritten, "feed/v", None, false),
+        // restored version
+        (BucketEventKind::Overwritten, "feed/v", None, false),
+        (BucketEventKind::Deleted, "feed/moved/c", None, false),
+        (BucketEventKind::Deleted, "feed/t", None, false),
+        (BucketEventKind::Deleted, "feed/v", None, false),
+        (BucketEventKind::Deleted, "feed/moved", None, true),
+        (BucketEventKind::Deleted, "feed", None, true),
+    ];
+    assert_eq!(changes.len(), expected.len());
+    // changes made within the same millisecond may be listed in any order
+    let mut unmatched: Vec<&BucketChangeDto> = changes.iter().collect();
+    for (kind, path, old_path, directory) in expected {
+        let index = unmatched
+            .iter()
+            .position(|change| change.event == kind && change.path == path)
+            .unwrap_or_else(|| panic!("Missing {kind:?} change on {path}"));
+        let change = unmatched.remove(index);
+        assert_eq!(change.old_path.as_deref(), old_path);
+        assert_eq!(change.directory, directory);
+    }
+    assert!(changes.windows(2).all(|pair| pair[0].time <= pair[1].time));
+    let position = |kind, path| {
+        changes
+            .iter()
+            .position(|change: &BucketChangeDto| change.event == kind && change.path == path)
+            .unwrap()
+    };
+    assert!(
+        position(BucketEventKind::Created, "feed/a")
+            < position(BucketEventKind::Overwritten, "feed/a")
+    );
+    header!("Listed the changes in order");
+
+    let (changes, _) = drain_changes(Some(cursor.clone()), None, &args).await;
+    assert!(changes.is_empty());
+    assert_eq!(
+        list_changes(Some("not-a-cursor"), None, &args)
+            .await
+            .status(),
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        list_changes(Some(&cursor), Some(0), &args).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+    header!("Rejected invalid requests");
+}
@@ -0,0 +1,150 @@
+use crate::directory_test::{create_file, stat_entity, NodeArgs, FILE_SIZE};
+use crate::file_transfer_test::delete_file;
+use crate::utils::Logger;
+use data::dto::entity::{AppDto, BucketDto, UploadSessionRequest, UploadSessionStartResponse};
+use http::header::{AUTHORIZATION, CONTENT_LENGTH, ETAG, IF_MATCH, IF_NONE_MATCH};
+use http::StatusCode;
+use log::info;
+use reqwest_middleware::ClientBuilder;
+
+async fn conditional_upload(
+    name: &str,
+    condition: (http::HeaderName, &str),
+    args: &NodeArgs<'_>,
+) -> StatusCode {
+    args.client
+        .post(format!(
+            "http://{}/api/file/upload/oneshot/{}/{}/{}",
+            args.node, args.app_id, args.bucket_id, name,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .header(CONTENT_LENGTH, FILE_SIZE.to_string())
+        .header(condition.0, condition.1)
+        .body(vec![0u8; FILE_SIZE])
+        .send()
+        .await
+        .expect("")
+        .status()
+}
+
+async fn conditional_download(name: &str, etag: &str, args: &NodeArgs<'_>) -> StatusCode {
+    args.client
+        .get(format!(
+            "http://{}/api/file/download/{}/{}/{}",
+            args.node, args.app_id, args.bucket_id, name,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .header(IF_NONE_MATCH, etag)
+        .send()
+        .await
+        .expect("")
+        .status()
+}
+
+async fn start_conditional_session(
+    name: &str,
+    condition: (http::HeaderName, &str),
+    args: &NodeArgs<'_>,
+) -> UploadSessionStartResponse {
+    args.client
+        .post(format!(
+            "http://{}/api/file/upload/durable/{}/{}/{}",
+            args.node, args.app_id, args.bucket_id, name,
+        ))
+        .json(&UploadSessionRequest {
+            size: FILE_SIZE as u64,
+            metadata: None,
+        })
+        .header(AUTHORIZATION, format!("Bearer {}", args.token))
+        .header(condition.0, condition.1)
+        .send()
+        .await
+        .expect("")
+        .json::<UploadSessionStartResponse>()
+        .await
+        .expect("")
+}
+
+async fn put_session(session_id: &str, args: &NodeArgs<'_>) -> StatusCode {
+    args.client
+        .put(format!(
+            "http://{}/api/file/upload/put/{}/{}/{session_id}",
+            args.node, args.app_id, args.bucket_id,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .header(CONTENT_LENGTH, FILE_SIZE.to_string())
+        .body(vec![1u8; FILE_SIZE])
+        .send()
+        .await
+        .expect("")
+        .status()
+}
+
+pub async fn conditional_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: &user_token,
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    create_file("conditional", &args).await;
+    let etag = format!(
+        "\"{}\"",
+        stat_entity("conditional", &args).await.etag.expect("")
+    );
+    let response = args
+        .client
+        .get(format!(
+            "http://{}/api/file/download/{}/{}/conditional",
+            args.node, args.app_id, args.bucket_id,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .send()
+        .await
+        .expect("");
+    assert_eq!(response.headers()[ETAG], etag.as_str());
+    assert_eq!(
+        conditional_download("conditional", &etag, &args).await,
+        StatusCode::NOT_MODIFIED
+    );
+    header!("Download revalidation");
+
+    assert_eq!(
+        conditional_upload("conditional", (IF_NONE_MATCH, "*"), &args).await,
+        StatusCode::PRECONDITION_FAILED
+    );
+    assert_eq!(
+        conditional_upload("conditional", (IF_MATCH, "\"stale\""), &args).await,
+        StatusCode::PRECONDITION_FAILED
+    );
+    assert!(conditional_upload("conditional", (IF_MATCH, &etag), &args)
+        .await
+        .is_success());
+    assert_eq!(
+        conditional_download("conditional", &etag, &args).await,
+        StatusCode::OK
+    );
+    header!("Conditional overwrite");
+
+    // The condition held when the session started, but no longer does once it completes.
+    let session =
+        start_conditional_session("conditional_durable", (IF_NONE_MATCH, "*"), &args).await;
+    create_file("conditional_durable", &args).await;
+    let etag = stat_entity("conditional_durable", &args).await.etag;
+    assert_eq!(
+        put_session(&session.code, &args).await,
+        StatusCode::PRECONDITION_FAILED
+    );
+    assert_eq!(stat_entity("conditional_durable", &args).await.etag, etag);
+    header!("Conditional durable upload rechecked at commit");
+
+    delete_file("conditional", args.node, &args).await;
+    delete_file("conditional_durable", args.node, &args).await;
+}
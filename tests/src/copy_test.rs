@@ -0,0 +1,83 @@
+use crate::directory_test::{create_dir, create_file, stat_entity, NodeArgs, FILE_SIZE};
+use crate::file_transfer_test::fetch_bucket_info;
+use crate::utils::Logger;
+use data::dto::entity::{AppDto, BucketDto, CopyEntityRequest};
+use http::header::AUTHORIZATION;
+use http::StatusCode;
+use log::info;
+use reqwest_middleware::ClientBuilder;
+
+async fn copy_entity(kind: &str, name: &str, to: &str, args: &NodeArgs<'_>) -> StatusCode {
+    let req = CopyEntityRequest {
+        to: to.to_string(),
+        bucket_id: None,
+    };
+
+    args.client
+        .post(format!(
+            "http://{}/api/{kind}/copy/{}/{}/{}",
+            args.node, args.app_id, args.bucket_id, name,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .json(&req)
+        .send()
+        .await
+        .expect("")
+        .status()
+}
+
+pub async fn copy_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, _user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: "",
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    let initial = fetch_bucket_info(args.token, args.app_id, args.bucket_id, args.client).await;
+
+    create_file("copy_src/a", &args).await;
+    create_file("copy_src/nested/b", &args).await;
+    create_dir("copy_src/empty", &args).await;
+    header!("Created test files");
+
+    assert!(copy_entity("file", "copy_src/a", "copy_a", &args)
+        .await
+        .is_success());
+    let copied = stat_entity("copy_a", &args).await;
+    assert_eq!(copied.name, "copy_a");
+    assert_eq!(copied.size, FILE_SIZE as u64);
+    assert_ne!(copied.etag, stat_entity("copy_src/a", &args).await.etag);
+    header!("Copied file");
+
+    assert!(copy_entity("directory", "copy_src", "copy_dst", &args)
+        .await
+        .is_success());
+    assert_eq!(stat_entity("copy_dst/a", &args).await.name, "a");
+    assert_eq!(stat_entity("copy_dst/nested/b", &args).await.name, "b");
+    assert!(stat_entity("copy_dst/empty", &args).await.is_dir);
+    header!("Copied directory");
+
+    let bucket = fetch_bucket_info(args.token, args.app_id, args.bucket_id, args.client).await;
+    assert_eq!(bucket.file_count, initial.file_count + 5);
+    assert_eq!(
+        bucket.space_taken,
+        initial.space_taken + 5 * FILE_SIZE as i64
+    );
+
+    assert_eq!(
+        copy_entity("directory", "copy_src", "copy_dst", &args).await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        copy_entity("directory", "copy_src", "copy_src/inner", &args).await,
+        StatusCode::BAD_REQUEST
+    );
+    header!("Rejected invalid copies");
+}
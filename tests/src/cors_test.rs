@@ -0,0 +1,156 @@
+use crate::directory_test::{create_file, delete_dir, NodeArgs};
+use crate::file_transfer_test::delete_file;
+use crate::utils::Logger;
+use dashboard_lib::public::routes::bucket::EditBucketCorsRequest;
+use data::dto::entity::{AppDto, BucketDto, CorsRuleDto, CorsRuleList};
+use http::header::{
+    ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE,
+    ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, AUTHORIZATION, ORIGIN,
+};
+use http::StatusCode;
+use log::info;
+use reqwest::Method;
+use reqwest_middleware::ClientBuilder;
+
+const ALLOWED_ORIGIN: &str = "https://allowed.example";
+const OTHER_ORIGIN: &str = "https://other.example";
+
+async fn set_cors_rules(rules: Vec<CorsRuleDto>, args: &NodeArgs<'_>) {
+    assert!(args
+        .client
+        .patch(format!(
+            "http://127.0.0.4:4002/api/bucket/cors/{}/{}",
+            args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.user_token))
+        .json(&EditBucketCorsRequest { rules })
+        .send()
+        .await
+        .expect("")
+        .status()
+        .is_success());
+}
+
+async fn get_cors_rules(args: &NodeArgs<'_>) -> CorsRuleList {
+    args.client
+        .get(format!(
+            "http://127.0.0.4:4002/api/bucket/cors/{}/{}",
+            args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.user_token))
+        .send()
+        .await
+        .expect("")
+        .json::<CorsRuleList>()
+        .await
+        .expect("")
+}
+
+async fn preflight(origin: &str, method: &str, args: &NodeArgs<'_>) -> reqwest::Response {
+    args.client
+        .request(Method::OPTIONS, download_url(args))
+        .header(ORIGIN, origin)
+        .header(ACCESS_CONTROL_REQUEST_METHOD, method)
+        .header(ACCESS_CONTROL_REQUEST_HEADERS, "authorization")
+        .send()
+        .await
+        .expect("")
+}
+
+async fn download(origin: &str, token: Option<&str>, args: &NodeArgs<'_>) -> reqwest::Response {
+    let mut req = args.client.get(download_url(args)).header(ORIGIN, origin);
+    if let Some(token) = token {
+        req = req.header(AUTHORIZATION, format!("Bearer {token}"));
+    }
+    req.send().await.expect("")
+}
+
+fn download_url(args: &NodeArgs<'_>) -> String {
+    format!(
+        "http://{}/api/file/download/{}/{}/cors/file",
+        args.node, args.app_id, args.bucket_id
+    )
+}
+
+pub async fn cors_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: &user_token,
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    create_file("cors/file", &args).await;
+    set_cors_rules(
+        vec![CorsRuleDto {
+            allowed_origins: vec![ALLOWED_ORIGIN.to_string()],
+            allowed_methods: vec!["GET".to_string(), "HEAD".to_string()],
+            allowed_headers: vec!["Authorization".to_string()],
+            expose_headers: vec!["ETag".to_string()],
+            max_age: 600,
+        }],
+        &args,
+    )
+    .await;
+    assert_eq!(get_cors_rules(&args).await.rules.len(), 1);
+    header!("CORS rules set");
+
+    let response = preflight(ALLOWED_ORIGIN, "GET", &args).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(
+        response.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+        ALLOWED_ORIGIN
+    );
+    assert_eq!(
+        response.headers().get(ACCESS_CONTROL_MAX_AGE).unwrap(),
+        "600"
+    );
+    assert_eq!(
+        preflight(OTHER_ORIGIN, "GET", &args).await.status(),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        preflight(ALLOWED_ORIGIN, "DELETE", &args).await.status(),
+        StatusCode::FORBIDDEN
+    );
+    header!("Preflight evaluated");
+
+    let response = download(ALLOWED_ORIGIN, Some(args.token), &args).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+        ALLOWED_ORIGIN
+    );
+    assert_eq!(
+        response
+            .headers()
+            .get(ACCESS_CONTROL_EXPOSE_HEADERS)
+            .unwrap(),
+        "ETag"
+    );
+    let response = download(OTHER_ORIGIN, Some(args.token), &args).await;
+    assert!(response
+        .headers()
+        .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+        .is_none());
+    // failed authentications are readable by allowed origins
+    let response = download(ALLOWED_ORIGIN, None, &args).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        response.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+        ALLOWED_ORIGIN
+    );
+    header!("Requests evaluated");
+
+    set_cors_rules(vec![], &args).await;
+    assert!(get_cors_rules(&args).await.rules.is_empty());
+    delete_file("cors/file", args.node, &args).await;
+    assert_eq!(delete_dir("cors", false, &args).await, StatusCode::OK);
+    header!("CORS rules removed");
+}
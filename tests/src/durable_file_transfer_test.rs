@@ -23,7 +23,10 @@ async fn start_upload_session(
 ) -> UploadSessionStartResponse {
     let file = File::open(path).await.unwrap();
     let size = file.metadata().await.unwrap().len();
-    let req = UploadSessionRequest { size };
+    let req = UploadSessionRequest {
+        size,
+        metadata: None,
+    };
 
     args.client
         .post(format!(
@@ -0,0 +1,159 @@
+use crate::directory_test::{stat_entity, NodeArgs};
+use crate::utils::Logger;
+use chrono::Utc;
+use data::dto::entity::{AppDto, BucketDto, ExtractionResult};
+use http::header::AUTHORIZATION;
+use http::StatusCode;
+use log::info;
+use node_lib::archive::{ArchiveEntry, ArchiveFormat, ArchiveWriter, EntryKind};
+use reqwest::Body;
+use reqwest_middleware::ClientBuilder;
+use std::io::Cursor;
+use tokio_util::codec::{BytesCodec, FramedRead};
+
+const CONTENTS: &[u8] = b"extracted contents";
+
+fn build_archive(format: ArchiveFormat, files: &[&str], directories: &[&str]) -> Vec<u8> {
+    let mut writer = ArchiveWriter::new(format);
+    for directory in directories {
+        writer.start_entry(&ArchiveEntry {
+            path: directory.to_string(),
+            kind: EntryKind::Directory,
+            size: 0,
+            modified: Utc::now(),
+        });
+        writer.finish_entry().unwrap();
+    }
+    for file in files {
+        writer.start_entry(&ArchiveEntry {
+            path: file.to_string(),
+            kind: EntryKind::File,
+            size: CONTENTS.len() as u64,
+            modified: Utc::now(),
+        });
+        writer.write_data(CONTENTS);
+        writer.finish_entry().unwrap();
+    }
+    writer.finish().unwrap();
+    writer.take_output()
+}
+
+async fn extract(
+    path: &str,
+    format: &str,
+    body: impl Into<Body>,
+    args: &NodeArgs<'_>,
+) -> Result<ExtractionResult, StatusCode> {
+    let response = args
+        .client
+        .post(format!(
+            "http://{}/api/directory/extract/{}/{}/{path}?format={format}",
+            args.node, args.app_id, args.bucket_id,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    if !response.status().is_success() {
+        return Err(response.status());
+    }
+    Ok(response.json::<ExtractionResult>().await.unwrap())
+}
+
+async fn download(name: &str, args: &NodeArgs<'_>) -> Vec<u8> {
+    args.client
+        .get(format!(
+            "http://{}/api/file/download/{}/{}/{}",
+            args.node, args.app_id, args.bucket_id, name,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .send()
+        .await
+        .expect("")
+        .bytes()
+        .await
+        .unwrap()
+        .to_vec()
+}
+
+async fn stat_entity_status(name: &str, args: &NodeArgs<'_>) -> StatusCode {
+    args.client
+        .get(format!(
+            "http://{}/api/bucket/stat/{}/{}/{name}",
+            args.node, args.app_id, args.bucket_id,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+pub async fn extract_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, _user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: "",
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    let tar = build_archive(ArchiveFormat::Tar, &["./a", "sub/b"], &["sub", "empty"]);
+    let result = extract("extract_tar", "tar", tar, &args).await.unwrap();
+    assert_eq!((result.files, result.directories), (2, 2));
+    assert_eq!(download("extract_tar/a", &args).await, CONTENTS);
+    assert_eq!(download("extract_tar/sub/b", &args).await, CONTENTS);
+    assert!(stat_entity("extract_tar/empty", &args).await.is_dir);
+    header!("Extracted tar archive");
+
+    let gzip = build_archive(ArchiveFormat::TarGz, &["c"], &[]);
+    let result = extract("extract_tar_gz", "tar.gz", gzip, &args)
+        .await
+        .unwrap();
+    assert_eq!(result.files, 1);
+    assert_eq!(download("extract_tar_gz/c", &args).await, CONTENTS);
+    header!("Extracted gzipped tar archive");
+
+    let zip = build_archive(ArchiveFormat::Zip, &["nested/d", "e"], &["nested"]);
+    let result = extract("extract_zip", "zip", zip, &args).await.unwrap();
+    assert_eq!((result.files, result.directories), (2, 1));
+    assert_eq!(download("extract_zip/nested/d", &args).await, CONTENTS);
+    assert_eq!(download("extract_zip/e", &args).await, CONTENTS);
+    header!("Extracted zip archive");
+
+    for format in [ArchiveFormat::Tar, ArchiveFormat::Zip] {
+        let escaping = build_archive(format, &["../escaped"], &[]);
+        assert_eq!(
+            extract("extract_escape", format.extension(), escaping, &args).await,
+            Err(StatusCode::BAD_REQUEST)
+        );
+    }
+    let zip = build_archive(ArchiveFormat::Zip, &["fine", "/absolute"], &[]);
+    assert_eq!(
+        extract("extract_escape", "zip", zip, &args).await,
+        Err(StatusCode::BAD_REQUEST)
+    );
+    // zip archives are validated as a whole before anything is written
+    assert_eq!(
+        stat_entity_status("extract_escape/fine", &args).await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        extract("extract_garbage", "zip", vec![1; 100], &args).await,
+        Err(StatusCode::BAD_REQUEST)
+    );
+    // zip archives are only buffered when their length is known upfront
+    let zip = build_archive(ArchiveFormat::Zip, &["fine"], &[]);
+    let chunked = Body::wrap_stream(FramedRead::new(Cursor::new(zip), BytesCodec::new()));
+    assert_eq!(
+        extract("extract_chunked", "zip", chunked, &args).await,
+        Err(StatusCode::BAD_REQUEST)
+    );
+    header!("Rejected invalid archives");
+}
@@ -84,7 +84,7 @@ async fn create_application(
         .expect("")
 }
 
-async fn create_bucket(
+pub(crate) async fn create_bucket(
     user_token: &str,
     app_dto: &AppDto,
     name: String,
@@ -95,6 +95,9 @@ async fn create_bucket(
         app_id: app_dto.id,
         quota: 256 * 1024 * 1024,
         atomic_upload: false,
+        versioning: false,
+        trash_retention: None,
+        object_lock_retention: None,
     };
 
     client
@@ -165,7 +168,7 @@ macro_rules! assert_bucket_info {
     }};
 }
 
-async fn issue_token(
+pub(crate) async fn issue_token(
     app: &AppDto,
     bucket_id: Uuid,
     name: String,
@@ -189,6 +192,7 @@ async fn issue_token(
             ])
             .into(),
         }],
+        limits: None,
     };
 
     client
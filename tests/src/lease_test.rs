@@ -0,0 +1,253 @@
+use crate::directory_test::{create_file, delete_dir, NodeArgs, FILE_SIZE};
+use crate::file_transfer_test::issue_token;
+use crate::utils::Logger;
+use data::dto::entity::{
+    AppDto, BucketDto, LeaseAcquireRequest, LeaseDto, LeaseMode, LeaseReleaseRequest,
+    LeaseRenewRequest, RenameEntityRequest,
+};
+use http::header::{AUTHORIZATION, CONTENT_LENGTH};
+use http::StatusCode;
+use log::info;
+use reqwest_middleware::ClientBuilder;
+use uuid::Uuid;
+
+async fn acquire(
+    path: &str,
+    mode: LeaseMode,
+    ttl: u32,
+    args: &NodeArgs<'_>,
+) -> Result<LeaseDto, StatusCode> {
+    let response = args
+        .client
+        .post(format!(
+            "http://{}/api/file/lease/acquire/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .json(&LeaseAcquireRequest { mode, ttl })
+        .send()
+        .await
+        .unwrap();
+    if !response.status().is_success() {
+        return Err(response.status());
+    }
+    Ok(response.json().await.unwrap())
+}
+
+async fn renew(path: &str, id: Uuid, args: &NodeArgs<'_>) -> Result<LeaseDto, StatusCode> {
+    let response = args
+        .client
+        .post(format!(
+            "http://{}/api/file/lease/renew/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .json(&LeaseRenewRequest { id, ttl: 120 })
+        .send()
+        .await
+        .unwrap();
+    if !response.status().is_success() {
+        return Err(response.status());
+    }
+    Ok(response.json().await.unwrap())
+}
+
+async fn release(path: &str, id: Uuid, args: &NodeArgs<'_>) -> StatusCode {
+    args.client
+        .delete(format!(
+            "http://{}/api/file/lease/release/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .json(&LeaseReleaseRequest { id })
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+async fn upload(path: &str, args: &NodeArgs<'_>) -> StatusCode {
+    args.client
+        .post(format!(
+            "http://{}/api/file/upload/oneshot/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .header(CONTENT_LENGTH, FILE_SIZE.to_string())
+        .body(vec![0u8; FILE_SIZE])
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+async fn rename(kind: &str, path: &str, to: &str, args: &NodeArgs<'_>) -> StatusCode {
+    args.client
+        .post(format!(
+            "http://{}/api/{kind}/rename/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .json(&RenameEntityRequest { to: to.to_string() })
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+async fn delete(path: &str, args: &NodeArgs<'_>) -> StatusCode {
+    args.client
+        .delete(format!(
+            "http://{}/api/file/delete/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+pub async fn lease_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+    let other_token = issue_token(
+        &app_dto,
+        bucket_dto.id,
+        "lease".to_string(),
+        &user_token,
+        &client,
+    )
+    .await
+    .token;
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: "",
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+    // served by the other node, as leases are shared across the cluster
+    let other = NodeArgs {
+        node: "127.0.0.3:4001",
+        token: &other_token,
+        ..args
+    };
+
+    create_file("lease/a", &args).await;
+    let lease = acquire("lease/a", LeaseMode::Exclusive, 60, &args)
+        .await
+        .unwrap();
+    assert_eq!(lease.mode, LeaseMode::Exclusive);
+    header!("Acquired exclusive lease");
+
+    assert_eq!(
+        acquire("lease/a", LeaseMode::Shared, 60, &other)
+            .await
+            .err(),
+        Some(StatusCode::LOCKED)
+    );
+    assert_eq!(upload("lease/a", &other).await, StatusCode::LOCKED);
+    assert_eq!(delete("lease/a", &other).await, StatusCode::LOCKED);
+    assert_eq!(
+        rename("file", "lease/a", "lease/b", &other).await,
+        StatusCode::LOCKED
+    );
+    assert_eq!(
+        rename("file", "lease/c", "lease/a", &other).await,
+        StatusCode::LOCKED
+    );
+    assert_eq!(
+        rename("directory", "lease", "leased", &other).await,
+        StatusCode::LOCKED
+    );
+    assert_eq!(delete_dir("lease", true, &other).await, StatusCode::LOCKED);
+    header!("Rejected writes of another token");
+
+    assert_eq!(upload("lease/a", &args).await, StatusCode::OK);
+    header!("Allowed writes of the holder");
+
+    assert_eq!(
+        renew("lease/a", lease.id, &other).await.err(),
+        Some(StatusCode::NOT_FOUND)
+    );
+    assert_eq!(
+        release("lease/a", lease.id, &other).await,
+        StatusCode::NOT_FOUND
+    );
+    let renewed = renew("lease/a", lease.id, &args).await.unwrap();
+    assert!(renewed.expires > lease.expires);
+    header!("Renewed lease");
+
+    assert_eq!(release("lease/a", lease.id, &args).await, StatusCode::OK);
+    assert_eq!(
+        release("lease/a", lease.id, &args).await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(upload("lease/a", &other).await, StatusCode::OK);
+    header!("Released lease");
+
+    let shared = acquire("lease/a", LeaseMode::Shared, 60, &args)
+        .await
+        .unwrap();
+    let other_shared = acquire("lease/a", LeaseMode::Shared, 60, &other)
+        .await
+        .unwrap();
+    assert_eq!(
+        acquire("lease/a", LeaseMode::Exclusive, 60, &args)
+            .await
+            .err(),
+        Some(StatusCode::LOCKED)
+    );
+    assert_eq!(upload("lease/a", &other).await, StatusCode::OK);
+    assert_eq!(release("lease/a", shared.id, &args).await, StatusCode::OK);
+    assert_eq!(
+        release("lease/a", other_shared.id, &other).await,
+        StatusCode::OK
+    );
+    header!("Shared leases");
+
+    let pending = acquire("lease/pending", LeaseMode::Exclusive, 1, &args)
+        .await
+        .unwrap();
+    assert_eq!(upload("lease/pending", &other).await, StatusCode::LOCKED);
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+    assert_eq!(upload("lease/pending", &other).await, StatusCode::OK);
+    assert_eq!(
+        renew("lease/pending", pending.id, &args).await.err(),
+        Some(StatusCode::NOT_FOUND)
+    );
+    header!("Expired lease on a new file");
+
+    let destination = acquire("moved/a", LeaseMode::Exclusive, 60, &args)
+        .await
+        .unwrap();
+    assert_eq!(
+        rename("directory", "lease", "moved", &other).await,
+        StatusCode::LOCKED
+    );
+    assert_eq!(
+        release("moved/a", destination.id, &args).await,
+        StatusCode::OK
+    );
+    header!("Rejected directory rename onto a leased path");
+
+    assert_eq!(
+        acquire("lease/a", LeaseMode::Exclusive, 0, &args)
+            .await
+            .err(),
+        Some(StatusCode::BAD_REQUEST)
+    );
+    assert_eq!(
+        acquire("lease/a", LeaseMode::Exclusive, 3601, &args)
+            .await
+            .err(),
+        Some(StatusCode::BAD_REQUEST)
+    );
+    header!("Rejected invalid ttl");
+
+    assert_eq!(delete_dir("lease", true, &other).await, StatusCode::OK);
+}
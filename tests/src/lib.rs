@@ -2,23 +2,73 @@ pub mod file_transfer_test;
 pub mod test_configs;
 #[macro_use]
 pub mod utils;
+pub mod append_test;
+pub mod archive_test;
+pub mod audit_test;
+pub mod batch_test;
+pub mod bucket_deletion_test;
+pub mod bucket_settings_test;
+pub mod change_feed_test;
 pub mod concurrent_upload_test;
+pub mod conditional_test;
+pub mod copy_test;
+pub mod cors_test;
 pub mod directory_test;
 pub mod durable_file_transfer_test;
+pub mod extract_test;
+pub mod lease_test;
+pub mod lifecycle_test;
+pub mod listing_test;
+pub mod metadata_test;
 pub mod move_test;
+pub mod notification_test;
+pub mod object_lock_test;
+pub mod public_access_test;
+pub mod range_test;
+pub mod range_write_test;
+pub mod rate_limit_test;
+pub mod recursive_delete_test;
 pub mod resiliency_test;
+pub mod snapshot_test;
+pub mod trash_test;
+pub mod versioning_test;
 
 #[cfg(test)]
 mod tests {
+    use crate::append_test::append_test;
+    use crate::archive_test::archive_test;
+    use crate::audit_test::audit_test;
+    use crate::batch_test::batch_test;
+    use crate::bucket_deletion_test::bucket_deletion_test;
+    use crate::bucket_settings_test::bucket_settings_test;
+    use crate::change_feed_test::change_feed_test;
     use crate::concurrent_upload_test::concurrent_test;
+    use crate::conditional_test::conditional_test;
+    use crate::copy_test::copy_test;
+    use crate::cors_test::cors_test;
     use crate::directory_test::directory_test;
     use crate::durable_file_transfer_test::test_durable_upload;
+    use crate::extract_test::extract_test;
     use crate::file_transfer_test::test_file_transfer;
+    use crate::lease_test::lease_test;
+    use crate::lifecycle_test::lifecycle_test;
+    use crate::listing_test::listing_test;
+    use crate::metadata_test::metadata_test;
     use crate::move_test::move_test;
+    use crate::notification_test::notification_test;
+    use crate::object_lock_test::object_lock_test;
+    use crate::public_access_test::public_access_test;
+    use crate::range_test::range_test;
+    use crate::range_write_test::range_write_test;
+    use crate::rate_limit_test::rate_limit_test;
+    use crate::recursive_delete_test::recursive_delete_test;
     use crate::resiliency_test::test_controller_reboot_resiliency;
+    use crate::snapshot_test::snapshot_test;
     use crate::test_configs::{
         TEST_CONTROLLER_CONFIG, TEST_DASHBOARD_1_CONFIG, TEST_NODE_1_CONFIG, TEST_NODE_2_CONFIG,
     };
+    use crate::trash_test::trash_test;
+    use crate::versioning_test::versioning_test;
     use auth_framework::adapter::r#impl::basic_authenticator::BASIC_TYPE_IDENTIFIER;
     use controller_lib::public::routes::node_management::RegisterCodeCreateRequest;
     use controller_lib::setup::auth_routes::RegisterRequest;
@@ -162,9 +212,84 @@ mod tests {
         big_header!("TEST directory management");
         directory_test(user_setup.clone()).await;
 
+        big_header!("TEST listing");
+        listing_test(user_setup.clone()).await;
+
         big_header!("TEST file movement");
         move_test(user_setup.clone()).await;
 
+        big_header!("TEST copy");
+        copy_test(user_setup.clone()).await;
+
+        big_header!("TEST append");
+        append_test(user_setup.clone()).await;
+
+        big_header!("TEST archive download");
+        archive_test(user_setup.clone()).await;
+
+        big_header!("TEST archive extraction");
+        extract_test(user_setup.clone()).await;
+
+        big_header!("TEST leases");
+        lease_test(user_setup.clone()).await;
+
+        big_header!("TEST notifications");
+        notification_test(user_setup.clone()).await;
+
+        big_header!("TEST change feed");
+        change_feed_test(user_setup.clone()).await;
+
+        big_header!("TEST snapshots");
+        snapshot_test(user_setup.clone()).await;
+
+        big_header!("TEST object lock");
+        object_lock_test(user_setup.clone()).await;
+
+        big_header!("TEST public access");
+        public_access_test(user_setup.clone()).await;
+
+        big_header!("TEST cors");
+        cors_test(user_setup.clone()).await;
+
+        big_header!("TEST rate limits");
+        rate_limit_test(user_setup.clone()).await;
+
+        big_header!("TEST audit log");
+        audit_test(user_setup.clone()).await;
+
+        big_header!("TEST bucket settings");
+        bucket_settings_test(user_setup.clone()).await;
+
+        big_header!("TEST lifecycle rules");
+        lifecycle_test(user_setup.clone()).await;
+
+        big_header!("TEST bucket deletion");
+        bucket_deletion_test(user_setup.clone()).await;
+
+        big_header!("TEST batch");
+        batch_test(user_setup.clone()).await;
+
+        big_header!("TEST recursive delete");
+        recursive_delete_test(user_setup.clone()).await;
+
+        big_header!("TEST versioning");
+        versioning_test(user_setup.clone()).await;
+
+        big_header!("TEST trash");
+        trash_test(user_setup.clone()).await;
+
+        big_header!("TEST metadata");
+        metadata_test(user_setup.clone()).await;
+
+        big_header!("TEST conditional requests");
+        conditional_test(user_setup.clone()).await;
+
+        big_header!("TEST range downloads");
+        range_test(user_setup.clone()).await;
+
+        big_header!("TEST ranged writes");
+        range_write_test(user_setup.clone()).await;
+
         big_header!("TEST concurrent");
         concurrent_test(user_setup.clone()).await;
 
@@ -0,0 +1,164 @@
+use crate::directory_test::NodeArgs;
+use crate::utils::Logger;
+use dashboard_lib::public::routes::bucket::{EditBucketLifecycleRequest, LifecycleRuleRequest};
+use data::dto::entity::{AppDto, BucketDto, LifecycleRuleList};
+use data::model::file_model::{LifecycleAction, LifecycleCondition};
+use http::header::AUTHORIZATION;
+use http::StatusCode;
+use reqwest_middleware::ClientBuilder;
+use serde_json::json;
+
+const DAY: u64 = 24 * 60 * 60;
+
+async fn edit_lifecycle(body: &serde_json::Value, args: &NodeArgs<'_>) -> StatusCode {
+    args.client
+        .patch(format!(
+            "http://127.0.0.4:4002/api/bucket/lifecycle/{}/{}",
+            args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.user_token))
+        .json(body)
+        .send()
+        .await
+        .expect("")
+        .status()
+}
+
+async fn set_lifecycle(rules: Vec<LifecycleRuleRequest>, args: &NodeArgs<'_>) {
+    let body = serde_json::to_value(EditBucketLifecycleRequest { rules }).unwrap();
+    assert!(edit_lifecycle(&body, args).await.is_success());
+}
+
+async fn get_lifecycle(args: &NodeArgs<'_>) -> LifecycleRuleList {
+    args.client
+        .get(format!(
+            "http://127.0.0.4:4002/api/bucket/lifecycle/{}/{}",
+            args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.user_token))
+        .send()
+        .await
+        .expect("")
+        .json::<LifecycleRuleList>()
+        .await
+        .expect("")
+}
+
+fn rule(
+    prefix: &str,
+    action: LifecycleAction,
+    condition: LifecycleCondition,
+    age: u64,
+) -> LifecycleRuleRequest {
+    LifecycleRuleRequest {
+        prefix: prefix.to_string(),
+        action,
+        condition,
+        age,
+    }
+}
+
+pub async fn lifecycle_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: &user_token,
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    assert!(get_lifecycle(&args).await.rules.is_empty());
+
+    // The ages are far off, so the worker does not act on the other tests' files meanwhile.
+    set_lifecycle(
+        vec![
+            rule(
+                "lifecycle/logs/",
+                LifecycleAction::Expire,
+                LifecycleCondition::Age,
+                365 * DAY,
+            ),
+            rule(
+                "lifecycle/",
+                LifecycleAction::PurgeVersions,
+                LifecycleCondition::LastModified,
+                90 * DAY,
+            ),
+            rule(
+                "",
+                LifecycleAction::AbortUploads,
+                LifecycleCondition::LastModified,
+                30 * DAY,
+            ),
+        ],
+        &args,
+    )
+    .await;
+    let rules = get_lifecycle(&args).await.rules;
+    assert_eq!(rules.len(), 3);
+    assert_eq!(rules[0].prefix, "lifecycle/logs/");
+    assert_eq!(rules[0].action, LifecycleAction::Expire);
+    assert_eq!(rules[0].condition, LifecycleCondition::Age);
+    assert_eq!(rules[0].age, 365 * DAY as i64);
+    assert_eq!(rules[1].action, LifecycleAction::PurgeVersions);
+    assert_eq!(rules[1].condition, LifecycleCondition::LastModified);
+    assert_eq!(rules[2].prefix, "");
+    assert_eq!(rules[2].action, LifecycleAction::AbortUploads);
+    assert_ne!(rules[0].id, rules[1].id);
+
+    // Editing replaces the whole list
+    set_lifecycle(
+        vec![rule(
+            "lifecycle/",
+            LifecycleAction::Expire,
+            LifecycleCondition::LastModified,
+            DAY,
+        )],
+        &args,
+    )
+    .await;
+    let rules = get_lifecycle(&args).await.rules;
+    assert_eq!(rules.len(), 1);
+    assert_eq!(rules[0].condition, LifecycleCondition::LastModified);
+    assert_eq!(rules[0].age, DAY as i64);
+
+    // Unknown actions, conditions and negative ages are rejected, leaving the rules untouched
+    for invalid in [
+        json!({ "rules": [{ "action": "Archive", "condition": "Age", "age": 1 }] }),
+        json!({ "rules": [{ "action": "Expire", "condition": "Created", "age": 1 }] }),
+        json!({ "rules": [{ "action": "Expire", "condition": "Age", "age": -1 }] }),
+        json!({ "rules": [{ "action": "Expire", "condition": "Age" }] }),
+    ] {
+        assert!(edit_lifecycle(&invalid, &args).await.is_client_error());
+    }
+    assert_eq!(get_lifecycle(&args).await.rules.len(), 1);
+
+    // The prefix is optional
+    set_lifecycle(
+        vec![rule(
+            "",
+            LifecycleAction::AbortUploads,
+            LifecycleCondition::Age,
+            DAY,
+        )],
+        &args,
+    )
+    .await;
+    assert!(edit_lifecycle(
+        &json!({ "rules": [{ "action": "AbortUploads", "condition": "Age", "age": DAY }] }),
+        &args,
+    )
+    .await
+    .is_success());
+    let rules = get_lifecycle(&args).await.rules;
+    assert_eq!(rules.len(), 1);
+    assert_eq!(rules[0].prefix, "");
+
+    set_lifecycle(vec![], &args).await;
+    assert!(get_lifecycle(&args).await.rules.is_empty());
+}
@@ -0,0 +1,130 @@
+use crate::directory_test::{create_dir, create_file, NodeArgs};
+use crate::utils::Logger;
+use data::dto::entity::{AppDto, BucketDto, EntityList};
+use http::header::AUTHORIZATION;
+use http::StatusCode;
+use log::info;
+use reqwest_middleware::ClientBuilder;
+
+async fn list(query: &str, args: &NodeArgs<'_>) -> Result<EntityList, StatusCode> {
+    fetch_list(
+        format!(
+            "http://{}/api/directory/list/{}/{}/listing?{query}",
+            args.node, args.app_id, args.bucket_id,
+        ),
+        args,
+    )
+    .await
+}
+
+async fn list_bucket_files(query: &str, args: &NodeArgs<'_>) -> Result<EntityList, StatusCode> {
+    fetch_list(
+        format!(
+            "http://{}/api/bucket/list/files/{}/{}?{query}",
+            args.node, args.app_id, args.bucket_id,
+        ),
+        args,
+    )
+    .await
+}
+
+async fn fetch_list(url: String, args: &NodeArgs<'_>) -> Result<EntityList, StatusCode> {
+    let response = args
+        .client
+        .get(url)
+        .header(AUTHORIZATION, args.token.to_string())
+        .send()
+        .await
+        .unwrap();
+    if !response.status().is_success() {
+        return Err(response.status());
+    }
+    Ok(response.json::<EntityList>().await.unwrap())
+}
+
+fn names(list: &EntityList) -> Vec<&str> {
+    list.entities
+        .iter()
+        .map(|entity| entity.name.as_str())
+        .collect()
+}
+
+pub async fn listing_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, _user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: "",
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    create_dir("listing/sub", &args).await;
+    for name in ["a-1", "a-2", "b-1", "c"] {
+        create_file(&format!("listing/{name}"), &args).await;
+    }
+    header!("Created test files");
+
+    let mut listed = vec![];
+    let mut cursor: Option<String> = None;
+    loop {
+        let query = match &cursor {
+            Some(cursor) => format!("limit=2&cursor={cursor}"),
+            None => "limit=2".to_string(),
+        };
+        let page = list(&query, &args).await.unwrap();
+        assert!(page.entities.len() <= 2);
+        listed.extend(names(&page).into_iter().map(str::to_string));
+        cursor = page.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(listed, vec!["sub", "a-1", "a-2", "b-1", "c"]);
+    header!("Paged through the directory");
+
+    let page = list("order=desc", &args).await.unwrap();
+    assert_eq!(names(&page), vec!["c", "b-1", "a-2", "a-1", "sub"]);
+    assert!(page.next_cursor.is_none());
+    header!("Listed in descending order");
+
+    let page = list("prefix=a-", &args).await.unwrap();
+    assert_eq!(names(&page), vec!["a-1", "a-2"]);
+    let page = list("delimiter=-", &args).await.unwrap();
+    assert_eq!(names(&page), vec!["sub", "c"]);
+    assert_eq!(page.common_prefixes, vec!["a-", "b-"]);
+    header!("Listed with a prefix and a delimiter");
+
+    let page = list_bucket_files("prefix=listing/a-", &args).await.unwrap();
+    assert_eq!(names(&page), vec!["a-1", "a-2"]);
+    let page = list_bucket_files("prefix=listing/&delimiter=-", &args)
+        .await
+        .unwrap();
+    assert_eq!(names(&page), vec!["c"]);
+    assert_eq!(page.common_prefixes, vec!["listing/a-", "listing/b-"]);
+    header!("Listed the bucket by full paths");
+
+    let page = list("min_size=1", &args).await.unwrap();
+    assert_eq!(names(&page), vec!["a-1", "a-2", "b-1", "c"]);
+    let page = list("max_size=0", &args).await.unwrap();
+    assert_eq!(names(&page), vec!["sub"]);
+    let page = list("modified_after=2100-01-01T00:00:00Z", &args)
+        .await
+        .unwrap();
+    assert!(page.entities.is_empty());
+    header!("Listed with filters");
+
+    assert_eq!(
+        list("cursor=invalid", &args).await.unwrap_err(),
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        list("limit=0", &args).await.unwrap_err(),
+        StatusCode::BAD_REQUEST
+    );
+    header!("Rejected invalid queries");
+}
@@ -0,0 +1,99 @@
+use crate::directory_test::{stat_entity, NodeArgs, FILE_SIZE};
+use crate::file_transfer_test::delete_file;
+use crate::utils::Logger;
+use data::dto::entity::{AppDto, BucketDto, FileMetadataDto};
+use http::header::{AUTHORIZATION, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE};
+use log::info;
+use reqwest_middleware::ClientBuilder;
+use std::collections::HashMap;
+
+async fn upload_with_metadata(name: &str, args: &NodeArgs<'_>) {
+    assert!(args
+        .client
+        .post(format!(
+            "http://{}/api/file/upload/oneshot/{}/{}/{}",
+            args.node, args.app_id, args.bucket_id, name,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .header(CONTENT_LENGTH, FILE_SIZE.to_string())
+        .header(CONTENT_TYPE, "text/plain")
+        .header(CACHE_CONTROL, "max-age=60")
+        .header("x-meowith-meta-owner", "test")
+        .body(vec![0u8; FILE_SIZE])
+        .send()
+        .await
+        .expect("")
+        .status()
+        .is_success());
+}
+
+async fn update_metadata(name: &str, req: &FileMetadataDto, args: &NodeArgs<'_>) {
+    assert!(args
+        .client
+        .patch(format!(
+            "http://{}/api/file/metadata/{}/{}/{}",
+            args.node, args.app_id, args.bucket_id, name,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .json(req)
+        .send()
+        .await
+        .expect("")
+        .status()
+        .is_success());
+}
+
+pub async fn metadata_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: &user_token,
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    upload_with_metadata("with_metadata", &args).await;
+    let metadata = stat_entity("with_metadata", &args)
+        .await
+        .metadata
+        .expect("");
+    assert_eq!(metadata.content_type.as_deref(), Some("text/plain"));
+    assert_eq!(metadata.cache_control.as_deref(), Some("max-age=60"));
+    assert_eq!(metadata.user.get("owner").map(String::as_str), Some("test"));
+    header!("Metadata stored on upload");
+
+    let response = args
+        .client
+        .get(format!(
+            "http://{}/api/file/download/{}/{}/with_metadata",
+            args.node, args.app_id, args.bucket_id,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .send()
+        .await
+        .expect("");
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/plain");
+    assert_eq!(response.headers()[CACHE_CONTROL], "max-age=60");
+    assert_eq!(response.headers()["x-meowith-meta-owner"], "test");
+    header!("Metadata returned on download");
+
+    let req = FileMetadataDto {
+        content_type: Some("application/json".to_string()),
+        content_disposition: None,
+        cache_control: None,
+        user: HashMap::from([("edited".to_string(), "yes".to_string())]),
+    };
+    update_metadata("with_metadata", &req, &args).await;
+    assert_eq!(
+        stat_entity("with_metadata", &args).await.metadata,
+        Some(req)
+    );
+    header!("Metadata edited");
+
+    delete_file("with_metadata", args.node, &args).await;
+}
@@ -0,0 +1,275 @@
+use crate::directory_test::{create_file, delete_dir, rename_dir, NodeArgs};
+use crate::file_transfer_test::delete_file;
+use crate::move_test::rename_file;
+use crate::utils::Logger;
+use actix_web::web::{Bytes, Data};
+use actix_web::{post, App, HttpRequest, HttpResponse, HttpServer};
+use dashboard_lib::public::routes::bucket::{
+    EditBucketNotificationsRequest, NotificationConfigRequest,
+};
+use data::dto::entity::{AppDto, BucketDto, BucketEventDto, NotificationConfigList};
+use data::model::file_model::BucketEventKind;
+use http::header::AUTHORIZATION;
+use log::info;
+use node_lib::public::service::notification_service::{
+    sign, DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER,
+};
+use reqwest_middleware::ClientBuilder;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const RECEIVER: &str = "127.0.0.1:4010";
+const SECRET: &str = "webhook-secret";
+
+struct Delivery {
+    hook: String,
+    event: String,
+    delivery: String,
+    signature: String,
+    body: Bytes,
+}
+
+#[derive(Default)]
+struct Received(Mutex<Vec<Delivery>>);
+
+#[post("/{hook}")]
+async fn receive(req: HttpRequest, body: Bytes, received: Data<Received>) -> HttpResponse {
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    received.0.lock().unwrap().push(Delivery {
+        hook: req.match_info().get("hook").unwrap().to_string(),
+        event: header(EVENT_HEADER),
+        delivery: header(DELIVERY_HEADER),
+        signature: header(SIGNATURE_HEADER),
+        body,
+    });
+    HttpResponse::Ok().finish()
+}
+
+async fn edit_notifications(
+    configs: Vec<NotificationConfigRequest>,
+    args: &NodeArgs<'_>,
+) -> http::StatusCode {
+    args.client
+        .patch(format!(
+            "http://127.0.0.4:4002/api/bucket/notifications/{}/{}",
+            args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.user_token))
+        .json(&EditBucketNotificationsRequest { configs })
+        .send()
+        .await
+        .expect("")
+        .status()
+}
+
+async fn set_notifications(configs: Vec<NotificationConfigRequest>, args: &NodeArgs<'_>) {
+    assert!(edit_notifications(configs, args).await.is_success());
+}
+
+async fn get_notifications(args: &NodeArgs<'_>) -> NotificationConfigList {
+    args.client
+        .get(format!(
+            "http://127.0.0.4:4002/api/bucket/notifications/{}/{}",
+            args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.user_token))
+        .send()
+        .await
+        .expect("")
+        .json::<NotificationConfigList>()
+        .await
+        .expect("")
+}
+
+fn config(hook: &str, events: Vec<BucketEventKind>, prefix: &str) -> NotificationConfigRequest {
+    NotificationConfigRequest {
+        id: None,
+        events,
+        prefix: prefix.to_string(),
+        url: format!("http://{RECEIVER}/{hook}"),
+        secret: SECRET.to_string(),
+    }
+}
+
+/// Waits for the deliveries of a hook, which are made in the background.
+async fn await_events(hook: &str, count: usize, received: &Received) -> Vec<BucketEventDto> {
+    for _ in 0..100 {
+        {
+            let received = received.0.lock().unwrap();
+            let deliveries: Vec<&Delivery> = received
+                .iter()
+                .filter(|delivery| delivery.hook == hook)
+                .collect();
+            if deliveries.len() >= count {
+                assert_eq!(deliveries.len(), count);
+                return deliveries
+                    .into_iter()
+                    .map(|delivery| {
+                        assert_eq!(
+                            delivery.signature,
+                            format!("sha256={}", sign(SECRET, &delivery.body).unwrap())
+                        );
+                        let event: BucketEventDto = serde_json::from_slice(&delivery.body).unwrap();
+                        assert_eq!(delivery.delivery, event.id.to_string());
+                        assert_eq!(
+                            delivery.event,
+                            serde_json::to_value(event.event).unwrap().as_str().unwrap()
+                        );
+                        event
+                    })
+                    .collect();
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Expected {count} deliveries to {hook}");
+}
+
+fn find(events: &[BucketEventDto], kind: BucketEventKind, path: &str) -> BucketEventDto {
+    events
+        .iter()
+        .find(|event| event.event == kind && event.path == path)
+        .unwrap_or_else(|| panic!("Missing {kind:?} event on {path}"))
+        .clone()
+}
+
+pub async fn notification_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let received = Data::new(Received::default());
+    let server_received = received.clone();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(server_received.clone())
+            .service(receive)
+    })
+    .workers(1)
+    .bind(RECEIVER)
+    .unwrap()
+    .run();
+    let server_handle = server.handle();
+    tokio::spawn(server);
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: &user_token,
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    set_notifications(
+        vec![
+            config(
+                "all",
+                vec![
+                    BucketEventKind::Created,
+                    BucketEventKind::Overwritten,
+                    BucketEventKind::Deleted,
+                    BucketEventKind::Renamed,
+                ],
+                "hooks/",
+            ),
+            config("deleted", vec![BucketEventKind::Deleted], ""),
+        ],
+        &args,
+    )
+    .await;
+    let configs = get_notifications(&args).await.configs;
+    assert_eq!(configs.len(), 2);
+    assert_eq!(configs[0].prefix, "hooks/");
+    assert_eq!(configs[1].events, vec![BucketEventKind::Deleted]);
+    header!("Configured webhooks");
+
+    create_file("hooks/a", &args).await;
+    create_file("hooks/a", &args).await;
+    rename_file("hooks/a", "hooks/b", &args).await;
+    delete_file("hooks/b", args.node, &args).await;
+    create_file("other/a", &args).await;
+    delete_file("other/a", "127.0.0.3:4001", &args).await;
+
+    let events = await_events("all", 4, &received).await;
+    let created = find(&events, BucketEventKind::Created, "hooks/a");
+    assert_eq!(created.app_id, app_dto.id);
+    assert_eq!(created.bucket_id, bucket_dto.id);
+    assert!(!created.directory);
+    assert!(created.etag.is_some());
+    let overwritten = find(&events, BucketEventKind::Overwritten, "hooks/a");
+    assert_ne!(overwritten.etag, created.etag);
+    let renamed = find(&events, BucketEventKind::Renamed, "hooks/b");
+    assert_eq!(renamed.old_path.as_deref(), Some("hooks/a"));
+    assert_eq!(renamed.etag, overwritten.etag);
+    find(&events, BucketEventKind::Deleted, "hooks/b");
+
+    let deleted = await_events("deleted", 2, &received).await;
+    find(&deleted, BucketEventKind::Deleted, "hooks/b");
+    find(&deleted, BucketEventKind::Deleted, "other/a");
+    header!("Delivered matching events");
+
+    create_file("hooks/c/d", &args).await;
+    rename_dir("hooks/c", "hooks/e", &args).await;
+    let events = await_events("all", 6, &received).await;
+    let renamed = find(&events, BucketEventKind::Renamed, "hooks/e");
+    assert_eq!(renamed.old_path.as_deref(), Some("hooks/c"));
+    assert!(renamed.directory);
+    header!("Delivered directory rename");
+
+    let deleted = config("deleted", vec![BucketEventKind::Deleted], "");
+    set_notifications(
+        vec![
+            NotificationConfigRequest {
+                id: Some(configs[0].id),
+                ..config("all", vec![BucketEventKind::Deleted], "hooks/")
+            },
+            deleted,
+        ],
+        &args,
+    )
+    .await;
+    let edited = get_notifications(&args).await.configs;
+    assert_eq!(edited[0].id, configs[0].id);
+    assert_eq!(edited[0].events, vec![BucketEventKind::Deleted]);
+    assert_eq!(edited[1].id, configs[1].id);
+    assert_eq!(
+        edit_notifications(
+            vec![NotificationConfigRequest {
+                id: Some(uuid::Uuid::new_v4()),
+                ..config("all", vec![BucketEventKind::Deleted], "")
+            }],
+            &args,
+        )
+        .await,
+        http::StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        edit_notifications(
+            vec![NotificationConfigRequest {
+                url: "ftp://127.0.0.1/all".to_string(),
+                ..config("all", vec![BucketEventKind::Deleted], "")
+            }],
+            &args,
+        )
+        .await,
+        http::StatusCode::BAD_REQUEST
+    );
+    header!("Edited webhooks keep their ids");
+
+    set_notifications(vec![], &args).await;
+    assert!(get_notifications(&args).await.configs.is_empty());
+    assert_eq!(delete_dir("hooks", true, &args).await, http::StatusCode::OK);
+    delete_dir("other", true, &args).await;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(received.0.lock().unwrap().len(), 8);
+    header!("Removed webhooks");
+
+    server_handle.stop(true).await;
+}
@@ -0,0 +1,170 @@
+use crate::directory_test::{create_file, delete_dir, stat_entity, NodeArgs, FILE_SIZE};
+use crate::file_transfer_test::delete_file;
+use crate::utils::Logger;
+use dashboard_lib::public::routes::bucket::EditBucketObjectLockRequest;
+use data::dto::entity::{
+    AppDto, BucketDto, DeleteDirectoryRequest, LegalHoldRequest, RenameEntityRequest,
+};
+use http::header::{AUTHORIZATION, CONTENT_LENGTH};
+use http::StatusCode;
+use log::info;
+use reqwest_middleware::ClientBuilder;
+use std::time::Duration;
+
+async fn set_object_lock(retention: u64, args: &NodeArgs<'_>) {
+    let req = EditBucketObjectLockRequest { retention };
+
+    assert!(args
+        .client
+        .patch(format!(
+            "http://127.0.0.4:4002/api/bucket/object-lock/{}/{}",
+            args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.user_token))
+        .json(&req)
+        .send()
+        .await
+        .expect("")
+        .status()
+        .is_success());
+}
+
+async fn set_legal_hold(path: &str, hold: bool, args: &NodeArgs<'_>) {
+    assert!(args
+        .client
+        .put(format!(
+            "http://{}/api/file/hold/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.token))
+        .json(&LegalHoldRequest { hold })
+        .send()
+        .await
+        .expect("")
+        .status()
+        .is_success());
+}
+
+async fn try_upload(path: &str, args: &NodeArgs<'_>) -> StatusCode {
+    args.client
+        .post(format!(
+            "http://{}/api/file/upload/oneshot/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .header(CONTENT_LENGTH, FILE_SIZE.to_string())
+        .body(vec![0u8; FILE_SIZE])
+        .send()
+        .await
+        .expect("")
+        .status()
+}
+
+async fn try_delete(path: &str, args: &NodeArgs<'_>) -> StatusCode {
+    args.client
+        .delete(format!(
+            "http://{}/api/file/delete/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.token))
+        .send()
+        .await
+        .expect("")
+        .status()
+}
+
+async fn try_rename(kind: &str, path: &str, to: &str, args: &NodeArgs<'_>) -> StatusCode {
+    args.client
+        .post(format!(
+            "http://{}/api/{kind}/rename/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .json(&RenameEntityRequest { to: to.to_string() })
+        .send()
+        .await
+        .expect("")
+        .status()
+}
+
+async fn try_delete_dir(path: &str, args: &NodeArgs<'_>) -> reqwest::Response {
+    args.client
+        .delete(format!(
+            "http://{}/api/directory/delete/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .json(&DeleteDirectoryRequest { recursive: true })
+        .send()
+        .await
+        .expect("")
+}
+
+pub async fn object_lock_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: &user_token,
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    set_object_lock(5, &args).await;
+    create_file("lock/retained", &args).await;
+    set_object_lock(0, &args).await;
+    assert!(stat_entity("lock/retained", &args)
+        .await
+        .retain_until
+        .is_some());
+
+    assert_eq!(
+        try_upload("lock/retained", &args).await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        try_delete("lock/retained", &args).await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        try_rename("file", "lock/retained", "lock/moved", &args).await,
+        StatusCode::FORBIDDEN
+    );
+    let response = try_delete_dir("lock", &args).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("ObjectLocked"), "{body}");
+    header!("Retained file protected");
+
+    tokio::time::sleep(Duration::from_secs(6)).await;
+    assert!(try_upload("lock/retained", &args).await.is_success());
+    assert!(stat_entity("lock/retained", &args)
+        .await
+        .retain_until
+        .is_none());
+    header!("Retention passed");
+
+    set_legal_hold("lock/retained", true, &args).await;
+    assert_eq!(
+        stat_entity("lock/retained", &args).await.legal_hold,
+        Some(true)
+    );
+    assert_eq!(
+        try_delete("lock/retained", &args).await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        try_rename("directory", "lock", "unlocked", &args).await,
+        StatusCode::FORBIDDEN
+    );
+    header!("Held file protected");
+
+    set_legal_hold("lock/retained", false, &args).await;
+    delete_file("lock/retained", args.node, &args).await;
+    assert_eq!(delete_dir("lock", false, &args).await, StatusCode::OK);
+    header!("Hold removed");
+}
@@ -0,0 +1,141 @@
+use crate::directory_test::{create_file, delete_dir, NodeArgs, FILE_SIZE};
+use crate::file_transfer_test::delete_file;
+use crate::utils::Logger;
+use dashboard_lib::public::routes::bucket::EditBucketPublicAccessRequest;
+use data::dto::entity::{AppDto, BucketDto, EntityList, PublicAccessDto};
+use http::header::AUTHORIZATION;
+use http::StatusCode;
+use log::info;
+use reqwest_middleware::ClientBuilder;
+
+const REQUESTS_PER_MINUTE: u32 = 5;
+
+async fn set_public_access(prefixes: Vec<String>, args: &NodeArgs<'_>) {
+    let req = EditBucketPublicAccessRequest {
+        prefixes,
+        listing: true,
+        requests_per_minute: REQUESTS_PER_MINUTE,
+    };
+
+    assert!(args
+        .client
+        .patch(format!(
+            "http://127.0.0.4:4002/api/bucket/public-access/{}/{}",
+            args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.user_token))
+        .json(&req)
+        .send()
+        .await
+        .expect("")
+        .status()
+        .is_success());
+}
+
+async fn get_public_access(args: &NodeArgs<'_>) -> PublicAccessDto {
+    args.client
+        .get(format!(
+            "http://127.0.0.4:4002/api/bucket/public-access/{}/{}",
+            args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.user_token))
+        .send()
+        .await
+        .expect("")
+        .json::<PublicAccessDto>()
+        .await
+        .expect("")
+}
+
+fn public_url(action: &str, path: &str, args: &NodeArgs<'_>) -> String {
+    format!(
+        "http://{}/api/public/{action}/{}/{}/{path}",
+        args.node, args.app_id, args.bucket_id
+    )
+}
+
+pub async fn public_access_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: &user_token,
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    create_file("public/asset", &args).await;
+    create_file("private/secret", &args).await;
+    set_public_access(vec!["public/".to_string()], &args).await;
+    let public_access = get_public_access(&args).await;
+    assert_eq!(public_access.prefixes, vec!["public/".to_string()]);
+    assert_eq!(public_access.requests_per_minute, REQUESTS_PER_MINUTE);
+    header!("Public access set");
+
+    let response = client
+        .get(public_url("download", "public/asset", &args))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.bytes().await.unwrap().len(), FILE_SIZE);
+    let response = client
+        .head(public_url("download", "public/asset", &args))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        client
+            .get(public_url("download", "private/secret", &args))
+            .send()
+            .await
+            .unwrap()
+            .status(),
+        StatusCode::UNAUTHORIZED
+    );
+    header!("Anonymous downloads");
+
+    let listed: EntityList = client
+        .get(public_url("list", "public", &args))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(listed.entities.len(), 1);
+    assert_eq!(
+        client
+            .get(public_url("list", "", &args))
+            .send()
+            .await
+            .unwrap()
+            .status(),
+        StatusCode::UNAUTHORIZED
+    );
+    header!("Anonymous listing");
+
+    // every anonymous request above counted towards the limit
+    let response = client
+        .get(public_url("download", "public/asset", &args))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("RateLimited"), "{body}");
+    header!("Rate limited");
+
+    set_public_access(vec![], &args).await;
+    assert!(get_public_access(&args).await.prefixes.is_empty());
+    delete_file("public/asset", args.node, &args).await;
+    delete_file("private/secret", args.node, &args).await;
+    assert_eq!(delete_dir("public", false, &args).await, StatusCode::OK);
+    assert_eq!(delete_dir("private", false, &args).await, StatusCode::OK);
+    header!("Public access removed");
+}
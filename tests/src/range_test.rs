@@ -0,0 +1,99 @@
+use crate::directory_test::NodeArgs;
+use crate::file_transfer_test::delete_file;
+use crate::utils::Logger;
+use data::dto::entity::{AppDto, BucketDto};
+use http::header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, RANGE};
+use http::StatusCode;
+use log::info;
+use reqwest_middleware::ClientBuilder;
+
+const RANGE_FILE_SIZE: u8 = 100;
+
+pub async fn range_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: &user_token,
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    assert!(args
+        .client
+        .post(format!(
+            "http://{}/api/file/upload/oneshot/{}/{}/ranged",
+            args.node, args.app_id, args.bucket_id,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .header(CONTENT_LENGTH, RANGE_FILE_SIZE.to_string())
+        .body((0..RANGE_FILE_SIZE).collect::<Vec<u8>>())
+        .send()
+        .await
+        .expect("")
+        .status()
+        .is_success());
+
+    let download = |range: &'static str| {
+        args.client
+            .get(format!(
+                "http://{}/api/file/download/{}/{}/ranged",
+                args.node, args.app_id, args.bucket_id,
+            ))
+            .header(AUTHORIZATION, args.token.to_string())
+            .header(RANGE, range)
+            .send()
+    };
+
+    let response = download("bytes=10-19").await.expect("");
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.headers()[CONTENT_RANGE], "bytes 10-19/100");
+    assert_eq!(
+        response.bytes().await.expect("").to_vec(),
+        (10..20).collect::<Vec<u8>>()
+    );
+    header!("Single range");
+
+    let response = download("bytes=0-1,50-51,1000-1001").await.expect("");
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    let content_type = response.headers()[CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let boundary = content_type
+        .strip_prefix("multipart/byteranges; boundary=")
+        .expect("");
+    let body = response.bytes().await.expect("").to_vec();
+    let mut expected = format!(
+        "\r\n--{boundary}\r\nContent-Type: application/octet-stream\r\nContent-Range: bytes 0-1/100\r\n\r\n"
+    )
+    .into_bytes();
+    expected.extend([0u8, 1u8]);
+    expected.extend(
+        format!("\r\n--{boundary}\r\nContent-Type: application/octet-stream\r\nContent-Range: bytes 50-51/100\r\n\r\n")
+            .into_bytes(),
+    );
+    expected.extend([50u8, 51u8]);
+    expected.extend(format!("\r\n--{boundary}--\r\n").into_bytes());
+    assert_eq!(body, expected);
+    header!("Multiple ranges");
+
+    let response = download("bytes=0-,0-,10-19,50-").await.expect("");
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.headers()[CONTENT_RANGE], "bytes 0-99/100");
+    assert_eq!(
+        response.bytes().await.expect("").to_vec(),
+        (0..RANGE_FILE_SIZE).collect::<Vec<u8>>()
+    );
+    header!("Overlapping ranges");
+
+    let response = download("bytes=1000-1001").await.expect("");
+    assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(response.headers()[CONTENT_RANGE], "bytes */100");
+
+    delete_file("ranged", args.node, &args).await;
+}
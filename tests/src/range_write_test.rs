@@ -0,0 +1,126 @@
+use crate::directory_test::{create_file, stat_entity, NodeArgs, FILE_SIZE};
+use crate::file_transfer_test::delete_file;
+use crate::utils::Logger;
+use crate::versioning_test::{delete_version, list_versions, restore_version, set_versioning};
+use data::dto::entity::{AppDto, BucketDto};
+use http::header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_RANGE};
+use http::StatusCode;
+use log::info;
+use reqwest_middleware::ClientBuilder;
+
+pub(crate) async fn write_range(
+    name: &str,
+    range: &str,
+    body: &[u8],
+    args: &NodeArgs<'_>,
+) -> StatusCode {
+    args.client
+        .put(format!(
+            "http://{}/api/file/write/{}/{}/{}",
+            args.node, args.app_id, args.bucket_id, name,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .header(CONTENT_LENGTH, body.len().to_string())
+        .header(CONTENT_RANGE, range)
+        .body(body.to_vec())
+        .send()
+        .await
+        .expect("")
+        .status()
+}
+
+async fn download(name: &str, args: &NodeArgs<'_>) -> Vec<u8> {
+    args.client
+        .get(format!(
+            "http://{}/api/file/download/{}/{}/{}",
+            args.node, args.app_id, args.bucket_id, name,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .send()
+        .await
+        .expect("")
+        .bytes()
+        .await
+        .unwrap()
+        .to_vec()
+}
+
+pub async fn range_write_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: &user_token,
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    create_file("range_write", &args).await;
+    let original = stat_entity("range_write", &args).await;
+    header!("Created test file");
+
+    assert!(write_range("range_write", "bytes 2-4/*", b"abc", &args)
+        .await
+        .is_success());
+    assert!(write_range(
+        "range_write",
+        &format!("bytes 9-9/{FILE_SIZE}"),
+        b"z",
+        &args
+    )
+    .await
+    .is_success());
+    let mut expected = vec![0u8; FILE_SIZE];
+    expected[2..5].copy_from_slice(b"abc");
+    expected[9] = b'z';
+    assert_eq!(download("range_write", &args).await, expected);
+
+    let rewritten = stat_entity("range_write", &args).await;
+    assert_eq!(rewritten.size, FILE_SIZE as u64);
+    assert_ne!(rewritten.etag, original.etag);
+    header!("Overwrote file ranges");
+
+    assert_eq!(
+        write_range("range_write", "bytes 8-11/*", b"abcd", &args).await,
+        StatusCode::RANGE_NOT_SATISFIABLE
+    );
+    assert_eq!(
+        write_range("range_write", "bytes 0-1/20", b"ab", &args).await,
+        StatusCode::RANGE_NOT_SATISFIABLE
+    );
+    assert_eq!(
+        write_range("range_write", "bytes 0-3/*", b"ab", &args).await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(download("range_write", &args).await, expected);
+    header!("Rejected invalid ranges");
+
+    set_versioning(true, &args).await;
+    assert!(write_range("range_write", "bytes 0-1/*", b"xy", &args)
+        .await
+        .is_success());
+    let mut overwritten = expected.clone();
+    overwritten[0..2].copy_from_slice(b"xy");
+    assert_eq!(download("range_write", &args).await, overwritten);
+    let versions = list_versions("range_write", &args).await;
+    assert_eq!(versions.versions.len(), 1);
+    header!("Ranged write retained a version");
+
+    restore_version("range_write", versions.versions[0].version_id, &args).await;
+    assert_eq!(download("range_write", &args).await, expected);
+    let versions = list_versions("range_write", &args).await;
+    assert_eq!(versions.versions.len(), 1);
+    header!("Restored the version before the ranged write");
+
+    set_versioning(false, &args).await;
+    delete_version("range_write", versions.versions[0].version_id, &args).await;
+    delete_file("range_write", args.node, &args).await;
+    assert!(list_versions("range_write", &args)
+        .await
+        .versions
+        .is_empty());
+}
@@ -0,0 +1,222 @@
+use crate::directory_test::{delete_dir, NodeArgs};
+use crate::file_transfer_test::delete_file;
+use crate::utils::Logger;
+use commons::permission::PermissionList;
+use dashboard_lib::public::routes::token::AppTokenResponse;
+use data::dto::entity::{AppDto, BucketDto, RateLimitsDto, ScopedPermission, TokenIssueRequest};
+use data::model::permission_model::UserPermission;
+use http::header::{AUTHORIZATION, CONTENT_LENGTH, RETRY_AFTER};
+use http::StatusCode;
+use log::info;
+use reqwest_middleware::ClientBuilder;
+use std::time::{Duration, Instant};
+
+/// Three times the bandwidth each node of the two node cluster allows, every transfer takes two seconds.
+const TRANSFER_SIZE: usize = 24 * 1024;
+const BYTES_PER_SECOND: u64 = 16 * 1024;
+
+async fn issue_limited_token(name: &str, limits: RateLimitsDto, args: &NodeArgs<'_>) -> String {
+    let req = TokenIssueRequest {
+        app_id: args.app_id,
+        name: name.to_string(),
+        perms: vec![ScopedPermission {
+            bucket_id: args.bucket_id,
+            allowance: PermissionList(vec![
+                UserPermission::Read,
+                UserPermission::Write,
+                UserPermission::Overwrite,
+                UserPermission::FetchBucketInfo,
+            ])
+            .into(),
+        }],
+        limits: Some(limits),
+    };
+
+    args.client
+        .post("http://127.0.0.4:4002/api/app/token/issue")
+        .json(&req)
+        .header(AUTHORIZATION, format!("Bearer {}", args.user_token))
+        .send()
+        .await
+        .expect("")
+        .json::<AppTokenResponse>()
+        .await
+        .expect("")
+        .token
+}
+
+async fn set_app_limits(limits: RateLimitsDto, args: &NodeArgs<'_>) {
+    assert!(args
+        .client
+        .patch(format!(
+            "http://127.0.0.4:4002/api/app/limits/{}",
+            args.app_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.user_token))
+        .json(&limits)
+        .send()
+        .await
+        .expect("")
+        .status()
+        .is_success());
+    // the nodes drop the cached limits once the invalidation reaches them
+    tokio::time::sleep(Duration::from_secs(1)).await;
+}
+
+async fn get_app_limits(args: &NodeArgs<'_>) -> RateLimitsDto {
+    args.client
+        .get(format!(
+            "http://127.0.0.4:4002/api/app/limits/{}",
+            args.app_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.user_token))
+        .send()
+        .await
+        .expect("")
+        .json::<RateLimitsDto>()
+        .await
+        .expect("")
+}
+
+async fn stat(path: &str, token: &str, args: &NodeArgs<'_>) -> reqwest::Response {
+    args.client
+        .get(format!(
+            "http://{}/api/bucket/stat/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {token}"))
+        .send()
+        .await
+        .expect("")
+}
+
+async fn download(path: &str, token: &str, args: &NodeArgs<'_>) -> reqwest::Response {
+    args.client
+        .get(format!(
+            "http://{}/api/file/download/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {token}"))
+        .send()
+        .await
+        .expect("")
+}
+
+async fn upload(path: &str, token: &str, args: &NodeArgs<'_>) {
+    let response = args
+        .client
+        .post(format!(
+            "http://{}/api/file/upload/oneshot/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {token}"))
+        .header(CONTENT_LENGTH, TRANSFER_SIZE.to_string())
+        .body(vec![0u8; TRANSFER_SIZE])
+        .send()
+        .await
+        .expect("");
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+fn assert_retry_after(response: &reqwest::Response) {
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response
+        .headers()
+        .get(RETRY_AFTER)
+        .expect("Missing Retry-After")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after >= 1);
+}
+
+pub async fn rate_limit_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: &user_token,
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    let throttled_token = issue_limited_token(
+        "throttled",
+        RateLimitsDto {
+            concurrent_transfers: 2,
+            upload_bytes_per_second: BYTES_PER_SECOND,
+            download_bytes_per_second: BYTES_PER_SECOND,
+            ..Default::default()
+        },
+        &args,
+    )
+    .await;
+    let start = Instant::now();
+    upload("limits/file", &throttled_token, &args).await;
+    assert!(start.elapsed() >= Duration::from_millis(1500));
+    header!("Upload throttled");
+
+    let start = Instant::now();
+    let first = download("limits/file", &throttled_token, &args).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    // each node allows one of the two concurrent transfers
+    let second = download("limits/file", &throttled_token, &args).await;
+    assert_retry_after(&second);
+    assert_eq!(first.bytes().await.unwrap().len(), TRANSFER_SIZE);
+    assert!(start.elapsed() >= Duration::from_millis(1500));
+    header!("Download throttled");
+
+    let hasty_token = issue_limited_token(
+        "hasty",
+        RateLimitsDto {
+            requests_per_second: 4,
+            ..Default::default()
+        },
+        &args,
+    )
+    .await;
+    assert_eq!(
+        stat("limits/file", &hasty_token, &args).await.status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        stat("limits/file", &hasty_token, &args).await.status(),
+        StatusCode::OK
+    );
+    assert_retry_after(&stat("limits/file", &hasty_token, &args).await);
+    assert_eq!(
+        stat("limits/file", &token, &args).await.status(),
+        StatusCode::OK
+    );
+    header!("Token requests limited");
+
+    let app_limits = RateLimitsDto {
+        requests_per_second: 2,
+        ..Default::default()
+    };
+    set_app_limits(app_limits, &args).await;
+    assert_eq!(get_app_limits(&args).await, app_limits);
+    assert_eq!(
+        stat("limits/file", &token, &args).await.status(),
+        StatusCode::OK
+    );
+    assert_retry_after(&stat("limits/file", &token, &args).await);
+    header!("App requests limited");
+
+    set_app_limits(RateLimitsDto::default(), &args).await;
+    assert!(get_app_limits(&args).await.is_unlimited());
+    for _ in 0..3 {
+        assert_eq!(
+            stat("limits/file", &token, &args).await.status(),
+            StatusCode::OK
+        );
+    }
+    delete_file("limits/file", args.node, &args).await;
+    assert_eq!(delete_dir("limits", false, &args).await, StatusCode::OK);
+    header!("Limits removed");
+}
@@ -0,0 +1,99 @@
+use crate::directory_test::{create_file, NodeArgs, FILE_SIZE};
+use crate::file_transfer_test::fetch_bucket_info;
+use crate::utils::Logger;
+use data::dto::entity::{AppDto, BucketDto, BucketJobDto, DeleteDirectoryRequest};
+use data::model::file_model::JobState;
+use http::header::AUTHORIZATION;
+use http::StatusCode;
+use log::info;
+use reqwest::Response;
+use reqwest_middleware::ClientBuilder;
+use std::time::Duration;
+
+/// Above the amount of files deleted within the request.
+const JOB_FILE_COUNT: usize = 101;
+
+async fn delete_recursive(name: &str, args: &NodeArgs<'_>) -> Response {
+    args.client
+        .delete(format!(
+            "http://{}/api/directory/delete/{}/{}/{name}",
+            args.node, args.app_id, args.bucket_id,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .json(&DeleteDirectoryRequest { recursive: true })
+        .send()
+        .await
+        .expect("")
+}
+
+async fn fetch_job(job: &BucketJobDto, args: &NodeArgs<'_>) -> BucketJobDto {
+    args.client
+        .get(format!(
+            "http://{}/api/bucket/jobs/{}/{}/{}",
+            args.node, args.app_id, args.bucket_id, job.id,
+        ))
+        .header(AUTHORIZATION, args.token.to_string())
+        .send()
+        .await
+        .expect("")
+        .json()
+        .await
+        .expect("")
+}
+
+pub async fn recursive_delete_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, _user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: "",
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    let initial = fetch_bucket_info(args.token, args.app_id, args.bucket_id, args.client).await;
+
+    create_file("tree/a", &args).await;
+    create_file("tree/sub/b", &args).await;
+    create_file("tree/sub/deeper/c", &args).await;
+    header!("Created small tree");
+
+    assert_eq!(
+        delete_recursive("tree", &args).await.status(),
+        StatusCode::OK
+    );
+    let bucket = fetch_bucket_info(args.token, args.app_id, args.bucket_id, args.client).await;
+    assert_eq!(bucket.file_count, initial.file_count);
+    assert_eq!(bucket.space_taken, initial.space_taken);
+    header!("Deleted small tree");
+
+    for i in 0..JOB_FILE_COUNT {
+        create_file(&format!("large_tree/{}/{i}", i % 4), &args).await;
+    }
+    header!("Created large tree");
+
+    let response = delete_recursive("large_tree", &args).await;
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let mut job: BucketJobDto = response.json().await.expect("");
+    assert_eq!(job.total_files, JOB_FILE_COUNT as u64);
+
+    for _ in 0..100 {
+        if job.state != JobState::Running {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        job = fetch_job(&job, &args).await;
+    }
+    assert_eq!(job.state, JobState::Completed);
+    assert_eq!(job.processed_files, JOB_FILE_COUNT as u64);
+    assert_eq!(job.deleted_size, (JOB_FILE_COUNT * FILE_SIZE) as u64);
+
+    let bucket = fetch_bucket_info(args.token, args.app_id, args.bucket_id, args.client).await;
+    assert_eq!(bucket.file_count, initial.file_count);
+    assert_eq!(bucket.space_taken, initial.space_taken);
+    header!("Deleted large tree in a job");
+}
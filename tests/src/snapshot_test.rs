@@ -0,0 +1,185 @@
+use crate::directory_test::{
+    create_file, delete_dir, list_folder, stat_entity, NodeArgs, FILE_SIZE,
+};
+use crate::file_transfer_test::delete_file;
+use crate::utils::Logger;
+use data::dto::entity::{
+    AppDto, BucketDto, BucketSnapshotDto, BucketSnapshotList, CreateSnapshotRequest, Entity,
+    EntityList, RestoreSnapshotRequest,
+};
+use http::header::AUTHORIZATION;
+use http::StatusCode;
+use log::info;
+use reqwest_middleware::ClientBuilder;
+use uuid::Uuid;
+
+async fn create_snapshot(name: &str, args: &NodeArgs<'_>) -> reqwest::Response {
+    args.client
+        .post(format!(
+            "http://{}/api/bucket/snapshots/{}/{}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.token))
+        .json(&CreateSnapshotRequest {
+            name: name.to_string(),
+        })
+        .send()
+        .await
+        .expect("")
+}
+
+async fn list_snapshots(args: &NodeArgs<'_>) -> BucketSnapshotList {
+    args.client
+        .get(format!(
+            "http://{}/api/bucket/snapshots/{}/{}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.token))
+        .send()
+        .await
+        .expect("")
+        .json::<BucketSnapshotList>()
+        .await
+        .expect("")
+}
+
+async fn delete_snapshot(snapshot_id: Uuid, args: &NodeArgs<'_>) -> StatusCode {
+    args.client
+        .delete(format!(
+            "http://{}/api/bucket/snapshots/{}/{}/{snapshot_id}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.token))
+        .send()
+        .await
+        .expect("")
+        .status()
+}
+
+async fn restore_snapshot(snapshot_id: Uuid, bucket_id: Uuid, args: &NodeArgs<'_>) -> StatusCode {
+    args.client
+        .post(format!(
+            "http://{}/api/bucket/snapshots/restore/{}/{}/{snapshot_id}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.token))
+        .json(&RestoreSnapshotRequest { bucket_id })
+        .send()
+        .await
+        .expect("")
+        .status()
+}
+
+async fn get_in_snapshot(url: String, snapshot_id: Uuid, args: &NodeArgs<'_>) -> reqwest::Response {
+    args.client
+        .get(url)
+        .query(&[("snapshot_id", snapshot_id.to_string())])
+        .header(AUTHORIZATION, format!("Bearer {}", args.token))
+        .send()
+        .await
+        .expect("")
+}
+
+pub async fn snapshot_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: &user_token,
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    create_file("snap/a", &args).await;
+    create_file("snap/b", &args).await;
+
+    let response = create_snapshot("first", &args).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let snapshot: BucketSnapshotDto = response.json().await.unwrap();
+    assert!(snapshot.file_count >= 2);
+    assert_eq!(
+        create_snapshot("first", &args).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+    assert!(list_snapshots(&args)
+        .await
+        .snapshots
+        .iter()
+        .any(|listed| listed.id == snapshot.id));
+    header!("Snapshot created");
+
+    delete_file("snap/a", "127.0.0.3:4001", &args).await;
+    let live = list_folder("snap", &args).await;
+    assert_eq!(live.entities.len(), 1);
+
+    let response = get_in_snapshot(
+        format!(
+            "http://{}/api/directory/list/{}/{}/snap",
+            args.node, args.app_id, args.bucket_id
+        ),
+        snapshot.id,
+        &args,
+    )
+    .await;
+    let listed: EntityList = response.json().await.unwrap();
+    assert_eq!(listed.entities.len(), 2);
+
+    let response = get_in_snapshot(
+        format!(
+            "http://127.0.0.3:4001/api/bucket/stat/{}/{}/snap/a",
+            args.app_id, args.bucket_id
+        ),
+        snapshot.id,
+        &args,
+    )
+    .await;
+    let entity: Entity = response.json().await.unwrap();
+    assert_eq!(entity.size, FILE_SIZE as u64);
+    header!("Snapshot browsed");
+
+    // the chunks of the deleted file are kept for the snapshot
+    let response = get_in_snapshot(
+        format!(
+            "http://127.0.0.3:4001/api/file/download/{}/{}/snap/a",
+            args.app_id, args.bucket_id
+        ),
+        snapshot.id,
+        &args,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.bytes().await.unwrap().len(), FILE_SIZE);
+    header!("Deleted file downloaded from the snapshot");
+
+    assert_eq!(
+        restore_snapshot(snapshot.id, args.bucket_id, &args).await,
+        StatusCode::BAD_REQUEST
+    );
+    // the token is scoped to a single bucket
+    assert_eq!(
+        restore_snapshot(snapshot.id, Uuid::new_v4(), &args).await,
+        StatusCode::UNAUTHORIZED
+    );
+    header!("Rejected invalid restores");
+
+    assert_eq!(delete_snapshot(snapshot.id, &args).await, StatusCode::OK);
+    assert!(list_snapshots(&args).await.snapshots.is_empty());
+    let response = get_in_snapshot(
+        format!(
+            "http://127.0.0.3:4001/api/bucket/stat/{}/{}/snap/b",
+            args.app_id, args.bucket_id
+        ),
+        snapshot.id,
+        &args,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(stat_entity("snap/b", &args).await.size, FILE_SIZE as u64);
+    delete_file("snap/b", "127.0.0.3:4001", &args).await;
+    assert_eq!(delete_dir("snap", false, &args).await, StatusCode::OK);
+    header!("Snapshot deleted");
+}
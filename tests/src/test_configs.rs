@@ -48,7 +48,10 @@ lazy_static! {
             fs_limits: FsLimitConfiguration {
                 max_path_length: 256,
                 max_directory_depth: 10,
-            }
+                max_zip_archive_size: 64 * 1024 * 1024,
+            },
+            // the webhook receiver of the tests listens on the loopback
+            allow_private_webhooks: true,
         },
     };
     pub static ref TEST_NODE_1_CONFIG: NodeConfigInstance = NodeConfigInstance {
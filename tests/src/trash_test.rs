@@ -0,0 +1,114 @@
+use crate::assert_bucket_info;
+use crate::directory_test::{create_file, stat_entity, NodeArgs, FILE_SIZE};
+use crate::file_transfer_test::{delete_file, fetch_bucket_info};
+use crate::utils::Logger;
+use dashboard_lib::public::routes::bucket::EditBucketTrashRequest;
+use data::dto::entity::{AppDto, BucketDto, TrashList, TrashRestoreResponse};
+use http::header::AUTHORIZATION;
+use log::info;
+use reqwest_middleware::ClientBuilder;
+
+pub(crate) async fn set_trash_retention(retention: u64, args: &NodeArgs<'_>) {
+    let req = EditBucketTrashRequest { retention };
+
+    assert!(args
+        .client
+        .patch(format!(
+            "http://127.0.0.4:4002/api/bucket/trash/{}/{}",
+            args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.user_token))
+        .json(&req)
+        .send()
+        .await
+        .expect("")
+        .status()
+        .is_success());
+}
+
+async fn list_trash(args: &NodeArgs<'_>) -> TrashList {
+    list_trash_page(&[], args).await
+}
+
+async fn list_trash_page(query: &[(&str, &str)], args: &NodeArgs<'_>) -> TrashList {
+    args.client
+        .get(format!(
+            "http://{}/api/bucket/trash/{}/{}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .query(query)
+        .header(AUTHORIZATION, format!("Bearer {}", args.token))
+        .send()
+        .await
+        .expect("")
+        .json::<TrashList>()
+        .await
+        .expect("")
+}
+
+pub(crate) async fn restore_trash(path: &str, args: &NodeArgs<'_>) -> TrashRestoreResponse {
+    args.client
+        .post(format!(
+            "http://{}/api/file/trash/restore/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.token))
+        .send()
+        .await
+        .expect("")
+        .json::<TrashRestoreResponse>()
+        .await
+        .expect("")
+}
+
+pub async fn trash_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: &user_token,
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    let before = fetch_bucket_info(args.token, args.app_id, args.bucket_id, args.client).await;
+    set_trash_retention(3600, &args).await;
+    header!("Enabled the trash");
+
+    create_file("trash_dir/trashed", &args).await;
+    delete_file("trash_dir/trashed", args.node, &args).await;
+    assert_eq!(list_trash(&args).await.entries.len(), 1);
+    assert_bucket_info!(
+        &args,
+        before.file_count,
+        before.space_taken + FILE_SIZE as i64
+    );
+    header!("Delete moved the file to the trash");
+
+    create_file("trash_dir/other", &args).await;
+    delete_file("trash_dir/other", args.node, &args).await;
+    let first = list_trash_page(&[("limit", "1")], &args).await;
+    assert_eq!(first.entries[0].path, "trash_dir/other");
+    let cursor = first.next_cursor.expect("");
+    let second = list_trash_page(&[("limit", "1"), ("cursor", &cursor)], &args).await;
+    assert_eq!(second.entries[0].path, "trash_dir/trashed");
+    assert!(second.next_cursor.is_none());
+    header!("Paginated the trash");
+
+    let response = restore_trash("trash_dir", &args).await;
+    assert_eq!(response.restored, 2);
+    assert!(!stat_entity("trash_dir/trashed", &args).await.is_dir);
+    assert!(list_trash(&args).await.entries.is_empty());
+    header!("Restored the trashed file");
+
+    set_trash_retention(0, &args).await;
+    delete_file("trash_dir/trashed", args.node, &args).await;
+    delete_file("trash_dir/other", args.node, &args).await;
+    assert!(list_trash(&args).await.entries.is_empty());
+    assert_bucket_info!(&args, before.file_count, before.space_taken);
+    header!("Delete without trash removed the file");
+}
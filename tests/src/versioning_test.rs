@@ -0,0 +1,158 @@
+use crate::assert_bucket_info;
+use crate::directory_test::{create_file, stat_entity, NodeArgs, FILE_SIZE};
+use crate::file_transfer_test::{delete_file, fetch_bucket_info};
+use crate::utils::Logger;
+use dashboard_lib::public::routes::bucket::EditBucketVersioningRequest;
+use data::dto::entity::{AppDto, BucketDto, FileVersionList, FileVersionRequest};
+use http::header::AUTHORIZATION;
+use log::info;
+use reqwest_middleware::ClientBuilder;
+use uuid::Uuid;
+
+pub(crate) async fn set_versioning(enabled: bool, args: &NodeArgs<'_>) {
+    let req = EditBucketVersioningRequest { enabled };
+
+    assert!(args
+        .client
+        .patch(format!(
+            "http://127.0.0.4:4002/api/bucket/versioning/{}/{}",
+            args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.user_token))
+        .json(&req)
+        .send()
+        .await
+        .expect("")
+        .status()
+        .is_success());
+}
+
+pub(crate) async fn list_versions(path: &str, args: &NodeArgs<'_>) -> FileVersionList {
+    list_versions_page(path, &[], args).await
+}
+
+async fn list_versions_page(
+    path: &str,
+    query: &[(&str, String)],
+    args: &NodeArgs<'_>,
+) -> FileVersionList {
+    args.client
+        .get(format!(
+            "http://{}/api/file/versions/list/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .query(query)
+        .header(AUTHORIZATION, format!("Bearer {}", args.token))
+        .send()
+        .await
+        .expect("")
+        .json::<FileVersionList>()
+        .await
+        .expect("")
+}
+
+pub(crate) async fn restore_version(path: &str, version_id: Uuid, args: &NodeArgs<'_>) {
+    assert!(args
+        .client
+        .post(format!(
+            "http://{}/api/file/versions/restore/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.token))
+        .json(&FileVersionRequest { version_id })
+        .send()
+        .await
+        .expect("")
+        .status()
+        .is_success());
+}
+
+pub(crate) async fn delete_version(path: &str, version_id: Uuid, args: &NodeArgs<'_>) {
+    assert!(args
+        .client
+        .delete(format!(
+            "http://{}/api/file/versions/delete/{}/{}/{path}",
+            args.node, args.app_id, args.bucket_id
+        ))
+        .header(AUTHORIZATION, format!("Bearer {}", args.token))
+        .json(&FileVersionRequest { version_id })
+        .send()
+        .await
+        .expect("")
+        .status()
+        .is_success());
+}
+
+pub async fn versioning_test(data: (AppDto, BucketDto, String, String)) {
+    let (app_dto, bucket_dto, token, user_token) = data;
+    let reqwest_client = reqwest::Client::builder().build().unwrap();
+    let client = ClientBuilder::new(reqwest_client).with(Logger).build();
+
+    let args = NodeArgs {
+        node: "127.0.0.2:4000",
+        token: &token,
+        user_token: &user_token,
+        app_id: app_dto.id,
+        bucket_id: bucket_dto.id,
+        client: &client,
+    };
+
+    let before = fetch_bucket_info(args.token, args.app_id, args.bucket_id, args.client).await;
+    set_versioning(true, &args).await;
+    header!("Enabled versioning");
+
+    create_file("versioned", &args).await;
+    create_file("versioned", &args).await;
+    let versions = list_versions("versioned", &args).await;
+    assert_eq!(versions.versions.len(), 1);
+    assert_bucket_info!(
+        &args,
+        before.file_count + 1,
+        before.space_taken + 2 * FILE_SIZE as i64
+    );
+    header!("Overwrite retained a version");
+
+    delete_file("versioned", args.node, &args).await;
+    let versions = list_versions("versioned", &args).await;
+    assert_eq!(versions.versions.len(), 2);
+    assert!(versions.versions[0].archived >= versions.versions[1].archived);
+    assert!(versions.next_cursor.is_none());
+    assert_bucket_info!(
+        &args,
+        before.file_count,
+        before.space_taken + 2 * FILE_SIZE as i64
+    );
+    header!("Delete retained a version");
+
+    let first = list_versions_page("versioned", &[("limit", "1".to_string())], &args).await;
+    assert_eq!(first.versions.len(), 1);
+    assert_eq!(
+        first.versions[0].version_id,
+        versions.versions[0].version_id
+    );
+    let cursor = first.next_cursor.expect("Missing cursor");
+    let rest = list_versions_page(
+        "versioned",
+        &[("limit", "1".to_string()), ("cursor", cursor.to_string())],
+        &args,
+    )
+    .await;
+    assert_eq!(rest.versions.len(), 1);
+    assert_eq!(rest.versions[0].version_id, versions.versions[1].version_id);
+    assert!(rest.next_cursor.is_none());
+    header!("Versions paginated");
+
+    restore_version("versioned", versions.versions[0].version_id, &args).await;
+    let restored = stat_entity("versioned", &args).await;
+    assert!(!restored.is_dir);
+    let versions = list_versions("versioned", &args).await;
+    assert_eq!(versions.versions.len(), 1);
+    header!("Restored a version");
+
+    set_versioning(false, &args).await;
+    delete_version("versioned", versions.versions[0].version_id, &args).await;
+    delete_file("versioned", args.node, &args).await;
+    assert!(list_versions("versioned", &args).await.versions.is_empty());
+    assert_bucket_info!(&args, before.file_count, before.space_taken);
+    header!("Versions removed");
+}